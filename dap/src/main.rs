@@ -0,0 +1,15 @@
+//! Debug Adapter Protocol server for SimplicityHL.
+//!
+//! The server communicates with the client over stdin and stdout.
+
+mod protocol;
+mod server;
+mod session;
+
+use std::io;
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    server::Server::new(stdin.lock(), stdout.lock()).run()
+}
@@ -0,0 +1,48 @@
+//! Base protocol of the Debug Adapter Protocol.
+//!
+//! Each message is a JSON object that is preceded by a `Content-Length` header.
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+/// Read the next message from the `reader`.
+///
+/// Return `None` if the reader reached the end of its input.
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let content_length = content_length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length"))?;
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Write the `message` to the `writer`.
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(
+        writer,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    writer.flush()
+}
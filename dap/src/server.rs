@@ -0,0 +1,310 @@
+//! Handling of Debug Adapter Protocol requests.
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+use crate::protocol::{read_message, write_message};
+use crate::session::{LaunchArguments, Session, Stop};
+
+/// The only thread of a SimplicityHL program.
+const THREAD_ID: u64 = 1;
+
+/// Debug adapter that communicates with a client over a reader and a writer.
+pub struct Server<R, W> {
+    reader: R,
+    writer: W,
+    seq: u64,
+    session: Option<Session>,
+    stop_on_entry: bool,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    /// Create a debug adapter that reads requests from the `reader`
+    /// and writes responses and events to the `writer`.
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            seq: 0,
+            session: None,
+            stop_on_entry: false,
+        }
+    }
+
+    /// Handle requests until the client disconnects or closes the connection.
+    pub fn run(&mut self) -> io::Result<()> {
+        while let Some(message) = read_message(&mut self.reader)? {
+            if message["type"] != "request" {
+                continue;
+            }
+            let command = message["command"].as_str().unwrap_or_default().to_string();
+            let result = self.handle(&command, &message["arguments"]);
+            let (success, body, error) = match result {
+                Ok(body) => (true, body, None),
+                Err(error) => (false, Value::Null, Some(error)),
+            };
+            let mut response = json!({
+                "type": "response",
+                "request_seq": message["seq"],
+                "success": success,
+                "command": command,
+                "body": body,
+            });
+            if let Some(error) = error {
+                response["message"] = json!(error);
+            }
+            self.send(response)?;
+
+            match command.as_str() {
+                "launch" if success => self.send_event("initialized", Value::Null)?,
+                "configurationDone" => {
+                    let stop_on_entry = self.stop_on_entry;
+                    self.resume(|session| session.start(stop_on_entry))?;
+                }
+                "continue" => self.resume(Session::continue_)?,
+                "next" => self.resume(Session::step_over)?,
+                "stepIn" => self.resume(Session::step_in)?,
+                "stepOut" => self.resume(Session::step_out)?,
+                "disconnect" => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle the request and return the body of its response.
+    fn handle(&mut self, command: &str, arguments: &Value) -> Result<Value, String> {
+        match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+            })),
+            "launch" => {
+                let arguments = LaunchArguments::deserialize(arguments)?;
+                self.stop_on_entry = arguments.stop_on_entry;
+                self.session = Some(Session::launch(&arguments)?);
+                Ok(Value::Null)
+            }
+            "setBreakpoints" => {
+                let lines: Vec<usize> = arguments["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .map(|line| line as usize)
+                    .collect();
+                let verified = match &mut self.session {
+                    Some(session) => session.set_breakpoints(&lines),
+                    None => vec![false; lines.len()],
+                };
+                let breakpoints: Vec<Value> = lines
+                    .iter()
+                    .zip(verified)
+                    .map(|(line, verified)| json!({ "verified": verified, "line": line }))
+                    .collect();
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "threads" => Ok(json!({
+                "threads": [{ "id": THREAD_ID, "name": "main" }],
+            })),
+            "stackTrace" => {
+                let session = self.session()?;
+                let source = json!({
+                    "name": session.source().file_name().map(|name| name.to_string_lossy()),
+                    "path": session.source().to_string_lossy(),
+                });
+                let frames: Vec<Value> = session
+                    .frames()
+                    .iter()
+                    .enumerate()
+                    .map(|(id, frame)| {
+                        json!({
+                            "id": id,
+                            "name": frame.name,
+                            "source": source,
+                            "line": frame.span.start.line,
+                            "column": frame.span.start.col,
+                            "endLine": frame.span.end.line,
+                            "endColumn": frame.span.end.col,
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+            }
+            "scopes" => {
+                let frame_id = arguments["frameId"].as_u64().unwrap_or_default();
+                Ok(json!({
+                    "scopes": [{
+                        "name": "Locals",
+                        "presentationHint": "locals",
+                        // Reference 0 means that there are no variables
+                        "variablesReference": frame_id + 1,
+                        "expensive": false,
+                    }],
+                }))
+            }
+            "variables" => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or_default();
+                let frames = self.session()?.frames();
+                let variables: Vec<Value> = (reference as usize)
+                    .checked_sub(1)
+                    .and_then(|frame_id| frames.get(frame_id))
+                    .map(|frame| frame.variables.as_slice())
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|(identifier, value)| {
+                        json!({
+                            "name": identifier.as_inner(),
+                            "value": value.to_string(),
+                            "type": value.ty().to_string(),
+                            "variablesReference": 0,
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "variables": variables }))
+            }
+            "continue" => Ok(json!({ "allThreadsContinued": true })),
+            "configurationDone" | "next" | "stepIn" | "stepOut" | "pause" | "disconnect" => {
+                Ok(Value::Null)
+            }
+            _ => Err(format!("Unsupported request `{command}`")),
+        }
+    }
+
+    fn session(&self) -> Result<&Session, String> {
+        self.session
+            .as_ref()
+            .ok_or_else(|| "No program was launched".to_string())
+    }
+
+    /// Resume the program and report where it stops.
+    fn resume<F: FnOnce(&mut Session) -> Stop>(&mut self, resume: F) -> io::Result<()> {
+        let Some(session) = &mut self.session else {
+            return Ok(());
+        };
+        let stop = resume(session);
+        let output = session.take_output();
+        for output in output {
+            self.send_event("output", json!({ "category": "stdout", "output": output }))?;
+        }
+
+        let (reason, description) = match stop {
+            Stop::Entry => ("entry", None),
+            Stop::Step => ("step", None),
+            Stop::Breakpoint => ("breakpoint", None),
+            Stop::Exception(description) => ("exception", Some(description)),
+            Stop::Terminated { success } => {
+                self.send_event("exited", json!({ "exitCode": i32::from(!success) }))?;
+                return self.send_event("terminated", Value::Null);
+            }
+        };
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(description) = description {
+            body["description"] = json!("Execution failed");
+            body["text"] = json!(description);
+        }
+        self.send_event("stopped", body)
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.writer, &message)
+    }
+}
+
+impl LaunchArguments {
+    fn deserialize(arguments: &Value) -> Result<Self, String> {
+        serde_json::from_value(arguments.clone())
+            .map_err(|error| format!("Invalid launch arguments: {error}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn request(seq: u64, command: &str, arguments: Value) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let message = json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        });
+        write_message(&mut bytes, &message).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_session() {
+        let program = std::env::temp_dir().join("simplicityhl_dap_test.simf");
+        std::fs::write(
+            &program,
+            "fn main() {\n    let a: u8 = 1;\n    assert!(jet::eq_8(dbg!(a), 1));\n}\n",
+        )
+        .unwrap();
+        let input = [
+            request(1, "initialize", json!({ "adapterID": "simplicityhl" })),
+            request(2, "launch", json!({ "program": program })),
+            request(
+                3,
+                "setBreakpoints",
+                json!({ "breakpoints": [{ "line": 3 }] }),
+            ),
+            request(4, "configurationDone", Value::Null),
+            request(5, "variables", json!({ "variablesReference": 1 })),
+            request(6, "continue", json!({ "threadId": THREAD_ID })),
+            request(7, "disconnect", Value::Null),
+        ]
+        .concat();
+
+        let mut output = Vec::new();
+        Server::new(Cursor::new(input), &mut output).run().unwrap();
+        let mut output = Cursor::new(output);
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            messages.push(message);
+        }
+        let summary: Vec<String> = messages
+            .iter()
+            .map(|message| match message["type"].as_str() {
+                Some("response") => format!("{} {}", message["command"], message["success"]),
+                _ => format!("{} {}", message["event"], message["body"]),
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                r#""initialize" true"#,
+                r#""launch" true"#,
+                r#""initialized" null"#,
+                r#""setBreakpoints" true"#,
+                r#""configurationDone" true"#,
+                r#""stopped" {"allThreadsStopped":true,"reason":"breakpoint","threadId":1}"#,
+                r#""variables" true"#,
+                r#""continue" true"#,
+                r#""output" {"category":"stdout","output":"a = 1\n"}"#,
+                r#""exited" {"exitCode":0}"#,
+                r#""terminated" null"#,
+                r#""disconnect" true"#,
+            ]
+        );
+        assert_eq!(messages[3]["body"]["breakpoints"][0]["verified"], true);
+        assert_eq!(
+            messages[6]["body"]["variables"],
+            json!([{ "name": "a", "value": "1", "type": "u8", "variablesReference": 0 }])
+        );
+        std::fs::remove_file(program).unwrap();
+    }
+}
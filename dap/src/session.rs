@@ -0,0 +1,470 @@
+//! Recorded execution of a SimplicityHL program.
+//!
+//! The Bit Machine cannot pause in the middle of a program,
+//! so the program runs to completion when it is launched,
+//! and the session replays the recorded steps afterwards.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use simplicityhl::debug::{TraceEvent, Tracer, TrackedStepName};
+use simplicityhl::error::Span;
+use simplicityhl::parse::{self, ParseFromStr};
+use simplicityhl::simplicity::BitMachine;
+use simplicityhl::str::Identifier;
+use simplicityhl::{dummy_env, elements, Arguments, TemplateProgram, Value, WitnessValues};
+
+/// Arguments of the `launch` request.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchArguments {
+    /// Path of the SimplicityHL program.
+    pub program: PathBuf,
+    /// Path of the witness file.
+    #[serde(default)]
+    pub witness: Option<PathBuf>,
+    /// Path of the arguments file.
+    #[serde(default)]
+    pub arguments: Option<PathBuf>,
+    /// Description of the transaction environment.
+    #[serde(default)]
+    pub environment: Environment,
+    /// Stop at the first statement instead of the first breakpoint.
+    #[serde(default)]
+    pub stop_on_entry: bool,
+}
+
+/// Description of the dummy transaction that spends the program.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Environment {
+    /// Consensus encoding of the lock time of the transaction.
+    pub lock_time: u32,
+    /// Consensus encoding of the sequence of the spent input.
+    pub sequence: u32,
+    /// Include an explicit fee output in the transaction.
+    pub include_fee_output: bool,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            lock_time: 0,
+            sequence: u32::MAX,
+            include_fee_output: false,
+        }
+    }
+}
+
+/// Reason why the debuggee stopped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// Stopped at the first statement.
+    Entry,
+    /// Stopped after a step request.
+    Step,
+    /// Stopped at a breakpoint.
+    Breakpoint,
+    /// Stopped because the program failed, with a description of the failure.
+    Exception(String),
+    /// The program finished.
+    Terminated {
+        /// The program ran successfully.
+        success: bool,
+    },
+}
+
+/// Stack frame of a stopped program.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// Name of the function.
+    pub name: String,
+    /// Span of the current statement.
+    pub span: Span,
+    /// Variables in scope of the current statement.
+    pub variables: Vec<(Identifier, Value)>,
+}
+
+/// Execution of a SimplicityHL program that is replayed step by step.
+#[derive(Debug)]
+pub struct Session {
+    source: PathBuf,
+    events: Vec<TraceEvent>,
+    /// Error of the execution, if the program failed.
+    error: Option<String>,
+    /// Body span and name of each custom function.
+    functions: Vec<(Span, String)>,
+    /// Lines where a statement starts.
+    step_lines: BTreeSet<usize>,
+    breakpoints: BTreeSet<usize>,
+    /// Index of the step event where the program stopped.
+    position: Option<usize>,
+    /// Index of the next event to replay.
+    next: usize,
+    /// Output of `dbg!` that was replayed but not yet reported.
+    output: Vec<String>,
+    finished: bool,
+}
+
+impl Session {
+    /// Load the files of the launch request and run the program.
+    pub fn launch(arguments: &LaunchArguments) -> Result<Self, String> {
+        let text = read(&arguments.program)?;
+        let parameters = match &arguments.arguments {
+            Some(path) => serde_json::from_str(&read(path)?).map_err(|e| e.to_string())?,
+            None => Arguments::default(),
+        };
+        let witness = match &arguments.witness {
+            Some(path) => serde_json::from_str(&read(path)?).map_err(|e| e.to_string())?,
+            None => WitnessValues::default(),
+        };
+        Self::new(
+            arguments.program.clone(),
+            &text,
+            parameters,
+            witness,
+            &arguments.environment,
+        )
+    }
+
+    /// Compile the program for the debugger and run it to record its steps.
+    ///
+    /// A program that fails at runtime still yields a session,
+    /// which stops with an exception where the program failed.
+    pub fn new(
+        source: PathBuf,
+        text: &str,
+        arguments: Arguments,
+        witness: WitnessValues,
+        environment: &Environment,
+    ) -> Result<Self, String> {
+        let compiled = TemplateProgram::new(text)
+            .and_then(|template| template.instantiate_for_debugger(arguments))?;
+        let satisfied = compiled.satisfy(witness)?;
+        let env = dummy_env::dummy_with(
+            elements::LockTime::from_consensus(environment.lock_time),
+            elements::Sequence::from_consensus(environment.sequence),
+            environment.include_fee_output,
+        );
+
+        let mut tracer = Tracer::new(satisfied.debug_symbols());
+        let mut mac = BitMachine::for_program(satisfied.redeem()).map_err(|e| e.to_string())?;
+        let error = mac
+            .exec_with_tracker(satisfied.redeem(), &env, &mut tracer)
+            .err()
+            .map(|error| error.to_string());
+
+        let step_lines = satisfied
+            .debug_symbols()
+            .steps()
+            .filter(|step| matches!(step.name(), TrackedStepName::Statement(..)))
+            .map(|step| step.span().start.line.get())
+            .collect();
+        let functions = parse::Program::parse_from_str(text)
+            .map(|program| {
+                program
+                    .items()
+                    .iter()
+                    .filter_map(|item| match item {
+                        parse::Item::Function(function) => {
+                            Some((*function.body().as_ref(), function.name().to_string()))
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            source,
+            events: tracer.into_events(),
+            error,
+            functions,
+            step_lines,
+            breakpoints: BTreeSet::new(),
+            position: None,
+            next: 0,
+            output: Vec::new(),
+            finished: false,
+        })
+    }
+
+    /// Access the path of the program.
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// Set the breakpoints of the program to the given lines.
+    ///
+    /// Return whether each breakpoint is verified, which is the case if a statement starts on its line.
+    pub fn set_breakpoints(&mut self, lines: &[usize]) -> Vec<bool> {
+        self.breakpoints = lines
+            .iter()
+            .copied()
+            .filter(|line| self.step_lines.contains(line))
+            .collect();
+        lines
+            .iter()
+            .map(|line| self.breakpoints.contains(line))
+            .collect()
+    }
+
+    /// Start the replay, either at the first statement or at the first breakpoint.
+    pub fn start(&mut self, stop_on_entry: bool) -> Stop {
+        match stop_on_entry {
+            true => {
+                let target = self.next_step(|_| true);
+                self.replay_until(target, Stop::Entry)
+            }
+            false => self.continue_(),
+        }
+    }
+
+    /// Run until the next breakpoint.
+    pub fn continue_(&mut self) -> Stop {
+        let target = self.next_step(|span| self.breakpoints.contains(&span.start.line.get()));
+        self.replay_until(target, Stop::Breakpoint)
+    }
+
+    /// Run until the next statement of the current function, stepping over function calls.
+    pub fn step_over(&mut self) -> Stop {
+        let depth = self.depth();
+        self.advance_with_depth(|event_depth| event_depth <= depth)
+    }
+
+    /// Run until the next statement, stepping into function calls.
+    pub fn step_in(&mut self) -> Stop {
+        self.advance_with_depth(|_| true)
+    }
+
+    /// Run until the next statement after the current function returns.
+    pub fn step_out(&mut self) -> Stop {
+        let depth = self.depth();
+        self.advance_with_depth(|event_depth| event_depth < depth)
+    }
+
+    /// Take the output of `dbg!` that was replayed since the last call.
+    pub fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Return the stack frames at the current statement, the innermost frame first.
+    pub fn frames(&self) -> Vec<Frame> {
+        let Some(position) = self.position else {
+            return Vec::new();
+        };
+        let mut stack = vec![("main".to_string(), None)];
+        for (index, event) in self.events[..=position].iter().enumerate() {
+            match event {
+                TraceEvent::EnterFunction(span) => stack.push((self.function_name(span), None)),
+                TraceEvent::ExitFunction(..) => {
+                    stack.pop();
+                }
+                TraceEvent::Step(..) => {
+                    if let Some(frame) = stack.last_mut() {
+                        frame.1 = Some(index);
+                    }
+                }
+                _ => {}
+            }
+        }
+        stack
+            .into_iter()
+            .rev()
+            .filter_map(|(name, step)| match &self.events[step?] {
+                TraceEvent::Step(span, variables) => Some(Frame {
+                    name,
+                    span: *span,
+                    variables: variables.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn function_name(&self, body: &Span) -> String {
+        self.functions
+            .iter()
+            .find(|(span, _)| span == body)
+            .map_or_else(|| "<function>".to_string(), |(_, name)| name.clone())
+    }
+
+    /// Return the number of custom functions that are active at the current statement.
+    fn depth(&self) -> usize {
+        self.depth_at(self.position.map_or(0, |position| position + 1))
+    }
+
+    fn depth_at(&self, index: usize) -> usize {
+        self.events[..index]
+            .iter()
+            .fold(0, |depth, event| match event {
+                TraceEvent::EnterFunction(..) => depth + 1,
+                TraceEvent::ExitFunction(..) => depth.saturating_sub(1),
+                _ => depth,
+            })
+    }
+
+    fn advance_with_depth<F: Fn(usize) -> bool>(&mut self, stop_at_depth: F) -> Stop {
+        let mut depth = self.depth_at(self.next);
+        let start = self.next;
+        let target =
+            self.events[start..]
+                .iter()
+                .enumerate()
+                .find_map(|(offset, event)| match event {
+                    TraceEvent::EnterFunction(..) => {
+                        depth += 1;
+                        None
+                    }
+                    TraceEvent::ExitFunction(..) => {
+                        depth = depth.saturating_sub(1);
+                        None
+                    }
+                    TraceEvent::Step(..) if stop_at_depth(depth) => Some(start + offset),
+                    _ => None,
+                });
+        self.replay_until(target, Stop::Step)
+    }
+
+    /// Return the index of the next step event whose span satisfies `stop_at`.
+    fn next_step<F: Fn(&Span) -> bool>(&self, stop_at: F) -> Option<usize> {
+        (self.next..self.events.len()).find(|&index| match &self.events[index] {
+            TraceEvent::Step(span, _) => stop_at(span),
+            _ => false,
+        })
+    }
+
+    /// Replay the events up to the step event at `target`, or up to the end of the program.
+    fn replay_until(&mut self, target: Option<usize>, reason: Stop) -> Stop {
+        if self.finished {
+            return Stop::Terminated {
+                success: self.error.is_none(),
+            };
+        }
+        let end = target.map_or(self.events.len(), |index| index + 1);
+        for event in &self.events[self.next..end] {
+            if let TraceEvent::Debug(debug) = event {
+                self.output
+                    .push(format!("{} = {}\n", debug.text(), debug.value()));
+            }
+        }
+        self.next = end;
+        if let Some(index) = target {
+            self.position = Some(index);
+            return reason;
+        }
+
+        self.finished = true;
+        match &self.error {
+            Some(error) => {
+                // Show where the program failed
+                self.position = self
+                    .events
+                    .iter()
+                    .rposition(|event| matches!(event, TraceEvent::Step(..)));
+                let call = self.events.iter().rev().find_map(|event| match event {
+                    TraceEvent::Call(call) => Some(call.text()),
+                    _ => None,
+                });
+                Stop::Exception(match call {
+                    Some(call) => format!("`{call}` failed: {error}"),
+                    None => error.clone(),
+                })
+            }
+            None => Stop::Terminated { success: true },
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|error| format!("{}: {error}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: &str = r#"fn double(x: u8) -> u8 {
+    let (_, y): (bool, u8) = jet::add_8(x, x);
+    y
+}
+
+fn main() {
+    let a: u8 = 3;
+    let b: u8 = double(a);
+    let c: u8 = double(dbg!(b));
+    assert!(jet::eq_8(c, 13));
+}"#;
+
+    fn session() -> Session {
+        Session::new(
+            PathBuf::from("test.simf"),
+            PROGRAM,
+            Arguments::default(),
+            WitnessValues::default(),
+            &Environment::default(),
+        )
+        .expect("program should compile")
+    }
+
+    fn location(session: &Session) -> Vec<(String, usize)> {
+        session
+            .frames()
+            .into_iter()
+            .map(|frame| (frame.name, frame.span.start.line.get()))
+            .collect()
+    }
+
+    #[test]
+    fn test_stepping() {
+        let mut session = session();
+        assert_eq!(session.set_breakpoints(&[2, 5, 9]), [true, false, true]);
+
+        assert_eq!(session.start(false), Stop::Breakpoint);
+        assert_eq!(
+            location(&session),
+            [("double".to_string(), 2), ("main".to_string(), 8)]
+        );
+        let variables = &session.frames()[0].variables;
+        assert_eq!(variables.len(), 1);
+        assert_eq!(variables[0].0.as_inner(), "x");
+        assert_eq!(variables[0].1.to_string(), "3");
+
+        assert_eq!(session.step_out(), Stop::Step);
+        assert_eq!(location(&session), [("main".to_string(), 9)]);
+        assert!(session.take_output().is_empty());
+
+        assert_eq!(session.step_over(), Stop::Step);
+        assert_eq!(location(&session), [("main".to_string(), 10)]);
+        assert_eq!(session.take_output(), ["b = 6\n"]);
+
+        match session.continue_() {
+            Stop::Exception(description) => {
+                assert!(
+                    description.starts_with("`assert!(jet::eq_8(c, 13))` failed"),
+                    "{description}"
+                )
+            }
+            stop => panic!("Unexpected stop: {stop:?}"),
+        }
+        assert_eq!(location(&session), [("main".to_string(), 10)]);
+        assert_eq!(session.continue_(), Stop::Terminated { success: false });
+    }
+
+    #[test]
+    fn test_step_in() {
+        let mut session = session();
+        assert_eq!(session.start(true), Stop::Entry);
+        assert_eq!(location(&session), [("main".to_string(), 7)]);
+        session.step_in();
+        session.step_in();
+        assert_eq!(
+            location(&session),
+            [("double".to_string(), 2), ("main".to_string(), 8)]
+        );
+        session.step_over();
+        session.step_over();
+        assert_eq!(location(&session), [("main".to_string(), 9)]);
+    }
+}
@@ -0,0 +1,212 @@
+#![cfg_attr(fuzzing, no_main)]
+
+/// Run the program on the Bit Machine and in the interpreter, under the dummy environment.
+///
+/// Check that both agree on success or failure and on the values passed to `dbg!`.
+#[cfg(any(fuzzing, test))]
+fn compare(
+    program: &simplicityhl::ast::Program,
+    program_text: &str,
+    arguments: simplicityhl::Arguments,
+    witness_values: simplicityhl::WitnessValues,
+) {
+    use simplicityhl::debug::{TraceEvent, Tracer};
+    use simplicityhl::interpret::Interpreter;
+    use simplicityhl::simplicity::BitMachine;
+    use simplicityhl::{dummy_env, TemplateProgram, Value};
+
+    let env = dummy_env::dummy();
+
+    let satisfied = TemplateProgram::new(program_text)
+        .expect("Output of fmt::Display should be a valid program")
+        .instantiate(arguments.clone(), true)
+        .expect("AST should compile with given arguments")
+        .satisfy(witness_values.clone())
+        .expect("Witness values should be consistent with the program");
+    let mut mac = match BitMachine::for_program(satisfied.redeem()) {
+        Ok(mac) => mac,
+        Err(..) => return,
+    };
+    let mut tracer = Tracer::new(satisfied.debug_symbols());
+    let compiled_success = mac
+        .exec_with_tracker(satisfied.redeem(), &env, &mut tracer)
+        .is_ok();
+    let compiled_debug_values: Vec<Value> = tracer
+        .into_events()
+        .into_iter()
+        .filter_map(|event| match event {
+            TraceEvent::Debug(debug_value) => Some(debug_value.value().clone()),
+            _ => None,
+        })
+        .collect();
+
+    let mut interpreter = Interpreter::new(&arguments, &witness_values, &env);
+    let interpreted_success = interpreter.run(program).is_ok();
+    let interpreted_debug_values: Vec<Value> = interpreter
+        .debug_values()
+        .iter()
+        .map(|(_, value)| value.clone())
+        .collect();
+
+    assert_eq!(
+        (compiled_success, compiled_debug_values),
+        (interpreted_success, interpreted_debug_values),
+        "Compiled and interpreted program should behave the same\n\n{program_text}\n{arguments}\n{witness_values}",
+    );
+}
+
+/// Check if the expression calls jets, directly or through custom functions,
+/// and if all of them lack a reference implementation.
+///
+/// The interpreter executes such jets on the Bit Machine,
+/// so comparing it against the compiled program would not exercise any reference implementation.
+#[cfg(any(fuzzing, test))]
+fn only_fallback_jets(expression: &simplicityhl::ast::Expression) -> bool {
+    use std::collections::HashSet;
+
+    use miniscript::iter::TreeLike;
+    use simplicityhl::ast::{CallName, ExprTree};
+    use simplicityhl::simplicity::jet::Elements;
+
+    fn collect(expression: &simplicityhl::ast::Expression, jets: &mut HashSet<Elements>) {
+        for node in ExprTree::Expression(expression).pre_order_iter() {
+            let ExprTree::Call(call) = node else {
+                continue;
+            };
+            match call.name() {
+                CallName::Jet(jet) => {
+                    jets.insert(*jet);
+                }
+                CallName::Custom(function)
+                | CallName::Fold(function, _)
+                | CallName::ArrayFold(function, _)
+                | CallName::ForWhile(function, _) => collect(function.body(), jets),
+                _ => {}
+            }
+        }
+    }
+
+    let mut jets = HashSet::new();
+    collect(expression, &mut jets);
+    !jets.is_empty()
+        && jets
+            .into_iter()
+            .all(|jet| !simplicityhl::interpret::has_reference(jet))
+}
+
+#[cfg(any(fuzzing, test))]
+fn do_test(data: &[u8]) {
+    use arbitrary::Arbitrary;
+
+    use simplicityhl::error::WithFile;
+    use simplicityhl::well_typed::WellTypedProgram;
+    use simplicityhl::{ast, ArbitraryOfType, Arguments, WitnessValues};
+
+    let mut u = arbitrary::Unstructured::new(data);
+    let program = match WellTypedProgram::arbitrary(&mut u) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+    let program_text = program.to_string();
+    let ast_program = ast::Program::analyze(program.as_parse_program())
+        .with_file(program_text.as_str())
+        .unwrap_or_else(|error| panic!("Generated program should be well-typed\n\n{error}"));
+    if only_fallback_jets(ast_program.main()) {
+        return;
+    }
+    let arguments = match Arguments::arbitrary_of_type(&mut u, ast_program.parameters()) {
+        Ok(arguments) => arguments,
+        Err(..) => return,
+    };
+    let witness_values = match WitnessValues::arbitrary_of_type(&mut u, ast_program.witness_types())
+    {
+        Ok(witness_values) => witness_values,
+        Err(..) => return,
+    };
+    compare(&ast_program, &program_text, arguments, witness_values);
+}
+
+#[cfg(fuzzing)]
+libfuzzer_sys::fuzz_target!(|data| do_test(data));
+
+#[cfg(not(fuzzing))]
+fn main() {}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use simplicityhl::parse::ParseFromStr;
+    use simplicityhl::{ast, parse, Arguments, WitnessValues};
+
+    fn compare_text(program_text: &str, witness_text: &str) {
+        let parse_program = parse::Program::parse_from_str(program_text).unwrap();
+        let ast_program = ast::Program::analyze(&parse_program).unwrap();
+        let witness_values = WitnessValues::parse_from_str(witness_text).unwrap();
+        super::compare(
+            &ast_program,
+            program_text,
+            Arguments::default(),
+            witness_values,
+        );
+    }
+
+    #[test]
+    fn duplicate_crash() {
+        let data = base64::prelude::BASE64_STANDARD
+            .decode("Cg==")
+            .expect("base64 should be valid");
+        super::do_test(&data);
+    }
+
+    #[test]
+    fn random_seeds() {
+        for seed in 0u32..64 {
+            let data = (0u32..4096)
+                .map(|i| {
+                    (seed.wrapping_mul(0x9e37_79b9) ^ i.wrapping_mul(0x85eb_ca6b)).to_le_bytes()[1]
+                })
+                .collect::<Vec<u8>>();
+            super::do_test(&data);
+        }
+    }
+
+    #[test]
+    fn fallback_jets() {
+        let only_fallback_jets = |program_text: &str| {
+            let parse_program = parse::Program::parse_from_str(program_text).unwrap();
+            let ast_program = ast::Program::analyze(&parse_program).unwrap();
+            super::only_fallback_jets(ast_program.main())
+        };
+        assert!(!only_fallback_jets("fn main() {}"));
+        assert!(!only_fallback_jets(
+            "fn main() { assert!(jet::is_zero_8(0)); }"
+        ));
+        assert!(only_fallback_jets(
+            "fn f() -> u8 { jet::leftmost_16_8(0) }\nfn main() { let x: u8 = f(); }"
+        ));
+        assert!(!only_fallback_jets(
+            "fn f() -> u8 { jet::leftmost_16_8(0) }\nfn main() { assert!(jet::is_zero_8(f())); }"
+        ));
+    }
+
+    #[test]
+    fn debug_before_failure() {
+        let program_text = r#"fn main() {
+    let x: Either<u8, u16> = witness::X;
+    let y: u8 = dbg!(unwrap_left::<u16>(x));
+    assert!(jet::is_zero_8(dbg!(y)));
+}"#;
+        compare_text(
+            program_text,
+            "mod witness { const X: Either<u8, u16> = Left(0); }",
+        );
+        compare_text(
+            program_text,
+            "mod witness { const X: Either<u8, u16> = Left(1); }",
+        );
+        compare_text(
+            program_text,
+            "mod witness { const X: Either<u8, u16> = Right(1); }",
+        );
+    }
+}
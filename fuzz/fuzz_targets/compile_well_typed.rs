@@ -0,0 +1,59 @@
+#![cfg_attr(fuzzing, no_main)]
+
+#[cfg(any(fuzzing, test))]
+fn do_test(data: &[u8]) {
+    use arbitrary::Arbitrary;
+
+    use simplicityhl::error::WithFile;
+    use simplicityhl::well_typed::WellTypedProgram;
+    use simplicityhl::{ast, named, ArbitraryOfType, Arguments};
+
+    let mut u = arbitrary::Unstructured::new(data);
+    let program = match WellTypedProgram::arbitrary(&mut u) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+    let ast_program = ast::Program::analyze(program.as_parse_program())
+        .with_file(program.to_string())
+        .unwrap_or_else(|error| panic!("Generated program should be well-typed\n\n{error}"));
+    let arguments = match Arguments::arbitrary_of_type(&mut u, ast_program.parameters()) {
+        Ok(arguments) => arguments,
+        Err(..) => return,
+    };
+    let simplicity_named_construct = ast_program
+        .compile(arguments, false)
+        .with_file(program.to_string())
+        .expect("AST should compile with given arguments");
+    let _simplicity_commit = named::forget_names(&simplicity_named_construct);
+}
+
+#[cfg(fuzzing)]
+libfuzzer_sys::fuzz_target!(|data| do_test(data));
+
+#[cfg(not(fuzzing))]
+fn main() {}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+
+    #[test]
+    fn duplicate_crash() {
+        let data = base64::prelude::BASE64_STANDARD
+            .decode("Cg==")
+            .expect("base64 should be valid");
+        super::do_test(&data);
+    }
+
+    #[test]
+    fn random_seeds() {
+        for seed in 0u32..64 {
+            let data = (0u32..4096)
+                .map(|i| {
+                    (seed.wrapping_mul(0x9e37_79b9) ^ i.wrapping_mul(0x85eb_ca6b)).to_le_bytes()[1]
+                })
+                .collect::<Vec<u8>>();
+            super::do_test(&data);
+        }
+    }
+}
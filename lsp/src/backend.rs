@@ -1,46 +1,64 @@
-use ropey::Rope;
 use serde_json::Value;
 
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 
 use tower_lsp_server::jsonrpc::Result;
 use tower_lsp_server::lsp_types::{
-    CompletionOptions, CompletionParams, CompletionResponse, Diagnostic,
+    AnnotatedTextEdit, ChangeAnnotation, CodeActionKind, CodeActionOptions, CodeActionParams,
+    CodeActionProviderCapability, CodeActionResponse, CodeLens, CodeLensOptions, CodeLensParams,
+    CompletionOptions, CompletionParams, CompletionResponse, Diagnostic, DiagnosticSeverity,
     DidChangeConfigurationParams, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
     DidChangeWorkspaceFoldersParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
-    DidSaveTextDocumentParams, ExecuteCommandParams, GotoDefinitionParams, GotoDefinitionResponse,
+    DidSaveTextDocumentParams, DocumentChanges, DocumentFormattingParams, DocumentSymbolParams,
+    DocumentSymbolResponse, ExecuteCommandParams, GotoDefinitionParams, GotoDefinitionResponse,
     Hover, HoverParams, HoverProviderCapability, InitializeParams, InitializeResult,
-    InitializedParams, Location, MarkupContent, MarkupKind, MessageType, OneOf, Range,
-    ReferenceParams, SaveOptions, SemanticTokensParams, SemanticTokensResult, ServerCapabilities,
+    InitializedParams, InlayHint, InlayHintParams, Location, MarkupContent, MarkupKind,
+    MessageType, OneOf, OptionalVersionedTextDocumentIdentifier, PrepareRenameResponse, Range,
+    ReferenceParams, RenameOptions, RenameParams, SaveOptions, SemanticTokens,
+    SemanticTokensFullOptions, SemanticTokensOptions, SemanticTokensParams,
+    SemanticTokensRangeParams, SemanticTokensRangeResult, SemanticTokensResult,
+    SemanticTokensServerCapabilities, ServerCapabilities, SignatureHelp, SignatureHelpOptions,
+    SignatureHelpParams, SymbolInformation, TextDocumentEdit, TextDocumentPositionParams,
     TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
-    TextDocumentSyncSaveOptions, Uri, WorkDoneProgressOptions, WorkspaceFoldersServerCapabilities,
-    WorkspaceServerCapabilities,
+    TextDocumentSyncSaveOptions, TextEdit, Uri, WorkDoneProgressOptions, WorkspaceEdit,
+    WorkspaceFoldersServerCapabilities, WorkspaceServerCapabilities, WorkspaceSymbol,
+    WorkspaceSymbolParams,
 };
-use tower_lsp_server::{Client, LanguageServer};
+use tower_lsp_server::{Client, LanguageServer, UriExt};
 
-use simplicityhl::{
-    ast,
-    error::{RichError, WithFile},
-    parse,
-    parse::ParseFromStr,
-};
+use simplicityhl::parse;
 
-use crate::completion::{self, CompletionProvider};
+use crate::code_actions;
+use crate::code_lens;
+use crate::completion::{self, scope::Scope, CompletionProvider};
+use crate::document::{Analysis, Document, Snapshot};
 use crate::error::LspError;
-use crate::function::Functions;
+use crate::hover;
+use crate::inlay_hints;
+use crate::key_files::{self, KeyFile};
+use crate::rename;
+use crate::semantic_tokens;
+use crate::signature_help;
+use crate::symbols;
 use crate::utils::{
-    find_all_references, find_function_name_range, find_related_call, get_call_span,
-    get_comments_from_lines, position_to_span, span_contains, span_to_positions,
+    document_range, find_all_references, find_function_name_range, find_related_call,
+    get_call_span, position_to_byte, position_to_span, span_contains, span_to_positions,
 };
 
-#[derive(Debug)]
-struct Document {
-    functions: Functions,
-    text: Rope,
-}
+/// Delay between the last change of a document and its analysis.
+///
+/// Requests that depend on positions in the document analyze it without delay.
+const ANALYSIS_DELAY: Duration = Duration::from_millis(200);
+
+/// Function that analyzes the given version of a program text, given its previous analysis.
+type Analyzer = fn(&str, i32, &Analysis) -> Analysis;
 
 #[derive(Debug)]
 pub struct Backend {
@@ -48,49 +66,63 @@ pub struct Backend {
 
     document_map: Arc<RwLock<HashMap<Uri, Document>>>,
 
+    /// Delayed analyses of changed documents.
+    ///
+    /// A pending analysis is cancelled when its document changes again.
+    pending_analyses: Mutex<HashMap<Uri, JoinHandle<()>>>,
+
     completion_provider: CompletionProvider,
-}
 
-struct TextDocumentItem<'a> {
-    uri: Uri,
-    text: &'a str,
-    version: Option<i32>,
+    /// Whether the client can ask the user to confirm annotated edits of a workspace edit.
+    change_annotation_support: OnceLock<bool>,
+
+    /// Whether the client can be asked to request the code lenses again.
+    code_lens_refresh_support: OnceLock<bool>,
+
+    /// Workspace folders whose `.simf` files are searched for workspace symbols.
+    workspace_folders: Mutex<Vec<Uri>>,
+
+    /// Function that analyzes a version of a program.
+    analyzer: Analyzer,
 }
 
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        #[allow(deprecated)]
+        let folders = match params.workspace_folders {
+            Some(folders) => folders.into_iter().map(|folder| folder.uri).collect(),
+            None => params.root_uri.into_iter().collect(),
+        };
+        *self
+            .workspace_folders
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = folders;
+
+        let code_lens_refresh_support = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.code_lens.as_ref())
+            .and_then(|code_lens| code_lens.refresh_support)
+            .unwrap_or(false);
+        let _ = self
+            .code_lens_refresh_support
+            .set(code_lens_refresh_support);
+
+        let workspace_edit = params
+            .capabilities
+            .workspace
+            .and_then(|workspace| workspace.workspace_edit);
+        let change_annotation_support = workspace_edit.is_some_and(|edit| {
+            edit.document_changes == Some(true) && edit.change_annotation_support.is_some()
+        });
+        let _ = self
+            .change_annotation_support
+            .set(change_annotation_support);
+
         Ok(InitializeResult {
             server_info: None,
-            capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Options(
-                    TextDocumentSyncOptions {
-                        open_close: Some(true),
-                        change: Some(TextDocumentSyncKind::FULL),
-                        save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
-                            include_text: Some(true),
-                        })),
-                        ..Default::default()
-                    },
-                )),
-                completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(false),
-                    trigger_characters: Some(vec![":".to_string(), "<".to_string()]),
-                    work_done_progress_options: WorkDoneProgressOptions::default(),
-                    all_commit_characters: None,
-                    completion_item: None,
-                }),
-                workspace: Some(WorkspaceServerCapabilities {
-                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
-                        supported: Some(true),
-                        change_notifications: Some(OneOf::Left(true)),
-                    }),
-                    file_operations: None,
-                }),
-                hover_provider: Some(HoverProviderCapability::Simple(true)),
-                definition_provider: Some(OneOf::Left(true)),
-                references_provider: Some(OneOf::Left(true)),
-                ..ServerCapabilities::default()
-            },
+            capabilities: server_capabilities(),
         })
     }
 
@@ -100,7 +132,14 @@ impl LanguageServer for Backend {
         Ok(())
     }
 
-    async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {}
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        let mut folders = self
+            .workspace_folders
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        folders.retain(|uri| !params.event.removed.iter().any(|folder| &folder.uri == uri));
+        folders.extend(params.event.added.into_iter().map(|folder| folder.uri));
+    }
 
     async fn did_change_configuration(&self, _: DidChangeConfigurationParams) {}
 
@@ -111,53 +150,214 @@ impl LanguageServer for Backend {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        self.on_change(TextDocumentItem {
-            uri: params.text_document.uri,
-            text: &params.text_document.text,
-            version: Some(params.text_document.version),
-        })
-        .await;
+        let uri = params.text_document.uri;
+        self.document_map.write().await.insert(
+            uri.clone(),
+            Document::new(&params.text_document.text, params.text_document.version),
+        );
+        self.analyze(&uri).await;
+        self.update_code_lenses(&uri).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        self.on_change(TextDocumentItem {
-            text: &params.content_changes[0].text,
-            uri: params.text_document.uri,
-            version: Some(params.text_document.version),
-        })
-        .await;
+        let uri = params.text_document.uri;
+        {
+            let mut documents = self.document_map.write().await;
+            let Some(doc) = documents.get_mut(&uri) else {
+                return;
+            };
+            for change in &params.content_changes {
+                // Changes of parts of the text cannot be applied to a text that diverged
+                if !doc.synced && change.range.is_some() {
+                    continue;
+                }
+                match doc.apply_change(change) {
+                    Ok(()) if change.range.is_none() => doc.synced = true,
+                    Ok(()) => {}
+                    Err(err) => {
+                        doc.synced = false;
+                        self.client
+                            .log_message(
+                                MessageType::ERROR,
+                                format!("Catch error while applying change: {err}"),
+                            )
+                            .await;
+                    }
+                }
+            }
+            if !doc.synced {
+                return;
+            }
+            doc.version = params.text_document.version;
+        }
+        self.schedule_analysis(uri);
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
-        if let Some(text) = params.text {
-            self.on_change(TextDocumentItem {
-                uri: params.text_document.uri,
-                text: &text,
-                version: None,
-            })
-            .await;
-        }
+        let uri = params.text_document.uri;
+        self.analyze(&uri).await;
+        self.update_code_lenses(&uri).await;
     }
 
-    async fn did_close(&self, _: DidCloseTextDocumentParams) {}
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        if let Some(pending) = self.pending_analyses().remove(&uri) {
+            pending.abort();
+        }
+        self.document_map.write().await.remove(&uri);
+    }
 
     async fn semantic_tokens_full(
         &self,
-        _: SemanticTokensParams,
+        params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
-        Ok(None)
+        let uri = &params.text_document.uri;
+        let doc = self.analyzed_document(uri).await?;
+
+        let data = semantic_tokens::semantic_tokens(&doc.analysis.syntax, &doc.text, None)?;
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        })))
     }
 
-    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        let uri = &params.text_document.uri;
+        let doc = self.analyzed_document(uri).await?;
+
+        let data =
+            semantic_tokens::semantic_tokens(&doc.analysis.syntax, &doc.text, Some(params.range))?;
+        Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        })))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let uri = &params.text_document.uri;
+        let doc = self.analyzed_document(uri).await?;
+
+        let hints = inlay_hints::inlay_hints(&doc.analysis, &doc.text, params.range)?;
+        Ok(Some(hints))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = &params.text_document.uri;
+        let has_lenses = |documents: &HashMap<Uri, Document>| {
+            documents
+                .get(uri)
+                .ok_or(LspError::DocumentNotFound(uri.to_owned()))
+                .map(|doc| doc.code_lenses.is_some())
+        };
+        // The request may arrive before the lenses of the opened document are computed
+        if !has_lenses(&*self.document_map.read().await)? {
+            self.update_code_lenses(uri).await;
+        }
         let documents = self.document_map.read().await;
-        let uri = &params.text_document_position.text_document.uri;
+        let doc = synced_document(documents.get(uri), uri)?;
+        Ok(doc.code_lenses.clone())
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let text = self
+            .latest_document(&params.text_document.uri)
+            .await?
+            .text
+            .to_string();
 
-        let doc = documents
-            .get(uri)
-            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+        let symbols = symbols::document_symbols(&text)?;
+        Ok(symbols.map(DocumentSymbolResponse::Nested))
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<OneOf<Vec<SymbolInformation>, Vec<WorkspaceSymbol>>>> {
+        let documents: Vec<(Uri, String)> = self
+            .document_map
+            .read()
+            .await
+            .iter()
+            .filter(|(_, doc)| doc.synced)
+            .map(|(uri, doc)| (uri.clone(), doc.text.to_string()))
+            .collect();
+        let folders: Vec<PathBuf> = self
+            .workspace_folders
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter_map(|uri| uri.to_file_path().map(Cow::into_owned))
+            .collect();
+
+        // Reading the workspace folders blocks
+        let symbols = tokio::task::spawn_blocking(move || {
+            symbols::workspace_symbols(&documents, &folders, &params.query)
+        })
+        .await
+        .map_err(|err| LspError::Internal(err.to_string()))?;
+        Ok(Some(OneOf::Right(symbols)))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = &params.text_document.uri;
+        let doc = self.analyzed_document(uri).await?;
+
+        let actions =
+            code_actions::code_actions(&doc, uri, params.range, &params.context.diagnostics)?;
+        Ok(Some(actions))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let documents = self.document_map.read().await;
+        let uri = &params.text_document.uri;
+
+        let doc = synced_document(documents.get(uri), uri)?;
+
+        let text = doc.text.to_string();
+        // Programs with syntax errors are left untouched; the error is already reported as diagnostic.
+        let Ok(formatted) = simplicityhl::format::format(&text) else {
+            return Ok(None);
+        };
+        if formatted == text {
+            return Ok(Some(vec![]));
+        }
+
+        Ok(Some(vec![TextEdit {
+            range: document_range(&doc.text)?,
+            new_text: formatted,
+        }]))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let doc = self.latest_document(uri).await?;
 
         let pos = params.text_document_position.position;
 
+        if let Some(path) = uri.to_file_path() {
+            if let Some(kind) = KeyFile::from_path(&path) {
+                let Some(program) = key_file_program(&self.document_map, &path)
+                    .await
+                    .and_then(|analysis| analysis.program.clone())
+                else {
+                    return Ok(None);
+                };
+                let items = key_files::completions(
+                    &doc.text.to_string(),
+                    position_to_byte(&doc.text, pos)?,
+                    kind,
+                    &program,
+                )?;
+                return Ok(Some(CompletionResponse::Array(items)));
+            }
+        }
+
         let line = doc
             .text
             .lines()
@@ -174,24 +374,48 @@ impl LanguageServer for Backend {
             "RopeSlice to str conversion failed".into(),
         ))?;
 
+        let parsed = &doc.analysis.parsed;
+        let offset = parsed.offset(&doc.text.to_string(), position_to_byte(&doc.text, pos)?);
+        let key_files = match uri.to_file_path() {
+            Some(path) => program_key_files(&self.document_map, &path).await,
+            None => vec![],
+        };
+        let scope = Scope::new(parsed, offset, &key_files);
+
         let completions = self
             .completion_provider
-            .process_completions(prefix, &doc.functions.functions_and_docs())
+            .process_completions(prefix, &doc.analysis.functions.functions_and_docs(), &scope)
             .map(CompletionResponse::Array);
 
         Ok(completions)
     }
 
-    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
-        let documents = self.document_map.read().await;
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
         let uri = &params.text_document_position_params.text_document.uri;
+        // The text is usually not parsable while typing arguments, so the latest analysis is enough
+        let doc = self.latest_document(uri).await?;
+
+        let position = params.text_document_position_params.position;
+        let prefix = doc
+            .text
+            .byte_slice(..position_to_byte(&doc.text, position)?)
+            .to_string();
 
-        let doc = documents
-            .get(uri)
-            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
-        let functions = doc.functions.functions();
+        Ok(signature_help::signature_help(
+            &prefix,
+            &doc.analysis.functions,
+        ))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let doc = self.analyzed_document(uri).await?;
+        let functions = doc.analysis.functions.functions();
 
         let token_pos = params.text_document_position_params.position;
+        if let Some(hover) = hover::symbol_hover(&doc, token_pos)? {
+            return Ok(Some(hover));
+        }
 
         let token_span = position_to_span(token_pos)?;
         let Ok(Some(call)) = find_related_call(&functions, token_span) else {
@@ -217,12 +441,9 @@ impl LanguageServer for Backend {
                 )
             }
             parse::CallName::Custom(func) => {
-                let (function, function_doc) =
-                    doc.functions
-                        .get(func.as_inner())
-                        .ok_or(LspError::FunctionNotFound(format!(
-                            "Function {func} is not found"
-                        )))?;
+                let (function, function_doc) = doc.analysis.functions.get(func.as_inner()).ok_or(
+                    LspError::FunctionNotFound(format!("Function {func} is not found")),
+                )?;
 
                 let template = completion::function_to_template(function, function_doc);
                 format!(
@@ -260,13 +481,9 @@ impl LanguageServer for Backend {
         &self,
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
-        let documents = self.document_map.read().await;
         let uri = &params.text_document_position_params.text_document.uri;
-
-        let doc = documents
-            .get(uri)
-            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
-        let functions = doc.functions.functions();
+        let doc = self.analyzed_document(uri).await?;
+        let functions = doc.analysis.functions.functions();
 
         let token_position = params.text_document_position_params.position;
         let token_span = position_to_span(token_position)?;
@@ -291,12 +508,9 @@ impl LanguageServer for Backend {
 
         match call.name() {
             simplicityhl::parse::CallName::Custom(func) => {
-                let function =
-                    doc.functions
-                        .get_func(func.as_inner())
-                        .ok_or(LspError::FunctionNotFound(format!(
-                            "Function {func} is not found"
-                        )))?;
+                let function = doc.analysis.functions.get_func(func.as_inner()).ok_or(
+                    LspError::FunctionNotFound(format!("Function {func} is not found")),
+                )?;
 
                 let (start, end) = span_to_positions(function.as_ref())?;
                 Ok(Some(GotoDefinitionResponse::from(Location::new(
@@ -308,14 +522,60 @@ impl LanguageServer for Backend {
         }
     }
 
-    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
-        let documents = self.document_map.read().await;
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let uri = &params.text_document.uri;
+        let doc = self.analyzed_document(uri).await?;
+
+        let Some(target) = rename::find_target(&doc.analysis.syntax, &doc.text, params.position)?
+        else {
+            return Ok(None);
+        };
+        let range = rename::target_range(&doc.analysis.syntax, &doc.text, &target)?;
+        Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
+            range,
+            placeholder: target.name,
+        }))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
         let uri = &params.text_document_position.text_document.uri;
+        let doc = self.analyzed_document(uri).await?;
+
+        let position = params.text_document_position.position;
+        let Some(target) = rename::find_target(&doc.analysis.syntax, &doc.text, position)? else {
+            return Ok(None);
+        };
+        let edits =
+            rename::rename_in_document(&doc.analysis.syntax, &doc.text, &target, &params.new_name)?;
+
+        // Witness and parameter names are also the keys of the sibling `.wit` and `.args` files
+        let mut key_file_edits = vec![];
+        if let (Some(kind), Some(path)) = (target.key_file(), uri.to_file_path()) {
+            for file in key_files::sibling_files(&path, kind) {
+                let Ok(json) = std::fs::read_to_string(&file) else {
+                    continue;
+                };
+                let Some(file_edits) =
+                    key_files::rename_json_key(&json, &target.name, &params.new_name)?
+                else {
+                    continue;
+                };
+                if let Some(file_uri) = Uri::from_file_path(&file) {
+                    key_file_edits.push((file_uri, file_edits));
+                }
+            }
+        }
+
+        Ok(Some(self.workspace_edit(uri, edits, key_file_edits)))
+    }
 
-        let doc = documents
-            .get(uri)
-            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
-        let functions = doc.functions.functions();
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let doc = self.analyzed_document(uri).await?;
+        let functions = doc.analysis.functions.functions();
 
         let token_position = params.text_document_position.position;
 
@@ -370,144 +630,611 @@ impl Backend {
         Self {
             client,
             document_map: Arc::new(RwLock::new(HashMap::new())),
+            pending_analyses: Mutex::new(HashMap::new()),
             completion_provider: CompletionProvider::new(),
+            change_annotation_support: OnceLock::new(),
+            code_lens_refresh_support: OnceLock::new(),
+            workspace_folders: Mutex::new(Vec::new()),
+            analyzer: Analysis::new,
+        }
+    }
+
+    /// Combine the edits of the document and the edits of its key files into one workspace edit.
+    ///
+    /// If the client supports change annotations, then the user has to confirm the edits of
+    /// the key files, because these files are not part of the program.
+    fn workspace_edit(
+        &self,
+        uri: &Uri,
+        edits: Vec<TextEdit>,
+        key_file_edits: Vec<(Uri, Vec<TextEdit>)>,
+    ) -> WorkspaceEdit {
+        let change_annotation_support = self.change_annotation_support.get() == Some(&true);
+        if !change_annotation_support || key_file_edits.is_empty() {
+            return WorkspaceEdit::new(
+                std::iter::once((uri.clone(), edits))
+                    .chain(key_file_edits)
+                    .collect(),
+            );
+        }
+
+        let annotation_id = "rename-keys".to_string();
+        let document_edit =
+            |uri: Uri, edits: Vec<OneOf<TextEdit, AnnotatedTextEdit>>| TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+                edits,
+            };
+        let mut document_edits = vec![document_edit(
+            uri.clone(),
+            edits.into_iter().map(OneOf::Left).collect(),
+        )];
+        document_edits.extend(key_file_edits.into_iter().map(|(uri, edits)| {
+            let edits = edits
+                .into_iter()
+                .map(|text_edit| {
+                    OneOf::Right(AnnotatedTextEdit {
+                        text_edit,
+                        annotation_id: annotation_id.clone(),
+                    })
+                })
+                .collect();
+            document_edit(uri, edits)
+        }));
+
+        WorkspaceEdit {
+            changes: None,
+            document_changes: Some(DocumentChanges::Edits(document_edits)),
+            change_annotations: Some(HashMap::from([(
+                annotation_id,
+                ChangeAnnotation {
+                    label: "Rename keys in witness and argument files".to_string(),
+                    needs_confirmation: Some(true),
+                    description: None,
+                },
+            )])),
         }
     }
 
-    /// Function which executed on change of file (`did_save`, `did_open` or `did_change` methods)
-    async fn on_change(&self, params: TextDocumentItem<'_>) {
-        let (err, document) = parse_program(params.text);
+    /// Analyze the document after [`ANALYSIS_DELAY`], unless it changes again before that.
+    fn schedule_analysis(&self, uri: Uri) {
+        let client = self.client.clone();
+        let documents = Arc::clone(&self.document_map);
+        let task_uri = uri.clone();
+        let analyzer = self.analyzer;
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(ANALYSIS_DELAY).await;
+            analyze(&client, &documents, &task_uri, analyzer).await;
+        });
 
-        let mut documents = self.document_map.write().await;
-        if let Some(doc) = document {
-            documents.insert(params.uri.clone(), doc);
-        } else if let Some(doc) = documents.get_mut(&params.uri) {
-            doc.text = Rope::from_str(params.text);
+        if let Some(stale) = self.pending_analyses().insert(uri, task) {
+            stale.abort();
         }
+    }
 
-        match err {
-            None => {
-                self.client
-                    .publish_diagnostics(params.uri.clone(), vec![], params.version)
-                    .await;
+    fn pending_analyses(&self) -> std::sync::MutexGuard<'_, HashMap<Uri, JoinHandle<()>>> {
+        // The map stays consistent even if a thread panicked while holding the lock
+        self.pending_analyses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Return the current text of the document together with its analysis.
+    ///
+    /// The document is analyzed right away if its analysis is out of date,
+    /// so positions in the analysis match positions in the text.
+    async fn analyzed_document(&self, uri: &Uri) -> std::result::Result<Snapshot, LspError> {
+        loop {
+            self.analyze(uri).await;
+
+            let documents = self.document_map.read().await;
+            let doc = synced_document(documents.get(uri), uri)?;
+            // The document may have changed again during the analysis
+            if doc.is_analyzed() {
+                return Ok(Snapshot {
+                    text: doc.text.clone(),
+                    analysis: Arc::clone(&doc.analysis),
+                });
             }
-            Some(err) => {
-                let (start, end) = match span_to_positions(err.span()) {
-                    Ok(result) => result,
-                    Err(err) => {
-                        self.client
-                            .log_message(
-                                MessageType::ERROR,
-                                format!("Catch error while parsing span: {err}"),
-                            )
-                            .await;
-                        return;
-                    }
-                };
+        }
+    }
+
+    /// Analyze the current version of the document, unless the analysis is already up to date.
+    async fn analyze(&self, uri: &Uri) {
+        analyze(&self.client, &self.document_map, uri, self.analyzer).await;
+    }
 
+    /// Compute the code lenses of the current version of the document
+    /// and ask the client to show them.
+    ///
+    /// Compiling the program is too slow for every keystroke, so this happens on open and on save.
+    async fn update_code_lenses(&self, uri: &Uri) {
+        let Ok(doc) = self.analyzed_document(uri).await else {
+            return;
+        };
+        let path = uri.to_file_path().map(Cow::into_owned);
+        let lenses = match tokio::task::spawn_blocking(move || {
+            code_lens::code_lenses(&doc, path.as_deref())
+        })
+        .await
+        {
+            Ok(Ok(lenses)) => lenses,
+            Ok(Err(err)) => {
                 self.client
-                    .publish_diagnostics(
-                        params.uri.clone(),
-                        vec![Diagnostic::new_simple(
-                            Range::new(start, end),
-                            err.error().to_string(),
-                        )],
-                        params.version,
+                    .log_message(
+                        MessageType::ERROR,
+                        format!("Catch error while computing code lenses: {err}"),
                     )
                     .await;
+                vec![]
             }
+            Err(_) => vec![],
+        };
+
+        if let Some(doc) = self.document_map.write().await.get_mut(uri) {
+            doc.code_lenses = Some(lenses);
+        }
+        if self.code_lens_refresh_support.get() == Some(&true) {
+            let _ = self.client.code_lens_refresh().await;
         }
     }
+
+    /// Return the current text of the document together with its latest analysis,
+    /// which may belong to an older text.
+    ///
+    /// This is cheap and suitable for requests that are sent on every keystroke.
+    async fn latest_document(&self, uri: &Uri) -> std::result::Result<Snapshot, LspError> {
+        let documents = self.document_map.read().await;
+        let doc = synced_document(documents.get(uri), uri)?;
+
+        Ok(Snapshot {
+            text: doc.text.clone(),
+            analysis: Arc::clone(&doc.analysis),
+        })
+    }
+}
+
+/// Return the document that was found for the given URI,
+/// unless its text diverged from the text of the client.
+fn synced_document<'a>(
+    doc: Option<&'a Document>,
+    uri: &Uri,
+) -> std::result::Result<&'a Document, LspError> {
+    let doc = doc.ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+    if !doc.synced {
+        return Err(LspError::DocumentUnsynced(uri.to_owned()));
+    }
+    Ok(doc)
 }
 
-/// Create [`Document`] using parsed program and code.
-fn create_document(program: &simplicityhl::parse::Program, text: &str) -> Document {
-    let mut document = Document {
-        functions: Functions::new(),
-        text: Rope::from_str(text),
+/// Return the capabilities of the server.
+fn server_capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::INCREMENTAL),
+                save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                    include_text: Some(false),
+                })),
+                ..Default::default()
+            },
+        )),
+        completion_provider: Some(CompletionOptions {
+            resolve_provider: Some(false),
+            trigger_characters: Some(vec![":".to_string(), "<".to_string(), "\"".to_string()]),
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+            all_commit_characters: None,
+            completion_item: None,
+        }),
+        workspace: Some(WorkspaceServerCapabilities {
+            workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                supported: Some(true),
+                change_notifications: Some(OneOf::Left(true)),
+            }),
+            file_operations: None,
+        }),
+        signature_help_provider: Some(SignatureHelpOptions {
+            trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+            retrigger_characters: None,
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Right(RenameOptions {
+            prepare_provider: Some(true),
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+        })),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        inlay_hint_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        workspace_symbol_provider: Some(OneOf::Left(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+            code_action_kinds: Some(vec![
+                CodeActionKind::QUICKFIX,
+                CodeActionKind::REFACTOR_EXTRACT,
+            ]),
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+            resolve_provider: None,
+        })),
+        code_lens_provider: Some(CodeLensOptions {
+            resolve_provider: Some(false),
+        }),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: semantic_tokens::legend(),
+                range: Some(true),
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                work_done_progress_options: WorkDoneProgressOptions::default(),
+            },
+        )),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// Analyze the current version of the document and publish its diagnostics,
+/// unless the analysis is already up to date.
+///
+/// The analysis is discarded if the document changes while it is running.
+/// If the analysis fails, then a failed analysis is stored for this version,
+/// so requests that wait for the analysis do not retry forever.
+async fn analyze(
+    client: &Client,
+    documents: &RwLock<HashMap<Uri, Document>>,
+    uri: &Uri,
+    analyzer: Analyzer,
+) {
+    if let Some(path) = uri.to_file_path() {
+        if let Some(kind) = KeyFile::from_path(&path) {
+            if documents
+                .read()
+                .await
+                .get(uri)
+                .is_some_and(|doc| doc.synced && !doc.is_analyzed())
+            {
+                validate_key_file(client, documents, uri, kind, &path).await;
+            }
+            return;
+        }
+    }
+
+    let (text, version, previous) = {
+        let documents = documents.read().await;
+        let Some(doc) = documents.get(uri) else {
+            return;
+        };
+        if doc.is_analyzed() || !doc.synced {
+            return;
+        }
+        (doc.text.to_string(), doc.version, Arc::clone(&doc.analysis))
     };
 
-    program
-        .items()
-        .iter()
-        .filter_map(|item| {
-            if let parse::Item::Function(func) = item {
-                Some(func)
-            } else {
-                None
+    let task_previous = Arc::clone(&previous);
+    let (analysis, failed) =
+        match tokio::task::spawn_blocking(move || analyzer(&text, version, &task_previous)).await {
+            Ok(analysis) => (analysis, false),
+            Err(err) => {
+                client
+                    .log_message(
+                        MessageType::ERROR,
+                        format!("Catch error while analyzing {uri:?}: {err}"),
+                    )
+                    .await;
+                (Analysis::failed(version, &previous), true)
             }
+        };
+    let analysis = Arc::new(analysis);
+
+    {
+        let mut documents = documents.write().await;
+        let Some(doc) = documents.get_mut(uri) else {
+            return;
+        };
+        if doc.version != version || doc.is_analyzed() {
+            return;
+        }
+        doc.analysis = Arc::clone(&analysis);
+    }
+    if failed {
+        return;
+    }
+
+    publish_diagnostics(client, uri, version, &analysis).await;
+
+    // The open key files of the program are validated against its new version
+    let Some(program_path) = uri.to_file_path() else {
+        return;
+    };
+    let key_file_documents: Vec<_> = documents
+        .read()
+        .await
+        .keys()
+        .filter_map(|key_file_uri| {
+            let path = key_file_uri.to_file_path()?.into_owned();
+            let kind = KeyFile::from_path(&path)?;
+            key_files::program_candidates(&path)
+                .contains(&program_path.to_path_buf())
+                .then(|| (key_file_uri.clone(), kind, path))
         })
-        .for_each(|func| {
-            let start_line = u32::try_from(func.as_ref().start.line.get()).unwrap_or_default() - 1;
+        .collect();
+    for (key_file_uri, kind, path) in key_file_documents {
+        validate_key_file(client, documents, &key_file_uri, kind, &path).await;
+    }
+}
 
-            document.functions.insert(
-                func.name().to_string(),
-                func.to_owned(),
-                get_comments_from_lines(start_line, &document.text),
-            );
+/// Return the analysis of the program that the key file belongs to,
+/// or `None` if there is no such program.
+///
+/// Open programs take precedence over their files on disk.
+async fn key_file_program(
+    documents: &RwLock<HashMap<Uri, Document>>,
+    key_file: &Path,
+) -> Option<Arc<Analysis>> {
+    for candidate in key_files::program_candidates(key_file) {
+        let Some(uri) = Uri::from_file_path(&candidate) else {
+            continue;
+        };
+        if let Some(doc) = documents.read().await.get(&uri) {
+            return Some(Arc::clone(&doc.analysis));
+        }
+        let Ok(text) = std::fs::read_to_string(&candidate) else {
+            continue;
+        };
+        let analysis =
+            tokio::task::spawn_blocking(move || Analysis::new(&text, 0, &Analysis::default()))
+                .await
+                .ok()?;
+        return Some(Arc::new(analysis));
+    }
+    None
+}
+
+/// Return the kinds and texts of the `.wit` and `.args` files of the program.
+///
+/// Open key files take precedence over their files on disk.
+async fn program_key_files(
+    documents: &RwLock<HashMap<Uri, Document>>,
+    program: &Path,
+) -> Vec<(KeyFile, String)> {
+    let mut files = Vec::new();
+    for kind in [KeyFile::Witness, KeyFile::Arguments] {
+        for path in key_files::sibling_files(program, kind) {
+            let open = match Uri::from_file_path(&path) {
+                Some(uri) => documents
+                    .read()
+                    .await
+                    .get(&uri)
+                    .filter(|doc| doc.synced)
+                    .map(|doc| doc.text.to_string()),
+                None => None,
+            };
+            if let Some(json) = open.or_else(|| std::fs::read_to_string(&path).ok()) {
+                files.push((kind, json));
+            }
+        }
+    }
+    files
+}
+
+/// Validate the current version of the `.wit` or `.args` file against its program
+/// and publish the diagnostics.
+async fn validate_key_file(
+    client: &Client,
+    documents: &RwLock<HashMap<Uri, Document>>,
+    uri: &Uri,
+    kind: KeyFile,
+    path: &Path,
+) {
+    let (json, version) = {
+        let documents = documents.read().await;
+        let Some(doc) = documents.get(uri) else {
+            return;
+        };
+        (doc.text.to_string(), doc.version)
+    };
+
+    let analysis = key_file_program(documents, path).await;
+    let program = analysis
+        .as_ref()
+        .and_then(|analysis| analysis.program.as_deref());
+    let diagnostics = match key_files::diagnostics(&json, kind, program) {
+        Ok(mut diagnostics) => {
+            let note = match &analysis {
+                None => Some("No program found for this file, so names and types are not checked"),
+                Some(analysis) if analysis.program.is_none() => {
+                    Some("The program has errors, so names and types are not checked")
+                }
+                Some(_) => None,
+            };
+            if let Some(note) = note {
+                diagnostics.push(Diagnostic {
+                    severity: Some(DiagnosticSeverity::INFORMATION),
+                    ..Diagnostic::new_simple(Range::default(), note.to_string())
+                });
+            }
+            Some(diagnostics)
+        }
+        Err(err) => {
+            client
+                .log_message(
+                    MessageType::ERROR,
+                    format!("Catch error while validating {uri:?}: {err}"),
+                )
+                .await;
+            None
+        }
+    };
+
+    {
+        let mut documents = documents.write().await;
+        let Some(doc) = documents.get_mut(uri) else {
+            return;
+        };
+        if doc.version != version {
+            return;
+        }
+        // The version counts as validated even if validation failed, so it is not retried
+        doc.analysis = Arc::new(Analysis {
+            version: Some(version),
+            ..Analysis::default()
         });
+    }
 
-    document
+    if let Some(diagnostics) = diagnostics {
+        client
+            .publish_diagnostics(uri.clone(), diagnostics, Some(version))
+            .await;
+    }
 }
 
-/// Parse program using [`simplicityhl`] compiler and return [`RichError`],
-/// which used in Diagnostic. Also create [`Document`] from parsed program.
-fn parse_program(text: &str) -> (Option<RichError>, Option<Document>) {
-    let program = match parse::Program::parse_from_str(text) {
-        Ok(p) => p,
-        Err(e) => return (Some(e), None),
+/// Publish the error of the analysis as diagnostic, or clear the diagnostics if there is no error.
+async fn publish_diagnostics(client: &Client, uri: &Uri, version: i32, analysis: &Analysis) {
+    let Some(err) = &analysis.error else {
+        client
+            .publish_diagnostics(uri.clone(), vec![], Some(version))
+            .await;
+        return;
+    };
+
+    let (start, end) = match span_to_positions(err.span()) {
+        Ok(result) => result,
+        Err(err) => {
+            client
+                .log_message(
+                    MessageType::ERROR,
+                    format!("Catch error while parsing span: {err}"),
+                )
+                .await;
+            return;
+        }
     };
 
-    (
-        ast::Program::analyze(&program).with_file(text).err(),
-        Some(create_document(&program, text)),
-    )
+    client
+        .publish_diagnostics(
+            uri.clone(),
+            vec![Diagnostic::new_simple(
+                Range::new(start, end),
+                err.error().to_string(),
+            )],
+            Some(version),
+        )
+        .await;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn sample_program() -> &'static str {
-        "fn add(a: u32, b: u32) -> u32 { let (_, res): (bool, u32) = jet::add_32(a, b); res }
-         fn main() {}"
-    }
-
-    fn invalid_program_on_ast() -> &'static str {
-        "fn add(a: u32, b: u32) -> u32 {}"
-    }
-
-    fn invalid_program_on_parsing() -> &'static str {
-        "fn add(a: u32 b: u32) -> u32 {}"
-    }
+    use tower_lsp_server::lsp_types::{
+        FormattingOptions, PartialResultParams, Position, TextDocumentContentChangeEvent,
+        TextDocumentIdentifier, TextDocumentItem, VersionedTextDocumentIdentifier,
+        WorkDoneProgressParams,
+    };
+    use tower_lsp_server::LspService;
 
-    #[test]
-    fn test_parse_program_valid() {
-        let (err, doc) = parse_program(sample_program());
-        assert!(err.is_none(), "Expected no parsing error");
-        let doc = doc.expect("Expected Some(Document)");
-        assert_eq!(doc.functions.map.len(), 2);
+    #[tokio::test]
+    async fn test_failed_analysis() {
+        let (service, _socket) = LspService::new(|client| Backend {
+            analyzer: |_, _, _| panic!("Analysis failed"),
+            ..Backend::new(client)
+        });
+        let backend = service.inner();
+        let uri = Uri::from_str("file:///tmp/failed.simf").unwrap();
+        let text_document = TextDocumentIdentifier { uri: uri.clone() };
+        let work_done_progress_params = WorkDoneProgressParams::default();
+
+        let requests = async {
+            backend
+                .did_open(DidOpenTextDocumentParams {
+                    text_document: TextDocumentItem::new(
+                        uri.clone(),
+                        "simplicityhl".to_string(),
+                        0,
+                        "fn main() {}".to_string(),
+                    ),
+                })
+                .await;
+            let hover = backend
+                .hover(HoverParams {
+                    text_document_position_params: TextDocumentPositionParams::new(
+                        text_document.clone(),
+                        Position::new(0, 3),
+                    ),
+                    work_done_progress_params: work_done_progress_params.clone(),
+                })
+                .await;
+            let lenses = backend
+                .code_lens(CodeLensParams {
+                    text_document: text_document.clone(),
+                    work_done_progress_params: work_done_progress_params.clone(),
+                    partial_result_params: PartialResultParams::default(),
+                })
+                .await;
+            let hints = backend
+                .inlay_hint(InlayHintParams {
+                    text_document: text_document.clone(),
+                    range: Range::new(Position::new(0, 0), Position::new(0, 12)),
+                    work_done_progress_params,
+                })
+                .await;
+            (hover, lenses, hints)
+        };
+        let (hover, lenses, hints) = tokio::time::timeout(Duration::from_secs(10), requests)
+            .await
+            .expect("Requests should not wait for a failed analysis");
+        assert_eq!(hover.unwrap(), None);
+        assert_eq!(lenses.unwrap(), Some(vec![]));
+        assert!(hints.unwrap().is_some_and(|hints| hints.is_empty()));
     }
 
-    #[test]
-    fn test_parse_program_invalid_ast() {
-        let (err, doc) = parse_program(invalid_program_on_ast());
-        assert!(
-            err.unwrap()
-                .to_string()
-                .contains("Expected expression of type `u32`, found type `()`"),
-            "Expected error on return type"
-        );
-        assert!(doc.is_some(), "Expected problem in AST build, not parse");
-    }
+    #[tokio::test]
+    async fn test_unsynced_document() {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let uri = Uri::from_str("file:///tmp/unsynced.simf").unwrap();
+        let change = |version: i32, range: Option<Range>, text: &str| DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), version),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range,
+                range_length: None,
+                text: text.to_string(),
+            }],
+        };
+        let formatting = || {
+            backend.formatting(DocumentFormattingParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                options: FormattingOptions::default(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+        };
 
-    #[test]
-    fn test_parse_program_invalid_parse() {
-        let (err, doc) = parse_program(invalid_program_on_parsing());
-        assert!(
-            err.unwrap().to_string().contains("Grammar error"),
-            "Expected `Grammar error`"
-        );
-        assert!(doc.is_none(), "Expected no document to return");
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "simplicityhl".to_string(),
+                    0,
+                    "fn main() {}".to_string(),
+                ),
+            })
+            .await;
+        let reversed = Range::new(Position::new(0, 5), Position::new(0, 1));
+        backend.did_change(change(1, Some(reversed), "x")).await;
+        let version = |backend: &Backend| {
+            let documents = backend.document_map.try_read().unwrap();
+            let doc = &documents[&uri];
+            (doc.version, doc.synced)
+        };
+        assert_eq!((0, false), version(backend));
+        assert!(formatting().await.is_err());
+
+        // Changes of parts of the text are ignored until the whole text arrives
+        let start = Range::new(Position::new(0, 0), Position::new(0, 0));
+        backend.did_change(change(2, Some(start), " ")).await;
+        assert_eq!((0, false), version(backend));
+        backend.did_change(change(3, None, "fn  main() {}")).await;
+        assert_eq!((3, true), version(backend));
+        let edits = formatting().await.unwrap().unwrap();
+        assert_eq!("fn main() {}\n", edits[0].new_text);
     }
 }
@@ -0,0 +1,891 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range as ByteRange;
+
+use pest::iterators::Pair;
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, Range, TextEdit, Uri,
+    WorkspaceEdit,
+};
+
+use simplicityhl::ast::ExprTree;
+use simplicityhl::error::Error;
+use simplicityhl::parse::Rule;
+use simplicityhl::simplicity::jet::Elements;
+use simplicityhl::types::{ResolvedType, StructuralType, TypeConstructible, TypeDeconstructible};
+
+use crate::document::{Analysis, Snapshot};
+use crate::error::LspError;
+use crate::syntax::TokenKind;
+use crate::utils::{byte_to_position, position_to_byte, span_to_bytes};
+
+/// Replacement of a byte range of the document text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Edit {
+    range: ByteRange<usize>,
+    text: String,
+}
+
+impl Edit {
+    fn new(range: ByteRange<usize>, text: impl Into<String>) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+
+    fn insert(offset: usize, text: impl Into<String>) -> Self {
+        Self::new(offset..offset, text)
+    }
+}
+
+/// Code action whose edits are expressed in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Action {
+    title: String,
+    kind: CodeActionKind,
+    edits: Vec<Edit>,
+}
+
+/// Compute the code actions for the given range of the document.
+///
+/// Quick fixes are offered for the error of the analysis if it overlaps the range.
+/// Refactorings are offered if the program is well-typed and the range selects an expression.
+/// Every action that is offered was checked to compile by analyzing the edited program.
+pub fn code_actions(
+    document: &Snapshot,
+    uri: &Uri,
+    range: Range,
+    diagnostics: &[Diagnostic],
+) -> Result<Vec<CodeActionOrCommand>, LspError> {
+    let source = document.text.to_string();
+    let selection = position_to_byte(&document.text, range.start)?
+        ..position_to_byte(&document.text, range.end)?;
+    let Ok(tree) = simplicityhl::parse::parse_syntax_tree(&source) else {
+        return Ok(vec![]);
+    };
+
+    let mut actions = Vec::new();
+    let mut fixed = Vec::new();
+    if let Some(error) = &document.analysis.error {
+        let span = span_to_bytes(&document.text, error.span())?;
+        if span.start <= selection.end && selection.start <= span.end {
+            let context = Context {
+                analysis: &document.analysis,
+                source: &source,
+                tree: &tree,
+            };
+            actions.extend(quick_fixes(&context, error.error(), &span)?);
+            let message = error.to_string();
+            fixed.extend(
+                diagnostics
+                    .iter()
+                    .filter(|diagnostic| message.contains(&diagnostic.message))
+                    .cloned(),
+            );
+        }
+    } else if selection.start < selection.end {
+        let context = Context {
+            analysis: &document.analysis,
+            source: &source,
+            tree: &tree,
+        };
+        actions.extend(extractions(&context, &document.text, selection)?);
+    }
+
+    actions
+        .into_iter()
+        .map(|action| {
+            let edits = action
+                .edits
+                .iter()
+                .map(|edit| {
+                    Ok(TextEdit {
+                        range: Range::new(
+                            byte_to_position(&document.text, edit.range.start)?,
+                            byte_to_position(&document.text, edit.range.end)?,
+                        ),
+                        new_text: edit.text.clone(),
+                    })
+                })
+                .collect::<Result<Vec<_>, LspError>>()?;
+            let is_fix = action.kind == CodeActionKind::QUICKFIX;
+            Ok(CodeActionOrCommand::CodeAction(CodeAction {
+                title: action.title,
+                kind: Some(action.kind),
+                diagnostics: (is_fix && !fixed.is_empty()).then(|| fixed.clone()),
+                edit: Some(WorkspaceEdit::new(HashMap::from([(uri.clone(), edits)]))),
+                ..CodeAction::default()
+            }))
+        })
+        .collect()
+}
+
+/// Document under edit.
+struct Context<'a> {
+    analysis: &'a Analysis,
+    source: &'a str,
+    tree: &'a Pair<'a, Rule>,
+}
+
+impl Context<'_> {
+    /// Iterate over all nodes of the syntax tree.
+    fn nodes(&self) -> impl Iterator<Item = Pair<'_, Rule>> {
+        self.tree.clone().into_inner().flatten()
+    }
+
+    /// Find the node of the given rule that covers exactly the given range.
+    fn node(&self, rule: Rule, range: &ByteRange<usize>) -> Option<Pair<'_, Rule>> {
+        self.nodes()
+            .find(|node| node.as_rule() == rule && byte_range(node) == *range)
+    }
+
+    /// Find the top-level item that contains the given offset.
+    fn item_at(&self, offset: usize) -> Option<Pair<'_, Rule>> {
+        self.tree
+            .clone()
+            .into_inner()
+            .filter(|node| node.as_rule() == Rule::item)
+            .find(|node| byte_range(node).contains(&offset))
+    }
+
+    /// Analyze the document after applying the edits.
+    fn analyze(&self, edits: &[Edit]) -> Analysis {
+        Analysis::new(&apply(self.source, edits), 0, &Analysis::default())
+    }
+
+    /// Check if the program is well-typed after applying the edits.
+    fn compiles(&self, edits: &[Edit]) -> bool {
+        self.analyze(edits).error.is_none()
+    }
+
+    /// Return the name that is not used anywhere in the program and that starts with `base`.
+    fn fresh_name(&self, base: &str) -> String {
+        let names: HashSet<&str> = self
+            .analysis
+            .syntax
+            .tokens()
+            .iter()
+            .map(|token| &self.source[token.start..token.end])
+            .collect();
+        let mut name = base.to_string();
+        let mut index = 1;
+        while names.contains(name.as_str()) {
+            name = format!("{base}{index}");
+            index += 1;
+        }
+        name
+    }
+}
+
+fn byte_range(node: &Pair<'_, Rule>) -> ByteRange<usize> {
+    node.as_span().start()..node.as_span().end()
+}
+
+/// Apply non-overlapping edits to the text.
+fn apply(source: &str, edits: &[Edit]) -> String {
+    let mut edits: Vec<&Edit> = edits.iter().collect();
+    edits.sort_by_key(|edit| (edit.range.start, edit.range.end));
+
+    let mut result = String::with_capacity(source.len());
+    let mut last = 0;
+    for edit in edits {
+        result.push_str(&source[last..edit.range.start]);
+        result.push_str(&edit.text);
+        last = edit.range.end;
+    }
+    result.push_str(&source[last..]);
+    result
+}
+
+fn quick_fix(title: String, edits: Vec<Edit>) -> Action {
+    Action {
+        title,
+        kind: CodeActionKind::QUICKFIX,
+        edits,
+    }
+}
+
+/// Compute the quick fixes for the error at the given range.
+///
+/// Only fixes after which the program compiles are returned.
+fn quick_fixes(
+    context: &Context,
+    error: &Error,
+    span: &ByteRange<usize>,
+) -> Result<Vec<Action>, LspError> {
+    let mut actions = Vec::new();
+    match error {
+        Error::ExpressionTypeMismatch(expected, found) => {
+            actions.extend(annotation_fix(context, span, found));
+            actions.extend(cast_fix(context, span, expected, found));
+        }
+        Error::UndefinedVariable(name) => {
+            actions.extend(variable_fixes(context, span, name.as_inner()));
+        }
+        Error::FunctionUndefined(name) => {
+            actions.extend(function_name_fixes(context, span, name.as_inner()));
+            actions.extend(function_stub(context, span, name.as_inner())?);
+        }
+        Error::JetDoesNotExist(name) => {
+            actions.extend(jet_fixes(context, span, name.as_inner()));
+        }
+        Error::InvalidNumberOfArguments(expected, found) => {
+            actions.extend(argument_fix(context, span, *expected, *found));
+        }
+        Error::MainRequired => {
+            let separator = match context.source {
+                source if source.trim().is_empty() => "",
+                source if source.ends_with('\n') => "\n",
+                _ => "\n\n",
+            };
+            actions.push(quick_fix(
+                "Add `main` function".to_string(),
+                vec![Edit::insert(
+                    context.source.len(),
+                    format!("{separator}fn main() {{\n}}\n"),
+                )],
+            ));
+        }
+        _ => {}
+    }
+    // Fixes that leave the program broken are not offered
+    actions.retain(|action| context.compiles(&action.edits));
+    Ok(actions)
+}
+
+/// Change the type annotation of the `let` statement or the return type of the function
+/// whose expression has the type `found`.
+fn annotation_fix(
+    context: &Context,
+    span: &ByteRange<usize>,
+    found: &ResolvedType,
+) -> Option<Action> {
+    for node in context.nodes() {
+        match node.as_rule() {
+            Rule::assignment => {
+                let mut inner = node.into_inner();
+                let ty = inner.nth(2)?;
+                let expression = inner.next()?;
+                if byte_range(&expression) == *span {
+                    return Some(quick_fix(
+                        format!("Change type annotation to `{found}`"),
+                        vec![Edit::new(byte_range(&ty), found.to_string())],
+                    ));
+                }
+            }
+            Rule::function => {
+                let inner: Vec<_> = node.into_inner().collect();
+                let (name, params, body) = (&inner[1], &inner[2], inner.last()?);
+                let tail = body
+                    .clone()
+                    .into_inner()
+                    .last()
+                    .filter(|node| node.as_rule() == Rule::expression);
+                let is_tail = byte_range(body) == *span
+                    || tail.is_some_and(|tail| byte_range(&tail) == *span);
+                // The main function cannot return a value
+                if !is_tail || name.as_str() == "main" {
+                    continue;
+                }
+                let edit = match inner
+                    .iter()
+                    .find(|node| node.as_rule() == Rule::function_return)
+                {
+                    Some(output) => {
+                        let ty = output.clone().into_inner().next()?;
+                        Edit::new(byte_range(&ty), found.to_string())
+                    }
+                    None => Edit::insert(params.as_span().end(), format!(" -> {found}")),
+                };
+                return Some(quick_fix(
+                    format!("Change return type to `{found}`"),
+                    vec![edit],
+                ));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Cast the expression into the expected type if both types have the same structure.
+fn cast_fix(
+    context: &Context,
+    span: &ByteRange<usize>,
+    expected: &ResolvedType,
+    found: &ResolvedType,
+) -> Option<Action> {
+    if StructuralType::from(expected) != StructuralType::from(found) {
+        return None;
+    }
+    let expression = &context.source[span.clone()];
+    Some(quick_fix(
+        format!("Cast `{found}` into `{expected}`"),
+        vec![Edit::new(
+            span.clone(),
+            format!("<{found}>::into({expression})"),
+        )],
+    ))
+}
+
+/// Replace the undefined variable with a similar variable that is in scope.
+fn variable_fixes(context: &Context, span: &ByteRange<usize>, name: &str) -> Vec<Action> {
+    let Some(item) = context.item_at(span.start) else {
+        return vec![];
+    };
+    let item = byte_range(&item);
+    let candidates = context
+        .analysis
+        .syntax
+        .tokens()
+        .iter()
+        .enumerate()
+        .filter(|(index, token)| {
+            matches!(token.kind, TokenKind::Variable | TokenKind::Parameter)
+                && token.is_definition(*index)
+                && item.start <= token.start
+                && token.end <= span.start
+        })
+        .map(|(_, token)| &context.source[token.start..token.end]);
+
+    similar_names(name, candidates)
+        .into_iter()
+        .map(|candidate| {
+            quick_fix(
+                format!("Replace with `{candidate}`"),
+                vec![Edit::new(span.clone(), candidate)],
+            )
+        })
+        .collect()
+}
+
+/// Replace the name of the undefined function with a similar function name.
+fn function_name_fixes(context: &Context, span: &ByteRange<usize>, name: &str) -> Vec<Action> {
+    let Some(call_name) = context
+        .node(Rule::call_expr, span)
+        .and_then(|call| call.into_inner().next())
+    else {
+        return vec![];
+    };
+    let candidates = context
+        .analysis
+        .functions
+        .functions()
+        .into_iter()
+        .map(|function| function.name().as_inner());
+
+    similar_names(name, candidates)
+        .into_iter()
+        .map(|candidate| {
+            quick_fix(
+                format!("Replace with `{candidate}`"),
+                vec![Edit::new(byte_range(&call_name), candidate)],
+            )
+        })
+        .collect()
+}
+
+/// Replace the name of the unknown jet with a similar jet name.
+fn jet_fixes(context: &Context, span: &ByteRange<usize>, name: &str) -> Vec<Action> {
+    let Some(offset) = context.source[span.clone()].find(&format!("jet::{name}")) else {
+        return vec![];
+    };
+    let start = span.start + offset + "jet::".len();
+    let names: Vec<String> = Elements::ALL
+        .iter()
+        .filter(|jet| !matches!(jet, Elements::CheckSigVerify | Elements::Verify))
+        .map(ToString::to_string)
+        .collect();
+
+    similar_names(name, names.iter().map(String::as_str))
+        .into_iter()
+        .map(|candidate| {
+            quick_fix(
+                format!("Replace with `jet::{candidate}`"),
+                vec![Edit::new(start..start + name.len(), candidate)],
+            )
+        })
+        .collect()
+}
+
+/// Add the missing arguments or remove the extra arguments of the call.
+///
+/// Missing arguments are only added for custom functions,
+/// where they are filled in with the names of the parameters.
+fn argument_fix(
+    context: &Context,
+    span: &ByteRange<usize>,
+    expected: usize,
+    found: usize,
+) -> Option<Action> {
+    let mut inner = context.node(Rule::call_expr, span)?.into_inner();
+    let name = inner.next()?;
+    let call_args = inner.next()?;
+    let mut args: Vec<String> = call_args
+        .clone()
+        .into_inner()
+        .map(|arg| arg.as_str().to_string())
+        .collect();
+
+    let title = if expected < found {
+        args.truncate(expected);
+        "Remove extra arguments"
+    } else {
+        let function = context.analysis.functions.get_func(name.as_str())?;
+        args.extend(
+            function.params()[found..expected]
+                .iter()
+                .map(|param| param.identifier().to_string()),
+        );
+        "Add missing arguments"
+    };
+    Some(quick_fix(
+        title.to_string(),
+        vec![Edit::new(
+            byte_range(&call_args),
+            format!("({})", args.join(", ")),
+        )],
+    ))
+}
+
+/// Create a function that is called but not defined.
+///
+/// The function is inserted before the function that calls it.
+/// The types of its parameters and of its output are inferred from the call site
+/// by analyzing the program with the stub and adopting the types that the analysis expects.
+/// Arguments that take their type from the parameter, like literals, leave the parameter as unit.
+fn function_stub(
+    context: &Context,
+    span: &ByteRange<usize>,
+    name: &str,
+) -> Result<Option<Action>, LspError> {
+    let Some(call) = context.node(Rule::call_expr, span) else {
+        return Ok(None);
+    };
+    let Some(item) = context.item_at(span.start) else {
+        return Ok(None);
+    };
+    let args: Vec<_> = call
+        .into_inner()
+        .nth(1)
+        .into_iter()
+        .flat_map(Pair::into_inner)
+        .collect();
+
+    // Arguments that are variables give their name to the parameter
+    let mut params: Vec<String> = Vec::with_capacity(args.len());
+    for (index, arg) in args.iter().enumerate() {
+        let text = arg.as_str();
+        let is_variable = context.analysis.syntax.tokens().iter().any(|token| {
+            token.kind == TokenKind::Variable && (token.start..token.end) == byte_range(arg)
+        });
+        let mut param = if is_variable {
+            text.to_string()
+        } else {
+            format!("arg{index}")
+        };
+        while params.contains(&param) {
+            param.push('_');
+        }
+        params.push(param);
+    }
+
+    let insert_at = item.as_span().start();
+    let mut param_types = vec![ResolvedType::unit(); args.len()];
+    let mut output = ResolvedType::unit();
+    let stub = |param_types: &[ResolvedType], output: &ResolvedType| {
+        let params: Vec<String> = params
+            .iter()
+            .zip(param_types)
+            .map(|(param, ty)| format!("{param}: {ty}"))
+            .collect();
+        let output = if output.is_unit() {
+            String::new()
+        } else {
+            format!(" -> {output}")
+        };
+        format!(
+            "fn {name}({}){output} {{\n    unwrap(None)\n}}\n\n",
+            params.join(", ")
+        )
+    };
+
+    // Each round fixes one type, so the number of rounds is bounded
+    for _ in 0..=args.len() {
+        let text = stub(&param_types, &output);
+        let analysis = context.analyze(&[Edit::insert(insert_at, text.as_str())]);
+        let Some(error) = &analysis.error else {
+            break;
+        };
+        let Error::ExpressionTypeMismatch(expected, found) = error.error() else {
+            break;
+        };
+        let edited = Rope::from_str(&apply(
+            context.source,
+            &[Edit::insert(insert_at, text.as_str())],
+        ));
+        let mut range = span_to_bytes(&edited, error.span())?;
+        if range.start < insert_at + text.len() {
+            break;
+        }
+        range = range.start - text.len()..range.end - text.len();
+
+        if let Some(index) = args.iter().position(|arg| byte_range(arg) == range) {
+            if param_types[index] == *found {
+                break;
+            }
+            param_types[index] = found.clone();
+        } else if range == *span && output != *expected {
+            output = expected.clone();
+        } else {
+            break;
+        }
+    }
+
+    Ok(Some(quick_fix(
+        format!("Create function `{name}`"),
+        vec![Edit::insert(insert_at, stub(&param_types, &output))],
+    )))
+}
+
+/// Return up to three candidates that are similar to the given name, most similar first.
+fn similar_names<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut similar: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    similar.sort_unstable();
+    similar.dedup();
+    similar
+        .into_iter()
+        .take(3)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Compute the edit distance between two strings,
+/// where swapping two adjacent characters counts as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+            if 1 < i && 1 < j && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+fn refactoring(title: &str, edits: Vec<Edit>) -> Action {
+    Action {
+        title: title.to_string(),
+        kind: CodeActionKind::REFACTOR_EXTRACT,
+        edits,
+    }
+}
+
+/// Compute the refactorings that extract the selected expression.
+fn extractions(
+    context: &Context,
+    text: &Rope,
+    selection: ByteRange<usize>,
+) -> Result<Vec<Action>, LspError> {
+    let selected = &context.source[selection.clone()];
+    let start = selection.start + (selected.len() - selected.trim_start().len());
+    let end = selection.end - (selected.len() - selected.trim_end().len());
+    let selection = start..end;
+    if context.node(Rule::expression, &selection).is_none() {
+        return Ok(vec![]);
+    }
+
+    let mut types = HashMap::new();
+    context.analysis.visit_expressions(|node| {
+        match node {
+            ExprTree::Expression(expression) => {
+                types.insert(
+                    span_to_bytes(text, expression.span())?,
+                    expression.ty().clone(),
+                );
+            }
+            ExprTree::Single(single) => {
+                types.insert(span_to_bytes(text, single.span())?, single.ty().clone());
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+    let Some(ty) = types.get(&selection) else {
+        return Ok(vec![]);
+    };
+
+    Ok(extract_let(context, &selection, ty)
+        .into_iter()
+        .chain(extract_function(context, &selection, ty, &types))
+        .collect())
+}
+
+/// Bind the selected expression to a new variable right before the statement that uses it.
+fn extract_let(
+    context: &Context,
+    selection: &ByteRange<usize>,
+    ty: &ResolvedType,
+) -> Option<Action> {
+    // Find the innermost statement or block tail that contains the selection
+    let statement = context
+        .nodes()
+        .filter(|node| node.as_rule() == Rule::block_expression)
+        .flat_map(Pair::into_inner)
+        .filter(|node| {
+            let range = byte_range(node);
+            range.start <= selection.start && selection.end <= range.end
+        })
+        .min_by_key(|node| node.as_str().len())?;
+    let insert_at = statement.as_span().start();
+
+    // Variables of the expression must already be defined before the new statement
+    let tokens = context.analysis.syntax.tokens();
+    let in_scope = tokens
+        .iter()
+        .filter(|token| selection.start <= token.start && token.end <= selection.end)
+        .filter_map(|token| token.definition)
+        .all(|definition| {
+            let start = tokens[definition].start;
+            start < insert_at || selection.contains(&start)
+        });
+    if !in_scope {
+        return None;
+    }
+
+    let line_start = context.source[..insert_at]
+        .rfind('\n')
+        .map_or(0, |index| index + 1);
+    let indent = &context.source[line_start..insert_at];
+    let indent = if indent.trim().is_empty() {
+        indent
+    } else {
+        " "
+    };
+    let name = context.fresh_name("value");
+    let expression = &context.source[selection.clone()];
+    let binding = format!("let {name}: {ty} = {expression};\n{indent}");
+
+    let edits = if insert_at == selection.start {
+        vec![Edit::new(selection.clone(), format!("{binding}{name}"))]
+    } else {
+        vec![
+            Edit::insert(insert_at, binding),
+            Edit::new(selection.clone(), name),
+        ]
+    };
+    context
+        .compiles(&edits)
+        .then(|| refactoring("Extract into `let` binding", edits))
+}
+
+/// Move the selected expression into a new function
+/// whose parameters are the variables that the expression uses.
+fn extract_function(
+    context: &Context,
+    selection: &ByteRange<usize>,
+    ty: &ResolvedType,
+    types: &HashMap<ByteRange<usize>, ResolvedType>,
+) -> Option<Action> {
+    let item = context.item_at(selection.start)?;
+    let tokens = context.analysis.syntax.tokens();
+
+    let mut definitions = Vec::new();
+    let mut params = Vec::new();
+    for token in tokens
+        .iter()
+        .filter(|token| selection.start <= token.start && token.end <= selection.end)
+        .filter(|token| matches!(token.kind, TokenKind::Variable | TokenKind::Parameter))
+    {
+        let definition = token.definition?;
+        if selection.contains(&tokens[definition].start) || definitions.contains(&definition) {
+            continue;
+        }
+        definitions.push(definition);
+        let name = &context.source[token.start..token.end];
+        params.push((name, types.get(&(token.start..token.end))?));
+    }
+
+    let name = context.fresh_name("extracted");
+    let signature: Vec<String> = params
+        .iter()
+        .map(|(param, ty)| format!("{param}: {ty}"))
+        .collect();
+    let args: Vec<&str> = params.iter().map(|(param, _)| *param).collect();
+    let output = if ty.is_unit() {
+        String::new()
+    } else {
+        format!(" -> {ty}")
+    };
+    let expression = &context.source[selection.clone()];
+
+    let edits = vec![
+        Edit::insert(
+            item.as_span().start(),
+            format!(
+                "fn {name}({}){output} {{\n    {expression}\n}}\n\n",
+                signature.join(", ")
+            ),
+        ),
+        Edit::new(selection.clone(), format!("{name}({})", args.join(", "))),
+    ];
+    context
+        .compiles(&edits)
+        .then(|| refactoring("Extract into function", edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    /// Run the code actions at the first occurrence of `needle` and apply each one.
+    fn run(text: &str, needle: &str) -> Vec<(String, String)> {
+        let start = text.find(needle).expect("needle");
+        let snapshot = Snapshot {
+            text: Rope::from_str(text),
+            analysis: Arc::new(Analysis::new(text, 0, &Analysis::default())),
+        };
+        let range = Range::new(
+            byte_to_position(&snapshot.text, start).unwrap(),
+            byte_to_position(&snapshot.text, start + needle.len()).unwrap(),
+        );
+        let uri: Uri = "file:///test.simf".parse().unwrap();
+
+        code_actions(&snapshot, &uri, range, &[])
+            .unwrap()
+            .into_iter()
+            .map(|action| {
+                let CodeActionOrCommand::CodeAction(action) = action else {
+                    unreachable!()
+                };
+                let mut edits = action.edit.unwrap().changes.unwrap().remove(&uri).unwrap();
+                edits.sort_by_key(|edit| edit.range.start);
+                let mut rope = snapshot.text.clone();
+                for edit in edits.iter().rev() {
+                    let start =
+                        rope.byte_to_char(position_to_byte(&rope, edit.range.start).unwrap());
+                    let end = rope.byte_to_char(position_to_byte(&rope, edit.range.end).unwrap());
+                    rope.remove(start..end);
+                    rope.insert(start, &edit.new_text);
+                }
+                (action.title, rope.to_string())
+            })
+            .collect()
+    }
+
+    fn compiles(text: &str) -> bool {
+        Analysis::new(text, 0, &Analysis::default()).error.is_none()
+    }
+
+    #[test]
+    fn test_type_mismatch_fixes() {
+        let text = "fn main() {\n    let a: u8 = 1;\n    let b: (u4, u4) = a;\n}";
+        let actions = run(text, "a;");
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].0, "Change type annotation to `u8`");
+        assert!(actions[0].1.contains("let b: u8 = a;"));
+        assert_eq!(actions[1].0, "Cast `u8` into `(u4, u4)`");
+        assert!(actions[1].1.contains("let b: (u4, u4) = <u8>::into(a);"));
+        assert!(actions.iter().all(|(_, text)| compiles(text)));
+
+        let text = "fn f(a: u8) -> u16 { a }\nfn main() { let b: u8 = f(1); }";
+        let actions = run(text, "a }");
+        assert_eq!(actions[0].0, "Change return type to `u8`");
+        assert!(compiles(&actions[0].1));
+
+        // Changing the annotation would break the statement that uses the variable
+        let text = "fn main() {\n    let a: u8 = 1;\n    let b: (u4, u4) = a;\n    let c: (u4, u4) = b;\n}";
+        let actions = run(text, "a;");
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].0, "Cast `u8` into `(u4, u4)`");
+        assert!(compiles(&actions[0].1));
+    }
+
+    #[test]
+    fn test_name_fixes() {
+        let text = "fn main() {\n    let value: u8 = 1;\n    let b: u8 = valeu;\n}";
+        let actions = run(text, "valeu");
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].0, "Replace with `value`");
+        assert!(compiles(&actions[0].1));
+
+        let text = "fn main() {\n    let (_, a): (bool, u32) = jet::ad_32(1, 2);\n}";
+        let actions = run(text, "ad_32");
+        assert_eq!(actions[0].0, "Replace with `jet::add_32`");
+        assert!(compiles(&actions[0].1));
+    }
+
+    #[test]
+    fn test_function_fixes() {
+        let text =
+            "fn main() {\n    let a: u8 = 1;\n    let b: u16 = double(a, jet::add_8(a, a));\n}";
+        let actions = run(text, "double");
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].0, "Create function `double`");
+        assert!(actions[0].1.starts_with(
+            "fn double(a: u8, arg1: (bool, u8)) -> u16 {\n    unwrap(None)\n}\n\nfn main()"
+        ));
+        assert!(compiles(&actions[0].1));
+
+        let text = "fn double(a: u8) -> u8 { a }\nfn main() { let b: u8 = doubel(1); }";
+        let actions = run(text, "doubel");
+        assert_eq!(actions[0].0, "Replace with `double`");
+        assert!(compiles(&actions[0].1));
+
+        let text = "fn add(a: u8, b: u8) -> u8 { a }\nfn main() { let b: u8 = add(1, 2, 3); }";
+        let actions = run(text, "add(1");
+        assert_eq!(actions[0].0, "Remove extra arguments");
+        assert!(actions[0].1.contains("add(1, 2);"));
+        assert!(compiles(&actions[0].1));
+
+        let actions = run("", "");
+        assert_eq!(actions[0].0, "Add `main` function");
+        assert!(compiles(&actions[0].1));
+    }
+
+    #[test]
+    fn test_extractions() {
+        let text = "fn main() {
+    let a: u32 = 1;
+    let (_, b): (bool, u32) = jet::add_32(a, 2);
+    assert!(jet::eq_32(b, 3));
+}";
+        let actions = run(text, "jet::add_32(a, 2)");
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].0, "Extract into `let` binding");
+        assert!(actions[0].1.contains(
+            "    let value: (bool, u32) = jet::add_32(a, 2);\n    let (_, b): (bool, u32) = value;"
+        ));
+        assert_eq!(actions[1].0, "Extract into function");
+        assert!(actions[1]
+            .1
+            .starts_with("fn extracted(a: u32) -> (bool, u32) {\n    jet::add_32(a, 2)\n}\n\n"));
+        assert!(actions.iter().all(|(_, text)| compiles(text)));
+
+        // Partial expressions cannot be extracted
+        assert!(run(text, "add_32(a").is_empty());
+    }
+}
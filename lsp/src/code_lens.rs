@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use base64::display::Base64Display;
+use base64::engine::general_purpose::STANDARD;
+use pest::iterators::Pair;
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{CodeLens, Command, Range};
+
+use simplicityhl::ast;
+use simplicityhl::parse::Rule;
+use simplicityhl::simplicity::jet::Elements;
+use simplicityhl::simplicity::node::SimpleFinalizer;
+use simplicityhl::simplicity::CommitNode;
+use simplicityhl::{named, Arguments};
+
+use crate::document::{custom_function, Snapshot};
+use crate::error::LspError;
+use crate::key_files::{self, KeyFile};
+use crate::utils::{byte_to_position, span_to_bytes};
+
+/// Client command that copies the CMR of a function to the clipboard.
+///
+/// The server cannot access the clipboard, so the command is implemented by the editor extension.
+pub const COPY_CMR: &str = "simplicityhl.copyCmr";
+/// Client command that copies the base64 encoding of the program to the clipboard.
+pub const COPY_PROGRAM: &str = "simplicityhl.copyProgram";
+
+/// Compute the code lenses above `main` and the custom functions of the program.
+///
+/// The lenses show the CMR, the static cost bound and the encoded size
+/// of the compiled function, without debug symbols and without witness data.
+/// The arguments of program parameters are taken from the `.args` file next to the program,
+/// or from the first `.*.args` variant if there is no such file.
+///
+/// Return no lenses if the program is not well-typed.
+pub fn code_lenses(doc: &Snapshot, path: Option<&Path>) -> Result<Vec<CodeLens>, LspError> {
+    let Some(program) = &doc.analysis.program else {
+        return Ok(vec![]);
+    };
+    let text = doc.text.to_string();
+    let Ok(tree) = simplicityhl::parse::parse_syntax_tree(&text) else {
+        return Ok(vec![]);
+    };
+
+    // Only functions that main calls are part of the typed program
+    let mut functions = HashMap::new();
+    doc.analysis.visit_expressions(|node| {
+        if let ast::ExprTree::Call(call) = node {
+            if let Some(function) = custom_function(call) {
+                functions.insert(span_to_bytes(&doc.text, function.body().span())?, function);
+            }
+        }
+        Ok(())
+    })?;
+    let arguments = arguments(program, path);
+
+    let mut lenses = Vec::new();
+    for node in tree
+        .into_inner()
+        .filter(|node| node.as_rule() == Rule::item)
+        .filter_map(|item| item.into_inner().next())
+        .filter(|node| node.as_rule() == Rule::function)
+    {
+        let inner: Vec<_> = node.into_inner().collect();
+        let (name, body) = (&inner[1], &inner[inner.len() - 1]);
+        let range = range(&doc.text, name)?;
+
+        let compiled = match &arguments {
+            Err(error) => Err(error.clone()),
+            Ok(arguments) if name.as_str() == "main" => program
+                .compile(arguments.clone(), false)
+                .map_err(|error| error.error().to_string()),
+            Ok(arguments) => {
+                let Some(function) = functions.get(&(body.as_span().start()..body.as_span().end()))
+                else {
+                    lenses.push(lens(range, "Not called by `main`".to_string(), None));
+                    continue;
+                };
+                program
+                    .compile_function(function, arguments.clone(), false)
+                    .map_err(|error| error.error().to_string())
+            }
+        };
+        let commit = match compiled {
+            Ok(commit) => named::forget_names(&commit),
+            Err(error) => {
+                lenses.push(lens(range, format!("Cannot compile: {error}"), None));
+                continue;
+            }
+        };
+        lenses.extend(statistics(&commit, range, name.as_str() == "main"));
+    }
+    Ok(lenses)
+}
+
+/// Create the lenses that show the statistics of the compiled function.
+fn statistics(commit: &Arc<CommitNode<Elements>>, range: Range, is_main: bool) -> Vec<CodeLens> {
+    let cmr = commit.cmr().to_string();
+    let program = commit.to_vec_without_witness();
+    // The cost bound depends only on the types of witnesses, so zero values will do
+    let cost = commit
+        .finalize(&mut SimpleFinalizer::new(std::iter::empty()))
+        .map_or_else(
+            |_| "?".to_string(),
+            |redeem| redeem.bounds().cost.to_string(),
+        );
+    let sizes = format!("cost ≤ {cost} mWU, {} bytes", program.len());
+
+    vec![
+        lens(range, format!("CMR {}…", &cmr[..16]), Some((COPY_CMR, cmr))),
+        lens(
+            range,
+            sizes,
+            is_main.then(|| {
+                (
+                    COPY_PROGRAM,
+                    Base64Display::new(&program, &STANDARD).to_string(),
+                )
+            }),
+        ),
+    ]
+}
+
+/// Return the arguments of the program parameters, or an error message.
+fn arguments(program: &ast::Program, path: Option<&Path>) -> Result<Arguments, String> {
+    if program.parameters().iter().next().is_none() {
+        return Ok(Arguments::default());
+    }
+    let Some(path) = path else {
+        return Err("Program parameters need an `.args` file".to_string());
+    };
+    let exact = path.with_extension(KeyFile::Arguments.extension());
+    let files = key_files::sibling_files(path, KeyFile::Arguments);
+    let args_file = match files.iter().find(|file| **file == exact) {
+        Some(file) => file,
+        None => files.first().unwrap_or(&exact),
+    };
+    let file_name = args_file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let Ok(json) = std::fs::read_to_string(args_file) else {
+        return Err(format!(
+            "Program parameters need arguments in `{file_name}`"
+        ));
+    };
+    let arguments = serde_json::from_str::<Arguments>(&json)
+        .map_err(|error| format!("Invalid `{file_name}`: {error}"))?;
+    arguments
+        .is_consistent(program.parameters())
+        .map_err(|error| format!("Invalid `{file_name}`: {error}"))?;
+    Ok(arguments)
+}
+
+/// Create a lens whose command is called with the given value, or a lens without action.
+fn lens(range: Range, title: String, command: Option<(&str, String)>) -> CodeLens {
+    let (command, arguments) = match command {
+        Some((command, value)) => (command.to_string(), Some(vec![value.into()])),
+        None => (String::new(), None),
+    };
+    CodeLens {
+        range,
+        command: Some(Command {
+            title,
+            command,
+            arguments,
+        }),
+        data: None,
+    }
+}
+
+fn range(text: &Rope, node: &Pair<'_, Rule>) -> Result<Range, LspError> {
+    Ok(Range::new(
+        byte_to_position(text, node.as_span().start())?,
+        byte_to_position(text, node.as_span().end())?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::document::Analysis;
+
+    fn lens_titles(text: &str) -> Vec<String> {
+        lens_titles_at(text, None)
+    }
+
+    fn lens_titles_at(text: &str, path: Option<&Path>) -> Vec<String> {
+        let snapshot = Snapshot {
+            text: Rope::from_str(text),
+            analysis: Arc::new(Analysis::new(text, 0, &Analysis::default())),
+        };
+        code_lenses(&snapshot, path)
+            .unwrap()
+            .into_iter()
+            .map(|lens| lens.command.unwrap().title)
+            .collect()
+    }
+
+    #[test]
+    fn test_code_lenses() {
+        let text = "fn double(x: u32) -> u32 {
+    let (_, y): (bool, u32) = jet::add_32(x, x);
+    y
+}
+
+fn unused() {}
+
+fn main() {
+    assert!(jet::eq_32(double(witness::X), 4));
+}";
+        let cmr = simplicityhl::CompiledProgram::new(text, Arguments::default(), false)
+            .unwrap()
+            .commit()
+            .cmr()
+            .to_string();
+
+        let titles = lens_titles(text);
+        assert_eq!(titles.len(), 5);
+        assert!(titles[0].starts_with("CMR "));
+        assert!(titles[1].starts_with("cost ≤ "));
+        assert_eq!(titles[2], "Not called by `main`");
+        assert_eq!(titles[3], format!("CMR {}…", &cmr[..16]));
+        assert!(titles[4].ends_with(" bytes"));
+
+        let text = "fn main() { assert!(jet::eq_32(param::X, 4)); }";
+        assert_eq!(
+            lens_titles(text),
+            vec!["Cannot compile: Program parameters need an `.args` file"]
+        );
+        assert!(lens_titles("fn main() { let x: u8 = 1 }").is_empty());
+    }
+
+    #[test]
+    fn test_arguments_variant() {
+        let dir = std::env::temp_dir().join("simplicityhl_lsp_arguments_variant");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let program = dir.join("check.simf");
+        let text = "fn main() { assert!(jet::eq_32(param::X, 4)); }";
+
+        assert_eq!(
+            lens_titles_at(text, Some(&program)),
+            vec!["Cannot compile: Program parameters need arguments in `check.args`"]
+        );
+        std::fs::write(
+            dir.join("check.four.args"),
+            r#"{ "X": { "value": "4", "type": "u32" } }"#,
+        )
+        .unwrap();
+        let titles = lens_titles_at(text, Some(&program));
+        assert_eq!(titles.len(), 2);
+        assert!(titles[0].starts_with("CMR "));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,6 +2,7 @@ use simplicityhl::parse::Function;
 
 pub mod builtin;
 pub mod jet;
+pub mod scope;
 pub mod tokens;
 pub mod type_cast;
 pub mod types;
@@ -10,6 +11,7 @@ use tower_lsp_server::lsp_types::{
     CompletionItem, CompletionItemKind, Documentation, InsertTextFormat, MarkupContent, MarkupKind,
 };
 
+use scope::Scope;
 use tokens::parse;
 use tokens::CompletionType;
 
@@ -27,6 +29,9 @@ pub struct CompletionProvider {
 
     /// Default Type cast completions.
     type_casts: Vec<CompletionItem>,
+
+    /// Builtin type aliases completions.
+    builtin_aliases: Vec<CompletionItem>,
 }
 
 impl CompletionProvider {
@@ -68,6 +73,7 @@ impl CompletionProvider {
             builtin: builtin_completion,
             modules: modules_completion,
             type_casts: type_casts_completion,
+            builtin_aliases: scope::builtin_alias_completions(),
         }
     }
 
@@ -82,17 +88,22 @@ impl CompletionProvider {
             .collect()
     }
 
-    /// Return completions based on line and functions provided.
+    /// Return completions based on line, functions and names in scope provided.
     pub fn process_completions(
         &self,
         prefix: &str,
         functions: &[(&Function, &str)],
+        scope: &Scope,
     ) -> Option<Vec<CompletionItem>> {
         let completion_type = parse(prefix)?;
 
         match completion_type {
             CompletionType::Jet => Some(self.jets.clone()),
 
+            CompletionType::Witness => Some(scope.witnesses.clone()),
+
+            CompletionType::Parameter => Some(scope.parameters.clone()),
+
             CompletionType::Assignment(type_name) => {
                 let to = type_name.as_str();
 
@@ -122,9 +133,17 @@ impl CompletionProvider {
 
             CompletionType::NonCompletionSymbol => None,
 
+            _ if tokens::is_type_position(prefix) => {
+                let mut completions = scope.aliases.clone();
+                completions.extend_from_slice(&self.builtin_aliases);
+
+                Some(completions)
+            }
+
             _ => {
-                let mut completions = CompletionProvider::get_function_completions(functions);
+                let mut completions = scope.variables.clone();
 
+                completions.extend(CompletionProvider::get_function_completions(functions));
                 completions.extend_from_slice(&self.builtin);
                 completions.extend_from_slice(&self.modules);
 
@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, HashMap};
+
+use tower_lsp_server::lsp_types::{
+    CompletionItem, CompletionItemKind, Documentation, InsertTextFormat, MarkupContent, MarkupKind,
+};
+
+use simplicityhl::parse::ParseFromStr;
+use simplicityhl::str::{AliasName, WitnessName};
+use simplicityhl::types::{AliasedType, BuiltinAlias, ResolvedType, TypeDeconstructible};
+
+use crate::document::Parsed;
+use crate::hover;
+use crate::key_files::{self, KeyFile};
+use crate::syntax::{Binding, TokenKind};
+
+/// All builtin type aliases.
+pub const BUILTIN_ALIASES: [BuiltinAlias; 24] = [
+    BuiltinAlias::Ctx8,
+    BuiltinAlias::Pubkey,
+    BuiltinAlias::Message,
+    BuiltinAlias::Message64,
+    BuiltinAlias::Signature,
+    BuiltinAlias::Scalar,
+    BuiltinAlias::Fe,
+    BuiltinAlias::Ge,
+    BuiltinAlias::Gej,
+    BuiltinAlias::Point,
+    BuiltinAlias::Height,
+    BuiltinAlias::Time,
+    BuiltinAlias::Distance,
+    BuiltinAlias::Duration,
+    BuiltinAlias::Lock,
+    BuiltinAlias::Outpoint,
+    BuiltinAlias::Confidential1,
+    BuiltinAlias::ExplicitAsset,
+    BuiltinAlias::Asset1,
+    BuiltinAlias::ExplicitAmount,
+    BuiltinAlias::Amount1,
+    BuiltinAlias::ExplicitNonce,
+    BuiltinAlias::Nonce,
+    BuiltinAlias::TokenAmount1,
+];
+
+/// Completions of the names that the program defines, as seen from one position.
+#[derive(Debug, Default)]
+pub struct Scope {
+    /// Local variables and function parameters that are visible at the position.
+    pub variables: Vec<CompletionItem>,
+    /// Type aliases of the program.
+    pub aliases: Vec<CompletionItem>,
+    /// Witnesses of the program and of its `.wit` files.
+    pub witnesses: Vec<CompletionItem>,
+    /// Parameters of the program and of its `.args` files.
+    pub parameters: Vec<CompletionItem>,
+}
+
+impl Scope {
+    /// Collect the names that are in scope at the byte offset of the parsed program.
+    ///
+    /// The key files are the texts of the `.wit` and `.args` files next to the program.
+    pub fn new(parsed: &Parsed, offset: usize, key_files: &[(KeyFile, String)]) -> Self {
+        let aliases = hover::aliases(&parsed.text).unwrap_or_default();
+        Self {
+            variables: variables(parsed, offset, &aliases),
+            aliases: alias_completions(&aliases),
+            witnesses: global_completions(parsed, KeyFile::Witness, key_files),
+            parameters: global_completions(parsed, KeyFile::Arguments, key_files),
+        }
+    }
+}
+
+/// Create the completions of the builtin type aliases.
+pub fn builtin_alias_completions() -> Vec<CompletionItem> {
+    BUILTIN_ALIASES
+        .iter()
+        .map(|alias| {
+            let mut item = item(
+                alias.to_string(),
+                CompletionItemKind::TYPE_PARAMETER,
+                Some(alias.resolve().to_string()),
+            );
+            item.documentation = Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: hover::builtin_alias_description(*alias).to_string(),
+            }));
+            item
+        })
+        .collect()
+}
+
+/// Complete the local variables and function parameters that are visible at the offset.
+///
+/// Shadowed variables are left out.
+fn variables(
+    parsed: &Parsed,
+    offset: usize,
+    aliases: &HashMap<AliasName, (AliasedType, ResolvedType)>,
+) -> Vec<CompletionItem> {
+    let tokens = parsed.syntax.tokens();
+    let mut variables: Vec<CompletionItem> = Vec::new();
+    for binding in parsed.syntax.visible_bindings(offset) {
+        let token = tokens[binding.token];
+        let name = &parsed.text[token.start..token.end];
+        // Later bindings shadow earlier ones
+        variables.retain(|item| item.label != name);
+        variables.push(item(
+            name.to_string(),
+            CompletionItemKind::VARIABLE,
+            binding_type(&parsed.text, binding, aliases),
+        ));
+    }
+    variables
+}
+
+/// Return the declared type of the variable, which is taken from the annotation of its pattern.
+///
+/// Aliases are kept if the variable can be found without resolving them.
+fn binding_type(
+    text: &str,
+    binding: &Binding,
+    aliases: &HashMap<AliasName, (AliasedType, ResolvedType)>,
+) -> Option<String> {
+    let declared = AliasedType::parse_from_str(&text[binding.ty.clone()]).ok()?;
+    if let Some(ty) = component(&declared, &binding.path) {
+        return Some(ty.to_string());
+    }
+    let resolved = declared
+        .resolve(|name| aliases.get(name).map(|(_, resolved)| resolved.clone()))
+        .ok()?;
+    component(&resolved, &binding.path).map(ToString::to_string)
+}
+
+/// Return the component of the type at the given position inside nested tuples and arrays.
+fn component<'a, T: TypeDeconstructible>(ty: &'a T, path: &[usize]) -> Option<&'a T> {
+    path.iter().try_fold(ty, |ty, &index| match ty.as_tuple() {
+        Some(components) => components.get(index).map(AsRef::as_ref),
+        None => ty.as_array().map(|(element, _)| element),
+    })
+}
+
+/// Complete the type aliases of the program with their definitions.
+fn alias_completions(
+    aliases: &HashMap<AliasName, (AliasedType, ResolvedType)>,
+) -> Vec<CompletionItem> {
+    let mut items: Vec<_> = aliases
+        .iter()
+        .map(|(name, (declared, _))| {
+            item(
+                name.to_string(),
+                CompletionItemKind::TYPE_PARAMETER,
+                Some(declared.to_string()),
+            )
+        })
+        .collect();
+    items.sort_by(|a, b| a.label.cmp(&b.label));
+    items
+}
+
+/// Complete the witnesses or parameters that the program uses or that its key files assign.
+///
+/// Types are taken from the typed program, or else from the key files.
+fn global_completions(
+    parsed: &Parsed,
+    kind: KeyFile,
+    key_files: &[(KeyFile, String)],
+) -> Vec<CompletionItem> {
+    let token_kind = match kind {
+        KeyFile::Witness => TokenKind::Witness,
+        KeyFile::Arguments => TokenKind::ModuleParameter,
+    };
+    let mut names: BTreeMap<String, Option<String>> = parsed
+        .syntax
+        .tokens()
+        .iter()
+        .filter(|token| token.kind == token_kind)
+        .map(|token| (parsed.text[token.start..token.end].to_string(), None))
+        .collect();
+    for (_, json) in key_files.iter().filter(|(file_kind, _)| *file_kind == kind) {
+        for (name, ty) in key_files::entries(json) {
+            let entry = names.entry(name).or_default();
+            if entry.is_none() {
+                *entry = ty;
+            }
+        }
+    }
+
+    names
+        .into_iter()
+        .filter(|(name, _)| WitnessName::parse_from_str(name).is_ok())
+        .map(|(name, ty)| {
+            let declared = parsed.program.as_ref().and_then(|program| {
+                let name = WitnessName::from_str_unchecked(&name);
+                match kind {
+                    KeyFile::Witness => program.witness_types().get(&name).cloned(),
+                    KeyFile::Arguments => program.parameters().get(&name).cloned(),
+                }
+            });
+            let ty = declared.map(|ty| ty.to_string()).or(ty);
+            item(name, CompletionItemKind::CONSTANT, ty)
+        })
+        .collect()
+}
+
+/// Create a completion item that inserts its label and shows the type as detail.
+fn item(label: String, kind: CompletionItemKind, ty: Option<String>) -> CompletionItem {
+    CompletionItem {
+        insert_text: Some(label.clone()),
+        label,
+        kind: Some(kind),
+        detail: ty,
+        insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::document::Analysis;
+
+    fn labels_and_details(items: &[CompletionItem]) -> Vec<(&str, Option<&str>)> {
+        items
+            .iter()
+            .map(|item| (item.label.as_str(), item.detail.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_scope() {
+        let text = "type Pair = (u8, Pubkey);
+
+fn main() {
+    let (a, b): Pair = (1, witness::KEY);
+    let c: u8 = a;
+    assert!(jet::eq_8(c, param::ONE));
+}";
+        let analysis = Analysis::new(text, 0, &Analysis::default());
+        let offset = text.find("assert!").unwrap();
+        let wit = r#"{ "KEY": { "value": "0x00", "type": "u64" }, "EXTRA": { "value": "0x00", "type": "u8" } }"#;
+        let scope = Scope::new(
+            &analysis.parsed,
+            offset,
+            &[(KeyFile::Witness, wit.to_string())],
+        );
+
+        assert_eq!(
+            labels_and_details(&scope.variables),
+            vec![("a", Some("u8")), ("b", Some("u256")), ("c", Some("u8"))]
+        );
+        assert_eq!(
+            labels_and_details(&scope.aliases),
+            vec![("Pair", Some("(u8, Pubkey)"))]
+        );
+        // The program takes precedence over the key file
+        assert_eq!(
+            labels_and_details(&scope.witnesses),
+            vec![("EXTRA", Some("u8")), ("KEY", Some("u256"))]
+        );
+        assert_eq!(
+            labels_and_details(&scope.parameters),
+            vec![("ONE", Some("u8"))]
+        );
+
+        // Completion keeps working while the text cannot be parsed
+        let typing = text.replace("assert!", "let d: u8 = ");
+        let analysis = Analysis::new(&typing, 1, &analysis);
+        let offset = analysis
+            .parsed
+            .offset(&typing, typing.find("let d").unwrap());
+        let scope = Scope::new(&analysis.parsed, offset, &[]);
+        assert_eq!(scope.variables.len(), 3);
+    }
+}
@@ -14,6 +14,8 @@ pub enum CompletionType {
     NonCompletionSymbol,
     OtherSymbol,
     Jet,
+    Witness,
+    Parameter,
     Assignment(String),
     Identifier(String),
     ClosingType,
@@ -43,6 +45,22 @@ fn parse_jet(input: &str) -> IResult<&str, CompletionType> {
     parser.parse(input)
 }
 
+/// Parses the `witness::` or `param::` namespace token, optionally followed by an identifier.
+fn parse_module_name(input: &str) -> IResult<&str, CompletionType> {
+    let name = || opt(take_while(|c: char| c.is_alphanumeric() || c == '_'));
+    let mut parser = alt((
+        value(
+            CompletionType::Witness,
+            recognize(pair(tag("witness::"), name())),
+        ),
+        value(
+            CompletionType::Parameter,
+            recognize(pair(tag("param::"), name())),
+        ),
+    ));
+    parser.parse(input)
+}
+
 /// Parses standard identifiers consisting of alphanumeric characters and underscores.
 fn parse_identifier(input: &str) -> IResult<&str, &str> {
     let mut parser = recognize(pair(
@@ -138,6 +156,7 @@ pub fn parse(input: &str) -> Option<CompletionType> {
             parse_assignment,
             parse_type_end,
             parse_jet,
+            parse_module_name,
             map(parse_identifier, |t| {
                 CompletionType::Identifier(t.to_owned())
             }),
@@ -153,6 +172,47 @@ pub fn parse(input: &str) -> Option<CompletionType> {
     completion.1.last().cloned()
 }
 
+/// Check if the cursor at the end of the line prefix is where a type is expected,
+/// possibly after the start of its name.
+///
+/// Types follow `:` and `->`, start the right-hand side of `type NAME =`,
+/// and are nested in tuples, arrays and generic arguments of types.
+pub fn is_type_position(prefix: &str) -> bool {
+    let before = trim_identifier(prefix).trim_end();
+    if before.ends_with("->") {
+        return true;
+    }
+    if let Some(rest) = before.strip_suffix(':') {
+        return !rest.ends_with(':');
+    }
+    if let Some(rest) = before.strip_suffix('=') {
+        let rest = trim_identifier(rest.trim_end()).trim_end();
+        return rest
+            .strip_suffix("type")
+            .is_some_and(|rest| rest.is_empty() || rest.ends_with(char::is_whitespace));
+    }
+    if !before.ends_with([',', '(', '[', '<']) {
+        return false;
+    }
+
+    // Find the bracket that encloses the cursor
+    let mut depth = 0usize;
+    for (index, c) in before.char_indices().rev() {
+        match c {
+            ')' | ']' | '>' => depth += 1,
+            '(' | '[' | '<' if depth == 0 => return c == '<' || is_type_position(&before[..index]),
+            '(' | '[' | '<' => depth -= 1,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Remove the identifier at the end of the text.
+fn trim_identifier(text: &str) -> &str {
+    text.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_')
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -196,6 +256,36 @@ mod tests {
         assert_eq!(parse(input), Some(CompletionType::Jet));
     }
 
+    #[test]
+    fn test_parse_module_names() {
+        assert_eq!(parse("witness::"), Some(CompletionType::Witness));
+        assert_eq!(parse("assert!(param::MA"), Some(CompletionType::Parameter));
+    }
+
+    #[test]
+    fn test_type_position() {
+        for prefix in [
+            "let x: ",
+            "let x: Pub",
+            "fn f(a: u8, b: (u8, ",
+            "fn f() -> Either<u8, Opt",
+            "type Pair = (u8, [",
+            "    None => (x, y), Some(z: ",
+        ] {
+            assert!(is_type_position(prefix), "{prefix}");
+        }
+        for prefix in [
+            "let (a, ",
+            "let x: u8 = ",
+            "jet::add_32(",
+            "fn f(a: u8, ",
+            "let x: u8 = foo(a, ",
+            "witness::",
+        ] {
+            assert!(!is_type_position(prefix), "{prefix}");
+        }
+    }
+
     #[test]
     fn test_parse_closing_type() {
         let input = ">::";
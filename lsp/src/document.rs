@@ -0,0 +1,367 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use miniscript::iter::TreeLike;
+
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{CodeLens, TextDocumentContentChangeEvent};
+
+use simplicityhl::{
+    ast::{self, ExprTree},
+    error::{RichError, WithFile},
+    parse,
+    parse::ParseFromStr,
+};
+
+use crate::error::LspError;
+use crate::function::Functions;
+use crate::syntax::SyntaxIndex;
+use crate::utils::{get_comments_from_lines, position_to_byte};
+
+/// Open text document.
+///
+/// The text is updated on every change,
+/// while the analysis is updated lazily and may lag behind the text.
+#[derive(Debug)]
+pub struct Document {
+    /// Current text of the document.
+    pub text: Rope,
+    /// Version of the current text.
+    pub version: i32,
+    /// Analysis of the latest analyzed version.
+    pub analysis: Arc<Analysis>,
+    /// Code lenses of the latest saved version, or `None` if they were not computed yet.
+    pub code_lenses: Option<Vec<CodeLens>>,
+    /// Whether the text matches the text of the client.
+    ///
+    /// The text diverges if a change cannot be applied.
+    /// Only a change of the whole text brings it back in sync.
+    pub synced: bool,
+}
+
+impl Document {
+    /// Create a document that has not been analyzed yet.
+    pub fn new(text: &str, version: i32) -> Self {
+        Self {
+            text: Rope::from_str(text),
+            version,
+            analysis: Arc::new(Analysis::default()),
+            code_lenses: None,
+            synced: true,
+        }
+    }
+
+    /// Apply the content change to the text.
+    ///
+    /// A change without range replaces the whole text.
+    /// A change whose range ends before it starts is rejected, because the texts diverged.
+    pub fn apply_change(
+        &mut self,
+        change: &TextDocumentContentChangeEvent,
+    ) -> Result<(), LspError> {
+        let Some(range) = change.range else {
+            self.text = Rope::from_str(&change.text);
+            return Ok(());
+        };
+        let start = self
+            .text
+            .byte_to_char(position_to_byte(&self.text, range.start)?);
+        let end = self
+            .text
+            .byte_to_char(position_to_byte(&self.text, range.end)?);
+        if end < start {
+            return Err(LspError::ConversionFailed(format!(
+                "Range of change ends before it starts: {range:?}"
+            )));
+        }
+        if start < end {
+            self.text.remove(start..end);
+        }
+        self.text.insert(start, &change.text);
+        Ok(())
+    }
+
+    /// Check if the analysis belongs to the current text.
+    pub fn is_analyzed(&self) -> bool {
+        self.analysis.version == Some(self.version)
+    }
+}
+
+/// Text of a document together with an analysis, taken at one point in time.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Text of the document.
+    pub text: Rope,
+    /// Analysis of the document.
+    ///
+    /// Depending on how the snapshot was taken, the analysis may belong to an older text.
+    pub analysis: Arc<Analysis>,
+}
+
+/// Result of parsing and analyzing one version of a document.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    /// Analyzed version of the document, or `None` if the document was never analyzed.
+    pub version: Option<i32>,
+    /// Custom functions of the program.
+    ///
+    /// If the program cannot be parsed, then the functions of the previous analysis are kept,
+    /// so completion keeps working while the user is typing.
+    pub functions: Functions,
+    /// Classified tokens of the program.
+    ///
+    /// Empty if the program cannot be parsed.
+    pub syntax: SyntaxIndex,
+    /// Typed program, if the program is well-typed.
+    pub program: Option<Arc<ast::Program>>,
+    /// First error of parsing or analyzing the program.
+    pub error: Option<RichError>,
+    /// Latest version of the program that could be parsed.
+    ///
+    /// If the program cannot be parsed, then the parsed program of the previous analysis is kept,
+    /// so completion keeps working while the user is typing.
+    pub parsed: Arc<Parsed>,
+}
+
+/// Text of a program that could be parsed, with its syntax index.
+#[derive(Debug, Default)]
+pub struct Parsed {
+    /// Text of the program.
+    pub text: String,
+    /// Classified tokens and bindings of the program.
+    pub syntax: SyntaxIndex,
+    /// Typed program of the latest version that was well-typed.
+    pub program: Option<Arc<ast::Program>>,
+}
+
+impl Parsed {
+    /// Map the byte offset in the current text to the corresponding offset in the parsed text.
+    ///
+    /// The offset is moved before the first byte where the two texts differ,
+    /// so it stays inside the scopes that enclose the edited part of the text.
+    pub fn offset(&self, current: &str, offset: usize) -> usize {
+        let common = self
+            .text
+            .bytes()
+            .zip(current.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        offset.min(common)
+    }
+}
+
+impl Analysis {
+    /// Parse and analyze the given version of the document text.
+    pub fn new(text: &str, version: i32, previous: &Analysis) -> Self {
+        let program = match parse::Program::parse_from_str(text) {
+            Ok(program) => program,
+            Err(error) => {
+                return Self {
+                    version: Some(version),
+                    functions: previous.functions.clone(),
+                    syntax: SyntaxIndex::default(),
+                    program: None,
+                    error: Some(error),
+                    parsed: Arc::clone(&previous.parsed),
+                }
+            }
+        };
+
+        let (typed, error) = match ast::Program::analyze(&program).with_file(text) {
+            Ok(typed) => (Some(Arc::new(typed)), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        let syntax = SyntaxIndex::new(text).unwrap_or_default();
+        let parsed = Arc::new(Parsed {
+            text: text.to_string(),
+            syntax: syntax.clone(),
+            program: typed.clone().or_else(|| previous.parsed.program.clone()),
+        });
+
+        Self {
+            version: Some(version),
+            functions: collect_functions(&program, text),
+            syntax,
+            program: typed,
+            error,
+            parsed,
+        }
+    }
+}
+
+impl Analysis {
+    /// Create the analysis of a version that could not be analyzed.
+    ///
+    /// Like for programs that cannot be parsed, the functions and the parsed program
+    /// of the previous analysis are kept.
+    pub fn failed(version: i32, previous: &Analysis) -> Self {
+        Self {
+            version: Some(version),
+            functions: previous.functions.clone(),
+            parsed: Arc::clone(&previous.parsed),
+            ..Self::default()
+        }
+    }
+
+    /// Visit each node of the typed program, including the bodies of custom functions.
+    ///
+    /// Bodies of custom functions are visited once, no matter how often they are called.
+    /// Functions that are not reachable from the main function are not part of the typed program.
+    pub fn visit_expressions<'a, F>(&'a self, mut f: F) -> Result<(), LspError>
+    where
+        F: FnMut(ExprTree<'a>) -> Result<(), LspError>,
+    {
+        let Some(program) = &self.program else {
+            return Ok(());
+        };
+        // Bodies of custom functions are not children of their calls in the expression tree
+        let mut bodies = vec![program.main()];
+        let mut visited = HashSet::new();
+        while let Some(body) = bodies.pop() {
+            if !visited.insert(*body.span()) {
+                continue;
+            }
+            for node in ExprTree::Expression(body).pre_order_iter() {
+                if let ExprTree::Call(call) = node {
+                    if let Some(function) = custom_function(call) {
+                        bodies.push(function.body());
+                    }
+                }
+                f(node)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return the custom function that the call runs, if any.
+pub fn custom_function(call: &ast::Call) -> Option<&ast::CustomFunction> {
+    match call.name() {
+        ast::CallName::Custom(function)
+        | ast::CallName::Fold(function, _)
+        | ast::CallName::ArrayFold(function, _)
+        | ast::CallName::ForWhile(function, _) => Some(function),
+        _ => None,
+    }
+}
+
+/// Collect the custom functions of the program together with their documentation.
+fn collect_functions(program: &parse::Program, text: &str) -> Functions {
+    let rope = Rope::from_str(text);
+    let mut functions = Functions::new();
+
+    program
+        .items()
+        .iter()
+        .filter_map(|item| {
+            if let parse::Item::Function(func) = item {
+                Some(func)
+            } else {
+                None
+            }
+        })
+        .for_each(|func| {
+            let start_line = u32::try_from(func.as_ref().start.line.get()).unwrap_or_default() - 1;
+
+            functions.insert(
+                func.name().to_string(),
+                func.to_owned(),
+                get_comments_from_lines(start_line, &rope),
+            );
+        });
+
+    functions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tower_lsp_server::lsp_types::{Position, Range};
+
+    fn sample_program() -> &'static str {
+        "fn add(a: u32, b: u32) -> u32 { let (_, res): (bool, u32) = jet::add_32(a, b); res }
+         fn main() {}"
+    }
+
+    fn invalid_program_on_ast() -> &'static str {
+        "fn add(a: u32, b: u32) -> u32 {}"
+    }
+
+    fn invalid_program_on_parsing() -> &'static str {
+        "fn add(a: u32 b: u32) -> u32 {}"
+    }
+
+    #[test]
+    fn test_parse_program_valid() {
+        let analysis = Analysis::new(sample_program(), 0, &Analysis::default());
+        assert!(analysis.error.is_none(), "Expected no parsing error");
+        assert!(analysis.program.is_some(), "Expected typed program");
+        assert_eq!(analysis.functions.map.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_program_invalid_ast() {
+        let analysis = Analysis::new(invalid_program_on_ast(), 0, &Analysis::default());
+        assert!(
+            analysis
+                .error
+                .unwrap()
+                .to_string()
+                .contains("Expected expression of type `u32`, found type `()`"),
+            "Expected error on return type"
+        );
+        assert_eq!(
+            analysis.functions.map.len(),
+            1,
+            "Expected problem in AST build, not parse"
+        );
+    }
+
+    #[test]
+    fn test_parse_program_invalid_parse() {
+        let previous = Analysis::new(sample_program(), 0, &Analysis::default());
+        let analysis = Analysis::new(invalid_program_on_parsing(), 1, &previous);
+        assert!(
+            analysis
+                .error
+                .unwrap()
+                .to_string()
+                .contains("Grammar error"),
+            "Expected `Grammar error`"
+        );
+        assert!(analysis.program.is_none());
+        assert!(analysis.syntax.tokens().is_empty());
+        // Functions of the last parsable version are kept
+        assert_eq!(analysis.functions.map.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_change() {
+        let mut document = Document::new("fn main() {\n    let a: u8 = 1;\n}", 0);
+        let change = |range: Option<Range>, text: &str| TextDocumentContentChangeEvent {
+            range,
+            range_length: None,
+            text: text.to_string(),
+        };
+
+        let replace_a = Range::new(Position::new(1, 8), Position::new(1, 9));
+        document
+            .apply_change(&change(Some(replace_a), "ab"))
+            .unwrap();
+        let insert = Range::new(Position::new(2, 0), Position::new(2, 0));
+        document
+            .apply_change(&change(Some(insert), "    ab\n"))
+            .unwrap();
+        assert_eq!(
+            document.text.to_string(),
+            "fn main() {\n    let ab: u8 = 1;\n    ab\n}"
+        );
+
+        document
+            .apply_change(&change(None, "fn main() {}"))
+            .unwrap();
+        assert_eq!(document.text.to_string(), "fn main() {}");
+    }
+}
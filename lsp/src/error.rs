@@ -28,6 +28,14 @@ pub enum LspError {
     #[error("Document not found: {0:?}")]
     DocumentNotFound(Uri),
 
+    /// The text of the document diverged from the text of the client.
+    #[error("Document is out of sync, waiting for its whole text: {0:?}")]
+    DocumentUnsynced(Uri),
+
+    /// The requested rename would produce an invalid program.
+    #[error("Invalid rename: {0}")]
+    InvalidRename(String),
+
     /// A generic or unexpected internal error.
     #[error("Internal error: {0}")]
     Internal(String),
@@ -45,6 +53,8 @@ impl LspError {
             LspError::CallNotFound(_) => 3,
             LspError::DocumentNotFound(_) => 4,
             LspError::IntegerConversionFailed(_) => 5,
+            LspError::InvalidRename(_) => 6,
+            LspError::DocumentUnsynced(_) => 7,
             LspError::Internal(_) => 100,
         }
     }
@@ -2,7 +2,7 @@ use simplicityhl::parse::Function;
 use std::collections::HashMap;
 
 /// Container for parsed functions and their corresponding source text.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Functions {
     /// The map from function name to its parsed representation and source text.
     pub map: HashMap<String, (Function, String)>,
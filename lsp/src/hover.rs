@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{
+    Hover, HoverContents, MarkupContent, MarkupKind, Position, Range,
+};
+
+use simplicityhl::ast::{self, ExprTree};
+use simplicityhl::parse::{self, ParseFromStr};
+use simplicityhl::str::{AliasName, WitnessName};
+use simplicityhl::types::{AliasedType, BuiltinAlias, ResolvedType};
+
+use crate::document::{Analysis, Snapshot};
+use crate::error::LspError;
+use crate::syntax::TokenKind;
+use crate::utils::{byte_to_position, get_comments_from_lines, position_to_byte, span_to_bytes};
+
+/// Compute the hover of the named symbol at the given position.
+///
+/// Symbols are variables, parameters of functions, witnesses, program parameters,
+/// constants, type aliases and builtin aliases. Calls are not handled here.
+pub fn symbol_hover(doc: &Snapshot, position: Position) -> Result<Option<Hover>, LspError> {
+    let syntax = &doc.analysis.syntax;
+    let Some(index) = syntax.token_at(position_to_byte(&doc.text, position)?) else {
+        return Ok(None);
+    };
+    let token = syntax.tokens()[index];
+    let name = doc.text.byte_slice(token.start..token.end).to_string();
+    let definition = token
+        .definition
+        .map(|definition| syntax.tokens()[definition]);
+
+    let (signature, description) = match token.kind {
+        TokenKind::Variable | TokenKind::Parameter => {
+            let Some(definition) = token.definition else {
+                return Ok(None);
+            };
+            let ty = variable_type(&doc.analysis, &doc.text, definition)?;
+            let signature = match ty {
+                Some(ty) => format!("{name}: {ty}"),
+                None => name.clone(),
+            };
+            let description = match token.kind {
+                TokenKind::Parameter => "Parameter",
+                _ => "Variable",
+            };
+            (signature, description.to_string())
+        }
+        TokenKind::Witness | TokenKind::ModuleParameter => {
+            let (namespace, description) = match token.kind {
+                TokenKind::Witness => ("witness", "Witness"),
+                _ => ("param", "Program parameter"),
+            };
+            let witness = WitnessName::from_str_unchecked(&name);
+            let ty = doc
+                .analysis
+                .program
+                .as_ref()
+                .and_then(|program| match token.kind {
+                    TokenKind::Witness => program.witness_types().get(&witness).cloned(),
+                    _ => program.parameters().get(&witness).cloned(),
+                });
+            let signature = match ty {
+                Some(ty) => format!("{namespace}::{name}: {ty}"),
+                None => format!("{namespace}::{name}"),
+            };
+            (signature, description.to_string())
+        }
+        TokenKind::Constant => {
+            let Some(constant) = constant(&doc.analysis, &doc.text.to_string(), &name) else {
+                return Ok(None);
+            };
+            (constant, "Constant".to_string())
+        }
+        TokenKind::TypeAlias => {
+            let Some(alias) = type_alias(&doc.text.to_string(), &name) else {
+                return Ok(None);
+            };
+            (alias, "Type alias".to_string())
+        }
+        TokenKind::BuiltinAlias => {
+            let Ok(alias) = BuiltinAlias::from_str(&name) else {
+                return Ok(None);
+            };
+            (
+                format!("type {name} = {}", alias.resolve()),
+                format!(
+                    "Built-in type alias\n\n{}",
+                    builtin_alias_description(alias)
+                ),
+            )
+        }
+        _ => return Ok(None),
+    };
+
+    let mut value = format!("```simplicityhl\n{signature}\n```\n---\n{description}");
+    // Implicitly declared witnesses and parameters point to their first use
+    if let Some(definition) = definition.filter(|definition| definition.start != token.start) {
+        let line = byte_to_position(&doc.text, definition.start)?.line;
+        value = format!("{value}, defined on line {}", line + 1);
+    }
+    if let Some(definition) = definition {
+        let line = byte_to_position(&doc.text, definition.start)?.line;
+        let comments = get_comments_from_lines(line, &doc.text);
+        if !comments.is_empty() {
+            value.push_str("\n\n");
+            value.push_str(&comments);
+        }
+    }
+
+    Ok(Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(Range::new(
+            byte_to_position(&doc.text, token.start)?,
+            byte_to_position(&doc.text, token.end)?,
+        )),
+    }))
+}
+
+/// Return the resolved type of the variable or parameter that the token defines.
+///
+/// The type is taken from a use of the variable in the typed program.
+/// Variables that are bound by `let` statements are typed by their pattern.
+/// Unused parameters are typed by their declaration.
+fn variable_type(
+    analysis: &Analysis,
+    text: &Rope,
+    definition: usize,
+) -> Result<Option<ResolvedType>, LspError> {
+    let tokens = analysis.syntax.tokens();
+    let defined = tokens[definition];
+    let name = text.byte_slice(defined.start..defined.end).to_string();
+
+    let mut found = None;
+    analysis.visit_expressions(|node| {
+        if found.is_some() {
+            return Ok(());
+        }
+        match node {
+            ExprTree::Single(single) => {
+                if let ast::SingleExpressionInner::Variable(identifier) = single.inner() {
+                    let start = span_to_bytes(text, single.span())?.start;
+                    let is_use = identifier.as_inner() == name
+                        && tokens.iter().any(|token| {
+                            token.start == start && token.definition == Some(definition)
+                        });
+                    if is_use {
+                        found = Some(single.ty().clone());
+                    }
+                }
+            }
+            ExprTree::Assignment(assignment) => {
+                // The variable must be bound by the pattern and not inside the expression
+                let start = span_to_bytes(text, assignment.span())?.start;
+                let expression = span_to_bytes(text, assignment.expression().span())?;
+                if (start..expression.start).contains(&defined.start) {
+                    if let Ok(types) = assignment
+                        .pattern()
+                        .is_of_type(assignment.expression().ty())
+                    {
+                        found = types.into_iter().find_map(|(identifier, ty)| {
+                            (identifier.as_inner() == name).then_some(ty)
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+    if found.is_some() || defined.kind != TokenKind::Parameter {
+        return Ok(found);
+    }
+
+    // The parameter belongs to the closest function that is defined before it
+    let Some(function) = tokens[..definition]
+        .iter()
+        .rev()
+        .find(|token| token.kind == TokenKind::Function)
+    else {
+        return Ok(None);
+    };
+    let function = text.byte_slice(function.start..function.end).to_string();
+    let Some(function) = analysis.functions.get_func(&function) else {
+        return Ok(None);
+    };
+    let Some(param) = function
+        .params()
+        .iter()
+        .find(|param| param.identifier().as_inner() == name)
+    else {
+        return Ok(None);
+    };
+    Ok(resolve(&text.to_string(), param.ty()))
+}
+
+/// Resolve the type with the aliases that the program defines.
+pub fn resolve(text: &str, ty: &AliasedType) -> Option<ResolvedType> {
+    let aliases = aliases(text)?;
+    ty.resolve(|name| aliases.get(name).map(|(_, resolved)| resolved.clone()))
+        .ok()
+}
+
+/// Return the declared and resolved types of the aliases of the program.
+pub fn aliases(text: &str) -> Option<HashMap<AliasName, (AliasedType, ResolvedType)>> {
+    let program = parse::Program::parse_from_str(text).ok()?;
+    let mut aliases = HashMap::new();
+    for item in program.items() {
+        let parse::Item::TypeAlias(alias) = item else {
+            continue;
+        };
+        // Aliases can only refer to aliases that are defined before them
+        let Ok(resolved) = alias.ty().resolve(|name| {
+            aliases
+                .get(name)
+                .map(|(_, resolved): &(_, ResolvedType)| resolved.clone())
+        }) else {
+            continue;
+        };
+        aliases.insert(alias.name().clone(), (alias.ty().clone(), resolved));
+    }
+    Some(aliases)
+}
+
+/// Return the declaration of the type alias with its structural type.
+fn type_alias(text: &str, name: &str) -> Option<String> {
+    let aliases = aliases(text)?;
+    let (declared, resolved) = aliases.get(&AliasName::from_str_unchecked(name))?;
+    let declared = declared.to_string();
+    let resolved = resolved.to_string();
+    if declared == resolved {
+        Some(format!("type {name} = {declared}"))
+    } else {
+        Some(format!("type {name} = {declared}\n// = {resolved}"))
+    }
+}
+
+/// Return the declaration of the constant with its value.
+///
+/// The value is only known if the program is well-typed.
+fn constant(analysis: &Analysis, text: &str, name: &str) -> Option<String> {
+    let program = parse::Program::parse_from_str(text).ok()?;
+    let constant = program.items().iter().find_map(|item| match item {
+        parse::Item::Constant(constant) if constant.name().as_inner() == name => Some(constant),
+        _ => None,
+    })?;
+    let value = analysis
+        .program
+        .as_ref()
+        .and_then(|program| program.constants().get(constant.name()));
+    match value {
+        Some(value) => Some(format!("const {name}: {} = {value}", constant.ty())),
+        None => Some(format!("const {name}: {}", constant.ty())),
+    }
+}
+
+/// Return the description of the builtin alias.
+pub fn builtin_alias_description(alias: BuiltinAlias) -> &'static str {
+    use BuiltinAlias as B;
+
+    match alias {
+        B::Ctx8 => "SHA-256 hash context: the buffer of up to 63 unhashed bytes, the number of hashed bytes and the midstate.",
+        B::Pubkey => "X-only public key (BIP 340).",
+        B::Message => "Message of 32 bytes.",
+        B::Message64 => "Message of 64 bytes.",
+        B::Signature => "Schnorr signature (BIP 340).",
+        B::Scalar => "Scalar of the secp256k1 group.",
+        B::Fe => "Element of the secp256k1 base field.",
+        B::Ge => "Point of the secp256k1 curve in affine coordinates.",
+        B::Gej => "Point of the secp256k1 curve in Jacobian coordinates.",
+        B::Point => "Compressed point of the secp256k1 curve: the parity of the y-coordinate and the x-coordinate.",
+        B::Height => "Absolute block height.",
+        B::Time => "Absolute UNIX timestamp.",
+        B::Distance => "Relative distance in blocks.",
+        B::Duration => "Relative duration in units of 512 seconds.",
+        B::Lock => "Absolute lock time, either a block height or a UNIX timestamp.",
+        B::Outpoint => "Transaction output: the transaction ID and the output index.",
+        B::Confidential1 => "Pedersen commitment: the parity of the y-coordinate and the x-coordinate.",
+        B::ExplicitAsset => "Explicit asset ID.",
+        B::Asset1 => "Asset ID, either confidential (left) or explicit (right).",
+        B::ExplicitAmount => "Explicit amount.",
+        B::Amount1 => "Amount, either confidential (left) or explicit (right).",
+        B::ExplicitNonce => "Explicit nonce.",
+        B::Nonce => "Nonce, either confidential (left) or explicit (right).",
+        B::TokenAmount1 => "Amount of issued tokens, either confidential (left) or explicit (right).",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    fn hover(text: &str, needle: &str) -> Option<String> {
+        let snapshot = Snapshot {
+            text: Rope::from_str(text),
+            analysis: Arc::new(Analysis::new(text, 0, &Analysis::default())),
+        };
+        let position = byte_to_position(&snapshot.text, text.find(needle).unwrap()).unwrap();
+        symbol_hover(&snapshot, position).unwrap().map(|hover| {
+            let HoverContents::Markup(content) = hover.contents else {
+                unreachable!()
+            };
+            content.value
+        })
+    }
+
+    const PROGRAM: &str = "/// Pair of a height and a key.
+type Entry = (Height, Pubkey);
+
+fn check(entry: Entry, unused: Entry) {
+    let (height, key): Entry = entry;
+    assert!(jet::eq_32(height, 10));
+}
+
+fn main() {
+    /// The checked entry.
+    let entry: Entry = (10, param::KEY);
+    check(entry, entry);
+    let _: Signature = witness::SIG;
+}
+
+mod param {
+    /// Key of the owner.
+    const KEY: Pubkey = 0x79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798;
+}";
+
+    #[test]
+    fn test_variable_hover() {
+        let value = hover(PROGRAM, "height, 10").unwrap();
+        assert!(
+            value.starts_with("```simplicityhl\nheight: u32\n```"),
+            "{value}"
+        );
+        assert!(value.contains("Variable, defined on line 5"), "{value}");
+
+        let value = hover(PROGRAM, "entry, entry").unwrap();
+        assert!(value.contains("entry: (u32, u256)"), "{value}");
+        assert!(
+            value.contains("defined on line 11\n\nThe checked entry."),
+            "{value}"
+        );
+
+        let value = hover(PROGRAM, "unused").unwrap();
+        assert!(
+            value.contains("unused: (u32, u256)\n```\n---\nParameter"),
+            "{value}"
+        );
+    }
+
+    #[test]
+    fn test_global_hover() {
+        let value = hover(PROGRAM, "KEY)").unwrap();
+        assert!(
+            value.starts_with("```simplicityhl\nparam::KEY: u256\n```"),
+            "{value}"
+        );
+        assert!(
+            value.contains("Program parameter, defined on line 18\n\nKey of the owner."),
+            "{value}"
+        );
+
+        let value = hover(PROGRAM, "SIG").unwrap();
+        assert!(
+            value.starts_with("```simplicityhl\nwitness::SIG: [u8; 64]\n```\n---\nWitness"),
+            "{value}"
+        );
+
+        let value = hover(PROGRAM, "Entry, unused").unwrap();
+        assert!(
+            value.contains("type Entry = (Height, Pubkey)\n// = (u32, u256)"),
+            "{value}"
+        );
+        assert!(value.contains("Pair of a height and a key."), "{value}");
+
+        let value = hover(PROGRAM, "Signature").unwrap();
+        assert!(
+            value.starts_with("```simplicityhl\ntype Signature = [u8; 64]\n```"),
+            "{value}"
+        );
+        assert!(value.contains("Schnorr signature"), "{value}");
+
+        assert_eq!(hover(PROGRAM, "check("), None);
+    }
+
+    #[test]
+    fn test_constant_hover() {
+        let text = "/// Number of keys.
+const N: u8 = jet::complement_8(252);
+
+fn main() {
+    let keys: [u8; N] = [0, 1, 3];
+    assert!(jet::eq_8(N, 3));
+}";
+        let value = hover(text, "N]").unwrap();
+        assert!(
+            value.starts_with("```simplicityhl\nconst N: u8 = 3\n```\n---\nConstant"),
+            "{value}"
+        );
+        assert!(
+            value.contains("defined on line 2\n\nNumber of keys."),
+            "{value}"
+        );
+
+        let value = hover(text, "N, 3").unwrap();
+        assert!(
+            value.starts_with("```simplicityhl\nconst N: u8 = 3\n```"),
+            "{value}"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{InlayHint, InlayHintKind, InlayHintLabel, Position, Range};
+
+use simplicityhl::ast::{self, ExprTree};
+use simplicityhl::pattern::Pattern;
+use simplicityhl::types::{AliasedType, BuiltinAlias};
+
+use crate::document::Analysis;
+use crate::error::LspError;
+use crate::syntax::TokenKind;
+use crate::utils::{byte_to_position, position_to_byte, span_to_positions};
+
+/// Compute the inlay hints inside the given range of the document.
+///
+/// There are three kinds of hints:
+/// - The resolved types of variables that are bound by tuple or array patterns.
+/// - The parameters at call sites of custom functions and jets.
+/// - The structural types behind builtin aliases like `Pubkey`.
+///
+/// Types and parameters are only known for well-typed programs.
+/// Code that is not reachable from the main function is not analyzed, so it gets no hints.
+pub fn inlay_hints(
+    analysis: &Analysis,
+    text: &Rope,
+    range: Range,
+) -> Result<Vec<InlayHint>, LspError> {
+    // Hints are sorted by position and deduplicated
+    let mut hints = BTreeMap::new();
+
+    alias_hints(analysis, text, &mut hints)?;
+    analysis.visit_expressions(|node| match node {
+        ExprTree::Assignment(assignment) => pattern_hints(analysis, text, assignment, &mut hints),
+        ExprTree::Call(call) => call_hints(call, &mut hints),
+        _ => Ok(()),
+    })?;
+
+    Ok(hints
+        .into_iter()
+        .filter(|((position, _), _)| range.start <= *position && *position <= range.end)
+        .map(|((position, label), kind)| InlayHint {
+            position,
+            label: InlayHintLabel::String(label),
+            kind: Some(kind),
+            text_edits: None,
+            tooltip: None,
+            padding_left: Some(kind == InlayHintKind::TYPE),
+            padding_right: Some(kind == InlayHintKind::PARAMETER),
+            data: None,
+        })
+        .collect())
+}
+
+type Hints = BTreeMap<(Position, String), InlayHintKind>;
+
+/// Show the structural type after each builtin alias.
+fn alias_hints(analysis: &Analysis, text: &Rope, hints: &mut Hints) -> Result<(), LspError> {
+    for token in analysis.syntax.tokens() {
+        if token.kind != TokenKind::BuiltinAlias {
+            continue;
+        }
+        let name = text.byte_slice(token.start..token.end).to_string();
+        let Ok(alias) = BuiltinAlias::from_str(&name) else {
+            continue;
+        };
+        let Ok(resolved) = AliasedType::from(alias).resolve_builtin() else {
+            continue;
+        };
+        hints.insert(
+            (byte_to_position(text, token.end)?, format!("= {resolved}")),
+            InlayHintKind::TYPE,
+        );
+    }
+    Ok(())
+}
+
+/// Show the type of each variable that a tuple or array pattern binds.
+///
+/// Variables that are bound directly are not annotated, because their type is written next to them.
+fn pattern_hints(
+    analysis: &Analysis,
+    text: &Rope,
+    assignment: &ast::Assignment,
+    hints: &mut Hints,
+) -> Result<(), LspError> {
+    let pattern = assignment.pattern();
+    if matches!(pattern, Pattern::Identifier(_) | Pattern::Ignore) {
+        return Ok(());
+    }
+    let Ok(types) = pattern.is_of_type(assignment.expression().ty()) else {
+        return Ok(());
+    };
+
+    let (start, _) = span_to_positions(assignment.span())?;
+    let start = position_to_byte(text, start)?;
+    // The variables of the pattern are the first variables that are defined inside the assignment
+    let bindings = analysis
+        .syntax
+        .tokens()
+        .iter()
+        .enumerate()
+        .filter(|(index, token)| {
+            start <= token.start && token.kind == TokenKind::Variable && token.is_definition(*index)
+        })
+        .take(types.len());
+
+    for (_, token) in bindings {
+        let name = text.byte_slice(token.start..token.end).to_string();
+        let Some(ty) = types
+            .iter()
+            .find_map(|(identifier, ty)| (identifier.as_inner() == name).then_some(ty))
+        else {
+            continue;
+        };
+        hints.insert(
+            (byte_to_position(text, token.end)?, format!(": {ty}")),
+            InlayHintKind::TYPE,
+        );
+    }
+    Ok(())
+}
+
+/// Show the parameter before each argument of a call of a custom function or of a jet.
+///
+/// Parameters of custom functions are shown by name.
+/// Parameters of jets have no names, so they are shown by their type.
+fn call_hints(call: &ast::Call, hints: &mut Hints) -> Result<(), LspError> {
+    let labels: Vec<String> = match call.name() {
+        ast::CallName::Custom(function) => function
+            .params()
+            .iter()
+            .map(|param| param.identifier().to_string())
+            .collect(),
+        ast::CallName::Jet(jet) => simplicityhl::jet::source_type(*jet)
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        _ => return Ok(()),
+    };
+    // Hints for a single argument of a jet add little information
+    if labels.len() < 2 && matches!(call.name(), ast::CallName::Jet(_)) {
+        return Ok(());
+    }
+
+    for (arg, label) in call.args().iter().zip(labels) {
+        if is_variable_named(arg, &label) {
+            continue;
+        }
+        let (start, _) = span_to_positions(arg.span())?;
+        hints.insert((start, format!("{label}:")), InlayHintKind::PARAMETER);
+    }
+    Ok(())
+}
+
+/// Check if the expression is a variable with the given name.
+fn is_variable_named(expr: &ast::Expression, name: &str) -> bool {
+    let ast::ExpressionInner::Single(single) = expr.inner() else {
+        return false;
+    };
+    matches!(single.inner(), ast::SingleExpressionInner::Variable(identifier) if identifier.as_inner() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hints(text: &str) -> Vec<(u32, u32, String)> {
+        let rope = Rope::from_str(text);
+        let analysis = Analysis::new(text, 0, &Analysis::default());
+        assert!(analysis.error.is_none(), "{:?}", analysis.error);
+        let range = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        inlay_hints(&analysis, &rope, range)
+            .unwrap()
+            .into_iter()
+            .map(|hint| {
+                let InlayHintLabel::String(label) = hint.label else {
+                    unreachable!()
+                };
+                (hint.position.line, hint.position.character, label)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pattern_and_jet_hints() {
+        let text = "fn main() {
+    let a: u32 = 1;
+    let (carry, sum): (bool, u32) = jet::add_32(a, 2);
+    assert!(jet::eq_32(sum, 3));
+    assert!(jet::eq_1(<bool>::into(carry), 0));
+}";
+        assert_eq!(
+            hints(text),
+            vec![
+                (2, 14, ": bool".to_string()),
+                (2, 19, ": u32".to_string()),
+                (2, 48, "u32:".to_string()),
+                (2, 51, "u32:".to_string()),
+                (3, 23, "u32:".to_string()),
+                (3, 28, "u32:".to_string()),
+                (4, 22, "u1:".to_string()),
+                (4, 43, "u1:".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_function_and_alias_hints() {
+        let text = "fn check(key: Pubkey, height: Height) {
+    let (_, [x, y]): (Lock, [u8; 2]) = (height, [1, 2]);
+}
+fn main() {
+    let key: Pubkey = 0x79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798;
+    check(key, 5)
+}";
+        assert_eq!(
+            hints(text),
+            vec![
+                (0, 20, "= u256".to_string()),
+                (0, 36, "= u32".to_string()),
+                (1, 14, ": u8".to_string()),
+                (1, 17, ": u8".to_string()),
+                (1, 26, "= u32".to_string()),
+                (4, 19, "= u256".to_string()),
+                (5, 15, "height:".to_string()),
+            ]
+        );
+    }
+}
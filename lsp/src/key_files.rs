@@ -0,0 +1,737 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range as ByteRange;
+use std::path::{Path, PathBuf};
+
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Diagnostic, DiagnosticSeverity, Range,
+    TextEdit,
+};
+
+use simplicityhl::ast;
+use simplicityhl::parse::ParseFromStr;
+use simplicityhl::str::WitnessName;
+use simplicityhl::types::ResolvedType;
+use simplicityhl::{Arguments, Parameters, Value, WitnessTypes, WitnessValues};
+
+use crate::error::LspError;
+use crate::utils::byte_to_position;
+
+/// JSON file that assigns values to names of a program.
+///
+/// The key files of `dir/program.simf` are `dir/program.EXT` and `dir/program.*.EXT`,
+/// like `htlc.complete.wit` for `htlc.simf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFile {
+    /// `.wit` file with the values of witnesses.
+    Witness,
+    /// `.args` file with the arguments of program parameters.
+    Arguments,
+}
+
+impl KeyFile {
+    /// Return the kind of key file that the path names, if any.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "wit" => Some(Self::Witness),
+            "args" => Some(Self::Arguments),
+            _ => None,
+        }
+    }
+
+    /// Return the file extension of this kind of key file.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Witness => "wit",
+            Self::Arguments => "args",
+        }
+    }
+
+    /// Return the names that the program declares for this kind of key file, with their types.
+    fn declared_names(self, program: &ast::Program) -> HashMap<WitnessName, ResolvedType> {
+        let names: Vec<_> = match self {
+            Self::Witness => program.witness_types().iter().collect(),
+            Self::Arguments => program.parameters().iter().collect(),
+        };
+        names
+            .into_iter()
+            .map(|(name, ty)| (name.clone(), ty.clone()))
+            .collect()
+    }
+
+    /// Check if the value is consistent with the type that the program declares for its name.
+    fn check_consistency(
+        self,
+        name: &WitnessName,
+        value: Value,
+        declared: &ResolvedType,
+    ) -> Result<(), simplicityhl::error::Error> {
+        let values = HashMap::from([(name.clone(), value)]);
+        let types = HashMap::from([(name.clone(), declared.clone())]);
+        match self {
+            Self::Witness => WitnessValues::from(values).is_consistent(&WitnessTypes::from(types)),
+            Self::Arguments => Arguments::from(values).is_consistent(&Parameters::from(types)),
+        }
+    }
+}
+
+/// Return the paths of the programs that the key file may belong to, most specific first.
+///
+/// The key file `dir/htlc.complete.wit` may belong to `dir/htlc.complete.simf` or `dir/htlc.simf`.
+pub fn program_candidates(key_file: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(mut stem)) = (
+        key_file.parent(),
+        key_file.file_stem().and_then(|stem| stem.to_str()),
+    ) else {
+        return vec![];
+    };
+
+    let mut candidates = vec![dir.join(format!("{stem}.simf"))];
+    while let Some((prefix, _)) = stem.rsplit_once('.') {
+        stem = prefix;
+        candidates.push(dir.join(format!("{stem}.simf")));
+    }
+    candidates
+}
+
+/// Find the key files of the program that have the given kind.
+pub fn sibling_files(program: &Path, kind: KeyFile) -> Vec<PathBuf> {
+    let (Some(dir), Some(stem)) = (program.parent(), program.file_stem()) else {
+        return vec![];
+    };
+    let Some(stem) = stem.to_str() else {
+        return vec![];
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let exact = format!("{stem}.{}", kind.extension());
+    let prefix = format!("{stem}.");
+    let suffix = format!(".{}", kind.extension());
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name == exact || (name.starts_with(&prefix) && name.ends_with(&suffix))
+                    })
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Compute the edits that rename the top-level key `old_name` of the JSON object to `new_name`.
+///
+/// Return `None` if the text is not a JSON object that contains the key.
+/// Return an error if the object already contains `new_name`.
+pub fn rename_json_key(
+    json: &str,
+    old_name: &str,
+    new_name: &str,
+) -> Result<Option<Vec<TextEdit>>, LspError> {
+    let Ok(serde_json::Value::Object(object)) = serde_json::from_str(json) else {
+        return Ok(None);
+    };
+    if !object.contains_key(old_name) {
+        return Ok(None);
+    }
+    if object.contains_key(new_name) {
+        return Err(LspError::InvalidRename(format!(
+            "Key `{new_name}` already exists"
+        )));
+    }
+
+    let text = Rope::from_str(json);
+    top_level_members(json)
+        .into_iter()
+        .filter(|member| json[member.key.clone()] == *old_name)
+        .map(|member| {
+            Ok(TextEdit {
+                range: byte_range_to_range(&text, &member.key)?,
+                new_text: new_name.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>, LspError>>()
+        .map(Some)
+}
+
+/// Validate the key file against the names and types that the program declares.
+///
+/// Without a program, only the syntax of the key file and its values is checked.
+pub fn diagnostics(
+    json: &str,
+    kind: KeyFile,
+    program: Option<&ast::Program>,
+) -> Result<Vec<Diagnostic>, LspError> {
+    let text = Rope::from_str(json);
+    let mut diagnostics = Diagnostics {
+        text: &text,
+        diagnostics: vec![],
+    };
+
+    if let Err(error) = serde_json::from_str::<serde_json::Value>(json) {
+        // Lines and columns of JSON errors start at 1
+        let line = error.line().saturating_sub(1).min(text.len_lines() - 1);
+        let offset = (text.line_to_byte(line) + error.column().saturating_sub(1)).min(json.len());
+        diagnostics.error(&(offset..offset), error.to_string())?;
+        return Ok(diagnostics.diagnostics);
+    }
+    let Some(object) = top_level_object(json) else {
+        diagnostics.error(
+            &(0..json.len()),
+            "Expected an object that maps names to values".to_string(),
+        )?;
+        return Ok(diagnostics.diagnostics);
+    };
+
+    let declared = program.map(|program| kind.declared_names(program));
+    let mut assigned = HashSet::new();
+    for member in members(json, object) {
+        let key = &json[member.key.clone()];
+        if !assigned.insert(key) {
+            diagnostics.error(&member.key, format!("Name `{key}` is assigned twice"))?;
+            continue;
+        }
+        let name = match WitnessName::parse_from_str(key) {
+            Ok(name) => name,
+            Err(error) => {
+                diagnostics.error(&member.key, error.error().to_string())?;
+                continue;
+            }
+        };
+        let Some((value, type_range)) = entry_value(json, &member, &mut diagnostics)? else {
+            continue;
+        };
+
+        let Some(declared) = &declared else {
+            continue;
+        };
+        match declared.get(&name) {
+            Some(ty) => {
+                if let Err(error) = kind.check_consistency(&name, value, ty) {
+                    diagnostics.error(&type_range, error.to_string())?;
+                }
+            }
+            None => diagnostics.push(
+                &member.key,
+                DiagnosticSeverity::WARNING,
+                match kind {
+                    KeyFile::Witness => format!("Witness `{name}` does not occur in the program"),
+                    KeyFile::Arguments => {
+                        format!("Parameter `{name}` does not occur in the program")
+                    }
+                },
+            )?,
+        }
+    }
+
+    let mut missing: Vec<_> = declared
+        .iter()
+        .flatten()
+        .filter(|(name, _)| !assigned.contains(name.as_inner()))
+        .map(|(name, _)| name.clone())
+        .collect();
+    missing.sort();
+    for name in missing {
+        let opening_brace = object..object + 1;
+        match kind {
+            KeyFile::Arguments => diagnostics.error(
+                &opening_brace,
+                simplicityhl::error::Error::ArgumentMissing(name).to_string(),
+            )?,
+            // Witnesses on branches that are pruned don't need a value
+            KeyFile::Witness => diagnostics.push(
+                &opening_brace,
+                DiagnosticSeverity::INFORMATION,
+                format!("Witness `{name}` has no value, so its branch must be pruned"),
+            )?,
+        }
+    }
+
+    Ok(diagnostics.diagnostics)
+}
+
+/// Parse the value of the entry of the key file.
+///
+/// Return the value together with the byte range of its type.
+fn entry_value(
+    json: &str,
+    member: &Member,
+    diagnostics: &mut Diagnostics,
+) -> Result<Option<(Value, ByteRange<usize>)>, LspError> {
+    if !json[member.value.clone()].starts_with('{') {
+        diagnostics.error(
+            &member.value,
+            "Expected an object with `value` and `type` fields".to_string(),
+        )?;
+        return Ok(None);
+    }
+
+    let mut value = None;
+    let mut ty = None;
+    for field in members(json, member.value.start) {
+        let slot = match &json[field.key.clone()] {
+            "value" => &mut value,
+            "type" => &mut ty,
+            other => {
+                diagnostics.error(
+                    &field.key,
+                    format!("Unknown field `{other}`, expected `value` or `type`"),
+                )?;
+                continue;
+            }
+        };
+        match serde_json::from_str::<String>(&json[field.value.clone()]) {
+            Ok(string) => *slot = Some((string, field.value)),
+            Err(_) => diagnostics.error(&field.value, "Expected a string".to_string())?,
+        }
+    }
+
+    let (Some((value, value_range)), Some((ty, type_range))) = (value, ty) else {
+        diagnostics.error(
+            &member.key,
+            "Expected an object with `value` and `type` fields".to_string(),
+        )?;
+        return Ok(None);
+    };
+    let ty = match ResolvedType::parse_from_str(&ty) {
+        Ok(ty) => ty,
+        Err(error) => {
+            diagnostics.error(&type_range, error.error().to_string())?;
+            return Ok(None);
+        }
+    };
+    match Value::parse_from_str(&value, &ty) {
+        Ok(value) => Ok(Some((value, type_range))),
+        Err(error) => {
+            diagnostics.error(&value_range, error.error().to_string())?;
+            Ok(None)
+        }
+    }
+}
+
+/// Return the names that the key file assigns, with the types of their entries if given.
+///
+/// The scan is lenient, so names are also found while the file is being edited.
+pub fn entries(json: &str) -> Vec<(String, Option<String>)> {
+    top_level_members(json)
+        .into_iter()
+        .map(|member| {
+            let ty = members(json, member.value.start)
+                .into_iter()
+                .find(|field| &json[field.key.clone()] == "type")
+                .and_then(|field| serde_json::from_str::<String>(&json[field.value]).ok());
+            (json[member.key].to_string(), ty)
+        })
+        .collect()
+}
+
+/// Complete the names and types of the program in its key file.
+///
+/// Inside a key, the names that the file does not assign yet are offered.
+/// Inside the `type` field of an entry, the declared type of its name is offered.
+/// Between entries, whole entries with an empty value are offered.
+pub fn completions(
+    json: &str,
+    offset: usize,
+    kind: KeyFile,
+    program: &ast::Program,
+) -> Result<Vec<CompletionItem>, LspError> {
+    let Some(object) = top_level_object(json) else {
+        return Ok(vec![]);
+    };
+    let text = Rope::from_str(json);
+    let declared = kind.declared_names(program);
+    let entries = members(json, object);
+
+    let mut unassigned: Vec<_> = declared
+        .iter()
+        .filter(|(name, _)| {
+            !entries
+                .iter()
+                .any(|member| json[member.key.clone()] == *name.as_inner())
+        })
+        .collect();
+    unassigned.sort_by_key(|(name, _)| *name);
+
+    for member in &entries {
+        // Cursor inside the quotes of the key
+        if member.key.start <= offset && offset <= member.key.end {
+            let range = byte_range_to_range(&text, &member.key)?;
+            return Ok(unassigned
+                .iter()
+                .map(|(name, ty)| CompletionItem {
+                    label: name.to_string(),
+                    kind: Some(CompletionItemKind::CONSTANT),
+                    detail: Some(ty.to_string()),
+                    text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                        range,
+                        name.to_string(),
+                    ))),
+                    ..CompletionItem::default()
+                })
+                .collect());
+        }
+        if !(member.value.start < offset && offset < member.value.end) {
+            continue;
+        }
+        // Cursor inside the value of the entry
+        let name = WitnessName::from_str_unchecked(&json[member.key.clone()]);
+        let Some(ty) = declared.get(&name) else {
+            return Ok(vec![]);
+        };
+        let type_field = members(json, member.value.start)
+            .into_iter()
+            .find(|field| &json[field.key.clone()] == "type");
+        let Some(content) = type_field.and_then(|field| string_content(json, &field.value)) else {
+            return Ok(vec![]);
+        };
+        if !(content.start <= offset && offset <= content.end) {
+            return Ok(vec![]);
+        }
+        return Ok(vec![CompletionItem {
+            label: ty.to_string(),
+            kind: Some(CompletionItemKind::TYPE_PARAMETER),
+            detail: Some(format!("Type of `{name}`")),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                byte_range_to_range(&text, &content)?,
+                ty.to_string(),
+            ))),
+            ..CompletionItem::default()
+        }]);
+    }
+
+    Ok(unassigned
+        .iter()
+        .map(|(name, ty)| CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::CONSTANT),
+            detail: Some(ty.to_string()),
+            insert_text: Some(format!(
+                "\"{name}\": {{ \"value\": \"\", \"type\": \"{ty}\" }}"
+            )),
+            ..CompletionItem::default()
+        })
+        .collect())
+}
+
+/// Collector of diagnostics with byte ranges.
+struct Diagnostics<'a> {
+    text: &'a Rope,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics<'_> {
+    fn push(
+        &mut self,
+        range: &ByteRange<usize>,
+        severity: DiagnosticSeverity,
+        message: String,
+    ) -> Result<(), LspError> {
+        self.diagnostics.push(Diagnostic {
+            severity: Some(severity),
+            ..Diagnostic::new_simple(byte_range_to_range(self.text, range)?, message)
+        });
+        Ok(())
+    }
+
+    fn error(&mut self, range: &ByteRange<usize>, message: String) -> Result<(), LspError> {
+        self.push(range, DiagnosticSeverity::ERROR, message)
+    }
+}
+
+fn byte_range_to_range(text: &Rope, range: &ByteRange<usize>) -> Result<Range, LspError> {
+    Ok(Range::new(
+        byte_to_position(text, range.start)?,
+        byte_to_position(text, range.end)?,
+    ))
+}
+
+/// Member of a JSON object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Member {
+    /// Byte range of the key, without quotes.
+    key: ByteRange<usize>,
+    /// Byte range of the value, which is empty if the value is missing.
+    value: ByteRange<usize>,
+}
+
+/// Return the offset of the opening brace of the top-level JSON object.
+fn top_level_object(json: &str) -> Option<usize> {
+    let start = skip_whitespace(json.as_bytes(), 0);
+    (json.as_bytes().get(start) == Some(&b'{')).then_some(start)
+}
+
+/// Return the members of the top-level JSON object.
+fn top_level_members(json: &str) -> Vec<Member> {
+    top_level_object(json).map_or_else(Vec::new, |object| members(json, object))
+}
+
+/// Return the members of the JSON object whose opening brace is at the given offset.
+///
+/// The scan is lenient, so members are also found while the user is typing.
+fn members(json: &str, object: usize) -> Vec<Member> {
+    let bytes = json.as_bytes();
+    let mut members = Vec::new();
+    if bytes.get(object) != Some(&b'{') {
+        return members;
+    }
+
+    let mut index = object + 1;
+    loop {
+        index = skip_whitespace(bytes, index);
+        match bytes.get(index) {
+            Some(b'"') => {
+                let end = string_end(bytes, index);
+                let key = index + 1..end;
+                index = skip_whitespace(bytes, end + 1);
+                let value = if bytes.get(index) == Some(&b':') {
+                    let start = skip_whitespace(bytes, index + 1);
+                    index = value_end(bytes, start);
+                    start..index
+                } else {
+                    index..index
+                };
+                members.push(Member { key, value });
+            }
+            Some(b',') => index += 1,
+            _ => return members,
+        }
+    }
+}
+
+/// Return the byte range of the content of the JSON string, without quotes.
+fn string_content(json: &str, value: &ByteRange<usize>) -> Option<ByteRange<usize>> {
+    let string = &json[value.clone()];
+    (2 <= string.len() && string.starts_with('"') && string.ends_with('"'))
+        .then(|| value.start + 1..value.end - 1)
+}
+
+fn skip_whitespace(bytes: &[u8], mut index: usize) -> usize {
+    while bytes.get(index).is_some_and(u8::is_ascii_whitespace) {
+        index += 1;
+    }
+    index
+}
+
+/// Return the offset of the quote that closes the string that opens at the given offset,
+/// or the end of the line if the string is not closed.
+fn string_end(bytes: &[u8], quote: usize) -> usize {
+    let mut index = quote + 1;
+    while index < bytes.len() && !matches!(bytes[index], b'"' | b'\n') {
+        index += if bytes[index] == b'\\' { 2 } else { 1 };
+    }
+    index.min(bytes.len())
+}
+
+/// Return the offset after the JSON value that starts at the given offset.
+fn value_end(bytes: &[u8], start: usize) -> usize {
+    match bytes.get(start) {
+        Some(b'"') => {
+            let end = string_end(bytes, start);
+            if bytes.get(end) == Some(&b'"') {
+                end + 1
+            } else {
+                end
+            }
+        }
+        Some(b'{' | b'[') => {
+            let mut depth = 0usize;
+            let mut index = start;
+            while index < bytes.len() {
+                match bytes[index] {
+                    b'"' => index = string_end(bytes, index),
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return index + 1;
+                        }
+                    }
+                    _ => {}
+                }
+                index += 1;
+            }
+            bytes.len()
+        }
+        _ => {
+            let mut index = start;
+            while index < bytes.len()
+                && !matches!(bytes[index], b',' | b'}' | b']')
+                && !bytes[index].is_ascii_whitespace()
+            {
+                index += 1;
+            }
+            index
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tower_lsp_server::lsp_types::Position;
+
+    use crate::document::Analysis;
+
+    const PROGRAM: &str = "fn main() {
+    let key: Pubkey = param::KEY;
+    let height: Height = param::HEIGHT;
+    let sig: Signature = witness::SIG;
+    let ok: bool = witness::OK;
+}";
+
+    fn program() -> std::sync::Arc<ast::Program> {
+        let analysis = Analysis::new(PROGRAM, 0, &Analysis::default());
+        analysis.program.clone().expect("well-typed program")
+    }
+
+    fn messages(json: &str, kind: KeyFile) -> Vec<(u32, String)> {
+        diagnostics(json, kind, Some(&program()))
+            .unwrap()
+            .into_iter()
+            .map(|diagnostic| (diagnostic.range.start.line, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_arguments_diagnostics() {
+        let json = r#"{
+    "KEY": { "value": "0x79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798", "type": "u32" },
+    "OTHER": { "value": "1", "type": "u8" }
+}"#;
+        assert_eq!(
+            messages(json, KeyFile::Arguments),
+            vec![
+                (
+                    1,
+                    "Expected expression of type `u32`; found something else".to_string()
+                ),
+                (
+                    2,
+                    "Parameter `OTHER` does not occur in the program".to_string()
+                ),
+                (0, "Parameter `HEIGHT` is missing an argument".to_string()),
+            ]
+        );
+
+        let json = r#"{
+    "KEY": { "value": "1", "type": "u256" },
+    "HEIGHT": { "value": "1", "type": "u16" }
+}"#;
+        assert_eq!(
+            messages(json, KeyFile::Arguments),
+            vec![(
+                2,
+                "Parameter `HEIGHT` was declared with type `u32` but its assigned argument is of type `u16`"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_witness_diagnostics() {
+        let json = r#"{
+    "OK": { "value": "true", "type": "bool" },
+    "OK": { "value": "false", "type": "bool" },
+    "1A": { "value": "1", "type": "u8" },
+    "B": { "value": "1" },
+    "C": { "value": "1", "type": "u7" }
+}"#;
+        let messages = messages(json, KeyFile::Witness);
+        assert_eq!(messages[0], (2, "Name `OK` is assigned twice".to_string()));
+        assert_eq!(messages[1].0, 3);
+        assert_eq!(
+            messages[2],
+            (
+                4,
+                "Expected an object with `value` and `type` fields".to_string()
+            )
+        );
+        assert_eq!(messages[3].0, 5);
+        assert_eq!(
+            messages[4],
+            (
+                0,
+                "Witness `SIG` has no value, so its branch must be pruned".to_string()
+            )
+        );
+
+        let json = "{\n    \"OK\": { \"value\": \"true\", \"type\": \"bool\" },\n}";
+        assert_eq!(messages_without_program(json).len(), 1);
+        assert!(messages_without_program("[]")[0].contains("Expected an object"));
+    }
+
+    fn messages_without_program(json: &str) -> Vec<String> {
+        diagnostics(json, KeyFile::Witness, None)
+            .unwrap()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_completions() {
+        let program = program();
+        let labels = |json: &str, needle: &str| {
+            let offset = json.find(needle).unwrap() + needle.len();
+            completions(json, offset, KeyFile::Arguments, &program)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.label)
+                .collect::<Vec<_>>()
+        };
+
+        let json = "{\n    \"KEY\": { \"value\": \"1\", \"type\": \"\" },\n    \"\n}";
+        assert_eq!(labels(json, "\"type\": \""), vec!["u256"]);
+        assert_eq!(labels(json, "},\n    \""), vec!["HEIGHT"]);
+        assert_eq!(labels("{\n}", "{"), vec!["HEIGHT", "KEY"]);
+    }
+
+    #[test]
+    fn test_program_candidates() {
+        let candidates = program_candidates(Path::new("dir/htlc.complete.wit"));
+        assert_eq!(
+            candidates,
+            vec![
+                PathBuf::from("dir/htlc.complete.simf"),
+                PathBuf::from("dir/htlc.simf")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rename_json_key() {
+        let json = r#"{
+    "A": { "value": "1", "type": "u8" },
+    "B": { "value": "{\"A\": 1}", "type": "A" }
+}"#;
+        let edits = rename_json_key(json, "A", "C").unwrap().unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.start, Position::new(1, 5));
+        assert_eq!(edits[0].range.end, Position::new(1, 6));
+
+        assert!(rename_json_key(json, "D", "C").unwrap().is_none());
+        assert!(rename_json_key(json, "A", "B").is_err());
+        assert!(rename_json_key("not json", "A", "C").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sibling_files() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+        let files = sibling_files(&examples.join("htlc.simf"), KeyFile::Witness);
+        let names: Vec<_> = files.iter().filter_map(|file| file.file_name()).collect();
+        assert_eq!(names, vec!["htlc.complete.wit"]);
+
+        let files = sibling_files(&examples.join("p2pk.simf"), KeyFile::Arguments);
+        let names: Vec<_> = files.iter().filter_map(|file| file.file_name()).collect();
+        assert_eq!(names, vec!["p2pk.args"]);
+    }
+}
@@ -1,9 +1,20 @@
 #![warn(clippy::all, clippy::pedantic)]
 
 mod backend;
+mod code_actions;
+mod code_lens;
 mod completion;
+mod document;
 mod error;
 mod function;
+mod hover;
+mod inlay_hints;
+mod key_files;
+mod rename;
+mod semantic_tokens;
+mod signature_help;
+mod symbols;
+mod syntax;
 mod utils;
 
 use backend::Backend;
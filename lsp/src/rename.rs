@@ -0,0 +1,221 @@
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{Position, Range, TextEdit};
+
+use crate::error::LspError;
+use crate::key_files::KeyFile;
+use crate::syntax::{SyntaxIndex, TokenKind};
+use crate::utils::{byte_to_position, position_to_byte};
+
+/// Symbol that is about to be renamed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameTarget {
+    /// Kind of the renamed symbol.
+    pub kind: TokenKind,
+    /// Current name of the symbol.
+    pub name: String,
+    /// Index of the token under the cursor.
+    token: usize,
+}
+
+impl RenameTarget {
+    /// Return the kind of the key files whose keys name the symbol.
+    ///
+    /// Witness values are stored in `.wit` files and program parameters in `.args` files.
+    pub fn key_file(&self) -> Option<KeyFile> {
+        match self.kind {
+            TokenKind::Witness => Some(KeyFile::Witness),
+            TokenKind::ModuleParameter => Some(KeyFile::Arguments),
+            _ => None,
+        }
+    }
+}
+
+/// Find the symbol at the given position that can be renamed.
+///
+/// Keywords, builtins, jets and literals cannot be renamed.
+pub fn find_target(
+    index: &SyntaxIndex,
+    text: &Rope,
+    position: Position,
+) -> Result<Option<RenameTarget>, LspError> {
+    let offset = position_to_byte(text, position)?;
+    let Some(token) = index.token_at(offset) else {
+        return Ok(None);
+    };
+    let kind = index.tokens()[token].kind;
+    let renamable = matches!(
+        kind,
+        TokenKind::Function
+            | TokenKind::Parameter
+            | TokenKind::Variable
+            | TokenKind::Constant
+            | TokenKind::TypeAlias
+            | TokenKind::Witness
+            | TokenKind::ModuleParameter
+    );
+    if !renamable || index.references(token).is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(RenameTarget {
+        kind,
+        name: text
+            .byte_slice(index.tokens()[token].start..index.tokens()[token].end)
+            .to_string(),
+        token,
+    }))
+}
+
+/// Return the range of the name that is renamed.
+pub fn target_range(
+    index: &SyntaxIndex,
+    text: &Rope,
+    target: &RenameTarget,
+) -> Result<Range, LspError> {
+    let token = &index.tokens()[target.token];
+    Ok(Range::new(
+        byte_to_position(text, token.start)?,
+        byte_to_position(text, token.end)?,
+    ))
+}
+
+/// Compute the edits that rename the symbol inside the document.
+///
+/// The renamed program must resolve every name to the same definition as before.
+/// This rejects names that are not valid identifiers, that are reserved,
+/// or that would capture or be captured by another binding.
+pub fn rename_in_document(
+    index: &SyntaxIndex,
+    text: &Rope,
+    target: &RenameTarget,
+    new_name: &str,
+) -> Result<Vec<TextEdit>, LspError> {
+    let references = index.references(target.token);
+    let source = text.to_string();
+
+    let mut renamed = String::with_capacity(source.len());
+    let mut last = 0;
+    for &reference in &references {
+        let token = &index.tokens()[reference];
+        renamed.push_str(&source[last..token.start]);
+        renamed.push_str(new_name);
+        last = token.end;
+    }
+    renamed.push_str(&source[last..]);
+
+    let invalid =
+        || LspError::InvalidRename(format!("Cannot rename `{}` to `{new_name}`", target.name));
+    let renamed_index = SyntaxIndex::new(&renamed).ok_or_else(invalid)?;
+    let same_resolution = renamed_index.tokens().len() == index.tokens().len()
+        && renamed_index
+            .tokens()
+            .iter()
+            .zip(index.tokens())
+            .all(|(new, old)| new.kind == old.kind && new.definition == old.definition);
+    if !same_resolution {
+        return Err(invalid());
+    }
+
+    references
+        .into_iter()
+        .map(|reference| {
+            let token = &index.tokens()[reference];
+            Ok(TextEdit {
+                range: Range::new(
+                    byte_to_position(text, token.start)?,
+                    byte_to_position(text, token.end)?,
+                ),
+                new_text: new_name.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename(text: &str, needle: &str, new_name: &str) -> Result<String, LspError> {
+        let rope = Rope::from_str(text);
+        let index = SyntaxIndex::new(text).unwrap();
+        let position = byte_to_position(&rope, text.find(needle).unwrap()).unwrap();
+        let target = find_target(&index, &rope, position)?.expect("renamable symbol");
+        let edits = rename_in_document(&index, &rope, &target, new_name)?;
+
+        let mut rope = rope;
+        for edit in edits.iter().rev() {
+            let start = position_to_byte(&rope, edit.range.start).unwrap();
+            let end = position_to_byte(&rope, edit.range.end).unwrap();
+            let start = rope.byte_to_char(start);
+            let end = rope.byte_to_char(end);
+            rope.remove(start..end);
+            rope.insert(start, &edit.new_text);
+        }
+        Ok(rope.to_string())
+    }
+
+    #[test]
+    fn test_rename_shadowed_variable() {
+        let text = "fn main() {
+    let x: u8 = 1;
+    let y: u8 = {
+        let x: u8 = x;
+        x
+    };
+    let z: u8 = x;
+}";
+        let expected = "fn main() {
+    let x: u8 = 1;
+    let y: u8 = {
+        let inner: u8 = x;
+        inner
+    };
+    let z: u8 = x;
+}";
+        assert_eq!(rename(text, "x: u8 = x", "inner").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_rename_globals() {
+        let text = "type Word = u32;
+fn f(w: Word) -> Word { w }
+fn main() { let a: Word = f(witness::W); assert!(jet::eq_32(a, param::P)); }";
+
+        let renamed = rename(text, "Word", "Int").unwrap();
+        assert_eq!(renamed.matches("Int").count(), 4);
+
+        let renamed = rename(text, "f(witness", "g").unwrap();
+        assert!(renamed.contains("fn g(w: Word)") && renamed.contains("= g(witness"));
+
+        let renamed = rename(text, "w }", "word").unwrap();
+        assert!(renamed.contains("fn f(word: Word) -> Word { word }"));
+
+        let renamed = rename(text, "W)", "SIG").unwrap();
+        assert!(renamed.contains("witness::SIG"));
+    }
+
+    #[test]
+    fn test_rename_rejects_conflicts() {
+        let text = "fn main() { let a: u8 = 1; let b: u8 = 2; let c: u8 = a; }";
+        // `a` would be shadowed by `b` at its use
+        assert!(rename(text, "a: u8", "b").is_err());
+        assert!(rename(text, "a: u8", "1a").is_err());
+        assert!(rename(text, "a: u8", "true").is_err());
+        assert!(rename(text, "a: u8", "d").is_ok());
+
+        let text = "fn f() {} fn g() {} fn main() { f(); g() }";
+        assert!(rename(text, "f()", "g").is_err());
+        assert!(rename(text, "f()", "unwrap").is_err());
+    }
+
+    #[test]
+    fn test_not_renamable() {
+        let text = "fn main() { let a: u8 = jet::add_8(1, 2); }";
+        let rope = Rope::from_str(text);
+        let index = SyntaxIndex::new(text).unwrap();
+        for needle in ["fn", "let", "u8", "add_8", "1"] {
+            let position = byte_to_position(&rope, text.find(needle).unwrap()).unwrap();
+            assert_eq!(find_target(&index, &rope, position).unwrap(), None);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{
+    Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend,
+};
+
+use crate::error::LspError;
+use crate::syntax::{SyntaxIndex, TokenKind};
+use crate::utils::{byte_to_position, position_to_byte};
+
+/// Token types, in the order of the legend.
+const TOKEN_TYPES: [SemanticTokenType; 10] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::MACRO,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::COMMENT,
+];
+
+/// Token modifiers, in the order of the legend.
+const TOKEN_MODIFIERS: [SemanticTokenModifier; 3] = [
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::READONLY,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+];
+
+const DECLARATION: u32 = 1 << 0;
+const READONLY: u32 = 1 << 1;
+const DEFAULT_LIBRARY: u32 = 1 << 2;
+
+/// Index of the comment type in [`TOKEN_TYPES`].
+const COMMENT: u32 = 9;
+
+/// Return the legend of the semantic tokens that the server produces.
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// Return the index of the token type and the bitset of token modifiers.
+fn encode_kind(kind: TokenKind) -> (u32, u32) {
+    match kind {
+        TokenKind::Keyword => (0, 0),
+        TokenKind::Function => (1, 0),
+        TokenKind::Jet | TokenKind::Builtin => (1, DEFAULT_LIBRARY),
+        TokenKind::Parameter => (2, 0),
+        TokenKind::ModuleParameter => (2, READONLY),
+        TokenKind::Variable => (3, 0),
+        TokenKind::Witness | TokenKind::Constant => (3, READONLY),
+        TokenKind::TypeAlias => (4, 0),
+        TokenKind::BuiltinAlias | TokenKind::BuiltinType => (4, DEFAULT_LIBRARY),
+        TokenKind::Namespace => (5, 0),
+        TokenKind::Macro => (6, DEFAULT_LIBRARY),
+        TokenKind::Number => (7, 0),
+        TokenKind::EnumMember => (8, 0),
+    }
+}
+
+/// Compute the semantic tokens of the document, optionally restricted to the given range.
+///
+/// Tokens that span multiple lines (block comments) are split into one token per line,
+/// because not every client supports multiline tokens.
+pub fn semantic_tokens(
+    index: &SyntaxIndex,
+    text: &Rope,
+    range: Option<Range>,
+) -> Result<Vec<SemanticToken>, LspError> {
+    let (range_start, range_end) = match range {
+        Some(range) => (
+            position_to_byte(text, range.start)?,
+            position_to_byte(text, range.end)?,
+        ),
+        None => (0, text.len_bytes()),
+    };
+
+    let mut entries: Vec<(usize, usize, u32, u32)> = index
+        .tokens()
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| token.start < token.end)
+        .map(|(i, token)| {
+            let (token_type, mut modifiers) = encode_kind(token.kind);
+            if token.is_definition(i) {
+                modifiers |= DECLARATION;
+            }
+            (token.start, token.end, token_type, modifiers)
+        })
+        .collect();
+    for comment in index.comments() {
+        split_lines(text, comment.start, comment.end, |start, end| {
+            entries.push((start, end, COMMENT, 0));
+        })?;
+    }
+    entries.sort_unstable();
+
+    let mut data = Vec::new();
+    let mut previous = tower_lsp_server::lsp_types::Position::new(0, 0);
+    for (start, end, token_type, token_modifiers_bitset) in entries {
+        if end <= range_start || range_end <= start {
+            continue;
+        }
+        let start = byte_to_position(text, start)?;
+        let end = byte_to_position(text, end)?;
+        let delta_line = start.line - previous.line;
+        let delta_start = if delta_line == 0 {
+            start.character - previous.character
+        } else {
+            start.character
+        };
+        data.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length: end.character - start.character,
+            token_type,
+            token_modifiers_bitset,
+        });
+        previous = start;
+    }
+
+    Ok(data)
+}
+
+/// Split the byte range into one range per line, excluding line breaks.
+fn split_lines<F>(text: &Rope, start: usize, end: usize, mut f: F) -> Result<(), LspError>
+where
+    F: FnMut(usize, usize),
+{
+    let first_line = text
+        .try_byte_to_line(start)
+        .map_err(|err| LspError::ConversionFailed(err.to_string()))?;
+    let last_line = text
+        .try_byte_to_line(end)
+        .map_err(|err| LspError::ConversionFailed(err.to_string()))?;
+
+    for line in first_line..=last_line {
+        let line_start = text.line_to_byte(line);
+        let line_text = text.line(line).to_string();
+        let content_len = line_text.trim_end_matches(['\n', '\r']).len();
+        let segment_start = start.max(line_start);
+        let segment_end = end.min(line_start + content_len);
+        if segment_start < segment_end {
+            f(segment_start, segment_end);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semantic_tokens() {
+        let text = "/* a\n b */\nfn main() { let x: u8 = 1; }";
+        let rope = Rope::from_str(text);
+        let index = SyntaxIndex::new(text).unwrap();
+        let tokens = semantic_tokens(&index, &rope, None).unwrap();
+        let decoded: Vec<(u32, u32, u32, u32)> = tokens
+            .iter()
+            .map(|t| (t.delta_line, t.delta_start, t.length, t.token_type))
+            .collect();
+
+        assert_eq!(
+            decoded,
+            vec![
+                (0, 0, 4, COMMENT), // `/* a`
+                (1, 0, 5, COMMENT), // ` b */`
+                (1, 0, 2, 0),       // `fn`
+                (0, 3, 4, 1),       // `main`
+                (0, 9, 3, 0),       // `let`
+                (0, 4, 1, 3),       // `x`
+                (0, 3, 2, 4),       // `u8`
+                (0, 5, 1, 7),       // `1`
+            ]
+        );
+        assert_eq!(tokens[3].token_modifiers_bitset, DECLARATION);
+    }
+
+    #[test]
+    fn test_semantic_tokens_range() {
+        let text = "fn f() {}\nfn main() {}";
+        let rope = Rope::from_str(text);
+        let index = SyntaxIndex::new(text).unwrap();
+        let range = Range::new(
+            tower_lsp_server::lsp_types::Position::new(1, 0),
+            tower_lsp_server::lsp_types::Position::new(1, 12),
+        );
+        let tokens = semantic_tokens(&index, &rope, Some(range)).unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!((tokens[0].delta_line, tokens[0].delta_start), (1, 0));
+    }
+}
@@ -0,0 +1,266 @@
+use std::str::FromStr;
+
+use tower_lsp_server::lsp_types::{
+    Documentation, MarkupContent, MarkupKind, ParameterInformation, ParameterLabel, SignatureHelp,
+    SignatureInformation,
+};
+
+use crate::completion::{self, builtin, types::FunctionTemplate};
+use crate::function::Functions;
+
+/// Call whose arguments enclose the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EnclosingCall<'a> {
+    /// Name of the called function, like `jet::add_32`, `array_fold`, `into` or `assert!`.
+    callee: &'a str,
+    /// Index of the argument under the cursor.
+    active_parameter: u32,
+}
+
+/// Compute the signature help for the call that encloses the end of `prefix`.
+///
+/// The text before the cursor usually does not parse while the user is typing arguments,
+/// so the enclosing call is found by scanning the text backwards.
+pub fn signature_help(prefix: &str, functions: &Functions) -> Option<SignatureHelp> {
+    let call = enclosing_call(prefix)?;
+    let template = find_template(call.callee, functions)?;
+    let signature = signature_information(&template, call.active_parameter);
+
+    Some(SignatureHelp {
+        signatures: vec![signature],
+        active_signature: Some(0),
+        active_parameter: Some(call.active_parameter),
+    })
+}
+
+/// Find the template of the called function.
+fn find_template(callee: &str, functions: &Functions) -> Option<FunctionTemplate> {
+    if let Some(name) = callee.strip_prefix("jet::") {
+        let jet = simplicityhl::simplicity::jet::Elements::from_str(name).ok()?;
+        return Some(completion::jet::jet_to_template(jet));
+    }
+    if let Some(template) = builtin::get_builtin_functions()
+        .into_iter()
+        .find(|template| template.display_name == callee)
+    {
+        return Some(template);
+    }
+    let (function, doc) = functions.get(callee)?;
+    Some(completion::function_to_template(function, doc))
+}
+
+/// Build the signature with the offsets of each parameter inside its label.
+fn signature_information(
+    template: &FunctionTemplate,
+    active_parameter: u32,
+) -> SignatureInformation {
+    let mut label = if template.display_name == "into" {
+        // The input type of a cast is written before the function name
+        format!("<{}>::into(", template.generics.join(", "))
+    } else if template.generics.is_empty() {
+        format!("{}(", template.display_name)
+    } else {
+        format!(
+            "{}::<{}>(",
+            template.display_name,
+            template.generics.join(", ")
+        )
+    };
+
+    let mut parameters = Vec::with_capacity(template.args.len());
+    for (index, arg) in template.args.iter().enumerate() {
+        if 0 < index {
+            label.push_str(", ");
+        }
+        let start = utf16_len(&label);
+        label.push_str(arg);
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, utf16_len(&label)]),
+            documentation: None,
+        });
+    }
+    label.push_str(") -> ");
+    label.push_str(if template.return_type.is_empty() {
+        "()"
+    } else {
+        &template.return_type
+    });
+
+    SignatureInformation {
+        label,
+        documentation: (!template.description.is_empty()).then(|| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: template.description.clone(),
+            })
+        }),
+        parameters: Some(parameters),
+        active_parameter: Some(active_parameter),
+    }
+}
+
+fn utf16_len(s: &str) -> u32 {
+    u32::try_from(s.encode_utf16().count()).unwrap_or(u32::MAX)
+}
+
+/// Find the call whose argument list encloses the end of `prefix`.
+///
+/// Return `None` if the cursor is not inside the arguments of a call,
+/// for instance because a statement or block lies in between.
+fn enclosing_call(prefix: &str) -> Option<EnclosingCall<'_>> {
+    // Brackets inside comments must not be counted
+    let mut code = prefix.as_bytes().to_vec();
+    for comment in simplicityhl::parse::comments(prefix) {
+        code[comment].fill(b' ');
+    }
+
+    let mut depth = 0usize;
+    let mut commas = 0u32;
+    for index in (0..code.len()).rev() {
+        match code[index] {
+            b')' | b']' => depth += 1,
+            // `->` is an arrow and not a closing bracket
+            b'>' if index == 0 || code[index - 1] != b'-' => depth += 1,
+            b'(' | b'[' | b'<' if 0 < depth => depth -= 1,
+            b'(' => {
+                if let Some(callee) = callee_before(&prefix[..index]) {
+                    return Some(EnclosingCall {
+                        callee,
+                        active_parameter: commas,
+                    });
+                }
+                // Parenthesized expression or tuple: the cursor is inside an argument
+                commas = 0;
+            }
+            b'[' | b'<' => commas = 0,
+            b',' if depth == 0 => commas += 1,
+            b'{' | b'}' | b';' if depth == 0 => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Return the name of the function that is called with the arguments that start after `text`.
+fn callee_before(text: &str) -> Option<&str> {
+    let text = text.trim_end();
+    // Skip generic arguments like in `array_fold::<f, 4>`
+    let text = match text.strip_suffix('>') {
+        Some(rest) => {
+            let open = matching_open_angle(rest)?;
+            rest[..open].strip_suffix("::")?
+        }
+        None => text,
+    };
+
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let name_start = text
+        .trim_end_matches('!')
+        .trim_end_matches(is_name_char)
+        .len();
+    let name = &text[name_start..];
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let before = &text[..name_start];
+    if before.ends_with("jet::") {
+        return Some(&text[name_start - "jet::".len()..]);
+    }
+    if name == "into" {
+        return before.ends_with(">::").then_some(name);
+    }
+    // Variant constructors and keywords take arguments without being functions
+    match name {
+        "Left" | "Right" | "Some" | "match" | "list!" => None,
+        _ => Some(name),
+    }
+}
+
+/// Return the index of the `<` that matches the `>` directly after `text`.
+fn matching_open_angle(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    for (index, byte) in text.bytes().enumerate().rev() {
+        match byte {
+            b'>' => depth += 1,
+            b'<' if depth == 0 => return Some(index),
+            b'<' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::document::Analysis;
+
+    fn call(prefix: &str) -> Option<(&str, u32)> {
+        enclosing_call(prefix).map(|call| (call.callee, call.active_parameter))
+    }
+
+    #[test]
+    fn test_enclosing_call() {
+        assert_eq!(call("let x: u32 = jet::add_32("), Some(("jet::add_32", 0)));
+        assert_eq!(call("jet::add_32(a, "), Some(("jet::add_32", 1)));
+        assert_eq!(call("jet::add_32(f(a, b), (c, "), Some(("jet::add_32", 1)));
+        assert_eq!(call("jet::add_32(f(a, b), g(c, "), Some(("g", 1)));
+        assert_eq!(call("array_fold::<sum, 7>(arr, "), Some(("array_fold", 1)));
+        assert_eq!(call("let x: u8 = <(u4, u4)>::into("), Some(("into", 0)));
+        assert_eq!(
+            call("assert!(jet::eq_32(a, /* ( */ b"),
+            Some(("jet::eq_32", 1))
+        );
+        assert_eq!(
+            call("unwrap_left::<Either<u8, u8>>(x"),
+            Some(("unwrap_left", 0))
+        );
+        assert_eq!(call("f(a, [b, "), Some(("f", 1)));
+        assert_eq!(call("Some(a, "), None);
+        assert_eq!(call("f(a); g"), None);
+        assert_eq!(call("fn main() { let a: u8 = 1"), None);
+    }
+
+    #[test]
+    fn test_signature_help() {
+        let functions = Functions::new();
+
+        let help = signature_help("jet::add_32(a, ", &functions).unwrap();
+        let signature = &help.signatures[0];
+        assert_eq!(signature.label, "add_32(u32, u32) -> (bool, u32)");
+        assert_eq!(help.active_parameter, Some(1));
+        let parameters = signature.parameters.as_ref().unwrap();
+        assert_eq!(parameters[1].label, ParameterLabel::LabelOffsets([12, 15]));
+
+        let help = signature_help("for_while::<f>(", &functions).unwrap();
+        assert_eq!(
+            help.signatures[0].label,
+            "for_while::<f>(accumulator: A, context: C) -> Either<B, A>"
+        );
+
+        let help = signature_help("<u8>::into(", &functions).unwrap();
+        assert_eq!(help.signatures[0].label, "<Input>::into(input) -> Output");
+
+        assert!(signature_help("unknown(", &functions).is_none());
+    }
+
+    #[test]
+    fn test_custom_function_signature() {
+        let text = "/// Add two numbers.\nfn add(a: u32, b: u32) -> u32 { a }\nfn main() {}";
+        let analysis = Analysis::new(text, 0, &Analysis::default());
+
+        let help = signature_help("add(1, ", &analysis.functions).unwrap();
+        let signature = &help.signatures[0];
+        assert_eq!(signature.label, "add(a: u32, b: u32) -> u32");
+        assert_eq!(
+            signature.documentation,
+            Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: "Add two numbers.".to_string()
+            }))
+        );
+    }
+}
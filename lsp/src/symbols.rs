@@ -0,0 +1,374 @@
+use std::path::{Path, PathBuf};
+
+use pest::iterators::Pair;
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{
+    DocumentSymbol, Location, OneOf, Range, SymbolKind, Uri, WorkspaceSymbol,
+};
+use tower_lsp_server::UriExt;
+
+use simplicityhl::parse::Rule;
+
+use crate::error::LspError;
+use crate::utils::byte_to_position;
+
+/// Compute the outline of the program.
+///
+/// The outline lists functions, type aliases and the constants of the `witness` and `param` modules.
+/// Functions contain their `let` bindings, and bindings contain the bindings of their expression.
+///
+/// Return `None` if the program cannot be parsed.
+pub fn document_symbols(text: &str) -> Result<Option<Vec<DocumentSymbol>>, LspError> {
+    let Ok(program) = simplicityhl::parse::parse_syntax_tree(text) else {
+        return Ok(None);
+    };
+    let outline = Outline {
+        text: &Rope::from_str(text),
+    };
+
+    let mut symbols = Vec::new();
+    for item in program
+        .into_inner()
+        .filter(|node| node.as_rule() == Rule::item)
+    {
+        if let Some(node) = item.into_inner().next() {
+            symbols.push(outline.item(&node)?);
+        }
+    }
+    Ok(Some(symbols))
+}
+
+/// Collect the global symbols of all open documents and of all `.simf` files in the folders
+/// whose names match the query.
+///
+/// Open documents take precedence over their files on disk.
+/// Documents that cannot be parsed contribute no symbols.
+pub fn workspace_symbols(
+    documents: &[(Uri, String)],
+    folders: &[PathBuf],
+    query: &str,
+) -> Vec<WorkspaceSymbol> {
+    let mut symbols = Vec::new();
+    for (uri, text) in documents {
+        symbols.extend(global_symbols(uri, text, query).unwrap_or_default());
+    }
+
+    let mut files = Vec::new();
+    for folder in folders {
+        simf_files(folder, &mut files);
+    }
+    for file in files {
+        let Some(uri) = Uri::from_file_path(&file) else {
+            continue;
+        };
+        if documents.iter().any(|(open, _)| *open == uri) {
+            continue;
+        }
+        let Ok(text) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        symbols.extend(global_symbols(&uri, &text, query).unwrap_or_default());
+    }
+    symbols
+}
+
+/// Return the symbols of the document that are visible outside of functions.
+fn global_symbols(uri: &Uri, text: &str, query: &str) -> Result<Vec<WorkspaceSymbol>, LspError> {
+    let mut symbols = Vec::new();
+    for symbol in document_symbols(text)?.unwrap_or_default() {
+        for child in symbol.children.iter().flatten() {
+            if child.kind == SymbolKind::CONSTANT && matches_query(&child.name, query) {
+                symbols.push(workspace_symbol(uri, child, Some(&symbol.name)));
+            }
+        }
+        if matches_query(&symbol.name, query) {
+            symbols.push(workspace_symbol(uri, &symbol, None));
+        }
+    }
+    Ok(symbols)
+}
+
+fn workspace_symbol(
+    uri: &Uri,
+    symbol: &DocumentSymbol,
+    container: Option<&str>,
+) -> WorkspaceSymbol {
+    WorkspaceSymbol {
+        name: symbol.name.clone(),
+        kind: symbol.kind,
+        tags: None,
+        container_name: container.map(str::to_string),
+        location: OneOf::Left(Location::new(uri.clone(), symbol.selection_range)),
+        data: None,
+    }
+}
+
+/// Check if the characters of the query appear in the name in order, ignoring case.
+fn matches_query(name: &str, query: &str) -> bool {
+    let mut name = name.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|wanted| name.any(|c| c == wanted))
+}
+
+/// Collect the `.simf` files inside the directory and its subdirectories.
+///
+/// Hidden directories and `target` directories are skipped.
+/// Symbolic links to directories are not followed, so links cannot form a cycle.
+fn simf_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            if !name.starts_with('.') && name != "target" {
+                simf_files(&path, files);
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "simf")
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// Builder of document symbols.
+struct Outline<'a> {
+    text: &'a Rope,
+}
+
+impl Outline<'_> {
+    fn range(&self, node: &Pair<'_, Rule>) -> Result<Range, LspError> {
+        Ok(Range::new(
+            byte_to_position(self.text, node.as_span().start())?,
+            byte_to_position(self.text, node.as_span().end())?,
+        ))
+    }
+
+    fn symbol(
+        &self,
+        kind: SymbolKind,
+        node: &Pair<'_, Rule>,
+        name: &Pair<'_, Rule>,
+        detail: Option<String>,
+        children: Vec<DocumentSymbol>,
+    ) -> Result<DocumentSymbol, LspError> {
+        #[allow(deprecated)]
+        Ok(DocumentSymbol {
+            name: compact(name.as_str()),
+            detail,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: self.range(node)?,
+            selection_range: self.range(name)?,
+            children: Some(children),
+        })
+    }
+
+    /// Create the symbol of a type alias, function or module.
+    fn item(&self, node: &Pair<'_, Rule>) -> Result<DocumentSymbol, LspError> {
+        let inner: Vec<_> = node.clone().into_inner().collect();
+        match node.as_rule() {
+            Rule::type_alias => self.symbol(
+                SymbolKind::TYPE_PARAMETER,
+                node,
+                &inner[1],
+                Some(compact(inner[2].as_str())),
+                vec![],
+            ),
+            Rule::function => {
+                let signature: Vec<&str> =
+                    inner[2..inner.len() - 1].iter().map(Pair::as_str).collect();
+                let mut bindings = Vec::new();
+                self.bindings(inner[inner.len() - 1].clone(), &mut bindings)?;
+                self.symbol(
+                    SymbolKind::FUNCTION,
+                    node,
+                    &inner[1],
+                    Some(compact(&signature.join(" "))),
+                    bindings,
+                )
+            }
+            _ => {
+                let constants = inner
+                    .iter()
+                    .filter(|node| node.as_rule() == Rule::module_assign)
+                    .map(|constant| {
+                        let inner: Vec<_> = constant.clone().into_inner().collect();
+                        self.symbol(
+                            SymbolKind::CONSTANT,
+                            constant,
+                            &inner[1],
+                            Some(compact(inner[2].as_str())),
+                            vec![],
+                        )
+                    })
+                    .collect::<Result<_, _>>()?;
+                self.symbol(SymbolKind::MODULE, node, &inner[1], None, constants)
+            }
+        }
+    }
+
+    /// Collect the symbols of the `let` bindings inside the node.
+    ///
+    /// Bindings inside the expression of another binding become its children.
+    /// Bindings that ignore their value are left out, but their children are kept.
+    fn bindings(
+        &self,
+        node: Pair<'_, Rule>,
+        symbols: &mut Vec<DocumentSymbol>,
+    ) -> Result<(), LspError> {
+        for child in node.into_inner() {
+            if child.as_rule() != Rule::assignment {
+                self.bindings(child, symbols)?;
+                continue;
+            }
+            let inner: Vec<_> = child.clone().into_inner().collect();
+            let (pattern, ty, expression) = (&inner[1], &inner[2], &inner[3]);
+            let mut children = Vec::new();
+            self.bindings(expression.clone(), &mut children)?;
+            if pattern.as_str() == "_" {
+                symbols.extend(children);
+            } else {
+                symbols.push(self.symbol(
+                    SymbolKind::VARIABLE,
+                    &child,
+                    pattern,
+                    Some(compact(ty.as_str())),
+                    children,
+                )?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replace each run of whitespace by a single space.
+fn compact(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outline(symbols: &[DocumentSymbol]) -> Vec<String> {
+        symbols
+            .iter()
+            .map(|symbol| {
+                let children = outline(symbol.children.as_deref().unwrap_or_default());
+                if children.is_empty() {
+                    symbol.name.clone()
+                } else {
+                    format!("{} [{}]", symbol.name, children.join(", "))
+                }
+            })
+            .collect()
+    }
+
+    const PROGRAM: &str = "type Word = u32;
+
+fn add(a: Word, b: Word) -> Word {
+    let (_, sum): (bool, u32) = jet::add_32(a, b);
+    sum
+}
+
+fn main() {
+    let x: Word = {
+        let y: Word = witness::Y;
+        y
+    };
+    let _: Word = {
+        let z: Word = add(x, param::Z);
+        z
+    };
+}
+
+mod witness {
+    const Y: u32 = 1;
+}
+
+mod param {
+    const Z: u32 = 2;
+}";
+
+    #[test]
+    fn test_document_symbols() {
+        let symbols = document_symbols(PROGRAM).unwrap().unwrap();
+        assert_eq!(
+            outline(&symbols),
+            vec![
+                "Word",
+                "add [(_, sum)]",
+                "main [x [y], z]",
+                "witness [Y]",
+                "param [Z]"
+            ]
+        );
+        assert_eq!(symbols[1].kind, SymbolKind::FUNCTION);
+        assert_eq!(
+            symbols[1].detail.as_deref(),
+            Some("(a: Word, b: Word) -> Word")
+        );
+        assert_eq!(
+            symbols[1].selection_range.start,
+            byte_to_position(&Rope::from_str(PROGRAM), 21).unwrap()
+        );
+
+        assert!(document_symbols("fn main( {}").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_workspace_symbols() {
+        let uri: Uri = "file:///program.simf".parse().unwrap();
+        let documents = vec![(uri, PROGRAM.to_string())];
+
+        let names = |query: &str| {
+            let mut names: Vec<_> = workspace_symbols(&documents, &[], query)
+                .into_iter()
+                .map(|symbol| (symbol.name, symbol.container_name))
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names("ad"), vec![("add".to_string(), None)]);
+        assert_eq!(
+            names("z"),
+            vec![("Z".to_string(), Some("param".to_string()))]
+        );
+        assert_eq!(names("").len(), 7);
+    }
+
+    #[test]
+    fn test_simf_files() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+        let mut files = Vec::new();
+        simf_files(&examples, &mut files);
+        assert!(files.iter().any(|file| file.ends_with("p2pk.simf")));
+        assert!(files.iter().all(|file| file
+            .extension()
+            .is_some_and(|extension| extension == "simf")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_simf_files_symlink_loop() {
+        let dir = std::env::temp_dir().join("simplicityhl_lsp_symlink_loop");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested/main.simf"), "fn main() {}").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("nested/loop")).unwrap();
+
+        let mut files = Vec::new();
+        simf_files(&dir, &mut files);
+        assert_eq!(files, vec![dir.join("nested/main.simf")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
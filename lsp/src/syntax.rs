@@ -0,0 +1,888 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use pest::iterators::Pair;
+use simplicityhl::parse::Rule;
+
+/// Kind of a classified token of the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// Keyword like `fn` or `let`.
+    Keyword,
+    /// Name of a custom function.
+    Function,
+    /// Parameter of a custom function.
+    Parameter,
+    /// Local variable that is bound by a `let` statement or a match arm.
+    Variable,
+    /// Name of a constant item, as in `const NAME: T = expr;`.
+    Constant,
+    /// Name of a type alias.
+    TypeAlias,
+    /// Name of a builtin type alias like `Pubkey`.
+    BuiltinAlias,
+    /// Builtin type like `u32` or `Either`.
+    BuiltinType,
+    /// Name of a jet.
+    Jet,
+    /// Name of a witness value, as in `witness::NAME`.
+    Witness,
+    /// Name of a program parameter, as in `param::NAME`.
+    ModuleParameter,
+    /// Namespace like `jet`, `witness` or `param`.
+    Namespace,
+    /// Builtin function like `unwrap` or `array_fold`.
+    Builtin,
+    /// Builtin macro like `assert!`.
+    Macro,
+    /// Number literal, array size or list bound.
+    Number,
+    /// Variant constructor or literal like `Left`, `None` or `true`.
+    EnumMember,
+}
+
+/// Classified token of the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Token {
+    /// Byte offset where the token starts.
+    pub start: usize,
+    /// Byte offset where the token ends (exclusive).
+    pub end: usize,
+    /// Kind of the token.
+    pub kind: TokenKind,
+    /// Index of the token that defines the named symbol, if the token names a symbol
+    /// and its definition was found.
+    ///
+    /// Definitions point to themselves.
+    pub definition: Option<usize>,
+}
+
+impl Token {
+    /// Check if the token defines the symbol that it names.
+    pub fn is_definition(&self, index: usize) -> bool {
+        self.definition == Some(index)
+    }
+}
+
+/// Local variable or function parameter, together with the part of the text where it is visible.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Binding {
+    /// Index of the token that defines the variable.
+    pub token: usize,
+    /// Byte range where the variable is visible.
+    pub scope: Range<usize>,
+    /// Byte range of the declared type of the pattern that binds the variable.
+    pub ty: Range<usize>,
+    /// Position of the variable inside nested tuple and array patterns.
+    ///
+    /// Empty if the pattern is the variable itself.
+    pub path: Vec<usize>,
+}
+
+/// Index of all tokens of a program, with names resolved to their definitions.
+///
+/// Local variables and function parameters are resolved according to the scoping rules
+/// of SimplicityHL, so shadowed bindings are told apart.
+#[derive(Debug, Clone, Default)]
+pub struct SyntaxIndex {
+    /// Tokens in source order.
+    tokens: Vec<Token>,
+    /// Byte ranges of comments in source order.
+    comments: Vec<std::ops::Range<usize>>,
+    /// Local variables and function parameters in source order.
+    bindings: Vec<Binding>,
+}
+
+impl SyntaxIndex {
+    /// Build the index of the given program.
+    ///
+    /// Return `None` if the program cannot be parsed.
+    pub fn new(text: &str) -> Option<Self> {
+        let program = simplicityhl::parse::parse_syntax_tree(text).ok()?;
+        let mut resolver = Resolver::default();
+        resolver.program(program);
+        let (tokens, bindings) = resolver.finish();
+        Some(Self {
+            tokens,
+            comments: simplicityhl::parse::comments(text),
+            bindings,
+        })
+    }
+
+    /// Access the tokens in source order.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Access the byte ranges of comments in source order.
+    pub fn comments(&self) -> &[std::ops::Range<usize>] {
+        &self.comments
+    }
+
+    /// Return the local variables and function parameters that are visible at the given byte offset.
+    ///
+    /// Shadowed variables are included; they come before the variables that shadow them.
+    pub fn visible_bindings(&self, offset: usize) -> impl Iterator<Item = &Binding> {
+        self.bindings
+            .iter()
+            .filter(move |binding| binding.scope.contains(&offset))
+    }
+
+    /// Return the index of the token at the given byte offset.
+    ///
+    /// An offset directly after a token also counts as inside the token,
+    /// unless another token starts at this offset.
+    pub fn token_at(&self, offset: usize) -> Option<usize> {
+        let mut touching = None;
+        for (index, token) in self.tokens.iter().enumerate() {
+            if token.start <= offset && offset < token.end {
+                return Some(index);
+            }
+            if token.end == offset {
+                touching = Some(index);
+            }
+        }
+        touching
+    }
+
+    /// Return the indices of all tokens that name the same symbol as the given token,
+    /// including the definition and the given token itself.
+    ///
+    /// Return an empty vector if the token names no resolved symbol.
+    pub fn references(&self, index: usize) -> Vec<usize> {
+        let Some(definition) = self.tokens.get(index).and_then(|token| token.definition) else {
+            return vec![];
+        };
+        self.tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| token.definition == Some(definition))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+/// Global symbol that may be referenced before it is defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Global {
+    Function,
+    Alias,
+    Constant,
+    Witness,
+    Parameter,
+}
+
+/// Walk the concrete syntax tree and classify each token.
+#[derive(Debug, Default)]
+struct Resolver {
+    tokens: Vec<Token>,
+    /// Stack of scopes that map variable names to their defining token.
+    scopes: Vec<HashMap<String, usize>>,
+    /// Map of global names to their defining token.
+    globals: HashMap<(Global, String), usize>,
+    /// References to global names that are resolved after the walk.
+    pending: Vec<(usize, Global, String)>,
+    /// Local variables and function parameters with their scopes.
+    bindings: Vec<Binding>,
+}
+
+/// Variable that a pattern binds: its name, its defining token and its position in the pattern.
+type PatternBinding = (String, usize, Vec<usize>);
+
+impl Resolver {
+    fn push(&mut self, start: usize, end: usize, kind: TokenKind) -> usize {
+        self.tokens.push(Token {
+            start,
+            end,
+            kind,
+            definition: None,
+        });
+        self.tokens.len() - 1
+    }
+
+    fn push_pair(&mut self, pair: &Pair<Rule>, kind: TokenKind) -> usize {
+        let span = pair.as_span();
+        self.push(span.start(), span.end(), kind)
+    }
+
+    /// Push the first `len` bytes of the pair as token.
+    fn push_prefix(&mut self, pair: &Pair<Rule>, len: usize, kind: TokenKind) -> usize {
+        let start = pair.as_span().start();
+        self.push(start, start + len, kind)
+    }
+
+    fn define_local(&mut self, pair: &Pair<Rule>, kind: TokenKind) -> (String, usize) {
+        let index = self.push_pair(pair, kind);
+        self.tokens[index].definition = Some(index);
+        (pair.as_str().to_string(), index)
+    }
+
+    fn define_global(&mut self, pair: &Pair<Rule>, kind: TokenKind, global: Global) {
+        let index = self.push_pair(pair, kind);
+        let name = pair.as_str().to_string();
+        // Duplicate definitions are compilation errors; keep the first one
+        let definition = *self.globals.entry((global, name)).or_insert(index);
+        self.tokens[index].definition = Some(definition);
+    }
+
+    fn refer_global(&mut self, pair: &Pair<Rule>, kind: TokenKind, global: Global) {
+        let index = self.push_pair(pair, kind);
+        self.pending
+            .push((index, global, pair.as_str().to_string()));
+    }
+
+    /// Refer to a local variable or, if there is none of this name, to a constant.
+    fn refer_variable(&mut self, pair: &Pair<Rule>) {
+        let name = pair.as_str();
+        let definition = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied());
+        match definition {
+            Some(definition) => {
+                let index = self.push_pair(pair, self.tokens[definition].kind);
+                self.tokens[index].definition = Some(definition);
+            }
+            None => self.refer_global(pair, TokenKind::Variable, Global::Constant),
+        }
+    }
+
+    fn bind(&mut self, token: usize, scope: Range<usize>, ty: &Pair<Rule>, path: Vec<usize>) {
+        self.bindings.push(Binding {
+            token,
+            scope,
+            ty: ty.as_span().start()..ty.as_span().end(),
+            path,
+        });
+    }
+
+    fn finish(mut self) -> (Vec<Token>, Vec<Binding>) {
+        for (index, global, name) in std::mem::take(&mut self.pending) {
+            match self.globals.get(&(global, name.clone())) {
+                Some(&definition) => {
+                    self.tokens[index].definition = Some(definition);
+                    self.tokens[index].kind = self.tokens[definition].kind;
+                }
+                // Witnesses and parameters are declared implicitly by their first use
+                None if matches!(global, Global::Witness | Global::Parameter) => {
+                    self.tokens[index].definition = Some(index);
+                    self.globals.insert((global, name), index);
+                }
+                None => {}
+            }
+        }
+        // Bindings are created when their scope ends, so inner scopes come first
+        self.bindings
+            .sort_by_key(|binding| self.tokens[binding.token].start);
+        (self.tokens, self.bindings)
+    }
+
+    fn program(&mut self, pair: Pair<Rule>) {
+        for item in pair
+            .into_inner()
+            .filter(|pair| pair.as_rule() == Rule::item)
+        {
+            let item = item.into_inner().next().unwrap();
+            match item.as_rule() {
+                Rule::type_alias => self.type_alias(item),
+                Rule::test_function => {
+                    let mut it = item.into_inner();
+                    self.push_pair(&it.next().unwrap(), TokenKind::Macro);
+                    self.function(it.next().unwrap());
+                }
+                Rule::function => self.function(item),
+                Rule::constant => self.constant(item),
+                Rule::module => self.module(item),
+                _ => {}
+            }
+        }
+    }
+
+    fn type_alias(&mut self, pair: Pair<Rule>) {
+        let mut it = pair.into_inner();
+        self.push_pair(&it.next().unwrap(), TokenKind::Keyword);
+        self.define_global(&it.next().unwrap(), TokenKind::TypeAlias, Global::Alias);
+        self.ty(it.next().unwrap());
+    }
+
+    fn constant(&mut self, pair: Pair<Rule>) {
+        let mut it = pair.into_inner();
+        self.push_pair(&it.next().unwrap(), TokenKind::Keyword);
+        self.define_global(&it.next().unwrap(), TokenKind::Constant, Global::Constant);
+        self.ty(it.next().unwrap());
+        self.expression(it.next().unwrap());
+    }
+
+    fn function(&mut self, pair: Pair<Rule>) {
+        let mut it = pair.into_inner();
+        self.push_pair(&it.next().unwrap(), TokenKind::Keyword);
+        self.define_global(&it.next().unwrap(), TokenKind::Function, Global::Function);
+
+        let mut scope = HashMap::new();
+        let mut params = Vec::new();
+        for param in it.next().unwrap().into_inner() {
+            let mut it = param.into_inner();
+            let (name, index) = self.define_local(&it.next().unwrap(), TokenKind::Parameter);
+            scope.insert(name, index);
+            let ty = it.next().unwrap();
+            params.push((index, ty.clone()));
+            self.ty(ty);
+        }
+
+        self.scopes.push(scope);
+        for pair in it {
+            if pair.as_rule() == Rule::function_return {
+                self.ty(pair.into_inner().next().unwrap());
+            } else {
+                let body = pair.as_span().start()..pair.as_span().end();
+                for (index, ty) in &params {
+                    self.bind(*index, body.clone(), ty, vec![]);
+                }
+                self.block_expression(pair);
+            }
+        }
+        self.scopes.pop();
+    }
+
+    fn module(&mut self, pair: Pair<Rule>) {
+        let mut it = pair.into_inner();
+        self.push_pair(&it.next().unwrap(), TokenKind::Keyword);
+        let module_name = it.next().unwrap();
+        self.push_pair(&module_name, TokenKind::Namespace);
+        let (kind, global) = match module_name.as_str() {
+            "param" => (TokenKind::ModuleParameter, Global::Parameter),
+            _ => (TokenKind::Witness, Global::Witness),
+        };
+
+        for assignment in it {
+            let mut it = assignment.into_inner();
+            self.push_pair(&it.next().unwrap(), TokenKind::Keyword);
+            self.define_global(&it.next().unwrap(), kind, global);
+            self.ty(it.next().unwrap());
+            self.expression(it.next().unwrap());
+        }
+    }
+
+    fn ty(&mut self, pair: Pair<Rule>) {
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::alias_name => self.refer_global(&pair, TokenKind::TypeAlias, Global::Alias),
+                Rule::builtin_alias => {
+                    self.push_pair(&pair, TokenKind::BuiltinAlias);
+                }
+                Rule::boolean_type | Rule::unsigned_type => {
+                    self.push_pair(&pair, TokenKind::BuiltinType);
+                }
+                Rule::sum_type => {
+                    self.push_prefix(&pair, "Either".len(), TokenKind::BuiltinType);
+                    pair.into_inner().for_each(|ty| self.ty(ty));
+                }
+                Rule::option_type => {
+                    self.push_prefix(&pair, "Option".len(), TokenKind::BuiltinType);
+                    pair.into_inner().for_each(|ty| self.ty(ty));
+                }
+                Rule::list_type => {
+                    self.push_prefix(&pair, "List".len(), TokenKind::BuiltinType);
+                    let mut it = pair.into_inner();
+                    self.ty(it.next().unwrap());
+                    self.size(&it.next().unwrap());
+                }
+                Rule::array_type => {
+                    let mut it = pair.into_inner();
+                    self.ty(it.next().unwrap());
+                    self.size(&it.next().unwrap());
+                }
+                Rule::tuple_type => pair.into_inner().for_each(|ty| self.ty(ty)),
+                _ => self.ty(pair),
+            }
+        }
+    }
+
+    /// Classify an array size or list bound, which is a number or the name of a constant.
+    fn size(&mut self, pair: &Pair<Rule>) {
+        match pair.as_rule() {
+            Rule::const_name => self.refer_global(pair, TokenKind::Constant, Global::Constant),
+            _ => {
+                self.push_pair(pair, TokenKind::Number);
+            }
+        }
+    }
+
+    /// Walk the pattern and return the variables that it binds.
+    ///
+    /// The path is the position of the pattern inside its enclosing patterns.
+    fn pattern(
+        &mut self,
+        pair: Pair<Rule>,
+        path: &mut Vec<usize>,
+        bindings: &mut Vec<PatternBinding>,
+    ) {
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::variable_pattern => {
+                    let identifier = pair.into_inner().next().unwrap();
+                    let (name, index) = self.define_local(&identifier, TokenKind::Variable);
+                    bindings.push((name, index, path.clone()));
+                }
+                Rule::tuple_pattern | Rule::array_pattern => {
+                    for (position, pair) in pair.into_inner().enumerate() {
+                        path.push(position);
+                        self.pattern(pair, path, bindings);
+                        path.pop();
+                    }
+                }
+                Rule::pattern => self.pattern(pair, path, bindings),
+                _ => {}
+            }
+        }
+    }
+
+    fn expression(&mut self, pair: Pair<Rule>) {
+        let pair = pair.into_inner().next().unwrap();
+        match pair.as_rule() {
+            Rule::block_expression => self.block_expression(pair),
+            _ => self.single_expression(pair),
+        }
+    }
+
+    fn block_expression(&mut self, pair: Pair<Rule>) {
+        self.scopes.push(HashMap::new());
+        let end = pair.as_span().end();
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::statement => {
+                    let pair = pair.into_inner().next().unwrap();
+                    match pair.as_rule() {
+                        Rule::assignment => self.assignment(pair, end),
+                        _ => self.expression(pair),
+                    }
+                }
+                _ => self.expression(pair),
+            }
+        }
+        self.scopes.pop();
+    }
+
+    /// Walk the assignment, whose variables are visible until the given end of the block.
+    fn assignment(&mut self, pair: Pair<Rule>, block_end: usize) {
+        let visible = pair.as_span().end()..block_end;
+        let mut it = pair.into_inner();
+        self.push_pair(&it.next().unwrap(), TokenKind::Keyword);
+        let mut bindings = Vec::new();
+        self.pattern(it.next().unwrap(), &mut vec![], &mut bindings);
+        let ty = it.next().unwrap();
+        for (_, index, path) in &bindings {
+            self.bind(*index, visible.clone(), &ty, path.clone());
+        }
+        self.ty(ty);
+        // The assigned expression cannot see the variables that it is assigned to
+        self.expression(it.next().unwrap());
+        let scope = self
+            .scopes
+            .last_mut()
+            .expect("assignments are inside blocks");
+        scope.extend(bindings.into_iter().map(|(name, index, _)| (name, index)));
+    }
+
+    fn single_expression(&mut self, pair: Pair<Rule>) {
+        let pair = pair.into_inner().next().unwrap();
+        match pair.as_rule() {
+            Rule::expression => self.expression(pair),
+            Rule::left_expr | Rule::right_expr | Rule::some_expr => {
+                let len = pair.as_str().find('(').unwrap_or_default();
+                self.push_prefix(&pair, len, TokenKind::EnumMember);
+                self.expression(pair.into_inner().next().unwrap());
+            }
+            Rule::none_expr | Rule::false_expr | Rule::true_expr => {
+                self.push_pair(&pair, TokenKind::EnumMember);
+            }
+            Rule::dec_literal | Rule::bin_literal | Rule::hex_literal => {
+                self.push_pair(&pair, TokenKind::Number);
+            }
+            Rule::witness_expr | Rule::param_expr => {
+                let (kind, global) = match pair.as_rule() {
+                    Rule::witness_expr => (TokenKind::Witness, Global::Witness),
+                    _ => (TokenKind::ModuleParameter, Global::Parameter),
+                };
+                let name = pair.clone().into_inner().next().unwrap();
+                let namespace_len = pair.as_str().find("::").unwrap_or_default();
+                self.push_prefix(&pair, namespace_len, TokenKind::Namespace);
+                self.refer_global(&name, kind, global);
+            }
+            Rule::variable_expr => self.refer_variable(&pair.into_inner().next().unwrap()),
+            Rule::call_expr => {
+                let mut it = pair.into_inner();
+                self.call_name(it.next().unwrap());
+                it.next()
+                    .unwrap()
+                    .into_inner()
+                    .for_each(|arg| self.expression(arg));
+            }
+            Rule::match_expr => self.match_expression(pair),
+            Rule::for_expr => self.for_expression(pair),
+            Rule::break_expr => {
+                self.push_pair(&pair, TokenKind::Keyword);
+            }
+            Rule::list_expr => {
+                self.push_prefix(&pair, "list!".len(), TokenKind::Macro);
+                pair.into_inner().for_each(|pair| self.expression(pair));
+            }
+            _ => pair.into_inner().for_each(|pair| self.expression(pair)),
+        }
+    }
+
+    fn call_name(&mut self, pair: Pair<Rule>) {
+        let pair = pair.into_inner().next().unwrap();
+        match pair.as_rule() {
+            Rule::jet => {
+                self.push_prefix(&pair, "jet".len(), TokenKind::Namespace);
+                let span = pair.as_span();
+                self.push(span.start() + "jet::".len(), span.end(), TokenKind::Jet);
+            }
+            Rule::function_name => self.refer_global(&pair, TokenKind::Function, Global::Function),
+            Rule::assert | Rule::panic | Rule::debug => {
+                self.push_pair(&pair, TokenKind::Macro);
+            }
+            Rule::unwrap => {
+                self.push_pair(&pair, TokenKind::Builtin);
+            }
+            Rule::type_cast => {
+                let end = pair.as_span().end();
+                self.ty(pair.into_inner().next().unwrap());
+                self.push(end - "into".len(), end, TokenKind::Builtin);
+            }
+            _ => {
+                let len = pair.as_str().find("::").unwrap_or_default();
+                self.push_prefix(&pair, len, TokenKind::Builtin);
+                for pair in pair.into_inner() {
+                    match pair.as_rule() {
+                        Rule::function_name => {
+                            self.refer_global(&pair, TokenKind::Function, Global::Function);
+                        }
+                        Rule::array_size | Rule::list_bound => {
+                            self.push_pair(&pair, TokenKind::Number);
+                        }
+                        _ => self.ty(pair),
+                    }
+                }
+            }
+        }
+    }
+
+    fn for_expression(&mut self, pair: Pair<Rule>) {
+        let mut it = pair.into_inner();
+        self.push_pair(&it.next().unwrap(), TokenKind::Keyword);
+        let mut bindings = Vec::new();
+        self.pattern(it.next().unwrap(), &mut vec![], &mut bindings);
+        let mut pair = it.next().unwrap();
+        let ty = match pair.as_rule() {
+            Rule::ty => {
+                let ty = pair;
+                self.ty(ty.clone());
+                pair = it.next().unwrap();
+                Some(ty)
+            }
+            _ => None,
+        };
+        self.push_pair(&pair, TokenKind::Keyword);
+
+        let iterable = it.next().unwrap().into_inner().next().unwrap();
+        match iterable.as_rule() {
+            Rule::range => {
+                for bound in iterable.into_inner() {
+                    self.size(&bound.into_inner().next().unwrap());
+                }
+            }
+            _ => self.refer_variable(&iterable.into_inner().next().unwrap()),
+        }
+
+        let mut pair = it.next().unwrap();
+        let mut scope = HashMap::new();
+        let mut accumulator = None;
+        if pair.as_rule() == Rule::for_accumulator {
+            let mut inner = pair.into_inner();
+            self.push_pair(&inner.next().unwrap(), TokenKind::Keyword);
+            let (name, index) = self.define_local(&inner.next().unwrap(), TokenKind::Variable);
+            let acc_ty = inner.next().unwrap();
+            self.ty(acc_ty.clone());
+            // The initial value cannot see the accumulator
+            self.expression(inner.next().unwrap());
+            accumulator = Some((name, index, acc_ty));
+            pair = it.next().unwrap();
+        }
+        let body = pair;
+        let visible = body.as_span().start()..body.as_span().end();
+
+        // The element type is inferred unless annotated, so only annotated variables are bound
+        if let Some(ty) = ty {
+            for (_, index, path) in &bindings {
+                self.bind(*index, visible.clone(), &ty, path.clone());
+            }
+        }
+        if let Some((name, index, acc_ty)) = accumulator {
+            self.bind(index, visible.clone(), &acc_ty, vec![]);
+            scope.insert(name, index);
+        }
+        scope.extend(bindings.into_iter().map(|(name, index, _)| (name, index)));
+
+        self.scopes.push(scope);
+        self.block_expression(body);
+        self.scopes.pop();
+    }
+
+    fn match_expression(&mut self, pair: Pair<Rule>) {
+        let mut it = pair.into_inner();
+        self.push_pair(&it.next().unwrap(), TokenKind::Keyword);
+        self.expression(it.next().unwrap());
+
+        for arm in it {
+            let mut it = arm.into_inner();
+            let pattern = it.next().unwrap().into_inner().next().unwrap();
+            let body = it.next().unwrap();
+            let mut scope = HashMap::new();
+            match pattern.as_rule() {
+                Rule::left_pattern | Rule::right_pattern | Rule::some_pattern => {
+                    let len = pattern.as_str().find('(').unwrap_or_default();
+                    self.push_prefix(&pattern, len, TokenKind::EnumMember);
+                    let mut it = pattern.into_inner();
+                    let (name, index) = self.define_local(&it.next().unwrap(), TokenKind::Variable);
+                    scope.insert(name, index);
+                    let ty = it.next().unwrap();
+                    let visible = body.as_span().start()..body.as_span().end();
+                    self.bind(index, visible, &ty, vec![]);
+                    self.ty(ty);
+                }
+                _ => {
+                    self.push_pair(&pattern, TokenKind::EnumMember);
+                }
+            }
+
+            self.scopes.push(scope);
+            match body.as_rule() {
+                Rule::block_expression => self.block_expression(body),
+                _ => self.single_expression(body),
+            }
+            self.scopes.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_text<'a>(text: &'a str, token: &Token) -> &'a str {
+        &text[token.start..token.end]
+    }
+
+    #[test]
+    fn test_classify_tokens() {
+        let text = "type Sig = Signature;
+fn f(a: u8) -> u8 { a }
+fn main() {
+    let b: Sig = witness::SIG;
+    assert!(jet::eq_8(f(1), param::ONE));
+}";
+        let index = SyntaxIndex::new(text).unwrap();
+        let classified: Vec<(&str, TokenKind)> = index
+            .tokens()
+            .iter()
+            .map(|token| (token_text(text, token), token.kind))
+            .collect();
+
+        for expected in [
+            ("type", TokenKind::Keyword),
+            ("Sig", TokenKind::TypeAlias),
+            ("Signature", TokenKind::BuiltinAlias),
+            ("f", TokenKind::Function),
+            ("a", TokenKind::Parameter),
+            ("u8", TokenKind::BuiltinType),
+            ("b", TokenKind::Variable),
+            ("witness", TokenKind::Namespace),
+            ("SIG", TokenKind::Witness),
+            ("assert!", TokenKind::Macro),
+            ("eq_8", TokenKind::Jet),
+            ("1", TokenKind::Number),
+            ("ONE", TokenKind::ModuleParameter),
+        ] {
+            assert!(classified.contains(&expected), "Missing {expected:?}");
+        }
+    }
+
+    #[test]
+    fn test_resolve_shadowing() {
+        let text = "fn main() {
+    let x: u8 = 1;
+    let y: u8 = {
+        let x: u8 = x;
+        x
+    };
+    let z: u8 = x;
+}";
+        let index = SyntaxIndex::new(text).unwrap();
+        let xs: Vec<(usize, &Token)> = index
+            .tokens()
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| token_text(text, token) == "x")
+            .collect();
+        assert_eq!(xs.len(), 5);
+
+        let (outer, inner) = (xs[0].0, xs[1].0);
+        let definitions: Vec<Option<usize>> =
+            xs.iter().map(|(_, token)| token.definition).collect();
+        assert_eq!(
+            definitions,
+            vec![
+                Some(outer),
+                Some(inner),
+                Some(outer),
+                Some(inner),
+                Some(outer)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forward_function_reference() {
+        let text = "fn main() { f() }\nfn f() {}";
+        let index = SyntaxIndex::new(text).unwrap();
+        let fs: Vec<(usize, &Token)> = index
+            .tokens()
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| token_text(text, token) == "f")
+            .collect();
+        assert_eq!(fs[0].1.definition, Some(fs[1].0));
+        assert!(fs[1].1.is_definition(fs[1].0));
+    }
+
+    #[test]
+    fn test_resolve_constants() {
+        let text = "const N: u8 = 2;\nfn main() { let a: [u8; N] = [N, N]; let N: u8 = 0; let b: u8 = N; }";
+        let index = SyntaxIndex::new(text).unwrap();
+        let ns: Vec<(usize, &Token)> = index
+            .tokens()
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| token_text(text, token) == "N")
+            .collect();
+        assert_eq!(ns.len(), 6);
+        let constant = ns[0].0;
+        for (_, token) in &ns[..4] {
+            assert_eq!(token.kind, TokenKind::Constant);
+            assert_eq!(token.definition, Some(constant));
+        }
+        // Local variables shadow constants
+        let local = ns[4].0;
+        assert_eq!(ns[5].1.kind, TokenKind::Variable);
+        assert_eq!(ns[5].1.definition, Some(local));
+    }
+
+    #[test]
+    fn test_references() {
+        let text = "fn main() { let a: u8 = witness::A; let b: u8 = a; let a: u8 = b; }";
+        let index = SyntaxIndex::new(text).unwrap();
+        let first_a = index.token_at(text.find("a:").unwrap()).unwrap();
+        let texts: Vec<&str> = index
+            .references(first_a)
+            .into_iter()
+            .map(|i| token_text(text, &index.tokens()[i]))
+            .collect();
+        assert_eq!(texts, vec!["a", "a"]);
+
+        // The cursor directly after the name still refers to it
+        let witness = index.token_at(text.find("A;").unwrap() + 1).unwrap();
+        assert_eq!(index.tokens()[witness].kind, TokenKind::Witness);
+        assert_eq!(index.references(witness), vec![witness]);
+
+        let keyword = index.token_at(0).unwrap();
+        assert!(index.references(keyword).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_for_loop() {
+        let text = "const N: u8 = 3;
+fn main() {
+    let xs: [u8; 2] = [1, 2];
+    let sum: u8 = for x: u8 in xs, mut sum: u8 = 0 {
+        let (_, sum): (bool, u8) = jet::add_8(sum, x);
+        sum
+    };
+    let n: u8 = for i in 0..N { break };
+}";
+        let index = SyntaxIndex::new(text).unwrap();
+        let find = |needle: &str| index.token_at(text.find(needle).unwrap()).unwrap();
+        let accumulator = find("sum: u8 = 0");
+        let element = find("x: u8 in");
+        assert_eq!(
+            index.tokens()[find("xs, mut")].definition,
+            Some(find("xs:"))
+        );
+        assert_eq!(
+            index.tokens()[find("sum, x)")].definition,
+            Some(accumulator)
+        );
+        assert_eq!(index.tokens()[find("x);")].definition, Some(element));
+        // Variables of the body shadow the accumulator
+        assert_eq!(
+            index.tokens()[find("sum\n    };")].definition,
+            Some(find("sum): (bool"))
+        );
+        for keyword in ["for x", "in xs", "mut", "break"] {
+            assert_eq!(index.tokens()[find(keyword)].kind, TokenKind::Keyword);
+        }
+        assert_eq!(index.tokens()[find("N {")].definition, Some(find("N:")));
+
+        let visible: Vec<&str> = index
+            .visible_bindings(text.find("let (_").unwrap())
+            .map(|binding| &text[binding.ty.clone()])
+            .collect();
+        assert_eq!(visible, vec!["[u8; 2]", "u8", "u8"]);
+    }
+
+    #[test]
+    fn test_visible_bindings() {
+        let text = "fn f(a: u8) -> u8 {
+    let (b, [c, d]): (u8, [u8; 2]) = (a, [a, a]);
+    match witness::E {
+        Left(e: u8) => {},
+        Right(f: u16) => {},
+    };
+    let a: u16 = 1;
+    b
+}";
+        let index = SyntaxIndex::new(text).unwrap();
+        let visible = |needle: &str| -> Vec<(&str, &str, Vec<usize>)> {
+            index
+                .visible_bindings(text.find(needle).unwrap())
+                .map(|binding| {
+                    let token = &index.tokens()[binding.token];
+                    (
+                        token_text(text, token),
+                        &text[binding.ty.clone()],
+                        binding.path.clone(),
+                    )
+                })
+                .collect()
+        };
+
+        assert_eq!(visible("(a, [a"), vec![("a", "u8", vec![])]);
+        assert_eq!(
+            visible("match"),
+            vec![
+                ("a", "u8", vec![]),
+                ("b", "(u8, [u8; 2])", vec![0]),
+                ("c", "(u8, [u8; 2])", vec![1, 0]),
+                ("d", "(u8, [u8; 2])", vec![1, 1]),
+            ]
+        );
+        let arm = visible("{},\n        Right");
+        assert_eq!(arm.last().unwrap(), &("e", "u8", vec![]));
+        // Shadowing variables come after the variables that they shadow
+        let end = visible("b\n}");
+        assert_eq!(end.last().unwrap(), &("a", "u16", vec![]));
+        assert_eq!(end.len(), 5);
+    }
+}
@@ -44,6 +44,15 @@ pub fn span_to_positions(
     ))
 }
 
+/// Convert [`simplicityhl::error::Span`] to the byte range that it covers in the document.
+pub fn span_to_bytes(
+    text: &Rope,
+    span: &simplicityhl::error::Span,
+) -> Result<std::ops::Range<usize>, LspError> {
+    let (start, end) = span_to_positions(span)?;
+    Ok(position_to_byte(text, start)?..position_to_byte(text, end)?)
+}
+
 /// Convert [`tower_lsp_server::lsp_types::Position`] to [`simplicityhl::error::Span`]
 ///
 /// Useful when [`tower_lsp_server::lsp_types::Position`] represents some singular point.
@@ -65,8 +74,54 @@ pub fn position_to_span(
     })
 }
 
-/// Get document comments, using lines above given line index. Only used to
-/// get documentation for custom functions.
+/// Convert a byte offset into the document to a [`lsp_types::Position`].
+///
+/// The character offset of the position is measured in UTF-16 code units.
+pub fn byte_to_position(text: &Rope, byte: usize) -> Result<lsp_types::Position, LspError> {
+    let char_index = text
+        .try_byte_to_char(byte)
+        .map_err(|err| LspError::ConversionFailed(err.to_string()))?;
+    let line = text.char_to_line(char_index);
+    let character =
+        text.char_to_utf16_cu(char_index) - text.char_to_utf16_cu(text.line_to_char(line));
+
+    Ok(lsp_types::Position::new(
+        u32::try_from(line)?,
+        u32::try_from(character)?,
+    ))
+}
+
+/// Convert a [`lsp_types::Position`] to a byte offset into the document.
+///
+/// Positions after the end of a line are clamped to the end of that line.
+pub fn position_to_byte(text: &Rope, position: lsp_types::Position) -> Result<usize, LspError> {
+    let line = position.line as usize;
+    if text.len_lines() <= line {
+        return Ok(text.len_bytes());
+    }
+    let line_start = text.line_to_char(line);
+    let line_utf16_start = text.char_to_utf16_cu(line_start);
+    let line_utf16_len = text.line(line).len_utf16_cu();
+    let utf16 = line_utf16_start + (position.character as usize).min(line_utf16_len);
+    let char_index = text
+        .try_utf16_cu_to_char(utf16)
+        .map_err(|err| LspError::ConversionFailed(err.to_string()))?;
+
+    Ok(text.char_to_byte(char_index))
+}
+
+/// Return the [`lsp_types::Range`] that covers the whole document.
+pub fn document_range(text: &Rope) -> Result<lsp_types::Range, LspError> {
+    let last_line = text.len_lines().saturating_sub(1);
+    let last_line_len = text.line(last_line).len_utf16_cu();
+
+    Ok(lsp_types::Range {
+        start: lsp_types::Position::new(0, 0),
+        end: lsp_types::Position::new(u32::try_from(last_line)?, u32::try_from(last_line_len)?),
+    })
+}
+
+/// Get document comments, using lines above given line index.
 pub fn get_comments_from_lines(line: u32, rope: &Rope) -> String {
     let mut lines = Vec::new();
 
@@ -79,6 +134,7 @@ pub fn get_comments_from_lines(line: u32, rope: &Rope) -> String {
             break;
         };
         let text = rope_slice.to_string();
+        let text = text.trim_start();
 
         if text.starts_with("///") {
             let doc = text
@@ -239,6 +295,29 @@ mod tests {
     use super::*;
     use ropey::Rope;
 
+    #[test]
+    fn test_byte_position_conversion() {
+        let text = Rope::from_str("fn main() {\n    // ü\n    x\n}");
+        let byte = text.to_string().find('x').unwrap();
+        let position = byte_to_position(&text, byte).unwrap();
+        assert_eq!(position, lsp_types::Position::new(2, 4));
+        assert_eq!(position_to_byte(&text, position).unwrap(), byte);
+
+        let end_of_comment = text.to_string().find('ü').unwrap() + 'ü'.len_utf8();
+        let position = byte_to_position(&text, end_of_comment).unwrap();
+        assert_eq!(position, lsp_types::Position::new(1, 8));
+        assert_eq!(position_to_byte(&text, position).unwrap(), end_of_comment);
+    }
+
+    #[test]
+    fn test_document_range() {
+        let range = document_range(&Rope::from_str("fn main() {\n}\n")).unwrap();
+        assert_eq!(range.end, lsp_types::Position::new(2, 0));
+
+        let range = document_range(&Rope::from_str("fn main() {}")).unwrap();
+        assert_eq!(range.end, lsp_types::Position::new(0, 12));
+    }
+
     #[test]
     fn test_get_comments_from_lines() {
         let text = Rope::from_str("/// This is a test.\n/// It has two lines.\nfn func() {}");
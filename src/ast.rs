@@ -1,5 +1,5 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::num::NonZeroUsize;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -9,7 +9,7 @@ use miniscript::iter::{Tree, TreeLike};
 use simplicity::jet::Elements;
 
 use crate::debug::{CallTracker, DebugSymbols, TrackedCallName};
-use crate::error::{Error, RichError, Span, WithSpan};
+use crate::error::{Error, Position, RichError, Span, WithSpan};
 use crate::num::{NonZeroPow2Usize, Pow2Usize};
 use crate::parse::MatchPattern;
 use crate::pattern::Pattern;
@@ -23,13 +23,14 @@ use crate::{impl_eq_hash, parse};
 
 /// A program consists of the main function.
 ///
-/// Other items such as custom functions or type aliases
+/// Other items such as custom functions, type aliases or constants
 /// are resolved during the creation of the AST.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Program {
     main: Expression,
     parameters: Parameters,
     witness_types: WitnessTypes,
+    constants: HashMap<Identifier, Value>,
     call_tracker: Arc<CallTracker>,
 }
 
@@ -51,6 +52,11 @@ impl Program {
         &self.witness_types
     }
 
+    /// Access the values of the constants of the program.
+    pub fn constants(&self) -> &HashMap<Identifier, Value> {
+        &self.constants
+    }
+
     /// Access the debug symbols of the program.
     pub fn debug_symbols(&self, file: &str) -> DebugSymbols {
         self.call_tracker.with_file(file)
@@ -73,6 +79,10 @@ pub enum Item {
     TypeAlias,
     /// A function.
     Function(Function),
+    /// A constant.
+    ///
+    /// A stub because the constant was evaluated during the creation of the AST.
+    Constant,
     /// A module, which is ignored.
     Module,
 }
@@ -86,6 +96,11 @@ pub enum Function {
     ///
     /// A stub because the definition of the function was moved to its calls in the main function.
     Custom,
+    /// A unit test.
+    ///
+    /// A stub because unit tests are only compiled by the test runner.
+    /// See [`parse::Program::test_program`].
+    Test,
     /// The main function.
     ///
     /// An expression that takes no inputs (unit) and that produces no output (unit).
@@ -520,10 +535,12 @@ impl TreeLike for ExprTree<'_> {
 /// 2. Resolving type aliases
 /// 3. Assigning types to each witness expression
 /// 4. Resolving calls to custom functions
+/// 5. Resolving constants
 #[derive(Clone, Debug, Eq, PartialEq, Default)]
 struct Scope {
     variables: Vec<HashMap<Identifier, ResolvedType>>,
     aliases: HashMap<AliasName, ResolvedType>,
+    constants: HashMap<Identifier, Value>,
     parameters: HashMap<WitnessName, ResolvedType>,
     witnesses: HashMap<WitnessName, ResolvedType>,
     functions: HashMap<FunctionName, CustomFunction>,
@@ -602,13 +619,52 @@ impl Scope {
 
     /// Resolve a type with aliases to a type without aliases.
     ///
+    /// Constants that are used as array sizes or list bounds are replaced by their values.
+    ///
     /// ## Errors
     ///
-    /// There are any undefined aliases.
+    /// - There are any undefined aliases.
+    /// - There are any undefined constants.
+    /// - A constant that is used as size is not an unsigned integer that fits into `usize`.
     pub fn resolve(&self, ty: &AliasedType) -> Result<ResolvedType, Error> {
         let get_alias =
             |name: &AliasName| -> Option<ResolvedType> { self.aliases.get(name).cloned() };
-        ty.resolve(get_alias).map_err(Error::UndefinedAlias)
+        ty.resolve_with(get_alias, |name| self.get_size(name))
+    }
+
+    /// Get the value of the constant as a size.
+    ///
+    /// ## Errors
+    ///
+    /// - The constant is undefined.
+    /// - The constant is not an unsigned integer that fits into `usize`.
+    pub fn get_size(&self, name: &Identifier) -> Result<usize, Error> {
+        let value = self
+            .get_constant(name)
+            .ok_or_else(|| Error::UndefinedConstant(name.clone()))?;
+        value
+            .as_usize()
+            .ok_or_else(|| Error::ConstantNotSize(name.clone(), value.ty().clone()))
+    }
+
+    /// Get the value of the constant.
+    pub fn get_constant(&self, name: &Identifier) -> Option<&Value> {
+        self.constants.get(name)
+    }
+
+    /// Insert a constant into the global map.
+    ///
+    /// ## Errors
+    ///
+    /// The constant has already been defined.
+    pub fn insert_constant(&mut self, name: Identifier, value: Value) -> Result<(), Error> {
+        match self.constants.entry(name) {
+            Entry::Occupied(entry) => Err(Error::ConstantRedefined(entry.key().clone())),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                Ok(())
+            }
+        }
     }
 
     /// Push a type alias into the global map.
@@ -662,11 +718,20 @@ impl Scope {
     ///
     /// 1. The map of parameter types.
     /// 2. The map of witness types.
-    /// 3. The function call tracker.
-    pub fn destruct(self) -> (Parameters, WitnessTypes, CallTracker) {
+    /// 3. The map of constant values.
+    /// 4. The function call tracker.
+    pub fn destruct(
+        self,
+    ) -> (
+        Parameters,
+        WitnessTypes,
+        HashMap<Identifier, Value>,
+        CallTracker,
+    ) {
         (
             Parameters::from(self.parameters),
             WitnessTypes::from(self.witnesses),
+            self.constants,
             self.call_tracker,
         )
     }
@@ -699,6 +764,31 @@ impl Scope {
     pub fn track_call<S: AsRef<Span>>(&mut self, span: &S, name: TrackedCallName) {
         self.call_tracker.track_call(*span.as_ref(), name);
     }
+
+    /// Track a statement or final expression of a block with its span,
+    /// together with the variables that are currently in scope.
+    ///
+    /// Variables are sorted by name. Shadowed variables are left out.
+    pub fn track_step<S: AsRef<Span>>(&mut self, span: &S) {
+        let mut variables = BTreeMap::new();
+        for scope in &self.variables {
+            variables.extend(scope.iter().map(|(i, ty)| (i.clone(), ty.clone())));
+        }
+        self.call_tracker
+            .track_step(*span.as_ref(), variables.into_iter().collect());
+    }
+
+    /// Track the body of a custom function with its span.
+    pub fn track_frame<S: AsRef<Span>>(&mut self, span: &S) {
+        self.call_tracker.track_frame(*span.as_ref());
+    }
+
+    /// Track the arm of a match expression with its span,
+    /// together with the span of the match expression.
+    pub fn track_branch<S: AsRef<Span>, T: AsRef<Span>>(&mut self, span: &S, matched: &T) {
+        self.call_tracker
+            .track_branch(*span.as_ref(), *matched.as_ref());
+    }
 }
 
 /// Part of the abstract syntax tree that can be generated from a precursor in the parse tree.
@@ -724,7 +814,7 @@ impl Program {
             .map(|s| Item::analyze(s, &unit, &mut scope))
             .collect::<Result<Vec<Item>, RichError>>()?;
         debug_assert!(scope.is_topmost());
-        let (parameters, witness_types, call_tracker) = scope.destruct();
+        let (parameters, witness_types, constants, call_tracker) = scope.destruct();
         let mut iter = items.into_iter().filter_map(|item| match item {
             Item::Function(Function::Main(expr)) => Some(expr),
             _ => None,
@@ -737,6 +827,7 @@ impl Program {
             main,
             parameters,
             witness_types,
+            constants,
             call_tracker: Arc::new(call_tracker),
         })
     }
@@ -759,6 +850,21 @@ impl AbstractSyntaxTree for Item {
             parse::Item::Function(function) => {
                 Function::analyze(function, ty, scope).map(Self::Function)
             }
+            parse::Item::Constant(constant) => {
+                let ty_expr = scope.resolve(constant.ty()).with_span(constant)?;
+                // Evaluate the expression in a copy of the scope,
+                // so it leaves no traces in the compiled program.
+                let mut const_scope = scope.clone();
+                let expression =
+                    Expression::analyze(constant.expression(), &ty_expr, &mut const_scope)?;
+                let value = Value::from_const_expr(&expression)
+                    .ok_or(Error::ExpressionNotConstant)
+                    .with_span(constant.expression())?;
+                scope
+                    .insert_constant(constant.name().clone(), value)
+                    .with_span(constant)?;
+                Ok(Self::Constant)
+            }
             parse::Item::Module => Ok(Self::Module),
         }
     }
@@ -771,6 +877,9 @@ impl AbstractSyntaxTree for Function {
         assert!(ty.is_unit(), "Function definitions cannot return anything");
         assert!(scope.is_topmost(), "Items live in the topmost scope only");
 
+        if from.is_test() {
+            return Ok(Self::Test);
+        }
         if from.name().as_inner() != "main" {
             let params = from
                 .params()
@@ -794,6 +903,7 @@ impl AbstractSyntaxTree for Function {
             }
             let body = Expression::analyze(from.body(), &ret, scope).map(Arc::new)?;
             scope.pop_scope();
+            scope.track_frame(body.as_ref());
             debug_assert!(scope.is_topmost());
             let function = CustomFunction { params, body };
             scope
@@ -861,6 +971,64 @@ impl AbstractSyntaxTree for Assignment {
 }
 
 impl Expression {
+    /// Create a single expression that was generated by the compiler.
+    fn synthetic(inner: SingleExpressionInner, ty: ResolvedType, span: Span) -> Self {
+        let single = SingleExpression {
+            inner,
+            ty: ty.clone(),
+            span,
+        };
+        Self {
+            inner: ExpressionInner::Single(single),
+            ty,
+            span,
+        }
+    }
+
+    /// Create a tuple expression that was generated by the compiler.
+    fn synthetic_tuple(elements: Vec<Self>, span: Span) -> Self {
+        let single = SingleExpression::tuple(Arc::from(elements), span);
+        Self {
+            ty: single.ty().clone(),
+            inner: ExpressionInner::Single(single),
+            span,
+        }
+    }
+
+    /// Create a block expression that was generated by the compiler.
+    fn synthetic_block(statements: Vec<Statement>, expression: Self, span: Span) -> Self {
+        Self {
+            ty: expression.ty().clone(),
+            inner: ExpressionInner::Block(Arc::from(statements), Some(Arc::new(expression))),
+            span,
+        }
+    }
+
+    /// Create a match expression that was generated by the compiler.
+    ///
+    /// Both arms must have the same type.
+    fn synthetic_match(
+        scrutinee: Self,
+        left: (MatchPattern, Self),
+        right: (MatchPattern, Self),
+        span: Span,
+    ) -> Self {
+        let ty = left.1.ty().clone();
+        let match_ = Match {
+            scrutinee: Arc::new(scrutinee),
+            left: MatchArm {
+                pattern: left.0,
+                expression: Arc::new(left.1),
+            },
+            right: MatchArm {
+                pattern: right.0,
+                expression: Arc::new(right.1),
+            },
+            span,
+        };
+        Self::synthetic(SingleExpressionInner::Match(match_), ty, span)
+    }
+
     /// Analyze an expression from the parse tree in a const context without predefined variables.
     ///
     /// Check if the expression is of the given type.
@@ -892,12 +1060,18 @@ impl AbstractSyntaxTree for Expression {
                 scope.push_scope();
                 let ast_statements = statements
                     .iter()
-                    .map(|s| Statement::analyze(s, &ResolvedType::unit(), scope))
+                    .map(|s| {
+                        scope.track_step(s);
+                        Statement::analyze(s, &ResolvedType::unit(), scope)
+                    })
                     .collect::<Result<Arc<[Statement]>, RichError>>()?;
                 let ast_expression = match expression {
-                    Some(expression) => Expression::analyze(expression, ty, scope)
-                        .map(Arc::new)
-                        .map(Some),
+                    Some(expression) => {
+                        scope.track_step(expression.as_ref());
+                        Expression::analyze(expression, ty, scope)
+                            .map(Arc::new)
+                            .map(Some)
+                    }
                     None if ty.is_unit() => Ok(None),
                     None => Err(Error::ExpressionTypeMismatch(
                         ty.clone(),
@@ -967,16 +1141,28 @@ impl AbstractSyntaxTree for SingleExpression {
                 SingleExpressionInner::Parameter(name.shallow_clone())
             }
             parse::SingleExpressionInner::Variable(identifier) => {
-                let bound_ty = scope
-                    .get_variable(identifier)
-                    .ok_or(Error::UndefinedVariable(identifier.clone()))
-                    .with_span(from)?;
-                if ty != bound_ty {
-                    return Err(Error::ExpressionTypeMismatch(ty.clone(), bound_ty.clone()))
+                // Local variables shadow constants
+                if let Some(bound_ty) = scope.get_variable(identifier) {
+                    if ty != bound_ty {
+                        return Err(Error::ExpressionTypeMismatch(ty.clone(), bound_ty.clone()))
+                            .with_span(from);
+                    }
+                    scope.insert_variable(identifier.clone(), ty.clone());
+                    SingleExpressionInner::Variable(identifier.clone())
+                } else {
+                    let value = scope
+                        .get_constant(identifier)
+                        .ok_or(Error::UndefinedVariable(identifier.clone()))
+                        .with_span(from)?;
+                    if ty != value.ty() {
+                        return Err(Error::ExpressionTypeMismatch(
+                            ty.clone(),
+                            value.ty().clone(),
+                        ))
                         .with_span(from);
+                    }
+                    SingleExpressionInner::Constant(value.clone())
                 }
-                scope.insert_variable(identifier.clone(), ty.clone());
-                SingleExpressionInner::Variable(identifier.clone())
             }
             parse::SingleExpressionInner::Expression(parse) => {
                 Expression::analyze(parse, ty, scope)
@@ -1059,6 +1245,12 @@ impl AbstractSyntaxTree for SingleExpression {
             parse::SingleExpressionInner::Match(match_) => {
                 Match::analyze(match_, ty, scope).map(SingleExpressionInner::Match)?
             }
+            parse::SingleExpressionInner::For(for_loop) => lower_for_loop(for_loop, ty, scope)
+                .map(Arc::new)
+                .map(SingleExpressionInner::Expression)?,
+            parse::SingleExpressionInner::Break => {
+                return Err(Error::BreakOutsideLoop).with_span(from);
+            }
         };
 
         Ok(Self {
@@ -1120,13 +1312,11 @@ impl AbstractSyntaxTree for Call {
                 let args_tys = crate::jet::source_type(jet)
                     .iter()
                     .map(AliasedType::resolve_builtin)
-                    .collect::<Result<Vec<ResolvedType>, AliasName>>()
-                    .map_err(Error::UndefinedAlias)
+                    .collect::<Result<Vec<ResolvedType>, Error>>()
                     .with_span(from)?;
                 check_argument_types(from.args(), &args_tys).with_span(from)?;
                 let out_ty = crate::jet::target_type(jet)
                     .resolve_builtin()
-                    .map_err(Error::UndefinedAlias)
                     .with_span(from)?;
                 check_output_type(&out_ty, ty).with_span(from)?;
                 scope.track_call(from, TrackedCallName::Jet);
@@ -1401,6 +1591,7 @@ impl AbstractSyntaxTree for Match {
             let ty_l = scope.resolve(ty_l).with_span(from)?;
             scope.insert_variable(id_l.clone(), ty_l);
         }
+        scope.track_branch(from.left().expression(), from);
         let ast_l = Expression::analyze(from.left().expression(), ty, scope).map(Arc::new)?;
         scope.pop_scope();
         scope.push_scope();
@@ -1408,6 +1599,7 @@ impl AbstractSyntaxTree for Match {
             let ty_r = scope.resolve(ty_r).with_span(from)?;
             scope.insert_variable(id_r.clone(), ty_r);
         }
+        scope.track_branch(from.right().expression(), from);
         let ast_r = Expression::analyze(from.right().expression(), ty, scope).map(Arc::new)?;
         scope.pop_scope();
 
@@ -1426,6 +1618,397 @@ impl AbstractSyntaxTree for Match {
     }
 }
 
+/// Values that a `for` loop iterates over.
+enum Iteration {
+    /// Array of the given element type and size.
+    Array(Expression, ResolvedType, usize),
+    /// List of the given element type and bound.
+    List(Expression, ResolvedType, NonZeroPow2Usize),
+    /// Range `start..end` of counter values of the given type.
+    Range(usize, usize, UIntType),
+}
+
+impl Iteration {
+    fn analyze(from: &parse::ForLoop, span: Span, scope: &Scope) -> Result<Self, RichError> {
+        let declared_ty = from
+            .ty()
+            .map(|ty| scope.resolve(ty))
+            .transpose()
+            .with_span(from)?;
+
+        let iteration = match from.iterable() {
+            parse::Iterable::Variable(name) => {
+                // Local variables shadow constants
+                let (inner, ty) = match scope.get_variable(name) {
+                    Some(ty) => (SingleExpressionInner::Variable(name.clone()), ty.clone()),
+                    None => {
+                        let value = scope
+                            .get_constant(name)
+                            .ok_or(Error::UndefinedVariable(name.clone()))
+                            .with_span(from)?;
+                        let ty = value.ty().clone();
+                        (SingleExpressionInner::Constant(value.clone()), ty)
+                    }
+                };
+                let iterable = Expression::synthetic(inner, ty.clone(), span);
+                if let Some((el_ty, size)) = ty.as_array() {
+                    Self::Array(iterable, el_ty.clone(), size)
+                } else if let Some((el_ty, bound)) = ty.as_list() {
+                    Self::List(iterable, el_ty.clone(), bound)
+                } else {
+                    return Err(Error::LoopNotIterable(ty)).with_span(from);
+                }
+            }
+            parse::Iterable::Range(start, end) => {
+                let get_bound = |bound: &parse::RangeBound| match bound {
+                    parse::RangeBound::Literal(n) => Ok(*n),
+                    parse::RangeBound::Constant(name) => scope.get_size(name),
+                };
+                let start = get_bound(start).with_span(from)?;
+                let end = get_bound(end).with_span(from)?;
+                let counter_ty = match &declared_ty {
+                    None if end <= 1 << 8 => UIntType::U8,
+                    None => UIntType::U16,
+                    Some(ty) => match ty.as_integer() {
+                        Some(int_ty @ (UIntType::U8 | UIntType::U16)) => int_ty,
+                        _ => return Err(Error::LoopCounterType(ty.clone())).with_span(from),
+                    },
+                };
+                if end < start || 1 << counter_ty.bit_width().get() < end {
+                    return Err(Error::LoopRangeInvalid(start, end, counter_ty)).with_span(from);
+                }
+                Self::Range(start, end, counter_ty)
+            }
+        };
+
+        match declared_ty {
+            Some(ty) if ty != iteration.element_type() => {
+                Err(Error::ExpressionTypeMismatch(ty, iteration.element_type())).with_span(from)
+            }
+            _ => Ok(iteration),
+        }
+    }
+
+    /// Return the type of the values that are iterated over.
+    fn element_type(&self) -> ResolvedType {
+        match self {
+            Self::Array(_, el_ty, _) | Self::List(_, el_ty, _) => el_ty.clone(),
+            Self::Range(_, _, counter_ty) => ResolvedType::from(*counter_ty),
+        }
+    }
+
+    /// Check if there are no values to iterate over.
+    fn is_empty(&self) -> bool {
+        match self {
+            Self::Array(_, _, size) => *size == 0,
+            Self::List(..) => false,
+            Self::Range(start, end, _) => start == end,
+        }
+    }
+}
+
+/// Lower a `for` loop onto the builtin loops.
+///
+/// The body of the loop becomes a function that takes the current element,
+/// the accumulator and a context tuple of the outer variables that the body uses.
+/// Loops over arrays and lists become folds. Loops over ranges become for-while loops
+/// that skip counter values outside the range and that exit at the end of the range.
+///
+/// A fold cannot exit early, so if the body may `break`,
+/// then the accumulator of the fold carries a flag that skips all remaining iterations.
+/// A for-while loop exits early by returning a left value.
+fn lower_for_loop(
+    from: &parse::ForLoop,
+    ty: &ResolvedType,
+    scope: &mut Scope,
+) -> Result<Expression, RichError> {
+    // Use the span of the `for` keyword for generated code,
+    // so it is never mistaken for a tracked statement or call
+    let start = from.span().start;
+    let span = Span::new(start, Position::new(start.line.get(), start.col.get() + 3));
+    let identifier = Identifier::from_str_unchecked;
+    let variable = |name: &str, ty: &ResolvedType| {
+        let inner = SingleExpressionInner::Variable(identifier(name));
+        Expression::synthetic(inner, ty.clone(), span)
+    };
+    let assignment = |pattern: Pattern, expression: Expression| {
+        Statement::Assignment(Assignment {
+            pattern,
+            expression,
+            span,
+        })
+    };
+
+    let (acc_ty, acc_aliased) = match from.accumulator() {
+        Some(acc) => (scope.resolve(acc.ty()).with_span(from)?, acc.ty().clone()),
+        None => (ResolvedType::unit(), AliasedType::unit()),
+    };
+    if &acc_ty != ty {
+        return Err(Error::ExpressionTypeMismatch(ty.clone(), acc_ty)).with_span(from);
+    }
+    let acc_pattern = from.accumulator().map_or(Pattern::Ignore, |acc| {
+        Pattern::Identifier(acc.name().clone())
+    });
+    let initial = match from.accumulator() {
+        Some(acc) => Expression::analyze(acc.initial(), &acc_ty, scope)?,
+        None => Expression::synthetic_tuple(vec![], span),
+    };
+    let iteration = Iteration::analyze(from, span, scope)?;
+    let element_ty = iteration.element_type();
+    let element_variables = from.pattern().is_of_type(&element_ty).with_span(from)?;
+
+    // The body can only access the parameters of its function,
+    // so the outer variables that the body uses are passed in the context
+    let captures: BTreeMap<Identifier, ResolvedType> = parse::ExprTree::Expression(from.body())
+        .pre_order_iter()
+        .filter_map(|node| match node {
+            parse::ExprTree::Single(single) => match single.inner() {
+                parse::SingleExpressionInner::Variable(name) => Some(name),
+                parse::SingleExpressionInner::For(for_loop) => match for_loop.iterable() {
+                    parse::Iterable::Variable(name) => Some(name),
+                    parse::Iterable::Range(..) => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|name| !element_variables.contains_key(*name))
+        .filter(|name| from.accumulator().map_or(true, |acc| acc.name() != *name))
+        .filter_map(|name| Some((name.clone(), scope.get_variable(name)?.clone())))
+        .collect();
+    let context_ty = ResolvedType::tuple(captures.values().cloned());
+    let context = Expression::synthetic_tuple(
+        captures
+            .iter()
+            .map(|(name, ty)| {
+                let inner = SingleExpressionInner::Variable(name.clone());
+                Expression::synthetic(inner, ty.clone(), span)
+            })
+            .collect(),
+        span,
+    );
+    let context_pattern = Pattern::tuple(captures.keys().cloned().map(Pattern::Identifier));
+
+    let breaking_body = from.breaking_body();
+    let body_ty = match breaking_body {
+        Some(_) => ResolvedType::either(acc_ty.clone(), acc_ty.clone()),
+        None => acc_ty.clone(),
+    };
+    let mut body_variables: HashMap<Identifier, ResolvedType> = captures.into_iter().collect();
+    if let Some(acc) = from.accumulator() {
+        body_variables.insert(acc.name().clone(), acc_ty.clone());
+    }
+    body_variables.extend(element_variables);
+    let outer_variables = std::mem::replace(&mut scope.variables, vec![body_variables]);
+    let body = Expression::analyze(
+        breaking_body.as_ref().unwrap_or(from.body()),
+        &body_ty,
+        scope,
+    );
+    scope.variables = outer_variables;
+    let body = body?;
+
+    if iteration.is_empty() {
+        return Ok(initial);
+    }
+
+    if let Iteration::Range(start, end, counter_ty) = iteration {
+        // The for-while loop returns a left value to exit and a right value to continue
+        let output_ty = ResolvedType::either(acc_ty.clone(), acc_ty.clone());
+        let exit = |acc: Expression| {
+            let inner = SingleExpressionInner::Either(Either::Left(Arc::new(acc)));
+            Expression::synthetic(inner, output_ty.clone(), span)
+        };
+        let proceed = |acc: Expression| {
+            let inner = SingleExpressionInner::Either(Either::Right(Arc::new(acc)));
+            Expression::synthetic(inner, output_ty.clone(), span)
+        };
+        let counter_lt = |bound: usize| {
+            let (jet, bound) = match counter_ty {
+                UIntType::U8 => (Elements::Lt8, UIntValue::from(u8::try_from(bound).unwrap())),
+                _ => (
+                    Elements::Lt16,
+                    UIntValue::from(u16::try_from(bound).unwrap()),
+                ),
+            };
+            let bound = SingleExpressionInner::Constant(Value::from(bound));
+            let call = Call {
+                name: CallName::Jet(jet),
+                args: Arc::from([
+                    variable("_counter", &element_ty),
+                    Expression::synthetic(bound, element_ty.clone(), span),
+                ]),
+                span,
+            };
+            Expression::synthetic(
+                SingleExpressionInner::Call(call),
+                ResolvedType::boolean(),
+                span,
+            )
+        };
+
+        let mut step = match breaking_body {
+            Some(_) => body,
+            None => proceed(body),
+        };
+        if 0 < start {
+            step = Expression::synthetic_match(
+                counter_lt(start),
+                (MatchPattern::False, step),
+                (MatchPattern::True, proceed(variable("_state", &acc_ty))),
+                span,
+            );
+        }
+        if end < 1 << counter_ty.bit_width().get() {
+            step = Expression::synthetic_match(
+                counter_lt(end),
+                (MatchPattern::False, exit(variable("_state", &acc_ty))),
+                (MatchPattern::True, step),
+                span,
+            );
+        }
+        let function = CustomFunction {
+            params: Arc::from([
+                FunctionParam {
+                    identifier: identifier("_state"),
+                    ty: acc_ty.clone(),
+                },
+                FunctionParam {
+                    identifier: identifier("_context"),
+                    ty: context_ty.clone(),
+                },
+                FunctionParam {
+                    identifier: identifier("_counter"),
+                    ty: element_ty.clone(),
+                },
+            ]),
+            body: Arc::new(Expression::synthetic_block(
+                vec![
+                    assignment(acc_pattern, variable("_state", &acc_ty)),
+                    assignment(context_pattern, variable("_context", &context_ty)),
+                    assignment(from.pattern().clone(), variable("_counter", &element_ty)),
+                ],
+                step,
+                span,
+            )),
+        };
+        let call = Call {
+            name: CallName::ForWhile(function, counter_ty.bit_width()),
+            args: Arc::from([initial, context]),
+            span,
+        };
+        let output = Expression::synthetic(SingleExpressionInner::Call(call), output_ty, span);
+        return Ok(Expression::synthetic_match(
+            output,
+            (
+                MatchPattern::Left(identifier("_result"), acc_aliased.clone()),
+                variable("_result", &acc_ty),
+            ),
+            (
+                MatchPattern::Right(identifier("_result"), acc_aliased),
+                variable("_result", &acc_ty),
+            ),
+            span,
+        ));
+    }
+
+    // The fold passes the context along with the accumulator
+    let (state_ty, state_pattern, initial_state, next_state) = match breaking_body {
+        None => (
+            ResolvedType::tuple([acc_ty.clone(), context_ty.clone()]),
+            Pattern::tuple([acc_pattern, Pattern::Identifier(identifier("_context"))]),
+            Expression::synthetic_tuple(vec![initial, context], span),
+            Expression::synthetic_tuple(vec![body, variable("_context", &context_ty)], span),
+        ),
+        Some(_) => {
+            let flagged_ty = ResolvedType::tuple([acc_ty.clone(), ResolvedType::boolean()]);
+            let state_ty = ResolvedType::tuple([flagged_ty, context_ty.clone()]);
+            let state_pattern = Pattern::tuple([
+                Pattern::tuple([acc_pattern, Pattern::Identifier(identifier("_done"))]),
+                Pattern::Identifier(identifier("_context")),
+            ]);
+            let flagged = |acc: Expression, is_done: bool| {
+                let is_done = SingleExpressionInner::Constant(Value::from(is_done));
+                let is_done = Expression::synthetic(is_done, ResolvedType::boolean(), span);
+                Expression::synthetic_tuple(vec![acc, is_done], span)
+            };
+            let initial_state =
+                Expression::synthetic_tuple(vec![flagged(initial, false), context], span);
+            let update = |is_done: bool| {
+                let acc = flagged(variable("_next", &acc_ty), is_done);
+                Expression::synthetic_tuple(vec![acc, variable("_context", &context_ty)], span)
+            };
+            let step = Expression::synthetic_match(
+                body,
+                (
+                    MatchPattern::Left(identifier("_next"), acc_aliased.clone()),
+                    update(true),
+                ),
+                (
+                    MatchPattern::Right(identifier("_next"), acc_aliased),
+                    update(false),
+                ),
+                span,
+            );
+            // Skip all iterations after the loop breaks
+            let next_state = Expression::synthetic_match(
+                variable("_done", &ResolvedType::boolean()),
+                (MatchPattern::False, step),
+                (MatchPattern::True, variable("_state", &state_ty)),
+                span,
+            );
+            (state_ty, state_pattern, initial_state, next_state)
+        }
+    };
+    let function = CustomFunction {
+        params: Arc::from([
+            FunctionParam {
+                identifier: identifier("_element"),
+                ty: element_ty.clone(),
+            },
+            FunctionParam {
+                identifier: identifier("_state"),
+                ty: state_ty.clone(),
+            },
+        ]),
+        body: Arc::new(Expression::synthetic_block(
+            vec![
+                assignment(state_pattern, variable("_state", &state_ty)),
+                assignment(context_pattern, variable("_context", &context_ty)),
+                assignment(from.pattern().clone(), variable("_element", &element_ty)),
+            ],
+            next_state,
+            span,
+        )),
+    };
+    let (iterable, name) = match iteration {
+        Iteration::Array(iterable, _, size) => {
+            let size = NonZeroUsize::new(size).expect("array is not empty");
+            (iterable, CallName::ArrayFold(function, size))
+        }
+        Iteration::List(iterable, _, bound) => (iterable, CallName::Fold(function, bound)),
+        Iteration::Range(..) => unreachable!("range was lowered above"),
+    };
+    let call = Call {
+        name,
+        args: Arc::from([iterable, initial_state]),
+        span,
+    };
+    let output = Expression::synthetic(SingleExpressionInner::Call(call), state_ty, span);
+    let result_pattern = match breaking_body {
+        None => Pattern::tuple([Pattern::Identifier(identifier("_result")), Pattern::Ignore]),
+        Some(_) => Pattern::tuple([
+            Pattern::tuple([Pattern::Identifier(identifier("_result")), Pattern::Ignore]),
+            Pattern::Ignore,
+        ]),
+    };
+    Ok(Expression::synthetic_block(
+        vec![assignment(result_pattern, output)],
+        variable("_result", &acc_ty),
+        span,
+    ))
+}
+
 fn analyze_named_module(
     name: ModuleName,
     from: &parse::ModuleProgram,
@@ -1530,6 +2113,15 @@ impl AbstractSyntaxTree for ModuleAssignment {
     }
 }
 
+impl AsRef<Span> for Statement {
+    fn as_ref(&self) -> &Span {
+        match self {
+            Self::Assignment(assignment) => assignment.as_ref(),
+            Self::Expression(expression) => expression.as_ref(),
+        }
+    }
+}
+
 impl AsRef<Span> for Assignment {
     fn as_ref(&self) -> &Span {
         &self.span
@@ -2,6 +2,8 @@
 
 mod builtins;
 
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::Arc;
 
 use either::Either;
@@ -12,7 +14,7 @@ use simplicity::{types, Cmr, FailEntropy};
 use self::builtins::array_fold;
 use crate::array::{BTreeSlice, Partition};
 use crate::ast::{
-    Call, CallName, Expression, ExpressionInner, Match, Program, SingleExpression,
+    Call, CallName, CustomFunction, Expression, ExpressionInner, Match, Program, SingleExpression,
     SingleExpressionInner, Statement,
 };
 use crate::debug::CallTracker;
@@ -20,6 +22,7 @@ use crate::error::{Error, RichError, Span, WithSpan};
 use crate::named::{self, CoreExt, PairBuilder};
 use crate::num::{NonZeroPow2Usize, Pow2Usize};
 use crate::pattern::{BasePattern, Pattern};
+use crate::source_map::{Recorder, SourceMap};
 use crate::str::WitnessName;
 use crate::types::{StructuralType, TypeDeconstructible};
 use crate::value::StructuralValue;
@@ -72,6 +75,10 @@ struct Scope<'brand> {
     /// Values for parameters inside the SimplicityHL program.
     arguments: Arguments,
     include_debug_symbols: bool,
+    /// Mark each statement and function body with a debug symbol, for debuggers.
+    include_step_symbols: bool,
+    /// Spans of the expressions that the compiled nodes come from.
+    source_map: Rc<RefCell<Recorder<named::ConstructNode<'brand, Elements>>>>,
 }
 
 impl<'brand> Scope<'brand> {
@@ -88,6 +95,7 @@ impl<'brand> Scope<'brand> {
         call_tracker: Arc<CallTracker>,
         arguments: Arguments,
         include_debug_symbols: bool,
+        include_step_symbols: bool,
     ) -> Self {
         Self {
             variables: vec![vec![Pattern::Ignore]],
@@ -95,6 +103,8 @@ impl<'brand> Scope<'brand> {
             call_tracker,
             arguments,
             include_debug_symbols,
+            include_step_symbols,
+            source_map: Rc::default(),
         }
     }
 
@@ -106,6 +116,8 @@ impl<'brand> Scope<'brand> {
             call_tracker: Arc::clone(&self.call_tracker),
             arguments: self.arguments.clone(),
             include_debug_symbols: self.include_debug_symbols,
+            include_step_symbols: self.include_step_symbols,
+            source_map: Rc::clone(&self.source_map),
         }
     }
 
@@ -215,6 +227,117 @@ impl<'brand> Scope<'brand> {
         }
     }
 
+    /// Record that the compiled `expr` comes from the expression with the given span.
+    pub fn record_span<S: AsRef<Span>>(&self, expr: &PairBuilder<ProgNode<'brand>>, span: &S) {
+        self.source_map
+            .borrow_mut()
+            .insert(expr.as_ref(), *span.as_ref());
+    }
+
+    /// Compute the debug symbol that marks the evaluation of the statement or expression
+    /// with the given span. The marker takes the current input value and returns unit.
+    ///
+    /// At runtime, the marker provides the values of the variables in scope.
+    /// Return `None` unless the scope includes step symbols.
+    pub fn step_symbol<S: AsRef<Span>>(
+        &self,
+        span: &S,
+    ) -> Result<Option<PairBuilder<ProgNode<'brand>>>, RichError> {
+        if !self.include_step_symbols {
+            return Ok(None);
+        }
+        let Some((cmr, variables)) = self.call_tracker.get_step(span.as_ref()) else {
+            return Ok(None);
+        };
+        let value = if variables.is_empty() {
+            PairBuilder::unit(self.ctx())
+        } else {
+            let identifiers = variables
+                .iter()
+                .map(|(identifier, _)| Pattern::Identifier(identifier.clone()));
+            let target = BasePattern::from(&Pattern::tuple(identifiers));
+            self.get(&target)
+                .expect("Variables in scope are part of the input pattern")
+        };
+        self.marker(value, *cmr).with_span(span).map(Some)
+    }
+
+    /// Compute the debug symbol that marks the evaluation of the match arm
+    /// with the given span. The marker takes any input value and returns unit.
+    ///
+    /// Return `None` unless the scope includes step symbols.
+    pub fn branch_symbol<S: AsRef<Span>>(
+        &self,
+        span: &S,
+    ) -> Result<Option<PairBuilder<ProgNode<'brand>>>, RichError> {
+        let Some(cmr) = self
+            .call_tracker
+            .get_branch(span.as_ref())
+            .filter(|_| self.include_step_symbols)
+        else {
+            return Ok(None);
+        };
+        self.marker(PairBuilder::unit(self.ctx()), cmr)
+            .with_span(span)
+            .map(Some)
+    }
+
+    /// Evaluate the debug symbol `marker` before the given `expr`,
+    /// which both take the same input value.
+    pub fn with_step_symbol<S: AsRef<Span>>(
+        &self,
+        marker: Option<PairBuilder<ProgNode<'brand>>>,
+        expr: PairBuilder<ProgNode<'brand>>,
+        span: &S,
+    ) -> Result<PairBuilder<ProgNode<'brand>>, RichError> {
+        match marker {
+            Some(marker) => {
+                let drop_iden = ProgNode::drop_(&ProgNode::iden(self.ctx()));
+                marker.pair(expr).comp(&drop_iden).with_span(span)
+            }
+            None => Ok(expr),
+        }
+    }
+
+    /// Attach debug symbols to the body of a custom function
+    /// that mark when the body is entered and when it is left.
+    ///
+    /// The semantics of the body remain unchanged.
+    pub fn with_frame_symbols<S: AsRef<Span>>(
+        &self,
+        body: PairBuilder<ProgNode<'brand>>,
+        span: &S,
+    ) -> Result<PairBuilder<ProgNode<'brand>>, RichError> {
+        let Some((enter, exit)) = self
+            .call_tracker
+            .get_frame(span.as_ref())
+            .filter(|_| self.include_step_symbols)
+        else {
+            return Ok(body);
+        };
+        let enter = self
+            .marker(PairBuilder::unit(self.ctx()), enter)
+            .with_span(span)?;
+        let exit = self
+            .marker(PairBuilder::unit(self.ctx()), exit)
+            .with_span(span)?;
+        let drop_iden = ProgNode::drop_(&ProgNode::iden(self.ctx()));
+        let take_iden = ProgNode::take(&ProgNode::iden(self.ctx()));
+        let entered = enter.pair(body).comp(&drop_iden).with_span(span)?;
+        entered.pair(exit).comp(&take_iden).with_span(span)
+    }
+
+    /// Create a no-op that passes the output of `value` to the debug symbol `cmr`.
+    fn marker(
+        &self,
+        value: PairBuilder<ProgNode<'brand>>,
+        cmr: Cmr,
+    ) -> Result<PairBuilder<ProgNode<'brand>>, types::Error> {
+        let false_and_value = ProgNode::bit(self.ctx(), false).pair(value);
+        let nop_assert = ProgNode::assertl_drop(&ProgNode::unit(self.ctx()), cmr);
+        false_and_value.comp(&nop_assert)
+    }
+
     pub fn get_argument(&self, name: &WitnessName) -> &Value {
         self.arguments
             .get(name)
@@ -230,11 +353,17 @@ fn compile_blk<'brand>(
 ) -> Result<PairBuilder<ProgNode<'brand>>, RichError> {
     if index >= stmts.len() {
         return match last_expr {
-            Some(expr) => expr.compile(scope),
+            Some(expr) => {
+                let marker = scope.step_symbol(expr)?;
+                let compiled = expr.compile(scope)?;
+                scope.with_step_symbol(marker, compiled, expr)
+            }
             None => Ok(PairBuilder::unit(scope.ctx())),
         };
     }
-    match &stmts[index] {
+    // The marker sees the variables before the statement assigns new ones
+    let marker = scope.step_symbol(&stmts[index])?;
+    let compiled = match &stmts[index] {
         Statement::Assignment(assignment) => {
             let expr = assignment.expression().compile(scope)?;
             scope.insert(assignment.pattern().clone());
@@ -249,7 +378,8 @@ fn compile_blk<'brand>(
             let drop_iden = ProgNode::drop_(&ProgNode::iden(scope.ctx()));
             pair.comp(&drop_iden).with_span(expression)
         }
-    }
+    }?;
+    scope.with_step_symbol(marker, compiled, &stmts[index])
 }
 
 impl Program {
@@ -264,19 +394,94 @@ impl Program {
         arguments: Arguments,
         include_debug_symbols: bool,
     ) -> Result<Arc<named::CommitNode<Elements>>, RichError> {
+        self.compile_with_symbols(arguments, include_debug_symbols, false)
+            .map(|(commit, _)| commit)
+    }
+
+    /// Compile the SimplicityHL source code to Simplicity target code for a debugger.
+    ///
+    /// The target code includes debug symbols for function calls,
+    /// and it marks each statement and each entry and exit of a custom function,
+    /// which the [`crate::debug::Tracer`] records during execution.
+    ///
+    /// The markers change the CMR, so the result is not meant to be deployed.
+    ///
+    /// ## Precondition
+    ///
+    /// The supplied `arguments` are consistent with the program's parameters.
+    /// Call [`Arguments::is_consistent`] before calling this method!
+    pub fn compile_for_debugger(
+        &self,
+        arguments: Arguments,
+    ) -> Result<Arc<named::CommitNode<Elements>>, RichError> {
+        self.compile_with_symbols(arguments, true, true)
+            .map(|(commit, _)| commit)
+    }
+
+    /// Compile the SimplicityHL source code to Simplicity target code,
+    /// together with the source map of the target code.
+    ///
+    /// Include debug symbols and step symbols as requested.
+    ///
+    /// ## Precondition
+    ///
+    /// The supplied `arguments` are consistent with the program's parameters.
+    /// Call [`Arguments::is_consistent`] before calling this method!
+    pub(crate) fn compile_with_symbols(
+        &self,
+        arguments: Arguments,
+        include_debug_symbols: bool,
+        include_step_symbols: bool,
+    ) -> Result<(Arc<named::CommitNode<Elements>>, SourceMap), RichError> {
         types::Context::with_context(|ctx| {
             let mut scope = Scope::new(
                 ctx,
                 Arc::clone(self.call_tracker()),
                 arguments,
                 include_debug_symbols,
+                include_step_symbols,
             );
 
             let main = self.main();
             let construct = main.compile(&mut scope).map(PairBuilder::build)?;
             // SimplicityHL types should be correct by construction. If not, assign the
             // whole main function as the span for them, which is as sensible as anything.
-            named::finalize_types(&construct).with_span(main)
+            let commit = named::finalize_types(&construct).with_span(main)?;
+            let source_map = scope.source_map.borrow().finish(&construct, &commit);
+            Ok((commit, source_map))
+        })
+    }
+
+    /// Compile the body of a custom function of the program to Simplicity target code.
+    ///
+    /// The resulting expression takes the tuple of function parameters as input,
+    /// like at every call of the function.
+    ///
+    /// ## Precondition
+    ///
+    /// The supplied `arguments` are consistent with the program's parameters.
+    /// Call [`Arguments::is_consistent`] before calling this method!
+    pub fn compile_function(
+        &self,
+        function: &CustomFunction,
+        arguments: Arguments,
+        include_debug_symbols: bool,
+    ) -> Result<Arc<named::CommitNode<Elements>>, RichError> {
+        types::Context::with_context(|ctx| {
+            let scope = Scope::new(
+                ctx,
+                Arc::clone(self.call_tracker()),
+                arguments,
+                include_debug_symbols,
+                false,
+            );
+
+            let mut function_scope = scope.child(function.params_pattern());
+            let construct = function
+                .body()
+                .compile(&mut function_scope)
+                .map(PairBuilder::build)?;
+            named::finalize_types(&construct).with_span(function.body())
         })
     }
 }
@@ -291,7 +496,9 @@ impl Expression {
                 scope.push_scope();
                 let res = compile_blk(stmts, scope, 0, expr.as_ref().map(Arc::as_ref));
                 scope.pop_scope();
-                res
+                let res = res?;
+                scope.record_span(&res, self);
+                Ok(res)
             }
             ExpressionInner::Single(e) => e.compile(scope),
         }
@@ -365,6 +572,7 @@ impl SingleExpression {
                 "",
             )
             .with_span(self)?;
+        scope.record_span(&expr, self);
         Ok(expr)
     }
 }
@@ -433,23 +641,27 @@ impl Call {
             CallName::Custom(function) => {
                 let mut function_scope = scope.child(function.params_pattern());
                 let body = function.body().compile(&mut function_scope)?;
+                let body = scope.with_frame_symbols(body, function.body())?;
                 args.comp(&body).with_span(self)
             }
             CallName::Fold(function, bound) => {
                 let mut function_scope = scope.child(function.params_pattern());
                 let body = function.body().compile(&mut function_scope)?;
+                let body = scope.with_frame_symbols(body, function.body())?;
                 let fold_body = list_fold(*bound, body.as_ref()).with_span(self)?;
                 args.comp(&fold_body).with_span(self)
             }
             CallName::ArrayFold(function, size) => {
                 let mut function_scope = scope.child(function.params_pattern());
                 let body = function.body().compile(&mut function_scope)?;
+                let body = scope.with_frame_symbols(body, function.body())?;
                 let fold_body = array_fold(*size, body.as_ref()).with_span(self)?;
                 args.comp(&fold_body).with_span(self)
             }
             CallName::ForWhile(function, bit_width) => {
                 let mut function_scope = scope.child(function.params_pattern());
                 let body = function.body().compile(&mut function_scope)?;
+                let body = scope.with_frame_symbols(body, function.body())?;
                 let fold_body = for_while(*bit_width, body).with_span(self)?;
                 args.comp(&fold_body).with_span(self)
             }
@@ -660,7 +872,7 @@ impl Match {
                 .cloned()
                 .map_or(Pattern::Ignore, Pattern::Identifier),
         );
-        let left = self.left().expression().compile(scope)?;
+        let left = compile_arm(self.left().expression(), scope)?;
         scope.pop_scope();
 
         scope.push_scope();
@@ -671,7 +883,7 @@ impl Match {
                 .cloned()
                 .map_or(Pattern::Ignore, Pattern::Identifier),
         );
-        let right = self.right().expression().compile(scope)?;
+        let right = compile_arm(self.right().expression(), scope)?;
         scope.pop_scope();
 
         let scrutinee = self.scrutinee().compile(scope)?;
@@ -680,3 +892,12 @@ impl Match {
         input.comp(&output).with_span(self)
     }
 }
+
+fn compile_arm<'brand>(
+    expr: &Expression,
+    scope: &mut Scope<'brand>,
+) -> Result<PairBuilder<ProgNode<'brand>>, RichError> {
+    let marker = scope.branch_symbol(expr)?;
+    let compiled = expr.compile(scope)?;
+    scope.with_step_symbol(marker, compiled, expr)
+}
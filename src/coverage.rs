@@ -0,0 +1,494 @@
+//! Coverage of SimplicityHL source code by executions on the Bit Machine
+//!
+//! Programs that are compiled for a debugger contain step symbols
+//! that mark statements, bodies of custom functions and match arms.
+//! Coverage counts how often each of these points of execution is reached
+//! across a set of runs.
+//!
+//! Coverage is reported in the [lcov] tracefile format
+//! or as source code that is annotated with execution counts.
+//!
+//! [lcov]: https://github.com/linux-test-project/lcov
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Arc;
+
+use simplicity::elements;
+use simplicity::jet::elements::ElementsEnv;
+
+use crate::debug::{DebugSymbols, TraceEvent, Tracer, TrackedStepName};
+use crate::error::Span;
+use crate::parse::{self, ParseFromStr};
+use crate::testing::{execute_with_tracer, TestFailure};
+use crate::SatisfiedProgram;
+
+/// Execution counts of the points of execution of a SimplicityHL program.
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    file: Arc<str>,
+    sources: Vec<Source>,
+    /// Name and line of custom functions, indexed by the span of their body.
+    function_names: HashMap<Span, (String, usize)>,
+    /// Execution counts of statements and match arms.
+    steps: HashMap<Span, usize>,
+    /// Execution counts of the bodies of custom functions.
+    functions: HashMap<Span, usize>,
+    /// Span of the match expression and execution count of match arms.
+    branches: HashMap<Span, (Span, usize)>,
+}
+
+/// Source file that is part of the covered program.
+#[derive(Clone, Debug)]
+struct Source {
+    name: String,
+    /// Line of the program where the file starts.
+    first_line: usize,
+    /// Number of lines of the file.
+    n_lines: usize,
+}
+
+/// Number of points of execution that were found and that were hit.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct Count {
+    found: usize,
+    hit: usize,
+}
+
+impl Coverage {
+    /// Create empty coverage of the program that consists of the given source files.
+    ///
+    /// The files are given as pairs of file name and file content.
+    /// The program is the concatenation of the files, separated by newlines,
+    /// in the given order.
+    ///
+    /// ## Errors
+    ///
+    /// The program is not a valid SimplicityHL program.
+    pub fn new<N: AsRef<str>, T: AsRef<str>>(files: &[(N, T)]) -> Result<Self, String> {
+        let mut sources = Vec::with_capacity(files.len());
+        let mut first_line = 1;
+        for (name, text) in files {
+            let n_lines = text.as_ref().split('\n').count();
+            sources.push(Source {
+                name: name.as_ref().to_string(),
+                first_line,
+                n_lines,
+            });
+            first_line += n_lines;
+        }
+        let file: Arc<str> = files
+            .iter()
+            .map(|(_, text)| text.as_ref())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into();
+        let program = parse::Program::parse_from_str(&file)?;
+        let function_names = program
+            .items()
+            .iter()
+            .filter_map(|item| match item {
+                parse::Item::Function(function) => Some((
+                    *function.body().as_ref(),
+                    (
+                        function.name().to_string(),
+                        function.as_ref().start.line.get(),
+                    ),
+                )),
+                _ => None,
+            })
+            .collect();
+
+        Ok(Self {
+            file,
+            sources,
+            function_names,
+            steps: HashMap::new(),
+            functions: HashMap::new(),
+            branches: HashMap::new(),
+        })
+    }
+
+    /// Access the text of the covered program.
+    pub fn file(&self) -> &Arc<str> {
+        &self.file
+    }
+
+    /// Record the `events` of a run of a program with the given `debug_symbols`.
+    ///
+    /// Points of execution that are part of the debug symbols count as covered code,
+    /// even if the run never reaches them.
+    pub fn record(&mut self, debug_symbols: &DebugSymbols, events: &[TraceEvent]) {
+        for step in debug_symbols.steps() {
+            match step.name() {
+                TrackedStepName::Statement(..) => {
+                    self.steps.entry(*step.span()).or_default();
+                }
+                TrackedStepName::EnterFunction => {
+                    self.functions.entry(*step.span()).or_default();
+                }
+                TrackedStepName::ExitFunction => {}
+                TrackedStepName::Branch(matched) => {
+                    self.steps.entry(*step.span()).or_default();
+                    self.branches.entry(*step.span()).or_insert((*matched, 0));
+                }
+            }
+        }
+        for event in events {
+            match event {
+                TraceEvent::Step(span, ..) => *self.steps.entry(*span).or_default() += 1,
+                TraceEvent::EnterFunction(span) => *self.functions.entry(*span).or_default() += 1,
+                TraceEvent::Branch(span) => {
+                    *self.steps.entry(*span).or_default() += 1;
+                    if let Some((_, count)) = self.branches.get_mut(span) {
+                        *count += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Run the satisfied program on the Bit Machine in the given environment
+    /// and record which parts of the source code were executed.
+    ///
+    /// The program must be compiled for a debugger, from the text of the covered program.
+    /// Otherwise, the run is not recorded.
+    ///
+    /// ## Errors
+    ///
+    /// The program fails at runtime. The run is recorded nonetheless.
+    pub fn run(
+        &mut self,
+        satisfied: &SatisfiedProgram,
+        env: &ElementsEnv<Arc<elements::Transaction>>,
+    ) -> Result<(), TestFailure> {
+        let mut tracer = Tracer::new(satisfied.debug_symbols());
+        let result = execute_with_tracer(satisfied, env, &mut tracer);
+        self.record(satisfied.debug_symbols(), tracer.events());
+        result
+    }
+
+    /// Return the coverage in the lcov tracefile format, with one record per source file.
+    pub fn to_lcov(&self) -> String {
+        let mut lcov = String::new();
+        for source in &self.sources {
+            writeln!(lcov, "TN:").unwrap();
+            writeln!(lcov, "SF:{}", source.name).unwrap();
+            let functions = self.functions_in(source);
+            for (name, line, _) in &functions {
+                writeln!(lcov, "FN:{line},{name}").unwrap();
+            }
+            for (name, _, hits) in &functions {
+                writeln!(lcov, "FNDA:{hits},{name}").unwrap();
+            }
+            let count = Count::of(functions.iter().map(|(_, _, hits)| *hits));
+            writeln!(lcov, "FNF:{}\nFNH:{}", count.found, count.hit).unwrap();
+
+            let branches = self.branches_in(source);
+            for (block, (line, arms)) in branches.iter().enumerate() {
+                let reached = arms.iter().any(|hits| 0 < *hits);
+                for (branch, hits) in arms.iter().enumerate() {
+                    let taken = if reached {
+                        hits.to_string()
+                    } else {
+                        "-".to_string()
+                    };
+                    writeln!(lcov, "BRDA:{line},{block},{branch},{taken}").unwrap();
+                }
+            }
+            let count = Count::of(branches.iter().flat_map(|(_, arms)| arms.iter().copied()));
+            writeln!(lcov, "BRF:{}\nBRH:{}", count.found, count.hit).unwrap();
+
+            let lines = self.lines_in(source);
+            for (line, hits) in &lines {
+                writeln!(lcov, "DA:{line},{hits}").unwrap();
+            }
+            let count = Count::of(lines.values().copied());
+            writeln!(lcov, "LF:{}\nLH:{}", count.found, count.hit).unwrap();
+            writeln!(lcov, "end_of_record").unwrap();
+        }
+        lcov
+    }
+
+    /// Return the source files, annotated with execution counts, together with their names.
+    ///
+    /// Each line is prefixed with the number of times it was executed.
+    /// Lines that were never executed are prefixed with `#####`.
+    /// Lines without points of execution have no prefix.
+    /// Each match expression is followed by the number of times that each arm was taken.
+    pub fn annotate(&self) -> impl Iterator<Item = (&str, String)> {
+        let lines = self.file.split('\n').collect::<Vec<_>>();
+        self.sources.iter().map(move |source| {
+            let hits = self.lines_in(source);
+            let functions = self.functions_in(source);
+            let branches = self.branches_in(source);
+            let mut report = String::new();
+
+            let count_lines = Count::of(hits.values().copied());
+            let count_functions = Count::of(functions.iter().map(|(_, _, hits)| *hits));
+            let count_branches =
+                Count::of(branches.iter().flat_map(|(_, arms)| arms.iter().copied()));
+            writeln!(
+                report,
+                "{:>6} | Lines: {count_lines}, functions: {count_functions}, branches: {count_branches}",
+                "-"
+            )
+            .unwrap();
+
+            let source_lines = &lines[source.first_line - 1..][..source.n_lines];
+            // Skip the empty line after the final newline
+            let n_lines = match source_lines.last() {
+                Some(line) if line.is_empty() && 1 < source_lines.len() => source.n_lines - 1,
+                _ => source.n_lines,
+            };
+            for (index, text) in source_lines[..n_lines].iter().enumerate() {
+                let line = index + 1;
+                let prefix = match hits.get(&line) {
+                    Some(0) => "#####".to_string(),
+                    Some(hits) => hits.to_string(),
+                    None => String::new(),
+                };
+                let annotated = format!("{prefix:>6} | {text}");
+                writeln!(report, "{}", annotated.trim_end()).unwrap();
+                for (_, arms) in branches.iter().filter(|(l, _)| *l == line) {
+                    for (branch, hits) in arms.iter().enumerate() {
+                        match hits {
+                            0 => writeln!(report, "{:>6} | branch {branch} never taken", ""),
+                            _ => writeln!(report, "{:>6} | branch {branch} taken {hits}", ""),
+                        }
+                        .unwrap();
+                    }
+                }
+            }
+            (source.name.as_str(), report)
+        })
+    }
+
+    /// Return the execution count of each line of the source file with points of execution.
+    ///
+    /// A line is executed as often as the most frequently executed statement
+    /// or match arm that starts on it.
+    fn lines_in(&self, source: &Source) -> BTreeMap<usize, usize> {
+        let mut lines = BTreeMap::new();
+        for (span, hits) in &self.steps {
+            if let Some(line) = source.local_line(span) {
+                let entry = lines.entry(line).or_default();
+                *entry = std::cmp::max(*entry, *hits);
+            }
+        }
+        lines
+    }
+
+    /// Return the name, the line and the execution count of each custom function
+    /// in the source file, sorted by line.
+    fn functions_in(&self, source: &Source) -> Vec<(&str, usize, usize)> {
+        let mut functions = self
+            .functions
+            .iter()
+            .filter_map(|(span, hits)| {
+                let (name, line) = self.function_names.get(span)?;
+                let line = source.local_line_number(*line)?;
+                Some((name.as_str(), line, *hits))
+            })
+            .collect::<Vec<_>>();
+        functions.sort_unstable_by_key(|(_, line, _)| *line);
+        functions
+    }
+
+    /// Return the line and the execution counts of the arms of each match expression
+    /// in the source file, sorted by position.
+    fn branches_in(&self, source: &Source) -> Vec<(usize, Vec<usize>)> {
+        let mut matches: HashMap<Span, Vec<(Span, usize)>> = HashMap::new();
+        for (arm, (matched, hits)) in &self.branches {
+            matches.entry(*matched).or_default().push((*arm, *hits));
+        }
+        let position = |span: &Span| (span.start.line, span.start.col);
+        let mut matches = matches
+            .into_iter()
+            .filter_map(|(matched, mut arms)| {
+                source.local_line(&matched)?;
+                arms.sort_unstable_by_key(|(arm, _)| position(arm));
+                Some((matched, arms))
+            })
+            .collect::<Vec<_>>();
+        matches.sort_unstable_by_key(|(matched, _)| position(matched));
+        matches
+            .into_iter()
+            .map(|(matched, arms)| {
+                let line = source
+                    .local_line(&matched)
+                    .expect("match is in source file");
+                (line, arms.into_iter().map(|(_, hits)| hits).collect())
+            })
+            .collect()
+    }
+}
+
+impl Source {
+    /// Return the line of the source file where the span starts.
+    fn local_line(&self, span: &Span) -> Option<usize> {
+        self.local_line_number(span.start.line.get())
+    }
+
+    /// Convert the line of the program into the line of the source file.
+    fn local_line_number(&self, line: usize) -> Option<usize> {
+        (self.first_line..self.first_line + self.n_lines)
+            .contains(&line)
+            .then(|| line - self.first_line + 1)
+    }
+}
+
+impl Count {
+    fn of<I: IntoIterator<Item = usize>>(hits: I) -> Self {
+        let mut count = Self::default();
+        for hits in hits {
+            count.found += 1;
+            if 0 < hits {
+                count.hit += 1;
+            }
+        }
+        count
+    }
+}
+
+impl std::fmt::Display for Count {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.hit, self.found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy_env;
+    use crate::str::FunctionName;
+    use crate::testing::TestSuite;
+    use crate::{Arguments, TemplateProgram, WitnessValues};
+
+    const PROGRAM: &str = r#"fn checked_add(a: u8, b: u8) -> Option<u8> {
+    let (carry, sum): (bool, u8) = jet::add_8(a, b);
+    match carry {
+        true => None,
+        false => Some(sum),
+    }
+}
+
+fn unused() {
+    assert!(true);
+}
+
+fn main() {
+    let sum: u8 = unwrap(checked_add(1, 2));
+    assert!(jet::eq_8(sum, 3));
+}
+"#;
+
+    const TESTS: &str = r#"#[test]
+fn add_small() {
+    assert!(jet::eq_8(unwrap(checked_add(1, 2)), 3));
+}
+
+#[test]
+fn add_twice() {
+    let a: u8 = unwrap(checked_add(1, 2));
+    let _: u8 = unwrap(checked_add(a, 3));
+}
+"#;
+
+    fn coverage() -> Coverage {
+        let mut coverage =
+            Coverage::new(&[("program.simf", PROGRAM), ("tests.simf", TESTS)]).unwrap();
+        let suite = TestSuite::new(Arc::clone(coverage.file())).unwrap();
+        let env = dummy_env::dummy();
+        for name in ["add_small", "add_twice"] {
+            let name = FunctionName::from_str_unchecked(name);
+            suite
+                .run_with_coverage(&name, &env, &mut coverage)
+                .expect("test should pass");
+        }
+        coverage
+    }
+
+    #[test]
+    fn lcov() {
+        let expected = "\
+TN:
+SF:program.simf
+FN:1,checked_add
+FN:9,unused
+FNDA:3,checked_add
+FNDA:0,unused
+FNF:2
+FNH:1
+BRDA:3,0,0,0
+BRDA:3,0,1,3
+BRF:2
+BRH:1
+DA:2,3
+DA:3,3
+DA:4,0
+DA:5,3
+DA:10,0
+LF:5
+LH:3
+end_of_record
+TN:
+SF:tests.simf
+FNF:0
+FNH:0
+BRF:0
+BRH:0
+DA:3,1
+DA:8,1
+DA:9,1
+LF:3
+LH:3
+end_of_record
+";
+        assert_eq!(expected, coverage().to_lcov());
+    }
+
+    #[test]
+    fn annotate() {
+        let expected = "     - | Lines: 3/5, functions: 1/2, branches: 1/2
+       | fn checked_add(a: u8, b: u8) -> Option<u8> {
+     3 |     let (carry, sum): (bool, u8) = jet::add_8(a, b);
+     3 |     match carry {
+       | branch 0 never taken
+       | branch 1 taken 3
+ ##### |         true => None,
+     3 |         false => Some(sum),
+       |     }
+       | }
+       |
+       | fn unused() {
+ ##### |     assert!(true);
+       | }
+       |
+       | fn main() {
+       |     let sum: u8 = unwrap(checked_add(1, 2));
+       |     assert!(jet::eq_8(sum, 3));
+       | }
+";
+        let coverage = coverage();
+        let reports = coverage.annotate().collect::<Vec<_>>();
+        assert_eq!(2, reports.len());
+        assert_eq!(
+            ("program.simf", expected),
+            (reports[0].0, reports[0].1.as_str())
+        );
+    }
+
+    #[test]
+    fn run_main() {
+        let mut coverage = Coverage::new(&[("program.simf", PROGRAM)]).unwrap();
+        let compiled = TemplateProgram::new(Arc::clone(coverage.file()))
+            .and_then(|template| template.instantiate_for_debugger(Arguments::default()))
+            .unwrap();
+        let satisfied = compiled.satisfy(WitnessValues::default()).unwrap();
+        coverage.run(&satisfied, &dummy_env::dummy()).unwrap();
+        assert!(coverage.to_lcov().contains("FNDA:1,checked_add\n"));
+        assert!(coverage.to_lcov().contains("DA:14,1\nDA:15,1\n"));
+    }
+}
@@ -3,17 +3,28 @@ use std::sync::Arc;
 
 use either::Either;
 use hashes::{sha256, Hash, HashEngine};
-use simplicity::{hashes, Cmr};
+use simplicity::bit_machine::ExecTracker;
+use simplicity::ffi::ffi::UWORD;
+use simplicity::jet::Elements;
+use simplicity::{hashes, types, Cmr, CommitNode, Ihr};
 
 use crate::error::Span;
-use crate::types::ResolvedType;
-use crate::value::{StructuralValue, Value};
+use crate::named;
+use crate::str::Identifier;
+use crate::types::{ResolvedType, TypeConstructible};
+use crate::value::{StructuralValue, Value, ValueInner};
 
 /// Tracker of SimplicityHL call expressions inside Simplicity target code.
 ///
 /// Tracking happens via CMRs that are inserted into the Simplicity target code.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
-pub struct DebugSymbols(HashMap<Cmr, TrackedCall>);
+pub struct DebugSymbols {
+    pub(crate) calls: HashMap<Cmr, TrackedCall>,
+    pub(crate) steps: HashMap<Cmr, TrackedStep>,
+}
+
+/// Variables in scope of a statement, with their types.
+pub type ScopeVariables = Arc<[(Identifier, ResolvedType)]>;
 
 /// Intermediate representation of tracked SimplicityHL call expressions
 /// that is mutable and that lacks information about the source file.
@@ -23,13 +34,17 @@ pub struct DebugSymbols(HashMap<Cmr, TrackedCall>);
 pub(crate) struct CallTracker {
     next_id: u32,
     map: HashMap<Span, (Cmr, TrackedCallName)>,
+    next_step_id: u32,
+    steps: HashMap<Span, (Cmr, ScopeVariables)>,
+    frames: HashMap<Span, (Cmr, Cmr)>,
+    branches: HashMap<Span, (Cmr, Span)>,
 }
 
 /// Call expression with a debug symbol.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TrackedCall {
-    text: Arc<str>,
-    name: TrackedCallName,
+    pub(crate) text: Arc<str>,
+    pub(crate) name: TrackedCallName,
 }
 
 /// Name of a call expression with a debug symbol.
@@ -69,6 +84,62 @@ pub struct DebugValue {
     value: Value,
 }
 
+/// Point of execution with a debug symbol where a debugger can stop.
+///
+/// Step symbols are only inserted into the Simplicity target code
+/// when compiling for a debugger.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TrackedStep {
+    pub(crate) span: Span,
+    pub(crate) name: TrackedStepName,
+}
+
+/// Kind of a point of execution with a debug symbol.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TrackedStepName {
+    /// A statement or the final expression of a block is about to be evaluated.
+    ///
+    /// The runtime input value is the tuple of the variables in scope, in the given order.
+    Statement(ScopeVariables),
+    /// The body of a custom function is entered.
+    EnterFunction,
+    /// The body of a custom function is left.
+    ExitFunction,
+    /// An arm of a match expression is about to be evaluated.
+    ///
+    /// Contains the span of the match expression.
+    Branch(Span),
+}
+
+/// Event of an execution on the Bit Machine, decoded via debug symbols.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TraceEvent {
+    /// A statement or the final expression of a block is about to be evaluated.
+    ///
+    /// Contains the span of the statement or expression and the values of the variables in scope.
+    Step(Span, Vec<(Identifier, Value)>),
+    /// The body of a custom function with the given span is entered.
+    EnterFunction(Span),
+    /// The body of a custom function with the given span is left.
+    ExitFunction(Span),
+    /// The match arm with the given span is about to be evaluated.
+    Branch(Span),
+    /// A fallible call expression is about to be evaluated.
+    Call(FallibleCall),
+    /// A debug expression was evaluated.
+    Debug(DebugValue),
+}
+
+/// Tracker that records the execution of a Simplicity program on the Bit Machine
+/// as a sequence of [`TraceEvent`]s.
+///
+/// Only code that was compiled with debug symbols produces events.
+#[derive(Debug, Clone)]
+pub struct Tracer<'a> {
+    debug_symbols: &'a DebugSymbols,
+    events: Vec<TraceEvent>,
+}
+
 impl DebugSymbols {
     /// Insert a tracked call expression.
     /// Use the SimplicityHL source `file` to extract the SimplicityHL text of the expression.
@@ -79,7 +150,7 @@ impl DebugSymbols {
             .and_then(|s| s.strip_suffix(")"))
             .unwrap_or(&text);
 
-        self.0.insert(
+        self.calls.insert(
             cmr,
             TrackedCall {
                 text: Arc::from(text),
@@ -90,12 +161,52 @@ impl DebugSymbols {
 
     /// Check if the given CMR tracks any call expressions.
     pub fn contains_key(&self, cmr: &Cmr) -> bool {
-        self.0.contains_key(cmr)
+        self.calls.contains_key(cmr)
+    }
+
+    /// Check if the given CMR is a debug symbol,
+    /// which tracks either a call expression or a point of execution.
+    pub fn is_symbol(&self, cmr: &Cmr) -> bool {
+        self.calls.contains_key(cmr) || self.steps.contains_key(cmr)
+    }
+
+    /// Remove the debug markers of these debug symbols from the given Simplicity program.
+    ///
+    /// If the program was compiled with these debug symbols,
+    /// then the result is the program that the compiler produces without debug symbols,
+    /// which has the CMR of the release build.
+    ///
+    /// ## Errors
+    ///
+    /// The program contains disconnect nodes or it is ill-typed without the markers.
+    pub fn strip(
+        &self,
+        program: &CommitNode<Elements>,
+    ) -> Result<Arc<CommitNode<Elements>>, String> {
+        types::Context::with_context(|ctx| {
+            let stripped = named::strip_markers(
+                &ctx,
+                program,
+                |cmr| self.is_symbol(&cmr),
+                |_| None::<simplicity::Value>,
+            )?;
+            stripped.node.finalize_types().map_err(|e| e.to_string())
+        })
     }
 
     /// Get the call expression that is tracked by the given CMR.
     pub fn get(&self, cmr: &Cmr) -> Option<&TrackedCall> {
-        self.0.get(cmr)
+        self.calls.get(cmr)
+    }
+
+    /// Get the point of execution that is tracked by the given CMR.
+    pub fn get_step(&self, cmr: &Cmr) -> Option<&TrackedStep> {
+        self.steps.get(cmr)
+    }
+
+    /// Iterate over the points of execution, in arbitrary order.
+    pub fn steps(&self) -> impl Iterator<Item = &TrackedStep> {
+        self.steps.values()
     }
 }
 
@@ -127,18 +238,55 @@ impl CallTracker {
         self.next_id += 1;
     }
 
+    /// Track a statement or final expression of a block with the given `span`
+    /// and with the `variables` that are in scope before it is evaluated.
+    pub fn track_step(&mut self, span: Span, variables: ScopeVariables) {
+        let cmr = self.next_step_cmr();
+        let _replaced = self.steps.insert(span, (cmr, variables));
+    }
+
+    /// Track the body of a custom function with the given `span`.
+    pub fn track_frame(&mut self, span: Span) {
+        let enter = self.next_step_cmr();
+        let exit = self.next_step_cmr();
+        let _replaced = self.frames.insert(span, (enter, exit));
+    }
+
+    /// Track the arm of a match expression with the given `span`.
+    /// The span of the surrounding match expression is `matched`.
+    pub fn track_branch(&mut self, span: Span, matched: Span) {
+        let cmr = self.next_step_cmr();
+        let _replaced = self.branches.insert(span, (cmr, matched));
+    }
+
     /// Get the CMR of the tracked function call with the given `span`.
     pub fn get_cmr(&self, span: &Span) -> Option<Cmr> {
         self.map.get(span).map(|x| x.0)
     }
 
+    /// Get the CMR and the variables in scope of the tracked statement with the given `span`.
+    pub fn get_step(&self, span: &Span) -> Option<&(Cmr, ScopeVariables)> {
+        self.steps.get(span)
+    }
+
+    /// Get the CMRs for entering and leaving the tracked function body with the given `span`.
+    pub fn get_frame(&self, span: &Span) -> Option<(Cmr, Cmr)> {
+        self.frames.get(span).copied()
+    }
+
+    /// Get the CMR of the tracked match arm with the given `span`.
+    pub fn get_branch(&self, span: &Span) -> Option<Cmr> {
+        self.branches.get(span).map(|x| x.0)
+    }
+
     fn next_id_cmr(&self) -> Cmr {
-        let tag_hash = sha256::Hash::hash(b"simfony\x1fdebug\x1f");
-        let mut engine = sha256::Hash::engine();
-        engine.input(tag_hash.as_ref());
-        engine.input(tag_hash.as_ref());
-        engine.input(self.next_id.to_be_bytes().as_ref());
-        Cmr::from_byte_array(sha256::Hash::from_engine(engine).to_byte_array())
+        tagged_cmr(b"simfony\x1fdebug\x1f", self.next_id)
+    }
+
+    fn next_step_cmr(&mut self) -> Cmr {
+        let cmr = tagged_cmr(b"simfony\x1fstep\x1f", self.next_step_id);
+        self.next_step_id += 1;
+        cmr
     }
 
     /// Create debug symbols by attaching information from the source `file`.
@@ -147,10 +295,41 @@ impl CallTracker {
         for (span, (cmr, name)) in &self.map {
             debug_symbols.insert(*span, *cmr, name.clone(), file);
         }
+        for (span, (cmr, variables)) in &self.steps {
+            let name = TrackedStepName::Statement(Arc::clone(variables));
+            debug_symbols
+                .steps
+                .insert(*cmr, TrackedStep { span: *span, name });
+        }
+        for (span, (enter, exit)) in &self.frames {
+            for (cmr, name) in [
+                (enter, TrackedStepName::EnterFunction),
+                (exit, TrackedStepName::ExitFunction),
+            ] {
+                debug_symbols
+                    .steps
+                    .insert(*cmr, TrackedStep { span: *span, name });
+            }
+        }
+        for (span, (cmr, matched)) in &self.branches {
+            let name = TrackedStepName::Branch(*matched);
+            debug_symbols
+                .steps
+                .insert(*cmr, TrackedStep { span: *span, name });
+        }
         debug_symbols
     }
 }
 
+fn tagged_cmr(tag: &[u8], id: u32) -> Cmr {
+    let tag_hash = sha256::Hash::hash(tag);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    engine.input(id.to_be_bytes().as_ref());
+    Cmr::from_byte_array(sha256::Hash::from_engine(engine).to_byte_array())
+}
+
 impl TrackedCall {
     /// Access the text of the SimplicityHL call expression.
     pub fn text(&self) -> &str {
@@ -219,3 +398,93 @@ impl DebugValue {
         &self.value
     }
 }
+
+impl TrackedStep {
+    /// Access the span of the statement, expression, function body or match arm.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Access the kind of the point of execution.
+    pub fn name(&self) -> &TrackedStepName {
+        &self.name
+    }
+
+    /// Supply the Simplicity input value of the point of execution at runtime.
+    /// Convert the value into the values of the variables in scope.
+    ///
+    /// Return `None` if the point of execution is not a statement
+    /// or if the Simplicity input value is of the wrong type.
+    pub fn variables(&self, value: &StructuralValue) -> Option<Vec<(Identifier, Value)>> {
+        let TrackedStepName::Statement(variables) = &self.name else {
+            return None;
+        };
+        let ty = ResolvedType::tuple(variables.iter().map(|(_, ty)| ty.clone()));
+        let value = Value::reconstruct(value, &ty)?;
+        let values = match value.inner() {
+            ValueInner::Tuple(values) if values.len() == variables.len() => values.to_vec(),
+            _ => return None,
+        };
+        Some(
+            variables
+                .iter()
+                .map(|(identifier, _)| identifier.clone())
+                .zip(values)
+                .collect(),
+        )
+    }
+}
+
+impl<'a> Tracer<'a> {
+    /// Create a tracker that decodes the execution via the given debug symbols.
+    pub fn new(debug_symbols: &'a DebugSymbols) -> Self {
+        Self {
+            debug_symbols,
+            events: Vec::new(),
+        }
+    }
+
+    /// Access the recorded events, in the order of execution.
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// Consume the tracker and return the recorded events, in the order of execution.
+    pub fn into_events(self) -> Vec<TraceEvent> {
+        self.events
+    }
+}
+
+impl ExecTracker<Elements> for Tracer<'_> {
+    fn track_left(&mut self, _: Ihr) {}
+
+    fn track_right(&mut self, _: Ihr) {}
+
+    fn track_jet_call(&mut self, _: &Elements, _: &[UWORD], _: &[UWORD], _: bool) {}
+
+    fn track_dbg_call(&mut self, cmr: &Cmr, value: simplicity::Value) {
+        let value = StructuralValue::from(value);
+        if let Some(step) = self.debug_symbols.get_step(cmr) {
+            let event = match step.name() {
+                TrackedStepName::Statement(..) => match step.variables(&value) {
+                    Some(variables) => TraceEvent::Step(step.span, variables),
+                    None => return,
+                },
+                TrackedStepName::EnterFunction => TraceEvent::EnterFunction(step.span),
+                TrackedStepName::ExitFunction => TraceEvent::ExitFunction(step.span),
+                TrackedStepName::Branch(..) => TraceEvent::Branch(step.span),
+            };
+            self.events.push(event);
+        } else if let Some(call) = self.debug_symbols.get(cmr) {
+            match call.map_value(&value) {
+                Some(Either::Left(call)) => self.events.push(TraceEvent::Call(call)),
+                Some(Either::Right(debug)) => self.events.push(TraceEvent::Debug(debug)),
+                None => {}
+            }
+        }
+    }
+
+    fn is_track_debug_enabled(&self) -> bool {
+        true
+    }
+}
@@ -324,12 +324,19 @@ pub enum Error {
     InvalidNumberOfArguments(usize, usize),
     FunctionNotFoldable(FunctionName),
     FunctionNotLoopable(FunctionName),
+    LoopNotIterable(ResolvedType),
+    LoopCounterType(ResolvedType),
+    LoopRangeInvalid(usize, usize, UIntType),
+    BreakOutsideLoop,
     ExpressionUnexpectedType(ResolvedType),
     ExpressionTypeMismatch(ResolvedType, ResolvedType),
     ExpressionNotConstant,
     IntegerOutOfBounds(UIntType),
     UndefinedVariable(Identifier),
     UndefinedAlias(AliasName),
+    UndefinedConstant(Identifier),
+    ConstantRedefined(Identifier),
+    ConstantNotSize(Identifier, ResolvedType),
     VariableReuseInPattern(Identifier),
     WitnessReused(WitnessName),
     WitnessTypeMismatch(WitnessName, ResolvedType, ResolvedType),
@@ -339,6 +346,14 @@ pub enum Error {
     ModuleRedefined(ModuleName),
     ArgumentMissing(WitnessName),
     ArgumentTypeMismatch(WitnessName, ResolvedType, ResolvedType),
+    WitnessMissing(WitnessName),
+    CannotInterpret(String),
+    AssertionFailed,
+    ExplicitPanic,
+    UnwrapLeftFailed,
+    UnwrapRightFailed,
+    UnwrapNoneFailed,
+    JetFailed(JetName),
 }
 
 #[rustfmt::skip]
@@ -421,6 +436,22 @@ impl fmt::Display for Error {
                 f,
                 "Expected a signature like `fn {name}(accumulator: A, context: C, counter u{{1,2,4,8,16}}) -> Either<B, A>` for a for-while loop"
             ),
+            Error::LoopNotIterable(ty) => write!(
+                f,
+                "Expected an array or a list to loop over; found `{ty}`"
+            ),
+            Error::LoopCounterType(ty) => write!(
+                f,
+                "Expected `u8` or `u16` as type of the loop counter; found `{ty}`"
+            ),
+            Error::LoopRangeInvalid(start, end, ty) => write!(
+                f,
+                "Range `{start}..{end}` cannot be iterated with a loop counter of type `{ty}`"
+            ),
+            Error::BreakOutsideLoop => write!(
+                f,
+                "`break` is only allowed as final expression of the body of a `for` loop"
+            ),
             Error::ExpressionUnexpectedType(ty) => write!(
                 f,
                 "Expected expression of type `{ty}`; found something else"
@@ -445,6 +476,18 @@ impl fmt::Display for Error {
                 f,
                 "Type alias `{identifier}` is not defined"
             ),
+            Error::UndefinedConstant(identifier) => write!(
+                f,
+                "Constant `{identifier}` is not defined"
+            ),
+            Error::ConstantRedefined(identifier) => write!(
+                f,
+                "Constant `{identifier}` was defined multiple times"
+            ),
+            Error::ConstantNotSize(identifier, ty) => write!(
+                f,
+                "Constant `{identifier}` of type `{ty}` cannot be used as a size; expected an unsigned integer that fits into `usize`"
+            ),
             Error::VariableReuseInPattern(identifier) => write!(
                 f,
                 "Variable `{identifier}` is used twice in the pattern"
@@ -481,6 +524,38 @@ impl fmt::Display for Error {
                 f,
                 "Parameter `{name}` was declared with type `{declared}` but its assigned argument is of type `{assigned}`"
             ),
+            Error::WitnessMissing(name) => write!(
+                f,
+                "Witness `{name}` is missing a value"
+            ),
+            Error::CannotInterpret(description) => write!(
+                f,
+                "Failed to interpret program: {description}"
+            ),
+            Error::AssertionFailed => write!(
+                f,
+                "Assertion failed"
+            ),
+            Error::ExplicitPanic => write!(
+                f,
+                "Explicit panic"
+            ),
+            Error::UnwrapLeftFailed => write!(
+                f,
+                "Called `unwrap_left` on a `Right` value"
+            ),
+            Error::UnwrapRightFailed => write!(
+                f,
+                "Called `unwrap_right` on a `Left` value"
+            ),
+            Error::UnwrapNoneFailed => write!(
+                f,
+                "Called `unwrap` on a `None` value"
+            ),
+            Error::JetFailed(name) => write!(
+                f,
+                "Jet `{name}` failed"
+            ),
         }
     }
 }
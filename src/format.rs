@@ -0,0 +1,1093 @@
+//! This module contains the source code formatter.
+//!
+//! The formatter works directly on the PEST parse tree, so it sees every token of the program.
+//! Comments are not part of the grammar, so they are collected from the source text
+//! and put back in place based on their position.
+//!
+//! Formatting is deterministic: the output depends only on the input text and the line width.
+//! Formatting already formatted code returns the same code.
+
+use pest::iterators::Pair;
+
+use crate::error::RichError;
+use std::ops::Range;
+
+use crate::parse::{self, ParseFromStr, Rule};
+
+/// Default maximum width of a line of formatted code.
+pub const DEFAULT_MAX_WIDTH: usize = 100;
+
+/// Number of spaces per level of indentation.
+const INDENT_WIDTH: usize = 4;
+
+/// Format the given SimplicityHL program using the default line width.
+///
+/// ## Errors
+///
+/// The string is not a valid SimplicityHL program.
+pub fn format(source: &str) -> Result<String, RichError> {
+    format_with_width(source, DEFAULT_MAX_WIDTH)
+}
+
+/// Format the given SimplicityHL program.
+///
+/// Lines are broken such that they don't exceed `max_width` characters where possible.
+/// Comments are preserved.
+///
+/// ## Errors
+///
+/// The string is not a valid SimplicityHL program.
+pub fn format_with_width(source: &str, max_width: usize) -> Result<String, RichError> {
+    // Reject everything that the compiler rejects during parsing
+    parse::Program::parse_from_str(source)?;
+    let program = parse::parse_syntax_tree(source)?;
+
+    let mut builder = Builder::new(source);
+    let mut docs = Vec::new();
+    builder.program(program, &mut docs);
+    Ok(render(&docs, max_width))
+}
+
+/// Layout document that is rendered into text.
+///
+/// Groups are printed on a single line if they fit.
+/// Otherwise, all line breaks that directly belong to the group are turned into newlines.
+#[derive(Clone, Debug)]
+enum Doc {
+    /// Verbatim text without newlines.
+    Text(String),
+    /// Space on a flat line, newline otherwise.
+    Line,
+    /// Nothing on a flat line, newline otherwise.
+    SoftLine,
+    /// Unconditional newline.
+    HardLine,
+    /// Text that is printed only on broken lines.
+    IfBreak(&'static str),
+    /// Force the enclosing group to break.
+    BreakParent,
+    /// Increase the indentation of the contained documents.
+    Indent(Vec<Doc>),
+    /// Print the contained documents on one line if possible.
+    Group(Vec<Doc>),
+}
+
+impl Doc {
+    fn text<S: Into<String>>(s: S) -> Self {
+        Self::Text(s.into())
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+fn render(docs: &[Doc], max_width: usize) -> String {
+    let mut output = String::new();
+    let mut column = 0;
+    let mut pending_indent = None;
+    let mut stack: Vec<(usize, Mode, &Doc)> =
+        docs.iter().rev().map(|doc| (0, Mode::Break, doc)).collect();
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(s) => {
+                if s.is_empty() {
+                    continue;
+                }
+                if let Some(indent) = pending_indent.take() {
+                    output.extend(std::iter::repeat(' ').take(indent));
+                }
+                output.push_str(s);
+                column += s.chars().count();
+            }
+            Doc::IfBreak(s) => {
+                if mode == Mode::Break {
+                    output.push_str(s);
+                    column += s.chars().count();
+                }
+            }
+            Doc::Line if mode == Mode::Flat => {
+                output.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                let trimmed_len = output.trim_end_matches(' ').len();
+                output.truncate(trimmed_len);
+                output.push('\n');
+                pending_indent = Some(indent);
+                column = indent;
+            }
+            Doc::BreakParent => {}
+            Doc::Indent(docs) => {
+                let indent = indent + INDENT_WIDTH;
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+            Doc::Group(docs) => {
+                let mode = match mode {
+                    Mode::Flat => Mode::Flat,
+                    Mode::Break => match fits(max_width.saturating_sub(column), docs, &stack) {
+                        true => Mode::Flat,
+                        false => Mode::Break,
+                    },
+                };
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+        }
+    }
+
+    output
+}
+
+/// Check if the given documents fit into the remaining `width` when printed on a flat line.
+///
+/// The documents that follow are taken into account until the next possible line break.
+fn fits(width: usize, docs: &[Doc], rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut remaining = width;
+    let mut next: Vec<(Mode, &Doc)> = docs.iter().rev().map(|doc| (Mode::Flat, doc)).collect();
+    let mut rest = rest.iter().rev();
+
+    loop {
+        let (mode, doc) = match next.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+        let consumed = match doc {
+            Doc::Text(s) => s.chars().count(),
+            Doc::IfBreak(s) => match mode {
+                Mode::Flat => 0,
+                Mode::Break => s.chars().count(),
+            },
+            Doc::Line | Doc::SoftLine if mode == Mode::Break => return true,
+            Doc::Line => 1,
+            Doc::SoftLine => 0,
+            Doc::HardLine => return true,
+            Doc::BreakParent if mode == Mode::Flat => return false,
+            Doc::BreakParent => 0,
+            Doc::Indent(docs) | Doc::Group(docs) => {
+                next.extend(docs.iter().rev().map(|doc| (mode, doc)));
+                0
+            }
+        };
+        match remaining.checked_sub(consumed) {
+            Some(x) => remaining = x,
+            None => return false,
+        }
+    }
+}
+
+/// Convert a parse tree into a layout document.
+struct Builder<'a> {
+    source: &'a str,
+    comments: Vec<Range<usize>>,
+    /// Index of the first comment that has not been emitted.
+    next_comment: usize,
+    /// Byte offset where the most recently emitted line item ended.
+    last_end: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            comments: parse::comments(source),
+            next_comment: 0,
+            last_end: 0,
+        }
+    }
+
+    /// Take the next comment if it starts before the given byte offset.
+    fn next_comment_before(&mut self, pos: usize) -> Option<Range<usize>> {
+        let comment = self.comments.get(self.next_comment)?.clone();
+        if comment.start < pos {
+            self.next_comment += 1;
+            Some(comment)
+        } else {
+            None
+        }
+    }
+
+    /// Emit the text of a comment.
+    ///
+    /// The continuation lines of block comments keep their indentation
+    /// relative to the start of the comment.
+    fn comment_text(&self, comment: &Range<usize>, out: &mut Vec<Doc>) {
+        let text = self.source[comment.start..comment.end].trim_end();
+        let line_start = self.source[..comment.start]
+            .rfind('\n')
+            .map_or(0, |n| n + 1);
+        let column = self.source[line_start..comment.start].chars().count();
+
+        for (index, line) in text.lines().enumerate() {
+            if 0 < index {
+                out.push(Doc::HardLine);
+                let indent = line
+                    .chars()
+                    .take(column)
+                    .take_while(|c| c.is_whitespace())
+                    .map(char::len_utf8)
+                    .sum::<usize>();
+                out.push(Doc::text(line[indent..].trim_end()));
+            } else {
+                out.push(Doc::text(line.trim_end()));
+            }
+        }
+    }
+
+    /// Emit the comments that start before the given byte offset inside an expression.
+    ///
+    /// Line comments force the enclosing group to break.
+    fn inline_comments(&mut self, pos: usize, out: &mut Vec<Doc>) {
+        while let Some(comment) = self.next_comment_before(pos) {
+            self.comment_text(&comment, out);
+            match self.source[comment.start..].starts_with("//") {
+                true => out.extend([Doc::BreakParent, Doc::HardLine]),
+                false => out.push(Doc::text(" ")),
+            }
+        }
+    }
+
+    /// Emit the comments that follow on the same line as the most recently emitted line item.
+    ///
+    /// Only comments that start before the byte offset `end` of the enclosing node are considered.
+    fn trailing_comments(&mut self, end: usize, out: &mut Vec<Doc>) {
+        while let Some(comment) = self.comments.get(self.next_comment).cloned() {
+            let gap = &self.source[self.last_end.min(comment.start)..comment.start];
+            if end <= comment.start || gap.contains('\n') {
+                break;
+            }
+            self.next_comment += 1;
+            out.push(Doc::text(" "));
+            self.comment_text(&comment, out);
+            out.push(Doc::BreakParent);
+            self.last_end = comment.end;
+        }
+    }
+
+    /// Return the start of the block comments that directly precede the given byte offset
+    /// on the same line.
+    ///
+    /// These comments belong to the element that starts at the offset.
+    fn attached_start(&self, pos: usize) -> usize {
+        let mut start = pos;
+        for comment in self.comments[self.next_comment..].iter().rev() {
+            if start <= comment.start {
+                continue;
+            }
+            let gap = &self.source[comment.end..start];
+            if self.source[comment.start..].starts_with("//")
+                || !gap.trim().is_empty()
+                || gap.contains('\n')
+            {
+                break;
+            }
+            start = comment.start;
+        }
+        start
+    }
+
+    /// Emit the comments before the end of an expression list.
+    ///
+    /// The comments follow the last element of the list.
+    fn closing_comments(&mut self, pos: usize, out: &mut Vec<Doc>) {
+        while let Some(comment) = self.next_comment_before(pos) {
+            out.push(Doc::text(" "));
+            self.comment_text(&comment, out);
+            out.push(Doc::BreakParent);
+        }
+    }
+
+    /// Emit a line break before the line item that starts at the given byte offset.
+    ///
+    /// A single empty line is kept if the source contains one or more empty lines.
+    fn line_break(&mut self, start: usize, first: &mut bool, out: &mut Vec<Doc>) {
+        if *first {
+            *first = false;
+            return;
+        }
+        out.push(Doc::HardLine);
+        let gap = &self.source[self.last_end.min(start)..start];
+        if 1 < gap.matches('\n').count() {
+            out.push(Doc::HardLine);
+        }
+    }
+
+    /// Emit the comments before the given byte offset on their own lines.
+    fn own_line_comments(&mut self, pos: usize, first: &mut bool, out: &mut Vec<Doc>) {
+        while let Some(comment) = self.next_comment_before(pos) {
+            self.line_break(comment.start, first, out);
+            self.comment_text(&comment, out);
+            self.last_end = comment.end;
+        }
+    }
+
+    /// Emit a sequence of line items, each on their own line.
+    ///
+    /// Comments before the byte offset `end` are emitted on their own lines.
+    fn lines<'i, I, F>(&mut self, pairs: I, end: usize, out: &mut Vec<Doc>, mut each: F)
+    where
+        I: Iterator<Item = Pair<'i, Rule>>,
+        F: FnMut(&mut Self, Pair<'i, Rule>, &mut Vec<Doc>),
+    {
+        let mut first = true;
+        for pair in pairs {
+            let span = pair.as_span();
+            self.own_line_comments(span.start(), &mut first, out);
+            self.line_break(span.start(), &mut first, out);
+            each(self, pair, out);
+            self.last_end = span.end();
+            self.trailing_comments(end, out);
+        }
+        self.own_line_comments(end, &mut first, out);
+    }
+
+    /// Emit a sequence of line items inside curly braces.
+    fn braced_lines<'i, I, F>(&mut self, pairs: I, end: usize, out: &mut Vec<Doc>, each: F)
+    where
+        I: Iterator<Item = Pair<'i, Rule>>,
+        F: FnMut(&mut Self, Pair<'i, Rule>, &mut Vec<Doc>),
+    {
+        let mut lines = Vec::new();
+        self.lines(pairs, end, &mut lines, each);
+        if lines.is_empty() {
+            out.push(Doc::text("{}"));
+        } else {
+            out.push(Doc::text("{"));
+            lines.insert(0, Doc::HardLine);
+            out.push(Doc::Indent(lines));
+            out.extend([Doc::HardLine, Doc::text("}")]);
+        }
+    }
+
+    /// Emit a comma-separated list of elements inside delimiters.
+    ///
+    /// If the list does not fit on a single line, then each element goes on its own line.
+    fn list<'i, I, F>(
+        &mut self,
+        elements: I,
+        delimiters: (&str, &str),
+        trailing: Trailing,
+        end: usize,
+        out: &mut Vec<Doc>,
+        mut each: F,
+    ) where
+        I: Iterator<Item = Pair<'i, Rule>>,
+        F: FnMut(&mut Self, Pair<'i, Rule>, &mut Vec<Doc>),
+    {
+        let mut inner = vec![Doc::SoftLine];
+        let mut is_empty = true;
+        for (index, element) in elements.enumerate() {
+            if 0 < index {
+                inner.push(Doc::text(","));
+                let start = self.attached_start(element.as_span().start());
+                self.trailing_comments(start, &mut inner);
+                inner.push(Doc::Line);
+            }
+            is_empty = false;
+            let element_end = element.as_span().end();
+            each(self, element, &mut inner);
+            self.last_end = element_end;
+        }
+        if is_empty {
+            out.push(Doc::text(delimiters.0));
+            self.inline_comments(end, out);
+            out.push(Doc::text(delimiters.1));
+            return;
+        }
+        match trailing {
+            Trailing::Never => {}
+            Trailing::IfBreak => inner.push(Doc::IfBreak(",")),
+            Trailing::Always => inner.push(Doc::text(",")),
+        }
+        self.trailing_comments(end, &mut inner);
+        self.closing_comments(end, &mut inner);
+
+        out.push(Doc::Group(vec![
+            Doc::text(delimiters.0),
+            Doc::Indent(inner),
+            Doc::SoftLine,
+            Doc::text(delimiters.1),
+        ]));
+    }
+
+    /// Emit verbatim text of a pair.
+    fn verbatim(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        self.inline_comments(pair.as_span().start(), out);
+        out.push(Doc::text(pair.as_str()));
+    }
+
+    fn program(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        debug_assert_eq!(pair.as_rule(), Rule::program);
+        let items = pair
+            .into_inner()
+            .filter(|pair| pair.as_rule() == Rule::item);
+        self.lines(items, self.source.len(), out, |this, item, out| {
+            this.item(item, out);
+        });
+        if !out.is_empty() {
+            out.push(Doc::HardLine);
+        }
+    }
+
+    fn item(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let pair = pair.into_inner().next().unwrap();
+        match pair.as_rule() {
+            Rule::type_alias => self.type_alias(pair, out),
+            Rule::test_function => self.test_function(pair, out),
+            Rule::function => self.function(pair, out),
+            Rule::constant => {
+                self.constant(pair, out);
+                out.push(Doc::text(";"));
+            }
+            Rule::module => self.module(pair, out),
+            _ => unreachable!("Corrupt grammar"),
+        }
+    }
+
+    fn type_alias(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let mut it = pair.into_inner();
+        let _type_keyword = it.next().unwrap();
+        out.push(Doc::text("type "));
+        self.verbatim(it.next().unwrap(), out);
+        out.push(Doc::text(" = "));
+        self.ty(it.next().unwrap(), out);
+        out.push(Doc::text(";"));
+    }
+
+    fn test_function(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let mut it = pair.into_inner();
+        self.verbatim(it.next().unwrap(), out);
+        out.push(Doc::HardLine);
+        self.function(it.next().unwrap(), out);
+    }
+
+    fn function(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let mut it = pair.into_inner();
+        let _fn_keyword = it.next().unwrap();
+        out.push(Doc::text("fn "));
+        self.verbatim(it.next().unwrap(), out);
+
+        let params = it.next().unwrap();
+        let end = params.as_span().end();
+        self.list(
+            params.into_inner(),
+            ("(", ")"),
+            Trailing::Never,
+            end,
+            out,
+            |this, param, out| {
+                let mut it = param.into_inner();
+                this.verbatim(it.next().unwrap(), out);
+                out.push(Doc::text(": "));
+                this.ty(it.next().unwrap(), out);
+            },
+        );
+
+        let mut body = it.next().unwrap();
+        if body.as_rule() == Rule::function_return {
+            out.push(Doc::text(" -> "));
+            self.ty(body.into_inner().next().unwrap(), out);
+            body = it.next().unwrap();
+        }
+        out.push(Doc::text(" "));
+        self.block_expression(body, out);
+    }
+
+    fn module(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let end = pair.as_span().end();
+        let mut it = pair.into_inner();
+        let _mod_keyword = it.next().unwrap();
+        out.push(Doc::text("mod "));
+        self.verbatim(it.next().unwrap(), out);
+        out.push(Doc::text(" "));
+        self.braced_lines(it, end, out, |this, assignment, out| {
+            this.constant(assignment, out);
+            out.push(Doc::text(";"));
+        });
+    }
+
+    /// Emit `const NAME: TYPE = EXPRESSION` without the trailing semicolon.
+    ///
+    /// Used for constant items and for the assignments inside modules.
+    fn constant(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let mut it = pair.into_inner();
+        let _const_keyword = it.next().unwrap();
+        out.push(Doc::text("const "));
+        self.verbatim(it.next().unwrap(), out);
+        out.push(Doc::text(": "));
+        self.ty(it.next().unwrap(), out);
+        out.push(Doc::text(" = "));
+        self.expression(it.next().unwrap(), out);
+    }
+
+    fn ty(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let end = pair.as_span().end();
+        let pair = match pair.as_rule() {
+            Rule::ty => pair.into_inner().next().unwrap(),
+            _ => pair,
+        };
+        self.inline_comments(pair.as_span().start(), out);
+        match pair.as_rule() {
+            Rule::sum_type => {
+                let mut it = pair.into_inner();
+                out.push(Doc::text("Either<"));
+                self.ty(it.next().unwrap(), out);
+                out.push(Doc::text(", "));
+                self.ty(it.next().unwrap(), out);
+                out.push(Doc::text(">"));
+            }
+            Rule::option_type => {
+                out.push(Doc::text("Option<"));
+                self.ty(pair.into_inner().next().unwrap(), out);
+                out.push(Doc::text(">"));
+            }
+            Rule::tuple_type => {
+                let elements: Vec<_> = pair.into_inner().collect();
+                let is_singleton = elements.len() == 1;
+                out.push(Doc::text("("));
+                for (index, element) in elements.into_iter().enumerate() {
+                    if 0 < index {
+                        out.push(Doc::text(", "));
+                    }
+                    self.ty(element, out);
+                }
+                if is_singleton {
+                    out.push(Doc::text(","));
+                }
+                out.push(Doc::text(")"));
+            }
+            Rule::array_type => {
+                let mut it = pair.into_inner();
+                out.push(Doc::text("["));
+                self.ty(it.next().unwrap(), out);
+                out.push(Doc::text("; "));
+                self.verbatim(it.next().unwrap(), out);
+                out.push(Doc::text("]"));
+            }
+            Rule::list_type => {
+                let mut it = pair.into_inner();
+                out.push(Doc::text("List<"));
+                self.ty(it.next().unwrap(), out);
+                out.push(Doc::text(", "));
+                self.verbatim(it.next().unwrap(), out);
+                out.push(Doc::text(">"));
+            }
+            _ => out.push(Doc::text(pair.as_str())),
+        }
+        self.inline_comments(end, out);
+    }
+
+    fn pattern(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let pair = pair.into_inner().next().unwrap();
+        self.inline_comments(pair.as_span().start(), out);
+        let end = pair.as_span().end();
+        match pair.as_rule() {
+            Rule::tuple_pattern => {
+                let elements: Vec<_> = pair.into_inner().collect();
+                let trailing = match elements.len() {
+                    1 => Trailing::Always,
+                    _ => Trailing::IfBreak,
+                };
+                self.list(
+                    elements.into_iter(),
+                    ("(", ")"),
+                    trailing,
+                    end,
+                    out,
+                    Self::pattern,
+                );
+            }
+            Rule::array_pattern => self.list(
+                pair.into_inner(),
+                ("[", "]"),
+                Trailing::IfBreak,
+                end,
+                out,
+                Self::pattern,
+            ),
+            _ => out.push(Doc::text(pair.as_str())),
+        }
+    }
+
+    fn expression(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        debug_assert_eq!(pair.as_rule(), Rule::expression);
+        let pair = pair.into_inner().next().unwrap();
+        match pair.as_rule() {
+            Rule::block_expression => self.block_expression(pair, out),
+            Rule::single_expression => self.single_expression(pair, out),
+            _ => unreachable!("Corrupt grammar"),
+        }
+    }
+
+    fn block_expression(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        self.inline_comments(pair.as_span().start(), out);
+        let end = pair.as_span().end();
+        self.braced_lines(pair.into_inner(), end, out, |this, pair, out| {
+            match pair.as_rule() {
+                Rule::statement => {
+                    let pair = pair.into_inner().next().unwrap();
+                    match pair.as_rule() {
+                        Rule::assignment => this.assignment(pair, out),
+                        _ => this.expression(pair, out),
+                    }
+                    out.push(Doc::text(";"));
+                }
+                _ => this.expression(pair, out),
+            }
+        });
+    }
+
+    fn assignment(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let mut it = pair.into_inner();
+        let _let_keyword = it.next().unwrap();
+        out.push(Doc::text("let "));
+        self.pattern(it.next().unwrap(), out);
+        out.push(Doc::text(": "));
+        self.ty(it.next().unwrap(), out);
+        out.push(Doc::text(" = "));
+        self.expression(it.next().unwrap(), out);
+    }
+
+    fn single_expression(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        self.inline_comments(pair.as_span().start(), out);
+        let outer_end = pair.as_span().end();
+        let pair = pair.into_inner().next().unwrap();
+        let end = pair.as_span().end();
+        match pair.as_rule() {
+            Rule::expression => {
+                out.push(Doc::text("("));
+                self.expression(pair, out);
+                self.inline_comments(outer_end, out);
+                out.push(Doc::text(")"));
+            }
+            Rule::left_expr | Rule::right_expr | Rule::some_expr => {
+                let constructor = match pair.as_rule() {
+                    Rule::left_expr => "Left(",
+                    Rule::right_expr => "Right(",
+                    _ => "Some(",
+                };
+                out.push(Doc::text(constructor));
+                self.expression(pair.into_inner().next().unwrap(), out);
+                self.inline_comments(end, out);
+                out.push(Doc::text(")"));
+            }
+            Rule::call_expr => {
+                let mut it = pair.into_inner();
+                self.call_name(it.next().unwrap(), out);
+                let args = it.next().unwrap();
+                let end = args.as_span().end();
+                self.list(
+                    args.into_inner(),
+                    ("(", ")"),
+                    Trailing::Never,
+                    end,
+                    out,
+                    Self::expression,
+                );
+            }
+            Rule::match_expr => self.match_expression(pair, out),
+            Rule::for_expr => self.for_expression(pair, out),
+            Rule::tuple_expr => {
+                let elements: Vec<_> = pair.into_inner().collect();
+                let trailing = match elements.len() {
+                    1 => Trailing::Always,
+                    _ => Trailing::IfBreak,
+                };
+                self.list(
+                    elements.into_iter(),
+                    ("(", ")"),
+                    trailing,
+                    end,
+                    out,
+                    Self::expression,
+                );
+            }
+            Rule::array_expr => self.list(
+                pair.into_inner(),
+                ("[", "]"),
+                Trailing::IfBreak,
+                end,
+                out,
+                Self::expression,
+            ),
+            Rule::list_expr => self.list(
+                pair.into_inner(),
+                ("list![", "]"),
+                Trailing::IfBreak,
+                end,
+                out,
+                Self::expression,
+            ),
+            _ => out.push(Doc::text(pair.as_str())),
+        }
+    }
+
+    fn call_name(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let pair = pair.into_inner().next().unwrap();
+        self.inline_comments(pair.as_span().start(), out);
+        let end = pair.as_span().end();
+        match pair.as_rule() {
+            Rule::unwrap_left | Rule::unwrap_right | Rule::is_none => {
+                let name = match pair.as_rule() {
+                    Rule::unwrap_left => "unwrap_left::<",
+                    Rule::unwrap_right => "unwrap_right::<",
+                    _ => "is_none::<",
+                };
+                out.push(Doc::text(name));
+                self.ty(pair.into_inner().next().unwrap(), out);
+                out.push(Doc::text(">"));
+            }
+            Rule::type_cast => {
+                out.push(Doc::text("<"));
+                self.ty(pair.into_inner().next().unwrap(), out);
+                out.push(Doc::text(">::into"));
+            }
+            Rule::array_fold | Rule::fold => {
+                let name = match pair.as_rule() {
+                    Rule::array_fold => "array_fold::<",
+                    _ => "fold::<",
+                };
+                let mut it = pair.into_inner();
+                out.push(Doc::text(name));
+                self.verbatim(it.next().unwrap(), out);
+                out.push(Doc::text(", "));
+                self.verbatim(it.next().unwrap(), out);
+                out.push(Doc::text(">"));
+            }
+            Rule::for_while => {
+                out.push(Doc::text("for_while::<"));
+                self.verbatim(pair.into_inner().next().unwrap(), out);
+                out.push(Doc::text(">"));
+            }
+            _ => out.push(Doc::text(pair.as_str())),
+        }
+        self.inline_comments(end, out);
+    }
+
+    fn match_expression(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let end = pair.as_span().end();
+        let mut it = pair.into_inner();
+        let _match_keyword = it.next().unwrap();
+        out.push(Doc::text("match "));
+        self.expression(it.next().unwrap(), out);
+        out.push(Doc::text(" "));
+        self.braced_lines(it, end, out, |this, arm, out| {
+            let mut it = arm.into_inner();
+            this.match_pattern(it.next().unwrap(), out);
+            out.push(Doc::text(" => "));
+            let body = it.next().unwrap();
+            match body.as_rule() {
+                Rule::block_expression => this.block_expression(body, out),
+                _ => {
+                    this.single_expression(body, out);
+                    out.push(Doc::text(","));
+                }
+            }
+        });
+    }
+
+    fn for_expression(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let mut it = pair.into_inner().peekable();
+        let _for_keyword = it.next().unwrap();
+        out.push(Doc::text("for "));
+        self.pattern(it.next().unwrap(), out);
+        if let Some(ty) = it.next_if(|pair| pair.as_rule() == Rule::ty) {
+            out.push(Doc::text(": "));
+            self.ty(ty, out);
+        }
+        let _in_keyword = it.next().unwrap();
+        out.push(Doc::text(" in "));
+        let iterable = it.next().unwrap().into_inner().next().unwrap();
+        match iterable.as_rule() {
+            Rule::range => {
+                let mut bounds = iterable.into_inner();
+                self.verbatim(bounds.next().unwrap(), out);
+                out.push(Doc::text(".."));
+                self.verbatim(bounds.next().unwrap(), out);
+            }
+            _ => self.verbatim(iterable, out),
+        }
+        if let Some(accumulator) = it.next_if(|pair| pair.as_rule() == Rule::for_accumulator) {
+            let mut it = accumulator.into_inner();
+            let _mut_keyword = it.next().unwrap();
+            out.push(Doc::text(", mut "));
+            self.verbatim(it.next().unwrap(), out);
+            out.push(Doc::text(": "));
+            self.ty(it.next().unwrap(), out);
+            out.push(Doc::text(" = "));
+            self.expression(it.next().unwrap(), out);
+        }
+        out.push(Doc::text(" "));
+        self.block_expression(it.next().unwrap(), out);
+    }
+
+    fn match_pattern(&mut self, pair: Pair<Rule>, out: &mut Vec<Doc>) {
+        let pair = pair.into_inner().next().unwrap();
+        self.inline_comments(pair.as_span().start(), out);
+        let end = pair.as_span().end();
+        let constructor = match pair.as_rule() {
+            Rule::left_pattern => "Left(",
+            Rule::right_pattern => "Right(",
+            Rule::some_pattern => "Some(",
+            _ => {
+                out.push(Doc::text(pair.as_str()));
+                return;
+            }
+        };
+        let mut it = pair.into_inner();
+        out.push(Doc::text(constructor));
+        self.verbatim(it.next().unwrap(), out);
+        out.push(Doc::text(": "));
+        self.ty(it.next().unwrap(), out);
+        self.inline_comments(end, out);
+        out.push(Doc::text(")"));
+    }
+}
+
+/// Whether a list has a trailing comma after its last element.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Trailing {
+    /// Never add a trailing comma.
+    Never,
+    /// Add a trailing comma if the list is broken across lines.
+    IfBreak,
+    /// Always add a trailing comma (singleton tuples).
+    Always,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_formats_to(input: &str, expected: &str) {
+        let formatted = format(input).unwrap();
+        assert_eq!(expected, formatted);
+        assert_eq!(formatted, format(&formatted).unwrap(), "Not idempotent");
+    }
+
+    #[test]
+    fn format_whitespace() {
+        assert_formats_to(
+            "fn   main ( ) {let  (a,b) :(u8,u8)=( 1 , 2 ) ;assert!( jet::eq_8(a,b) )}",
+            "fn main() {
+    let (a, b): (u8, u8) = (1, 2);
+    assert!(jet::eq_8(a, b))
+}
+",
+        );
+    }
+
+    #[test]
+    fn format_types() {
+        assert_formats_to(
+            "type  T=( u8 , ) ;type U=Either<[u8;2] , List<Option<u1 >,4> >;fn main(){}",
+            "type T = (u8,);
+type U = Either<[u8; 2], List<Option<u1>, 4>>;
+fn main() {}
+",
+        );
+    }
+
+    #[test]
+    fn format_match() {
+        assert_formats_to(
+            "fn main() { match witness::A { Left(x: u8) => { dbg!(x); }, Right(y: u8) => dbg!(y), } }",
+            "fn main() {
+    match witness::A {
+        Left(x: u8) => {
+            dbg!(x);
+        }
+        Right(y: u8) => dbg!(y),
+    }
+}
+",
+        );
+    }
+
+    #[test]
+    fn format_for_loop() {
+        assert_formats_to(
+            "fn main() { let s: u8 = for x in xs ,mut acc:u8=0{ match jet::eq_8(x,0) { true => break, false => acc, } } ; for i : u16 in 0 .. N {} }",
+            "fn main() {
+    let s: u8 = for x in xs, mut acc: u8 = 0 {
+        match jet::eq_8(x, 0) {
+            true => break,
+            false => acc,
+        }
+    };
+    for i: u16 in 0..N {}
+}
+",
+        );
+    }
+
+    #[test]
+    fn format_test_function() {
+        assert_formats_to(
+            "fn main() {}\n#[test]   fn  works(){assert!(true)}",
+            "fn main() {}
+#[test]
+fn works() {
+    assert!(true)
+}
+",
+        );
+    }
+
+    #[test]
+    fn format_module() {
+        assert_formats_to(
+            "mod witness{const A:u8=1;const B : bool = false;} mod param {} fn main() {}",
+            "mod witness {
+    const A: u8 = 1;
+    const B: bool = false;
+}
+mod param {}
+fn main() {}
+",
+        );
+    }
+
+    #[test]
+    fn format_constant() {
+        assert_formats_to(
+            "const  N:u8=jet::complement_8( 252 ) ;type T=[u8;N];fn main(){}",
+            "const N: u8 = jet::complement_8(252);
+type T = [u8; N];
+fn main() {}
+",
+        );
+    }
+
+    #[test]
+    fn format_comments() {
+        assert_formats_to(
+            "/*
+ * Header
+ */
+// Leading
+
+fn main() { // after brace
+    let a: u8 = 1; // trailing
+    /* block */ let b: u8 = 2;
+    let c: [u8; 2] = [
+        a, // first
+        b
+    ];
+    assert!(jet::eq_8(a, b)); // after call
+
+    // end of block
+}
+// end of file
+",
+            "/*
+ * Header
+ */
+// Leading
+
+fn main() {
+    // after brace
+    let a: u8 = 1; // trailing
+    /* block */
+    let b: u8 = 2;
+    let c: [u8; 2] = [
+        a, // first
+        b,
+    ];
+    assert!(jet::eq_8(a, b)); // after call
+
+    // end of block
+}
+// end of file
+",
+        );
+    }
+
+    #[test]
+    fn format_list_comments() {
+        let input = "fn main() {
+    let (a, b): (u8, u8) = ( /* a */ 1, 2 // two
+    );
+    let (_, c): (bool, u8) = jet::add_8(a, /* b */ b // bee
+    );
+}
+";
+        let expected = "fn main() {
+    let (a, b): (u8, u8) = (
+        /* a */ 1,
+        2, // two
+    );
+    let (_, c): (bool, u8) = jet::add_8(
+        a,
+        /* b */ b // bee
+    );
+}
+";
+        assert_formats_to(input, expected);
+        assert_formats_to(expected, expected);
+    }
+
+    #[test]
+    fn format_line_width() {
+        let input = "fn main() { let x: u32 = f(aaaaaaaa, bbbbbbbb, cccccccc); }";
+        let expected = "fn main() {
+    let x: u32 = f(
+        aaaaaaaa,
+        bbbbbbbb,
+        cccccccc
+    );
+}
+";
+        let formatted = format_with_width(input, 40).unwrap();
+        assert_eq!(expected, formatted);
+        assert_eq!(formatted, format_with_width(&formatted, 40).unwrap());
+        assert_eq!(
+            "fn main() {\n    let x: u32 = f(aaaaaaaa, bbbbbbbb, cccccccc);\n}\n",
+            format(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn format_invalid_program() {
+        assert!(format("fn main() {").is_err());
+    }
+
+    #[test]
+    fn format_examples() {
+        for entry in std::fs::read_dir("./examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(true, |ext| ext != "simf") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            let formatted = match format(&source) {
+                Ok(x) => x,
+                Err(error) => panic!("{}: {error}", path.display()),
+            };
+            assert_eq!(
+                formatted,
+                format(&formatted).unwrap(),
+                "{}: formatting is not idempotent",
+                path.display()
+            );
+            assert_eq!(
+                parse::Program::parse_from_str(&source).unwrap(),
+                parse::Program::parse_from_str(&formatted).unwrap(),
+                "{}: formatting changed the program",
+                path.display()
+            );
+            assert_eq!(
+                parse::comments(&source).len(),
+                parse::comments(&formatted).len(),
+                "{}: formatting lost comments",
+                path.display()
+            );
+        }
+    }
+}
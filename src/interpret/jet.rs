@@ -0,0 +1,851 @@
+//! Reference implementations of jets
+//!
+//! Jets without a reference implementation are executed on the Bit Machine
+//! (see [`has_reference`]). These fallback jets are:
+//!
+//! - Bit manipulation: `ch_*`, `maj_*`, `xor_xor_*`, `full_left_shift_*`, `full_right_shift_*`,
+//!   `leftmost_*`, `rightmost_*`, `left_extend_*`, `right_extend_*`, `left_pad_high_*`,
+//!   `left_pad_low_*`, `right_pad_high_*`, `right_pad_low_*`
+//! - Arithmetic: `div_mod_128_64`
+//! - Hash functions: `sha_256_block`
+//! - Elliptic curves and signatures: `fe_*`, `scalar_*`, `ge_*`, `gej_*`, `point_verify_1`,
+//!   `decompress`, `generate`, `scale`, `linear_combination_1`, `linear_verify_1`,
+//!   `hash_to_curve`, `swu`, `bip_0340_verify`, `check_sig_verify`, `verify`
+//! - Bitcoin: `parse_lock`, `parse_sequence`, `tapdata_init`, `build_tapbranch`,
+//!   `build_tapleaf_simplicity`, `build_taptweak`
+//! - Transaction introspection: `current_*` except `current_index` and `current_sequence`,
+//!   `input_*` except `input_sequence`, `output_*`, `issuance*`, `reissuance_*`, `calculate_*`,
+//!   `*_hash`, `new_issuance_contract`, `lbtc_asset`, `script_cmr`, `internal_key`,
+//!   `tapleaf_version`, `tappath`, `total_fee`, `transaction_id`
+
+use std::sync::Arc;
+
+use simplicity::hashes::{sha256, Hash, HashEngine};
+use simplicity::jet::elements::ElementsEnv;
+use simplicity::jet::Elements;
+use simplicity::node::{CoreConstructible as _, JetConstructible as _};
+use simplicity::{types, BitMachine, ConstructNode};
+
+use crate::num::{NonZeroPow2Usize, U256};
+use crate::types::{ResolvedType, TypeDeconstructible, UIntType};
+use crate::value::{StructuralValue, UIntValue, ValueConstructible, ValueInner};
+use crate::{elements, Value};
+
+/// A jet failed, for instance because the checked condition did not hold.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct JetFailed;
+
+/// Execute the `jet` on the given `args`.
+///
+/// The output is a value of the given `target` type.
+pub fn execute(
+    jet: Elements,
+    args: &[Value],
+    target: &ResolvedType,
+    env: &ElementsEnv<Arc<elements::Transaction>>,
+) -> Result<Value, JetFailed> {
+    match execute_reference(jet, args, target, env) {
+        Some(output) => output,
+        None => execute_on_bit_machine(jet, args, target, env),
+    }
+}
+
+/// Check if the `jet` has a reference implementation.
+///
+/// Jets without a reference implementation are executed on the Bit Machine.
+pub fn has_reference(jet: Elements) -> bool {
+    use Elements::*;
+
+    matches!(
+        jet,
+        One8 | One16
+            | One32
+            | One64
+            | Low1
+            | Low8
+            | Low16
+            | Low32
+            | Low64
+            | High1
+            | High8
+            | High16
+            | High32
+            | High64
+            | Add8
+            | Add16
+            | Add32
+            | Add64
+            | FullAdd8
+            | FullAdd16
+            | FullAdd32
+            | FullAdd64
+            | Subtract8
+            | Subtract16
+            | Subtract32
+            | Subtract64
+            | FullSubtract8
+            | FullSubtract16
+            | FullSubtract32
+            | FullSubtract64
+            | Multiply8
+            | Multiply16
+            | Multiply32
+            | Multiply64
+            | FullMultiply8
+            | FullMultiply16
+            | FullMultiply32
+            | FullMultiply64
+            | Increment8
+            | Increment16
+            | Increment32
+            | Increment64
+            | FullIncrement8
+            | FullIncrement16
+            | FullIncrement32
+            | FullIncrement64
+            | Decrement8
+            | Decrement16
+            | Decrement32
+            | Decrement64
+            | FullDecrement8
+            | FullDecrement16
+            | FullDecrement32
+            | FullDecrement64
+            | Negate8
+            | Negate16
+            | Negate32
+            | Negate64
+            | IsZero8
+            | IsZero16
+            | IsZero32
+            | IsZero64
+            | IsOne8
+            | IsOne16
+            | IsOne32
+            | IsOne64
+            | Le8
+            | Le16
+            | Le32
+            | Le64
+            | Lt8
+            | Lt16
+            | Lt32
+            | Lt64
+            | Min8
+            | Min16
+            | Min32
+            | Min64
+            | Max8
+            | Max16
+            | Max32
+            | Max64
+            | Median8
+            | Median16
+            | Median32
+            | Median64
+            | Divide8
+            | Divide16
+            | Divide32
+            | Divide64
+            | Modulo8
+            | Modulo16
+            | Modulo32
+            | Modulo64
+            | DivMod8
+            | DivMod16
+            | DivMod32
+            | DivMod64
+            | Divides8
+            | Divides16
+            | Divides32
+            | Divides64
+            | Eq1
+            | Eq8
+            | Eq16
+            | Eq32
+            | Eq64
+            | Eq256
+            | Complement1
+            | Complement8
+            | Complement16
+            | Complement32
+            | Complement64
+            | And1
+            | And8
+            | And16
+            | And32
+            | And64
+            | Or1
+            | Or8
+            | Or16
+            | Or32
+            | Or64
+            | Xor1
+            | Xor8
+            | Xor16
+            | Xor32
+            | Xor64
+            | Some1
+            | Some8
+            | Some16
+            | Some32
+            | Some64
+            | All8
+            | All16
+            | All32
+            | All64
+            | LeftShift8
+            | LeftShift16
+            | LeftShift32
+            | LeftShift64
+            | RightShift8
+            | RightShift16
+            | RightShift32
+            | RightShift64
+            | LeftShiftWith8
+            | LeftShiftWith16
+            | LeftShiftWith32
+            | LeftShiftWith64
+            | RightShiftWith8
+            | RightShiftWith16
+            | RightShiftWith32
+            | RightShiftWith64
+            | LeftRotate8
+            | LeftRotate16
+            | LeftRotate32
+            | LeftRotate64
+            | RightRotate8
+            | RightRotate16
+            | RightRotate32
+            | RightRotate64
+            | Sha256Iv
+            | Sha256Ctx8Init
+            | Sha256Ctx8Add1
+            | Sha256Ctx8Add2
+            | Sha256Ctx8Add4
+            | Sha256Ctx8Add8
+            | Sha256Ctx8Add16
+            | Sha256Ctx8Add32
+            | Sha256Ctx8Add64
+            | Sha256Ctx8Add128
+            | Sha256Ctx8Add256
+            | Sha256Ctx8Add512
+            | Sha256Ctx8AddBuffer511
+            | Sha256Ctx8Finalize
+            | Version
+            | LockTime
+            | CurrentIndex
+            | NumInputs
+            | NumOutputs
+            | CurrentSequence
+            | InputSequence
+            | TxIsFinal
+            | TxLockHeight
+            | TxLockTime
+            | TxLockDistance
+            | TxLockDuration
+            | CheckLockHeight
+            | CheckLockTime
+            | CheckLockDistance
+            | CheckLockDuration
+    )
+}
+
+/// Execute the `jet` using its reference implementation.
+///
+/// Return `None` if there is no reference implementation.
+fn execute_reference(
+    jet: Elements,
+    args: &[Value],
+    target: &ResolvedType,
+    env: &ElementsEnv<Arc<elements::Transaction>>,
+) -> Option<Result<Value, JetFailed>> {
+    use Elements::*;
+
+    let output = match jet {
+        /*
+         * Arithmetic
+         */
+        One8 | One16 | One32 | One64 => uint(1, target.as_integer()?),
+        Low1 | Low8 | Low16 | Low32 | Low64 => uint(0, target.as_integer()?),
+        High1 | High8 | High16 | High32 | High64 => uint(u128::MAX, target.as_integer()?),
+        Add8 | Add16 | Add32 | Add64 => {
+            let (x, y, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            with_carry(x + y > max(ty), x + y, ty)
+        }
+        FullAdd8 | FullAdd16 | FullAdd32 | FullAdd64 => {
+            let (carry, x, y, ty) = (
+                int(&args[0])?,
+                int(&args[1])?,
+                int(&args[2])?,
+                int_type(&args[2])?,
+            );
+            with_carry(carry + x + y > max(ty), carry + x + y, ty)
+        }
+        Subtract8 | Subtract16 | Subtract32 | Subtract64 => {
+            let (x, y, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            with_carry(x < y, x.wrapping_sub(y), ty)
+        }
+        FullSubtract8 | FullSubtract16 | FullSubtract32 | FullSubtract64 => {
+            let (borrow, x, y, ty) = (
+                int(&args[0])?,
+                int(&args[1])?,
+                int(&args[2])?,
+                int_type(&args[2])?,
+            );
+            with_carry(x < y + borrow, x.wrapping_sub(y + borrow), ty)
+        }
+        Multiply8 | Multiply16 | Multiply32 | Multiply64 => {
+            let (x, y) = (int(&args[0])?, int(&args[1])?);
+            uint(x * y, target.as_integer()?)
+        }
+        FullMultiply8 | FullMultiply16 | FullMultiply32 | FullMultiply64 => {
+            let (x, y) = int_pair(&args[0])?;
+            let (summand1, summand2) = int_pair(&args[1])?;
+            uint(x * y + summand1 + summand2, target.as_integer()?)
+        }
+        Increment8 | Increment16 | Increment32 | Increment64 => {
+            let (x, ty) = (int(&args[0])?, int_type(&args[0])?);
+            with_carry(x == max(ty), x + 1, ty)
+        }
+        FullIncrement8 | FullIncrement16 | FullIncrement32 | FullIncrement64 => {
+            let (carry, x, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            with_carry(x + carry > max(ty), x + carry, ty)
+        }
+        Decrement8 | Decrement16 | Decrement32 | Decrement64 => {
+            let (x, ty) = (int(&args[0])?, int_type(&args[0])?);
+            with_carry(x == 0, x.wrapping_sub(1), ty)
+        }
+        FullDecrement8 | FullDecrement16 | FullDecrement32 | FullDecrement64 => {
+            let (borrow, x, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            with_carry(x < borrow, x.wrapping_sub(borrow), ty)
+        }
+        Negate8 | Negate16 | Negate32 | Negate64 => {
+            let (x, ty) = (int(&args[0])?, int_type(&args[0])?);
+            with_carry(x != 0, x.wrapping_neg(), ty)
+        }
+        IsZero8 | IsZero16 | IsZero32 | IsZero64 => Value::from(int(&args[0])? == 0),
+        IsOne8 | IsOne16 | IsOne32 | IsOne64 => Value::from(int(&args[0])? == 1),
+        Le8 | Le16 | Le32 | Le64 => Value::from(int(&args[0])? <= int(&args[1])?),
+        Lt8 | Lt16 | Lt32 | Lt64 => Value::from(int(&args[0])? < int(&args[1])?),
+        Min8 | Min16 | Min32 | Min64 => {
+            let (x, y, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            uint(x.min(y), ty)
+        }
+        Max8 | Max16 | Max32 | Max64 => {
+            let (x, y, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            uint(x.max(y), ty)
+        }
+        Median8 | Median16 | Median32 | Median64 => {
+            let mut sorted = [int(&args[0])?, int(&args[1])?, int(&args[2])?];
+            sorted.sort_unstable();
+            uint(sorted[1], int_type(&args[0])?)
+        }
+        Divide8 | Divide16 | Divide32 | Divide64 => {
+            let (x, y, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            uint(x.checked_div(y).unwrap_or(0), ty)
+        }
+        Modulo8 | Modulo16 | Modulo32 | Modulo64 => {
+            let (x, y, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            uint(x.checked_rem(y).unwrap_or(x), ty)
+        }
+        DivMod8 | DivMod16 | DivMod32 | DivMod64 => {
+            let (x, y, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            Value::tuple([
+                uint(x.checked_div(y).unwrap_or(0), ty),
+                uint(x.checked_rem(y).unwrap_or(x), ty),
+            ])
+        }
+        Divides8 | Divides16 | Divides32 | Divides64 => {
+            let (x, y) = (int(&args[0])?, int(&args[1])?);
+            Value::from(y.checked_rem(x).unwrap_or(y) == 0)
+        }
+        /*
+         * Bitwise operations
+         */
+        Eq1 | Eq8 | Eq16 | Eq32 | Eq64 | Eq256 => Value::from(args[0] == args[1]),
+        Complement1 | Complement8 | Complement16 | Complement32 | Complement64 => {
+            let (x, ty) = (int(&args[0])?, int_type(&args[0])?);
+            uint(!x, ty)
+        }
+        And1 | And8 | And16 | And32 | And64 => {
+            let (x, y, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            uint(x & y, ty)
+        }
+        Or1 | Or8 | Or16 | Or32 | Or64 => {
+            let (x, y, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            uint(x | y, ty)
+        }
+        Xor1 | Xor8 | Xor16 | Xor32 | Xor64 => {
+            let (x, y, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            uint(x ^ y, ty)
+        }
+        Some1 | Some8 | Some16 | Some32 | Some64 => Value::from(int(&args[0])? != 0),
+        All8 | All16 | All32 | All64 => {
+            let (x, ty) = (int(&args[0])?, int_type(&args[0])?);
+            Value::from(x == max(ty))
+        }
+        LeftShift8 | LeftShift16 | LeftShift32 | LeftShift64 => {
+            let (amount, x, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            uint(shift_left(x, amount, 0, ty), ty)
+        }
+        RightShift8 | RightShift16 | RightShift32 | RightShift64 => {
+            let (amount, x, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            uint(shift_right(x, amount, 0, ty), ty)
+        }
+        LeftShiftWith8 | LeftShiftWith16 | LeftShiftWith32 | LeftShiftWith64 => {
+            let (bit, amount, x, ty) = (
+                int(&args[0])?,
+                int(&args[1])?,
+                int(&args[2])?,
+                int_type(&args[2])?,
+            );
+            uint(shift_left(x, amount, bit, ty), ty)
+        }
+        RightShiftWith8 | RightShiftWith16 | RightShiftWith32 | RightShiftWith64 => {
+            let (bit, amount, x, ty) = (
+                int(&args[0])?,
+                int(&args[1])?,
+                int(&args[2])?,
+                int_type(&args[2])?,
+            );
+            uint(shift_right(x, amount, bit, ty), ty)
+        }
+        LeftRotate8 | LeftRotate16 | LeftRotate32 | LeftRotate64 => {
+            let (amount, x, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            let amount = amount % bit_width(ty);
+            uint((x << amount) | (x >> (bit_width(ty) - amount)), ty)
+        }
+        RightRotate8 | RightRotate16 | RightRotate32 | RightRotate64 => {
+            let (amount, x, ty) = (int(&args[0])?, int(&args[1])?, int_type(&args[1])?);
+            let amount = amount % bit_width(ty);
+            uint((x >> amount) | (x << (bit_width(ty) - amount)), ty)
+        }
+        /*
+         * Hash functions
+         */
+        Sha256Iv => u256(sha256::HashEngine::default().midstate().to_byte_array()),
+        Sha256Ctx8Init => Sha256Context::default().to_value(),
+        Sha256Ctx8Add1
+        | Sha256Ctx8Add2
+        | Sha256Ctx8Add4
+        | Sha256Ctx8Add8
+        | Sha256Ctx8Add16
+        | Sha256Ctx8Add32
+        | Sha256Ctx8Add64
+        | Sha256Ctx8Add128
+        | Sha256Ctx8Add256
+        | Sha256Ctx8Add512
+        | Sha256Ctx8AddBuffer511 => {
+            let mut context = Sha256Context::from_value(&args[0])?;
+            context.add(&bytes(&args[1])?);
+            context.to_value()
+        }
+        Sha256Ctx8Finalize => u256(Sha256Context::from_value(&args[0])?.finalize()),
+        /*
+         * Transaction introspection
+         */
+        Version => uint(u128::from(env.tx().version), UIntType::U32),
+        LockTime => uint(env.tx().lock_time.to_consensus_u32().into(), UIntType::U32),
+        CurrentIndex => uint(env.ix().into(), UIntType::U32),
+        NumInputs => uint(env.tx().input.len() as u128, UIntType::U32),
+        NumOutputs => uint(env.tx().output.len() as u128, UIntType::U32),
+        CurrentSequence => match env.tx().input.get(env.ix() as usize) {
+            Some(input) => uint(input.sequence.to_consensus_u32().into(), UIntType::U32),
+            None => return Some(Err(JetFailed)),
+        },
+        InputSequence => match env.tx().input.get(int(&args[0])? as usize) {
+            Some(input) => Value::some(uint(
+                input.sequence.to_consensus_u32().into(),
+                UIntType::U32,
+            )),
+            None => Value::none(UIntType::U32.into()),
+        },
+        TxIsFinal => Value::from(Locks::new(env.tx()).is_final),
+        TxLockHeight => uint(Locks::new(env.tx()).height.into(), UIntType::U32),
+        TxLockTime => uint(Locks::new(env.tx()).time.into(), UIntType::U32),
+        TxLockDistance => uint(Locks::new(env.tx()).distance.into(), UIntType::U16),
+        TxLockDuration => uint(Locks::new(env.tx()).duration.into(), UIntType::U16),
+        CheckLockHeight | CheckLockTime | CheckLockDistance | CheckLockDuration => {
+            let locks = Locks::new(env.tx());
+            let lock = match jet {
+                CheckLockHeight => locks.height,
+                CheckLockTime => locks.time,
+                CheckLockDistance => locks.distance.into(),
+                _ => locks.duration.into(),
+            };
+            match int(&args[0])? <= u128::from(lock) {
+                true => Value::unit(),
+                false => return Some(Err(JetFailed)),
+            }
+        }
+        _ => return None,
+    };
+    debug_assert!(output.is_of_type(target));
+    Some(Ok(output))
+}
+
+/// Execute the `jet` on the Bit Machine.
+fn execute_on_bit_machine(
+    jet: Elements,
+    args: &[Value],
+    target: &ResolvedType,
+    env: &ElementsEnv<Arc<elements::Transaction>>,
+) -> Result<Value, JetFailed> {
+    let input = StructuralValue::from(&Value::tuple(args.iter().cloned()));
+    let program = types::Context::with_context(|ctx| {
+        let scribe = Arc::<ConstructNode<Elements>>::scribe(&ctx, input.as_ref());
+        let jet = Arc::<ConstructNode<Elements>>::jet(&ctx, jet);
+        Arc::<ConstructNode<Elements>>::comp(&scribe, &jet)
+            .expect("arguments are type-checked")
+            .finalize_unpruned()
+            .expect("program has no witness data")
+    });
+    let mut mac =
+        BitMachine::for_program(&program).expect("single jet is within reasonable bounds");
+    let output = mac.exec(&program, env).map_err(|_| JetFailed)?;
+    let output = Value::reconstruct(&StructuralValue::from(output), target)
+        .expect("output is of the target type");
+    Ok(output)
+}
+
+/// Create an unsigned integer of the given type, truncating excess bits.
+pub(super) fn uint(n: u128, ty: UIntType) -> Value {
+    let n = n & max(ty);
+    // Casts are safe because `n` was truncated to the bit width of `ty`
+    let value = match ty {
+        UIntType::U1 => UIntValue::U1(n as u8),
+        UIntType::U2 => UIntValue::U2(n as u8),
+        UIntType::U4 => UIntValue::U4(n as u8),
+        UIntType::U8 => UIntValue::U8(n as u8),
+        UIntType::U16 => UIntValue::U16(n as u16),
+        UIntType::U32 => UIntValue::U32(n as u32),
+        UIntType::U64 => UIntValue::U64(n as u64),
+        UIntType::U128 => UIntValue::U128(n),
+        UIntType::U256 => UIntValue::U256(U256::from(n)),
+    };
+    Value::from(value)
+}
+
+fn u256(bytes: [u8; 32]) -> Value {
+    Value::from(UIntValue::U256(U256::from_byte_array(bytes)))
+}
+
+/// Read a boolean or an unsigned integer of at most 128 bits.
+fn int(value: &Value) -> Option<u128> {
+    match value.inner() {
+        ValueInner::Boolean(bit) => Some(u128::from(*bit)),
+        ValueInner::UInt(integer) => match *integer {
+            UIntValue::U1(n) | UIntValue::U2(n) | UIntValue::U4(n) | UIntValue::U8(n) => {
+                Some(n.into())
+            }
+            UIntValue::U16(n) => Some(n.into()),
+            UIntValue::U32(n) => Some(n.into()),
+            UIntValue::U64(n) => Some(n.into()),
+            UIntValue::U128(n) => Some(n),
+            UIntValue::U256(_) => None,
+        },
+        _ => None,
+    }
+}
+
+fn int_pair(value: &Value) -> Option<(u128, u128)> {
+    match value.inner() {
+        ValueInner::Tuple(elements) if elements.len() == 2 => {
+            Some((int(&elements[0])?, int(&elements[1])?))
+        }
+        _ => None,
+    }
+}
+
+fn int_type(value: &Value) -> Option<UIntType> {
+    value.ty().as_integer()
+}
+
+fn bit_width(ty: UIntType) -> u128 {
+    ty.bit_width().get() as u128
+}
+
+/// Return the maximum value of the given integer type, up to 128 bits.
+fn max(ty: UIntType) -> u128 {
+    match bit_width(ty) {
+        128.. => u128::MAX,
+        width => (1 << width) - 1,
+    }
+}
+
+/// Create the pair of a carry bit and an integer.
+fn with_carry(carry: bool, n: u128, ty: UIntType) -> Value {
+    Value::tuple([Value::from(carry), uint(n, ty)])
+}
+
+/// Shift `x` to the left by `amount` bits and fill the vacated bits with `bit`.
+fn shift_left(x: u128, amount: u128, bit: u128, ty: UIntType) -> u128 {
+    let fill = if bit == 1 { max(ty) } else { 0 };
+    match amount < bit_width(ty) {
+        true => (x << amount) | (fill >> (bit_width(ty) - amount)),
+        false => fill,
+    }
+}
+
+/// Shift `x` to the right by `amount` bits and fill the vacated bits with `bit`.
+fn shift_right(x: u128, amount: u128, bit: u128, ty: UIntType) -> u128 {
+    let fill = if bit == 1 { max(ty) } else { 0 };
+    match amount < bit_width(ty) {
+        true => (x >> amount) | (fill << (bit_width(ty) - amount)),
+        false => fill,
+    }
+}
+
+/// Read the bytes of an integer or of an array or list of bytes, in Big Endian order.
+fn bytes(value: &Value) -> Option<Vec<u8>> {
+    match value.inner() {
+        ValueInner::UInt(UIntValue::U8(n)) => Some(vec![*n]),
+        ValueInner::UInt(UIntValue::U16(n)) => Some(n.to_be_bytes().to_vec()),
+        ValueInner::UInt(UIntValue::U32(n)) => Some(n.to_be_bytes().to_vec()),
+        ValueInner::UInt(UIntValue::U64(n)) => Some(n.to_be_bytes().to_vec()),
+        ValueInner::UInt(UIntValue::U128(n)) => Some(n.to_be_bytes().to_vec()),
+        ValueInner::UInt(UIntValue::U256(n)) => Some(n.to_byte_array().to_vec()),
+        ValueInner::Array(elements) | ValueInner::List(elements, _) => elements
+            .iter()
+            .map(|element| match element.inner() {
+                ValueInner::UInt(UIntValue::U8(n)) => Some(*n),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+/// Context of a SHA-256 hash engine, as the value of type `Ctx8`.
+///
+/// The context consists of the buffer of less than 64 bytes,
+/// the number of compressed blocks and the midstate.
+struct Sha256Context {
+    buffer: Vec<u8>,
+    blocks: u64,
+    midstate: sha256::Midstate,
+}
+
+impl Default for Sha256Context {
+    fn default() -> Self {
+        Self {
+            buffer: Vec::new(),
+            blocks: 0,
+            midstate: sha256::HashEngine::default().midstate(),
+        }
+    }
+}
+
+impl Sha256Context {
+    fn from_value(value: &Value) -> Option<Self> {
+        let ValueInner::Tuple(context) = value.inner() else {
+            return None;
+        };
+        let ValueInner::Tuple(state) = context.get(1)?.inner() else {
+            return None;
+        };
+        let (ValueInner::UInt(UIntValue::U64(blocks)), ValueInner::UInt(UIntValue::U256(midstate))) =
+            (state.first()?.inner(), state.get(1)?.inner())
+        else {
+            return None;
+        };
+        Some(Self {
+            buffer: bytes(context.first()?)?,
+            blocks: *blocks,
+            midstate: sha256::Midstate::from_byte_array(midstate.to_byte_array()),
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        let buffer = self
+            .buffer
+            .iter()
+            .map(|byte| uint((*byte).into(), UIntType::U8));
+        let bound = NonZeroPow2Usize::new(64).unwrap();
+        Value::tuple([
+            Value::list(buffer, UIntType::U8.into(), bound),
+            Value::tuple([
+                uint(self.blocks.into(), UIntType::U64),
+                u256(self.midstate.to_byte_array()),
+            ]),
+        ])
+    }
+
+    fn engine(&self) -> sha256::HashEngine {
+        // Cast is safe because the number of compressed bytes fits into the address space
+        sha256::HashEngine::from_midstate(self.midstate, self.blocks as usize * 64)
+    }
+
+    fn add(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+        let compressed_len = self.buffer.len() / 64 * 64;
+        let mut engine = self.engine();
+        engine.input(&self.buffer[..compressed_len]);
+        self.midstate = engine.midstate();
+        self.blocks += (compressed_len / 64) as u64;
+        self.buffer.drain(..compressed_len);
+    }
+
+    fn finalize(&self) -> [u8; 32] {
+        let mut engine = self.engine();
+        engine.input(&self.buffer);
+        sha256::Hash::from_engine(engine).to_byte_array()
+    }
+}
+
+/// Time locks of a transaction.
+struct Locks {
+    is_final: bool,
+    height: u32,
+    time: u32,
+    distance: u16,
+    duration: u16,
+}
+
+impl Locks {
+    fn new(tx: &elements::Transaction) -> Self {
+        let is_final = tx.input.iter().all(|input| input.sequence.is_final());
+        let lock_time = tx.lock_time.to_consensus_u32();
+        let (height, time) = match (is_final, lock_time < 500_000_000) {
+            (true, _) => (0, 0),
+            (false, true) => (lock_time, 0),
+            (false, false) => (0, lock_time),
+        };
+        let (mut distance, mut duration) = (0, 0);
+        if 2 <= tx.version {
+            for input in &tx.input {
+                let sequence = input.sequence.to_consensus_u32();
+                if sequence & (1 << 31) != 0 {
+                    continue;
+                }
+                // Cast is safe because the value is masked to 16 bits
+                let value = (sequence & 0xffff) as u16;
+                match sequence & (1 << 22) != 0 {
+                    true => duration = duration.max(value),
+                    false => distance = distance.max(value),
+                }
+            }
+        }
+        Self {
+            is_final,
+            height,
+            time,
+            distance,
+            duration,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::dummy_env;
+    use crate::jet::{source_type, target_type};
+    use crate::types::TypeInner;
+
+    /// Sample values of the given type, including edge cases.
+    ///
+    /// Return `None` if the type is not supported.
+    fn samples(ty: &ResolvedType) -> Option<Vec<Value>> {
+        match ty.as_inner() {
+            TypeInner::Boolean => Some(vec![Value::from(false), Value::from(true)]),
+            TypeInner::UInt(UIntType::U256) => Some(vec![
+                u256([0; 32]),
+                u256([0x5a; 32]),
+                Value::from(UIntValue::U256(U256::MAX)),
+            ]),
+            TypeInner::UInt(ty) => {
+                let values = [0, 1, 0x5a5a_5a5a_5a5a_5a5a, max(*ty) - 1, max(*ty)];
+                Some(values.into_iter().map(|n| uint(n, *ty)).dedup().collect())
+            }
+            TypeInner::Tuple(elements) => Some(
+                elements
+                    .iter()
+                    .map(|element| samples(element))
+                    .collect::<Option<Vec<_>>>()?
+                    .into_iter()
+                    .multi_cartesian_product()
+                    .map(Value::tuple)
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn reference_matches_bit_machine() {
+        let height = elements::locktime::Height::from_consensus(1000).unwrap();
+        let env = dummy_env::dummy_with(
+            elements::LockTime::Blocks(height),
+            elements::Sequence::from_height(10),
+            false,
+        );
+
+        for jet in Elements::ALL {
+            let target = target_type(jet).resolve_builtin().unwrap();
+            let Some(samples) = source_type(jet)
+                .iter()
+                .map(|ty| samples(&ty.resolve_builtin().unwrap()))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let args_samples: Vec<Vec<Value>> = match samples.is_empty() {
+                true => vec![vec![]],
+                false => samples.into_iter().multi_cartesian_product().collect(),
+            };
+            for args in args_samples {
+                let Some(output) = execute_reference(jet, &args, &target, &env) else {
+                    assert!(!has_reference(jet), "{jet} has a reference implementation");
+                    break;
+                };
+                assert!(has_reference(jet), "{jet} has no reference implementation");
+                let expected = execute_on_bit_machine(jet, &args, &target, &env);
+                assert_eq!(expected, output, "{jet} on {args:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn sha256_context() {
+        let env = dummy_env::dummy();
+        let ctx8 = target_type(Elements::Sha256Ctx8Init)
+            .resolve_builtin()
+            .unwrap();
+        let hash = target_type(Elements::Sha256Ctx8Finalize)
+            .resolve_builtin()
+            .unwrap();
+        let byte_list = |len: usize| {
+            let bytes = (0..len).map(|i| uint(i as u128, UIntType::U8));
+            let bound = NonZeroPow2Usize::new(512).unwrap();
+            Value::list(bytes, UIntType::U8.into(), bound)
+        };
+        let inputs = [
+            (Elements::Sha256Ctx8Add1, uint(0xab, UIntType::U8)),
+            (Elements::Sha256Ctx8Add32, u256([0x42; 32])),
+            (Elements::Sha256Ctx8AddBuffer511, byte_list(100)),
+            (Elements::Sha256Ctx8AddBuffer511, byte_list(0)),
+            (
+                Elements::Sha256Ctx8Add16,
+                uint(u128::MAX / 3, UIntType::U128),
+            ),
+            (Elements::Sha256Ctx8AddBuffer511, byte_list(511)),
+        ];
+
+        let mut context = execute(Elements::Sha256Ctx8Init, &[], &ctx8, &env).unwrap();
+        for (jet, bytes) in inputs {
+            let args = [context, bytes];
+            context = execute_reference(jet, &args, &ctx8, &env).unwrap().unwrap();
+            let expected = execute_on_bit_machine(jet, &args, &ctx8, &env).unwrap();
+            assert_eq!(expected, context, "{jet}");
+
+            let args = [context.clone()];
+            let jet = Elements::Sha256Ctx8Finalize;
+            let output = execute_reference(jet, &args, &hash, &env).unwrap();
+            assert_eq!(execute_on_bit_machine(jet, &args, &hash, &env), output);
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! Interpret the abstract syntax tree of a SimplicityHL program
+//!
+//! The interpreter evaluates expressions directly on [`Value`]s instead of compiling them to Simplicity.
+//! It serves as a second implementation of the semantics of SimplicityHL
+//! against which the compiler can be tested.
+
+pub(crate) mod jet;
+
+pub use jet::has_reference;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use either::Either;
+use simplicity::jet::elements::ElementsEnv;
+
+use crate::ast::{
+    Call, CallName, CustomFunction, Expression, ExpressionInner, Match, Program, SingleExpression,
+    SingleExpressionInner, Statement,
+};
+use crate::error::{Error, RichError, Span, WithSpan};
+use crate::pattern::Pattern;
+use crate::str::{Identifier, JetName};
+use crate::types::{TypeDeconstructible, UIntType};
+use crate::value::{StructuralValue, ValueConstructible, ValueInner};
+use crate::witness::{Arguments, WitnessValues};
+use crate::{elements, Value};
+
+/// Interpreter of SimplicityHL expressions.
+///
+/// Variables are bound to values in a stack of scopes.
+/// Each (nested) block expression introduces a new scope.
+/// A function call starts with a fresh stack that contains only the function parameters.
+pub struct Interpreter<'a> {
+    arguments: &'a Arguments,
+    witness_values: &'a WitnessValues,
+    env: &'a ElementsEnv<Arc<elements::Transaction>>,
+    scopes: Vec<HashMap<Identifier, Value>>,
+    debug_values: Vec<(Span, Value)>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Create an interpreter with the given `arguments`, `witness_values`
+    /// and transaction environment `env`.
+    pub fn new(
+        arguments: &'a Arguments,
+        witness_values: &'a WitnessValues,
+        env: &'a ElementsEnv<Arc<elements::Transaction>>,
+    ) -> Self {
+        Self {
+            arguments,
+            witness_values,
+            env,
+            scopes: vec![HashMap::new()],
+            debug_values: Vec::new(),
+        }
+    }
+
+    /// Run the main function of the given `program`.
+    ///
+    /// ## Errors
+    ///
+    /// The program fails, for instance because an assertion is violated.
+    /// The error is located at the expression that failed.
+    pub fn run(&mut self, program: &Program) -> Result<(), RichError> {
+        self.eval(program.main()).map(|_| ())
+    }
+
+    /// Access the values that were passed to `dbg!` so far, in the order of evaluation.
+    pub fn debug_values(&self) -> &[(Span, Value)] {
+        &self.debug_values
+    }
+
+    /// Evaluate the given `expression` in the current scope.
+    ///
+    /// ## Errors
+    ///
+    /// The expression fails.
+    pub fn eval(&mut self, expression: &Expression) -> Result<Value, RichError> {
+        match expression.inner() {
+            ExpressionInner::Single(single) => self.eval_single(single),
+            ExpressionInner::Block(statements, expression) => {
+                self.scopes.push(HashMap::new());
+                let output = self.eval_block(statements, expression.as_deref());
+                self.scopes.pop();
+                output
+            }
+        }
+    }
+
+    fn eval_block(
+        &mut self,
+        statements: &[Statement],
+        expression: Option<&Expression>,
+    ) -> Result<Value, RichError> {
+        for statement in statements {
+            match statement {
+                Statement::Assignment(assignment) => {
+                    let value = self.eval(assignment.expression())?;
+                    self.bind(assignment.pattern(), value)
+                        .with_span(assignment)?;
+                }
+                Statement::Expression(expression) => {
+                    self.eval(expression)?;
+                }
+            }
+        }
+        match expression {
+            Some(expression) => self.eval(expression),
+            None => Ok(Value::unit()),
+        }
+    }
+
+    fn eval_single(&mut self, single: &SingleExpression) -> Result<Value, RichError> {
+        let value = match single.inner() {
+            SingleExpressionInner::Constant(value) => value.clone(),
+            SingleExpressionInner::Witness(name) => self
+                .witness_values
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::WitnessMissing(name.shallow_clone()))
+                .with_span(single)?,
+            SingleExpressionInner::Parameter(name) => self
+                .arguments
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::ArgumentMissing(name.shallow_clone()))
+                .with_span(single)?,
+            SingleExpressionInner::Variable(identifier) => self
+                .scopes
+                .iter()
+                .rev()
+                .find_map(|scope| scope.get(identifier))
+                .cloned()
+                .ok_or_else(|| Error::UndefinedVariable(identifier.clone()))
+                .with_span(single)?,
+            SingleExpressionInner::Expression(expression) => self.eval(expression)?,
+            SingleExpressionInner::Tuple(elements) => Value::tuple(self.eval_all(elements)?),
+            SingleExpressionInner::Array(elements) => {
+                let ty = single.ty().as_array().expect("value is type-checked").0;
+                Value::array(self.eval_all(elements)?, ty.clone())
+            }
+            SingleExpressionInner::List(elements) => {
+                let (ty, bound) = single.ty().as_list().expect("value is type-checked");
+                Value::list(self.eval_all(elements)?, ty.clone(), bound)
+            }
+            SingleExpressionInner::Either(Either::Left(inner)) => {
+                let right = single.ty().as_either().expect("value is type-checked").1;
+                Value::left(self.eval(inner)?, right.clone())
+            }
+            SingleExpressionInner::Either(Either::Right(inner)) => {
+                let left = single.ty().as_either().expect("value is type-checked").0;
+                Value::right(left.clone(), self.eval(inner)?)
+            }
+            SingleExpressionInner::Option(None) => {
+                let inner = single.ty().as_option().expect("value is type-checked");
+                Value::none(inner.clone())
+            }
+            SingleExpressionInner::Option(Some(inner)) => Value::some(self.eval(inner)?),
+            SingleExpressionInner::Call(call) => self.eval_call(call, single)?,
+            SingleExpressionInner::Match(match_) => self.eval_match(match_)?,
+        };
+        debug_assert!(value.is_of_type(single.ty()));
+        Ok(value)
+    }
+
+    fn eval_all(&mut self, expressions: &[Expression]) -> Result<Vec<Value>, RichError> {
+        expressions
+            .iter()
+            .map(|expression| self.eval(expression))
+            .collect()
+    }
+
+    fn eval_call(&mut self, call: &Call, single: &SingleExpression) -> Result<Value, RichError> {
+        let mut args = self.eval_all(call.args())?;
+
+        match call.name() {
+            CallName::Jet(jet) => jet::execute(*jet, &args, single.ty(), self.env)
+                .map_err(|_| Error::JetFailed(JetName::from_str_unchecked(&jet.to_string())))
+                .with_span(call),
+            CallName::UnwrapLeft(..) => match single_arg(args).inner() {
+                ValueInner::Either(Either::Left(inner)) => Ok(inner.as_ref().clone()),
+                _ => Err(Error::UnwrapLeftFailed).with_span(call),
+            },
+            CallName::UnwrapRight(..) => match single_arg(args).inner() {
+                ValueInner::Either(Either::Right(inner)) => Ok(inner.as_ref().clone()),
+                _ => Err(Error::UnwrapRightFailed).with_span(call),
+            },
+            CallName::Unwrap => match single_arg(args).inner() {
+                ValueInner::Option(Some(inner)) => Ok(inner.as_ref().clone()),
+                _ => Err(Error::UnwrapNoneFailed).with_span(call),
+            },
+            CallName::IsNone(..) => {
+                let is_none = matches!(single_arg(args).inner(), ValueInner::Option(None));
+                Ok(Value::from(is_none))
+            }
+            CallName::Assert => match single_arg(args).inner() {
+                ValueInner::Boolean(true) => Ok(Value::unit()),
+                _ => Err(Error::AssertionFailed).with_span(call),
+            },
+            CallName::Panic => Err(Error::ExplicitPanic).with_span(call),
+            CallName::Debug => {
+                let value = single_arg(args);
+                self.debug_values.push((*call.span(), value.clone()));
+                Ok(value)
+            }
+            CallName::TypeCast(..) => {
+                let value = StructuralValue::from(&single_arg(args));
+                Value::reconstruct(&value, single.ty())
+                    .ok_or_else(|| Error::CannotInterpret(format!("Cannot cast `{value}`")))
+                    .with_span(call)
+            }
+            CallName::Custom(function) => self.call_function(function, args),
+            CallName::Fold(function, _) | CallName::ArrayFold(function, _) => {
+                let mut accumulator = args.pop().expect("fold takes two arguments");
+                let iterable = args.pop().expect("fold takes two arguments");
+                let (ValueInner::List(elements, _) | ValueInner::Array(elements)) =
+                    iterable.inner()
+                else {
+                    unreachable!("fold takes a list or an array")
+                };
+                for element in elements.iter() {
+                    accumulator =
+                        self.call_function(function, vec![element.clone(), accumulator])?;
+                }
+                Ok(accumulator)
+            }
+            CallName::ForWhile(function, bit_width) => {
+                let context = args.pop().expect("for-while takes two arguments");
+                let mut accumulator = args.pop().expect("for-while takes two arguments");
+                let counter_ty = UIntType::from_bit_width(*bit_width).expect("valid bit width");
+                for counter in 0..(1u128 << bit_width.get()) {
+                    let counter = jet::uint(counter, counter_ty);
+                    let output =
+                        self.call_function(function, vec![accumulator, context.clone(), counter])?;
+                    match output.inner() {
+                        ValueInner::Either(Either::Left(..)) => return Ok(output),
+                        ValueInner::Either(Either::Right(inner)) => {
+                            accumulator = inner.as_ref().clone();
+                        }
+                        _ => unreachable!("loop body returns an either value"),
+                    }
+                }
+                let left = single.ty().as_either().expect("value is type-checked").0;
+                Ok(Value::right(left.clone(), accumulator))
+            }
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        function: &CustomFunction,
+        args: Vec<Value>,
+    ) -> Result<Value, RichError> {
+        let params = function
+            .params()
+            .iter()
+            .map(|param| param.identifier().clone())
+            .zip(args)
+            .collect();
+        let caller_scopes = std::mem::replace(&mut self.scopes, vec![params]);
+        let output = self.eval(function.body());
+        self.scopes = caller_scopes;
+        output
+    }
+
+    fn eval_match(&mut self, match_: &Match) -> Result<Value, RichError> {
+        let scrutinee = self.eval(match_.scrutinee())?;
+        let (arm, inner) = match scrutinee.inner() {
+            ValueInner::Either(Either::Left(inner)) => (match_.left(), Some(inner)),
+            ValueInner::Either(Either::Right(inner)) => (match_.right(), Some(inner)),
+            ValueInner::Option(None) | ValueInner::Boolean(false) => (match_.left(), None),
+            ValueInner::Option(Some(inner)) => (match_.right(), Some(inner)),
+            ValueInner::Boolean(true) => (match_.right(), None),
+            _ => unreachable!("scrutinee is type-checked"),
+        };
+        let mut scope = HashMap::new();
+        if let (Some(identifier), Some(inner)) = (arm.pattern().as_variable(), inner) {
+            scope.insert(identifier.clone(), inner.as_ref().clone());
+        }
+        self.scopes.push(scope);
+        let output = self.eval(arm.expression());
+        self.scopes.pop();
+        output
+    }
+
+    /// Bind the given `value` to the variables of the given `pattern` in the current scope.
+    fn bind(&mut self, pattern: &Pattern, value: Value) -> Result<(), Error> {
+        let mut stack = vec![(pattern, value)];
+        while let Some((pattern, value)) = stack.pop() {
+            match (pattern, value.inner()) {
+                (Pattern::Identifier(identifier), _) => {
+                    let scope = self.scopes.last_mut().expect("there is a scope");
+                    scope.insert(identifier.clone(), value);
+                }
+                (Pattern::Ignore, _) => {}
+                (Pattern::Tuple(patterns), ValueInner::Tuple(values))
+                | (Pattern::Array(patterns), ValueInner::Array(values))
+                    if patterns.len() == values.len() =>
+                {
+                    stack.extend(patterns.iter().zip(values.iter().cloned()));
+                }
+                _ => {
+                    return Err(Error::CannotInterpret(format!(
+                        "Cannot bind `{value}` to pattern `{pattern}`"
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Take the single argument of a builtin function.
+fn single_arg(mut args: Vec<Value>) -> Value {
+    debug_assert_eq!(args.len(), 1);
+    args.pop().expect("builtin takes one argument")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dummy_env, TemplateProgram};
+
+    fn interpret(program_text: &str) -> Result<(), String> {
+        let env = dummy_env::dummy();
+        TemplateProgram::new(program_text).and_then(|program| {
+            program.interpret(Arguments::default(), WitnessValues::default(), &env)
+        })
+    }
+
+    #[test]
+    fn array_fold() {
+        let program_text = std::fs::read_to_string("./examples/array_fold.simf").unwrap();
+        interpret(&program_text).unwrap();
+    }
+
+    #[test]
+    fn for_while() {
+        let program_text = r#"fn f(acc: u8, ctx: u8, i: u4) -> Either<u4, u8> {
+    let (_, next): (bool, u8) = jet::add_8(acc, ctx);
+    match jet::eq_8(next, 12) {
+        true => Left(i),
+        false => Right(next),
+    }
+}
+
+fn main() {
+    let out: Either<u4, u8> = for_while::<f>(0, 3);
+    let i: u4 = unwrap_left::<u8>(out);
+    assert!(jet::eq_8(<(u4, u4)>::into((0, i)), 3));
+}"#;
+        interpret(program_text).unwrap();
+    }
+
+    #[test]
+    fn debug_values() {
+        let program_text = r#"fn main() {
+    let a: u8 = 2;
+    let (_, b): (bool, u8) = jet::add_8(dbg!(a), a);
+    assert!(jet::eq_8(dbg!(b), 4));
+}"#;
+        let program = TemplateProgram::new(program_text).unwrap();
+        let (arguments, witness_values) = (Arguments::default(), WitnessValues::default());
+        let env = dummy_env::dummy();
+        let mut interpreter = Interpreter::new(&arguments, &witness_values, &env);
+        interpreter.run(&program.simfony).unwrap();
+        let values: Vec<(usize, String)> = interpreter
+            .debug_values()
+            .iter()
+            .map(|(span, value)| (span.start.line.get(), value.to_string()))
+            .collect();
+        assert_eq!(values, [(3, "2".to_string()), (4, "4".to_string())]);
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let assertion = r#"fn main() {
+    assert!(jet::eq_8(1, 2));
+}"#;
+        let error = interpret(assertion).unwrap_err();
+        assert!(error.contains("2 |     assert!(jet::eq_8(1, 2));"));
+        assert!(error.ends_with("Assertion failed"));
+
+        let unwrap = r#"fn main() {
+    let x: Option<u8> = None;
+    let y: u8 = unwrap(x);
+}"#;
+        let error = interpret(unwrap).unwrap_err();
+        assert!(error.ends_with("Called `unwrap` on a `None` value"));
+
+        let jet = r#"fn main() {
+    jet::check_lock_height(1000);
+}"#;
+        let error = interpret(jet).unwrap_err();
+        assert!(error.ends_with("Jet `check_lock_height` failed"));
+    }
+}
@@ -1035,6 +1035,109 @@ pub fn target_type(jet: Elements) -> AliasedType {
     }
 }
 
+/// Check if the output of the `jet` depends on its arguments only.
+///
+/// Pure jets can be evaluated at compile time.
+/// Jets that read the transaction environment, such as time locks, introspection
+/// and the signature hashes of the spending transaction, are impure.
+pub fn is_pure(jet: Elements) -> bool {
+    !matches!(
+        jet,
+        Elements::CheckLockDistance
+            | Elements::CheckLockDuration
+            | Elements::CheckLockHeight
+            | Elements::CheckLockTime
+            | Elements::TxIsFinal
+            | Elements::TxLockDistance
+            | Elements::TxLockDuration
+            | Elements::TxLockHeight
+            | Elements::TxLockTime
+            | Elements::Issuance
+            | Elements::IssuanceAsset
+            | Elements::IssuanceEntropy
+            | Elements::IssuanceToken
+            | Elements::LbtcAsset
+            | Elements::InputAmountsHash
+            | Elements::InputAnnexesHash
+            | Elements::InputHash
+            | Elements::InputOutpointsHash
+            | Elements::InputScriptSigsHash
+            | Elements::InputScriptsHash
+            | Elements::InputSequencesHash
+            | Elements::InputUtxoHash
+            | Elements::InputUtxosHash
+            | Elements::InputsHash
+            | Elements::IssuanceAssetAmountsHash
+            | Elements::IssuanceBlindingEntropyHash
+            | Elements::IssuanceHash
+            | Elements::IssuanceRangeProofsHash
+            | Elements::IssuanceTokenAmountsHash
+            | Elements::IssuancesHash
+            | Elements::OutputAmountsHash
+            | Elements::OutputHash
+            | Elements::OutputNoncesHash
+            | Elements::OutputRangeProofsHash
+            | Elements::OutputScriptsHash
+            | Elements::OutputSurjectionProofsHash
+            | Elements::OutputsHash
+            | Elements::SigAllHash
+            | Elements::TapEnvHash
+            | Elements::TapleafHash
+            | Elements::TappathHash
+            | Elements::TxHash
+            | Elements::CurrentAmount
+            | Elements::CurrentAnnexHash
+            | Elements::CurrentAsset
+            | Elements::CurrentIndex
+            | Elements::CurrentIssuanceAssetAmount
+            | Elements::CurrentIssuanceAssetProof
+            | Elements::CurrentIssuanceTokenAmount
+            | Elements::CurrentIssuanceTokenProof
+            | Elements::CurrentNewIssuanceContract
+            | Elements::CurrentPegin
+            | Elements::CurrentPrevOutpoint
+            | Elements::CurrentReissuanceBlinding
+            | Elements::CurrentReissuanceEntropy
+            | Elements::CurrentScriptHash
+            | Elements::CurrentScriptSigHash
+            | Elements::CurrentSequence
+            | Elements::GenesisBlockHash
+            | Elements::InputAmount
+            | Elements::InputAnnexHash
+            | Elements::InputAsset
+            | Elements::InputPegin
+            | Elements::InputPrevOutpoint
+            | Elements::InputScriptHash
+            | Elements::InputScriptSigHash
+            | Elements::InputSequence
+            | Elements::InternalKey
+            | Elements::IssuanceAssetAmount
+            | Elements::IssuanceAssetProof
+            | Elements::IssuanceTokenAmount
+            | Elements::IssuanceTokenProof
+            | Elements::LockTime
+            | Elements::NewIssuanceContract
+            | Elements::NumInputs
+            | Elements::NumOutputs
+            | Elements::OutputAmount
+            | Elements::OutputAsset
+            | Elements::OutputIsFee
+            | Elements::OutputNonce
+            | Elements::OutputNullDatum
+            | Elements::OutputRangeProof
+            | Elements::OutputScriptHash
+            | Elements::OutputSurjectionProof
+            | Elements::ReissuanceBlinding
+            | Elements::ReissuanceEntropy
+            | Elements::ScriptCMR
+            | Elements::TapleafVersion
+            | Elements::Tappath
+            | Elements::TotalFee
+            | Elements::TransactionId
+            | Elements::Version
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3,21 +3,32 @@
 pub mod array;
 pub mod ast;
 pub mod compile;
+pub mod coverage;
 pub mod debug;
 pub mod dummy_env;
 pub mod error;
+pub mod format;
+pub mod interpret;
 pub mod jet;
 pub mod named;
 pub mod num;
 pub mod parse;
 pub mod pattern;
+#[cfg(feature = "arbitrary")]
+pub mod quickcheck;
+pub mod repl;
 #[cfg(feature = "serde")]
 mod serde;
+pub mod source_map;
 pub mod str;
+pub mod testing;
 pub mod types;
 pub mod value;
+#[cfg(feature = "arbitrary")]
+pub mod well_typed;
 mod witness;
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use simplicity::jet::elements::ElementsEnv;
@@ -30,6 +41,7 @@ pub use simplicity::elements;
 use crate::debug::DebugSymbols;
 use crate::error::WithFile;
 use crate::parse::ParseFromStr;
+use crate::source_map::SourceMap;
 pub use crate::types::ResolvedType;
 pub use crate::value::Value;
 pub use crate::witness::{Arguments, Parameters, WitnessTypes, WitnessValues};
@@ -79,17 +91,112 @@ impl TemplateProgram {
             .is_consistent(self.simfony.parameters())
             .map_err(|error| error.to_string())?;
 
-        let commit = self
+        let (commit, source_map) = self
             .simfony
-            .compile(arguments, include_debug_symbols)
+            .compile_with_symbols(arguments, include_debug_symbols, false)
             .with_file(Arc::clone(&self.file))?;
 
         Ok(CompiledProgram {
             debug_symbols: self.simfony.debug_symbols(self.file.as_ref()),
+            source_map,
             simplicity: commit,
             witness_types: self.simfony.witness_types().shallow_clone(),
         })
     }
+
+    /// Instantiate the template program with the given `arguments` for a debugger.
+    ///
+    /// The compiled program marks each statement and each entry and exit of a custom function,
+    /// so its CMR differs from the program that [`TemplateProgram::instantiate`] returns.
+    /// Execute it with a [`debug::Tracer`] to record its steps.
+    ///
+    /// ## Errors
+    ///
+    /// The arguments are not consistent with the parameters of the program.
+    /// Use [`TemplateProgram::parameters`] to see which parameters the program has.
+    pub fn instantiate_for_debugger(
+        &self,
+        arguments: Arguments,
+    ) -> Result<CompiledProgram, String> {
+        arguments
+            .is_consistent(self.simfony.parameters())
+            .map_err(|error| error.to_string())?;
+
+        let (commit, source_map) = self
+            .simfony
+            .compile_with_symbols(arguments, true, true)
+            .with_file(Arc::clone(&self.file))?;
+
+        Ok(CompiledProgram {
+            debug_symbols: self.simfony.debug_symbols(self.file.as_ref()),
+            source_map,
+            simplicity: commit,
+            witness_types: self.simfony.witness_types().shallow_clone(),
+        })
+    }
+
+    /// Instantiate the template program with the given `arguments` and with debug symbols,
+    /// and verify that the result is a debug build of the release build.
+    ///
+    /// The debug build includes step symbols if `for_debugger` is true.
+    /// Removing its debug markers must yield the same CMR as the release build
+    /// that [`TemplateProgram::instantiate`] returns without debug symbols.
+    ///
+    /// The returned program is the debug build, which has a different CMR than the release build.
+    /// Use [`CompiledProgram::instrument`] to trace a satisfied release build with its markers.
+    ///
+    /// ## Errors
+    ///
+    /// - The arguments are not consistent with the parameters of the program.
+    /// - The debug build is not equivalent to the release build.
+    pub fn instantiate_verified(
+        &self,
+        arguments: Arguments,
+        for_debugger: bool,
+    ) -> Result<CompiledProgram, String> {
+        let release = self.instantiate(arguments.clone(), false)?;
+        let debug = match for_debugger {
+            true => self.instantiate_for_debugger(arguments)?,
+            false => self.instantiate(arguments, true)?,
+        };
+        let stripped = debug.strip_debug_symbols()?;
+        let (release_cmr, stripped_cmr) = (release.commit().cmr(), stripped.commit().cmr());
+        if release_cmr != stripped_cmr {
+            return Err(format!(
+                "Debug build strips to CMR {stripped_cmr} instead of release CMR {release_cmr}"
+            ));
+        }
+        Ok(debug)
+    }
+
+    /// Run the template program with the given `arguments` and `witness_values`
+    /// in the given transaction environment, without compiling it to Simplicity.
+    ///
+    /// The program is evaluated by the reference [`interpret::Interpreter`].
+    ///
+    /// ## Errors
+    ///
+    /// - The arguments are not consistent with the parameters of the program.
+    /// - The witness values are not consistent with the witnesses of the program.
+    /// - The program fails, for instance because an assertion is violated.
+    pub fn interpret(
+        &self,
+        arguments: Arguments,
+        witness_values: WitnessValues,
+        env: &ElementsEnv<Arc<elements::Transaction>>,
+    ) -> Result<(), String> {
+        arguments
+            .is_consistent(self.simfony.parameters())
+            .map_err(|error| error.to_string())?;
+        witness_values
+            .is_consistent(self.simfony.witness_types())
+            .map_err(|error| error.to_string())?;
+
+        interpret::Interpreter::new(&arguments, &witness_values, env)
+            .run(&self.simfony)
+            .with_file(Arc::clone(&self.file))?;
+        Ok(())
+    }
 }
 
 /// A SimplicityHL program, compiled to Simplicity.
@@ -98,6 +205,7 @@ pub struct CompiledProgram {
     simplicity: Arc<named::CommitNode<Elements>>,
     witness_types: WitnessTypes,
     debug_symbols: DebugSymbols,
+    source_map: SourceMap,
 }
 
 impl CompiledProgram {
@@ -121,6 +229,100 @@ impl CompiledProgram {
         &self.debug_symbols
     }
 
+    /// Access the source map of the Simplicity target code.
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    /// Remove the debug markers from the program, which yields the release build:
+    /// the program that the compiler produces without debug symbols, with the same CMR.
+    ///
+    /// The release build has no debug symbols.
+    /// Its source map is carried over from the debug build.
+    ///
+    /// ## Errors
+    ///
+    /// The debug markers cannot be removed, which should not happen for compiled programs.
+    pub fn strip_debug_symbols(&self) -> Result<Self, String> {
+        let stripped = self.strip()?;
+        let origins = stripped.origins.into_iter().map(Some).collect::<Vec<_>>();
+        let source_map = self.source_map.map_nodes(&origins, &stripped.node);
+        Ok(Self {
+            simplicity: stripped.node,
+            witness_types: self.witness_types.shallow_clone(),
+            debug_symbols: DebugSymbols::default(),
+            source_map,
+        })
+    }
+
+    /// Remove the debug markers from the program and finalize the types of the result.
+    fn strip(&self) -> Result<named::Stripped<Arc<named::CommitNode<Elements>>>, String> {
+        simplicity::types::Context::with_context(|ctx| {
+            let stripped: named::Stripped<Arc<named::ConstructNode<Elements>>> =
+                named::strip_markers(
+                    &ctx,
+                    &self.simplicity,
+                    |cmr| self.debug_symbols.is_symbol(&cmr),
+                    str::WitnessName::shallow_clone,
+                )?;
+            let node = named::finalize_types(&stripped.node).map_err(|e| e.to_string())?;
+            Ok(named::Stripped {
+                node,
+                cmrs: stripped.cmrs,
+                origins: stripped.origins,
+            })
+        })
+    }
+
+    /// Satisfy the program, which is a debug build, such that it runs like the given `release`
+    /// program: the release build of this program, satisfied with witness data.
+    ///
+    /// The witness values are read from the witness nodes of the release program,
+    /// which may have been pruned or decoded from its encoding.
+    /// Witnesses in pruned branches are never used, so they take the zero value.
+    /// The result has the debug markers of this program, which do not change its outcome,
+    /// so executing it with a [`debug::Tracer`] attributes the execution of the release program
+    /// to the SimplicityHL source code.
+    ///
+    /// ## Errors
+    ///
+    /// - Removing the debug markers of this program does not yield the CMR of the release program.
+    /// - A witness value of the release program does not match its declared type.
+    pub fn instrument(&self, release: &RedeemNode<Elements>) -> Result<SatisfiedProgram, String> {
+        let stripped = self.strip()?;
+        let (release_cmr, stripped_cmr) = (release.cmr(), stripped.node.cmr());
+        if release_cmr != stripped_cmr {
+            return Err(format!(
+                "Debug build strips to CMR {stripped_cmr} instead of release CMR {release_cmr}"
+            ));
+        }
+
+        let mut witness_values = HashMap::new();
+        for (original, target) in named::pair_nodes(&stripped.node, release) {
+            let (simplicity::node::Inner::Witness(name), simplicity::node::Inner::Witness(value)) =
+                (original.inner(), target.inner())
+            else {
+                continue;
+            };
+            let ty = self
+                .witness_types
+                .get(name)
+                .ok_or_else(|| format!("Witness `{name}` is not declared"))?;
+            let value = Value::reconstruct(&value.shallow_clone().into(), ty).ok_or_else(|| {
+                format!("Witness `{name}` has a value that is not of type `{ty}`")
+            })?;
+            witness_values.insert(name.shallow_clone(), value);
+        }
+        for (name, ty) in self.witness_types.iter() {
+            if !witness_values.contains_key(name) {
+                let zero = simplicity::Value::zero(types::StructuralType::from(ty).as_ref());
+                let value = Value::reconstruct(&zero.into(), ty).expect("zero value has the type");
+                witness_values.insert(name.shallow_clone(), value);
+            }
+        }
+        self.satisfy(WitnessValues::from(witness_values))
+    }
+
     /// Access the Simplicity target code, without witness data.
     pub fn commit(&self) -> Arc<CommitNode<Elements>> {
         named::forget_names(&self.simplicity)
@@ -153,12 +355,16 @@ impl CompiledProgram {
             .map_err(|e| e.to_string())?;
 
         let mut simplicity_redeem = named::populate_witnesses(&self.simplicity, witness_values)?;
+        let mut source_map = self.source_map.clone();
         if let Some(env) = env {
-            simplicity_redeem = simplicity_redeem.prune(env).map_err(|e| e.to_string())?;
+            let pruned = simplicity_redeem.prune(env).map_err(|e| e.to_string())?;
+            source_map = source_map.follow(&simplicity_redeem, &pruned);
+            simplicity_redeem = pruned;
         }
         Ok(SatisfiedProgram {
             simplicity: simplicity_redeem,
             debug_symbols: self.debug_symbols.clone(),
+            source_map,
         })
     }
 }
@@ -168,6 +374,7 @@ impl CompiledProgram {
 pub struct SatisfiedProgram {
     simplicity: Arc<RedeemNode<Elements>>,
     debug_symbols: DebugSymbols,
+    source_map: SourceMap,
 }
 
 impl SatisfiedProgram {
@@ -188,6 +395,33 @@ impl SatisfiedProgram {
         compiled.satisfy(witness_values)
     }
 
+    /// Decode a satisfied program from its Simplicity encoding, including witness data,
+    /// and attach the given `debug_symbols` to it.
+    ///
+    /// The debug symbols can be loaded from the output of `simc --debug --json`,
+    /// so a [`debug::Tracer`] can trace programs that were compiled elsewhere.
+    /// The decoded program has an empty source map.
+    ///
+    /// ## Errors
+    ///
+    /// The bytes are not a valid encoding of a Simplicity program and its witness data.
+    pub fn decode(
+        program_bytes: &[u8],
+        witness_bytes: &[u8],
+        debug_symbols: DebugSymbols,
+    ) -> Result<Self, String> {
+        let simplicity = RedeemNode::decode(
+            simplicity::BitIter::from(program_bytes),
+            simplicity::BitIter::from(witness_bytes),
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(Self {
+            simplicity,
+            debug_symbols,
+            source_map: SourceMap::default(),
+        })
+    }
+
     /// Access the Simplicity target code, including witness data.
     pub fn redeem(&self) -> &Arc<RedeemNode<Elements>> {
         &self.simplicity
@@ -197,6 +431,11 @@ impl SatisfiedProgram {
     pub fn debug_symbols(&self) -> &DebugSymbols {
         &self.debug_symbols
     }
+
+    /// Access the source map of the Simplicity target code.
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
 }
 
 /// Recursively implement [`PartialEq`], [`Eq`] and [`std::hash::Hash`]
@@ -274,6 +513,9 @@ pub(crate) mod tests {
 
     pub(crate) struct TestCase<T> {
         program: T,
+        template: TemplateProgram,
+        arguments: Arguments,
+        witness_values: WitnessValues,
         lock_time: elements::LockTime,
         sequence: elements::Sequence,
         include_fee_output: bool,
@@ -291,7 +533,10 @@ pub(crate) mod tests {
                 Err(error) => panic!("{error}"),
             };
             Self {
+                template: program.clone(),
                 program,
+                arguments: Arguments::default(),
+                witness_values: WitnessValues::default(),
                 lock_time: elements::LockTime::ZERO,
                 sequence: elements::Sequence::MAX,
                 include_fee_output: false,
@@ -312,12 +557,15 @@ pub(crate) mod tests {
         }
 
         pub fn with_arguments(self, arguments: Arguments) -> TestCase<CompiledProgram> {
-            let program = match self.program.instantiate(arguments, true) {
+            let program = match self.program.instantiate(arguments.clone(), true) {
                 Ok(x) => x,
                 Err(error) => panic!("{error}"),
             };
             TestCase {
                 program,
+                template: self.template,
+                arguments,
+                witness_values: self.witness_values,
                 lock_time: self.lock_time,
                 sequence: self.sequence,
                 include_fee_output: self.include_fee_output,
@@ -353,12 +601,15 @@ pub(crate) mod tests {
             self,
             witness_values: WitnessValues,
         ) -> TestCase<SatisfiedProgram> {
-            let program = match self.program.satisfy(witness_values) {
+            let program = match self.program.satisfy(witness_values.clone()) {
                 Ok(x) => x,
                 Err(error) => panic!("{error}"),
             };
             TestCase {
                 program,
+                template: self.template,
+                arguments: self.arguments,
+                witness_values,
                 lock_time: self.lock_time,
                 sequence: self.sequence,
                 include_fee_output: self.include_fee_output,
@@ -406,7 +657,7 @@ pub(crate) mod tests {
             self
         }
 
-        fn run(self) -> Result<(), simplicity::bit_machine::ExecutionError> {
+        fn run(&self) -> Result<(), simplicity::bit_machine::ExecutionError> {
             let env = dummy_env::dummy_with(self.lock_time, self.sequence, self.include_fee_output);
             let pruned = self.program.redeem().prune(&env)?;
             let mut mac = BitMachine::for_program(&pruned)
@@ -414,11 +665,21 @@ pub(crate) mod tests {
             mac.exec(&pruned, &env).map(|_| ())
         }
 
+        fn interpret(&self) -> Result<(), String> {
+            let env = dummy_env::dummy_with(self.lock_time, self.sequence, self.include_fee_output);
+            self.template
+                .interpret(self.arguments.clone(), self.witness_values.clone(), &env)
+        }
+
         pub fn assert_run_success(self) {
             match self.run() {
                 Ok(()) => {}
                 Err(error) => panic!("Unexpected error: {error}"),
             }
+            match self.interpret() {
+                Ok(()) => {}
+                Err(error) => panic!("Unexpected error in interpreter: {error}"),
+            }
         }
     }
 
@@ -464,6 +725,13 @@ pub(crate) mod tests {
             .assert_run_success();
     }
 
+    #[test]
+    fn for_loop() {
+        TestCase::program_file("./examples/for_loop.simf")
+            .with_witness_values(WitnessValues::default())
+            .assert_run_success();
+    }
+
     #[test]
     fn hash_loop() {
         TestCase::program_file("./examples/hash_loop.simf")
@@ -649,6 +917,201 @@ fn main() {
             .assert_run_success();
     }
 
+    #[test]
+    fn constant() {
+        let prog_text = r#"const SIZE: u32 = 3;
+const BOUND: u8 = jet::complement_8(251);
+const SUM: (bool, u32) = jet::add_32(SIZE, 5);
+
+fn main() {
+    let xs: [u8; SIZE] = [1, 2, 3];
+    let ys: List<u8, BOUND> = list![1, 2, 3];
+    let (carry, sum): (bool, u32) = SUM;
+    assert!(jet::eq_32(sum, 8));
+    let SIZE: u32 = 0;
+    assert!(jet::is_zero_32(SIZE));
+}"#;
+        TestCase::program_text(Cow::Borrowed(prog_text))
+            .with_witness_values(WitnessValues::default())
+            .assert_run_success();
+    }
+
+    #[test]
+    fn constant_errors() {
+        let cases = [
+            (
+                "const N: u32 = jet::num_inputs();\nfn main() {}",
+                "Expression cannot be evaluated at compile time",
+            ),
+            (
+                "const N: u8 = 3;\nfn main() { let xs: List<u8, N> = list![]; }",
+                "as list bound, found 3",
+            ),
+            (
+                "const N: bool = false;\nfn main() { let xs: [u8; N] = []; }",
+                "Constant `N` of type `bool` cannot be used as a size",
+            ),
+            (
+                "const N: u8 = 1;\nconst N: u8 = 2;\nfn main() {}",
+                "Constant `N` was defined multiple times",
+            ),
+            (
+                "fn main() { let xs: [u8; N] = []; }",
+                "Constant `N` is not defined",
+            ),
+        ];
+        for (prog_text, expected) in cases {
+            match TemplateProgram::new(prog_text) {
+                Ok(_) => panic!("Accepted faulty program"),
+                Err(error) => assert!(error.contains(expected), "Unexpected error: {error}"),
+            }
+        }
+    }
+
+    #[test]
+    fn for_loop_iterables() {
+        let prog_text = r#"const N: u8 = 4;
+
+fn main() {
+    let xs: [u32; 4] = [1, 2, 3, 4];
+    let offset: u32 = 10;
+    let sum: u32 = for x in xs, mut acc: u32 = 0 {
+        let (_, acc): (bool, u32) = jet::add_32(acc, x);
+        let (_, acc): (bool, u32) = jet::add_32(acc, offset);
+        acc
+    };
+    assert!(jet::eq_32(sum, 50));
+
+    let ys: List<u8, 8> = list![3, 4, 5];
+    let sum: u8 = for y in ys, mut acc: u8 = 0 {
+        let (_, acc): (bool, u8) = jet::add_8(acc, y);
+        acc
+    };
+    assert!(jet::eq_8(sum, 12));
+
+    let sum: u8 = for i in 1..N, mut acc: u8 = 0 {
+        let (_, acc): (bool, u8) = jet::add_8(acc, i);
+        acc
+    };
+    assert!(jet::eq_8(sum, 6));
+
+    for x in xs {
+        assert!(jet::lt_32(x, 5))
+    };
+
+    let matrix: [[u8; 2]; 2] = [[1, 2], [3, 4]];
+    let sum: u8 = for row in matrix, mut acc: u8 = 0 {
+        for x in row, mut acc: u8 = acc {
+            let (_, acc): (bool, u8) = jet::add_8(acc, x);
+            acc
+        }
+    };
+    assert!(jet::eq_8(sum, 10));
+}"#;
+        TestCase::program_text(Cow::Borrowed(prog_text))
+            .with_witness_values(WitnessValues::default())
+            .assert_run_success();
+    }
+
+    #[test]
+    fn for_loop_break() {
+        let prog_text = r#"fn main() {
+    let xs: [u32; 4] = [1, 2, 3, 4];
+    let found: u32 = for x in xs, mut acc: u32 = 0 {
+        match jet::lt_32(x, 3) {
+            true => acc,
+            false => {
+                let acc: u32 = x;
+                break
+            }
+        }
+    };
+    assert!(jet::eq_32(found, 3));
+
+    let count: u8 = for i in 0..200, mut acc: u8 = 0 {
+        match jet::eq_8(i, 5) {
+            true => break,
+            false => {
+                let (_, acc): (bool, u8) = jet::add_8(acc, 1);
+                acc
+            }
+        }
+    };
+    assert!(jet::eq_8(count, 5));
+
+    let ys: List<u16, 4> = list![7, 8];
+    let last: u16 = for y in ys, mut acc: u16 = 0 { (break) };
+    assert!(jet::eq_16(last, 0));
+}"#;
+        TestCase::program_text(Cow::Borrowed(prog_text))
+            .with_witness_values(WitnessValues::default())
+            .assert_run_success();
+    }
+
+    #[test]
+    fn for_loop_errors() {
+        let cases = [
+            (
+                "fn main() { let x: u8 = 1; for y in x {} }",
+                "Expected an array or a list to loop over; found `u8`",
+            ),
+            (
+                "fn main() { let xs: [u8; 2] = [1, 2]; for x in xs { break; } }",
+                "`break` is only allowed as final expression of the body of a `for` loop",
+            ),
+            (
+                "fn main() { break }",
+                "`break` is only allowed as final expression of the body of a `for` loop",
+            ),
+            (
+                "fn main() { for i: u8 in 0..300 {} }",
+                "Range `0..300` cannot be iterated with a loop counter of type `u8`",
+            ),
+            (
+                "fn main() { for i in 3..2 {} }",
+                "Range `3..2` cannot be iterated with a loop counter of type `u8`",
+            ),
+            (
+                "fn main() { for i: u32 in 0..3 {} }",
+                "Expected `u8` or `u16` as type of the loop counter; found `u32`",
+            ),
+        ];
+        for (prog_text, expected) in cases {
+            match TemplateProgram::new(prog_text) {
+                Ok(_) => panic!("Accepted faulty program"),
+                Err(error) => assert!(error.contains(expected), "Unexpected error: {error}"),
+            }
+        }
+    }
+
+    #[test]
+    fn reserved_keywords() {
+        let cases = [
+            ("fn main() { let break: u8 = 1; }", "break"),
+            ("fn main() { let (a, in): (u8, u8) = (1, 2); }", "in"),
+            ("fn f(mut: u8) {}\nfn main() {}", "mut"),
+            (
+                "fn main() { let xs: [u8; 1] = [1]; for for in xs {} }",
+                "for in",
+            ),
+        ];
+        for (prog_text, keyword) in cases {
+            let Err(error) = TemplateProgram::new(prog_text) else {
+                panic!("Accepted faulty program");
+            };
+            let caret = error.lines().nth(2).unwrap().find('^').unwrap();
+            assert_eq!(
+                prog_text.rfind(keyword).unwrap() + "  | ".len(),
+                caret,
+                "Unexpected error: {error}"
+            );
+        }
+        assert!(str::Identifier::parse_from_str("break").is_err());
+
+        let prog_text = "fn main() {\n    let (format, inner, breaker, for_): (u8, u8, u8, u8) = (1, 2, 3, 4);\n}";
+        TemplateProgram::new(prog_text).expect("identifiers that start with a keyword are allowed");
+    }
+
     #[test]
     fn type_error_regression() {
         let prog_text = r#"fn main() {
@@ -663,4 +1126,172 @@ fn main() {
             .with_witness_values(WitnessValues::default())
             .assert_run_success();
     }
+
+    #[test]
+    fn strip_debug_symbols() {
+        for entry in std::fs::read_dir("./examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "simf") {
+                continue;
+            }
+            let prog_text = std::fs::read_to_string(&path).unwrap();
+            let template = TemplateProgram::new(prog_text.as_str()).unwrap();
+            let arguments = match template.parameters().iter().next() {
+                Some(_) => continue,
+                None => Arguments::default(),
+            };
+            let release = template.instantiate(arguments.clone(), false).unwrap();
+            for for_debugger in [false, true] {
+                let debug = template
+                    .instantiate_verified(arguments.clone(), for_debugger)
+                    .unwrap_or_else(|error| panic!("{}: {error}", path.display()));
+                let stripped = debug.strip_debug_symbols().unwrap();
+                assert_eq!(release.commit().cmr(), stripped.commit().cmr());
+                assert_eq!(
+                    release.commit().cmr(),
+                    debug.debug_symbols().strip(&debug.commit()).unwrap().cmr()
+                );
+                assert_eq!(&DebugSymbols::default(), stripped.debug_symbols());
+                assert_eq!(release.source_map(), stripped.source_map());
+            }
+        }
+    }
+
+    #[test]
+    fn trace_for_debugger() {
+        let prog_text = r#"fn double(x: u8) -> u8 {
+    let (_, y): (bool, u8) = jet::add_8(x, x);
+    y
+}
+
+fn main() {
+    let a: u8 = 3;
+    let b: u8 = double(a);
+    assert!(jet::eq_8(dbg!(b), 6));
+}"#;
+        let compiled = TemplateProgram::new(prog_text)
+            .and_then(|template| template.instantiate_for_debugger(Arguments::default()))
+            .expect("program should compile");
+        let release = CompiledProgram::new(prog_text, Arguments::default(), true).unwrap();
+        assert_ne!(compiled.commit().cmr(), release.commit().cmr());
+
+        let program = compiled.satisfy(WitnessValues::default()).unwrap();
+        let env = dummy_env::dummy();
+        let mut tracer = debug::Tracer::new(program.debug_symbols());
+        let mut mac = BitMachine::for_program(program.redeem()).unwrap();
+        mac.exec_with_tracker(program.redeem(), &env, &mut tracer)
+            .expect("program should run");
+
+        let line = |span: &error::Span| span.start.line;
+        let variables = |variables: &[(str::Identifier, value::Value)]| {
+            variables
+                .iter()
+                .map(|(identifier, value)| format!("{identifier}={value}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let events: Vec<String> = tracer
+            .events()
+            .iter()
+            .map(|event| match event {
+                debug::TraceEvent::Step(span, vars) => {
+                    format!("step {} [{}]", line(span), variables(vars))
+                }
+                debug::TraceEvent::EnterFunction(span) => format!("enter {}", line(span)),
+                debug::TraceEvent::ExitFunction(span) => format!("exit {}", line(span)),
+                debug::TraceEvent::Branch(span) => format!("branch {}", line(span)),
+                debug::TraceEvent::Call(call) => format!("call `{}`", call.text()),
+                debug::TraceEvent::Debug(debug) => {
+                    format!("dbg `{}` = {}", debug.text(), debug.value())
+                }
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "step 7 []",
+                "step 8 [a=3]",
+                "enter 1",
+                "step 2 [x=3]",
+                "call `jet::add_8(x, x)`",
+                "step 3 [x=3, y=6]",
+                "exit 1",
+                "step 9 [a=3, b=6]",
+                "dbg `b` = 6",
+                "call `jet::eq_8(dbg!(b), 6)`",
+                "call `assert!(jet::eq_8(dbg!(b), 6))`",
+            ]
+        );
+    }
+
+    #[test]
+    fn trace_release() {
+        use crate::value::ValueConstructible;
+
+        let prog_text = r#"fn main() {
+    let a: Either<u8, u16> = witness::A;
+    let b: u8 = witness::B;
+    match a {
+        Left(x: u8) => assert!(jet::eq_8(dbg!(x), b)),
+        Right(y: u16) => assert!(jet::eq_16(y, witness::C)),
+    };
+}"#;
+        let template = TemplateProgram::new(prog_text).unwrap();
+        let witness_values = WitnessValues::from(HashMap::from([
+            (
+                str::WitnessName::from_str_unchecked("A"),
+                Value::left(Value::u8(5), types::UIntType::U16.into()),
+            ),
+            (str::WitnessName::from_str_unchecked("B"), Value::u8(5)),
+            (str::WitnessName::from_str_unchecked("C"), Value::u16(7)),
+        ]));
+        let env = dummy_env::dummy();
+        let trace = |program: &SatisfiedProgram| {
+            let mut tracer = debug::Tracer::new(program.debug_symbols());
+            let mut mac = BitMachine::for_program(program.redeem()).unwrap();
+            mac.exec_with_tracker(program.redeem(), &env, &mut tracer)
+                .expect("program should run");
+            tracer.events().to_vec()
+        };
+
+        for for_debugger in [false, true] {
+            let debug = template
+                .instantiate_verified(Arguments::default(), for_debugger)
+                .unwrap();
+            let expected = trace(&debug.satisfy(witness_values.clone()).unwrap());
+            assert!(expected
+                .iter()
+                .any(|event| matches!(event, debug::TraceEvent::Debug(..))));
+
+            let release = template
+                .instantiate(Arguments::default(), false)
+                .unwrap()
+                .satisfy_with_env(witness_values.clone(), Some(&env))
+                .unwrap();
+            let (program_bytes, witness_bytes) = release.redeem().to_vec_with_witness();
+            let decoded =
+                SatisfiedProgram::decode(&program_bytes, &witness_bytes, DebugSymbols::default())
+                    .unwrap();
+            for release in [release.redeem(), decoded.redeem()] {
+                let instrumented = debug.instrument(release).unwrap();
+                assert_eq!(expected, trace(&instrumented));
+            }
+        }
+
+        let debug = template
+            .instantiate_verified(Arguments::default(), false)
+            .unwrap();
+        let other = CompiledProgram::new(
+            "fn main() {\n    assert!(jet::is_zero_8(witness::A));\n}",
+            Arguments::default(),
+            false,
+        )
+        .unwrap()
+        .satisfy(WitnessValues::from(HashMap::from([(
+            str::WitnessName::from_str_unchecked("A"),
+            Value::u8(0),
+        )])))
+        .unwrap();
+        assert!(debug.instrument(other.redeem()).is_err());
+    }
 }
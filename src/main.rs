@@ -1,8 +1,12 @@
 use base64::display::Base64Display;
 use base64::engine::general_purpose::STANDARD;
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 
+use simplicityhl::coverage::Coverage;
+use simplicityhl::simplicity::jet::elements::ElementsEnv;
+use simplicityhl::simplicity::RedeemNode;
 use simplicityhl::{Arguments, CompiledProgram};
+use std::sync::Arc;
 use std::{env, fmt};
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
@@ -12,6 +16,10 @@ struct Output {
     program: String,
     /// Simplicity witness result, base64 encoded, if the .wit file was provided.
     witness: Option<String>,
+    /// Debug symbols of the Simplicity program, if they were included.
+    #[cfg(feature = "serde")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debug_symbols: Option<simplicityhl::debug::DebugSymbols>,
 }
 
 impl fmt::Display for Output {
@@ -51,7 +59,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 Arg::new("debug")
                     .long("debug")
                     .action(ArgAction::SetTrue)
-                    .help("Include debug symbols in the output"),
+                    .help("Include debug symbols in the program, and in the JSON output"),
             )
             .arg(
                 Arg::new("json")
@@ -59,9 +67,209 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .action(ArgAction::SetTrue)
                     .help("Output in JSON"),
             )
+            .arg(
+                Arg::new("source_map")
+                    .long("source-map")
+                    .value_name("SOURCE_MAP_FILE")
+                    .action(ArgAction::Set)
+                    .help("Write the source map of the Simplicity program as JSON (requires feature 'serde')"),
+            )
+            .subcommand_negates_reqs(true)
+            .args_conflicts_with_subcommands(true)
+            .subcommand(
+                Command::new("fmt")
+                    .about("Format the given SimplicityHL program files in place")
+                    .arg(
+                        Arg::new("files")
+                            .required(true)
+                            .value_name("PROGRAM_FILE")
+                            .action(ArgAction::Append)
+                            .help("SimplicityHL program files to format"),
+                    )
+                    .arg(
+                        Arg::new("check")
+                            .long("check")
+                            .action(ArgAction::SetTrue)
+                            .help("Don't write the files; exit with an error if any file is not formatted"),
+                    )
+                    .arg(
+                        Arg::new("max_width")
+                            .long("max-width")
+                            .value_name("WIDTH")
+                            .value_parser(clap::value_parser!(usize))
+                            .help("Maximum width of a line"),
+                    ),
+            )
+            .subcommand(
+                Command::new("test")
+                    .about(
+                        "\
+                        Run the unit tests of the given SimplicityHL program on the Bit Machine.\n\
+                        Unit tests are functions marked with #[test]. Test files are appended to \
+                        the program, so their tests can call the functions of the program.\
+                        ",
+                    )
+                    .arg(
+                        Arg::new("prog_file")
+                            .required(true)
+                            .value_name("PROGRAM_FILE")
+                            .action(ArgAction::Set)
+                            .help("SimplicityHL program file to test"),
+                    )
+                    .arg(
+                        Arg::new("test_files")
+                            .value_name("TEST_FILE")
+                            .action(ArgAction::Append)
+                            .help("Additional files with unit tests for the program"),
+                    )
+                    .args(env_args())
+                    .arg(coverage_arg()),
+            )
+            .subcommand(
+                Command::new("run")
+                    .about(
+                        "\
+                        Run the given SimplicityHL program on the Bit Machine, once for each given \
+                        witness file or once without witness data if there are none (requires \
+                        feature 'serde' to be enabled for witness files).\
+                        ",
+                    )
+                    .arg(
+                        Arg::new("prog_file")
+                            .required(true)
+                            .value_name("PROGRAM_FILE")
+                            .action(ArgAction::Set)
+                            .help("SimplicityHL program file to run"),
+                    )
+                    .arg(
+                        Arg::new("wit_files")
+                            .value_name("WITNESS_FILE")
+                            .action(ArgAction::Append)
+                            .help("Files containing the witness data of each run"),
+                    )
+                    .arg(
+                        Arg::new("release")
+                            .long("release")
+                            .value_name("RELEASE_JSON")
+                            .action(ArgAction::Set)
+                            .conflicts_with("wit_files")
+                            .help(
+                                "Run the release program in the output of `simc --json` instead, \
+                                which must be the release build of the given program",
+                            ),
+                    )
+                    .args(env_args())
+                    .arg(coverage_arg()),
+            )
+            .subcommand(
+                Command::new("repl")
+                    .about(
+                        "\
+                        Evaluate SimplicityHL expressions interactively on the Bit Machine. \
+                        Enter `:help` for the list of commands.\
+                        ",
+                    )
+                    .args(env_args()),
+            )
+            .subcommand(
+                Command::new("strip")
+                    .about(
+                        "\
+                        Remove the debug markers from a program that was compiled with debug \
+                        symbols and print the release program with its CMR (requires feature \
+                        'serde' to be enabled).\
+                        ",
+                    )
+                    .arg(
+                        Arg::new("debug_file")
+                            .required(true)
+                            .value_name("DEBUG_JSON")
+                            .action(ArgAction::Set)
+                            .help("Output of `simc --debug --json` for the program"),
+                    )
+                    .arg(
+                        Arg::new("check")
+                            .long("check")
+                            .value_name("PROGRAM_FILE")
+                            .action(ArgAction::Set)
+                            .help(
+                                "SimplicityHL program file whose release build must have the same CMR",
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new("quickcheck")
+                    .about(
+                        "\
+                        Run the given SimplicityHL program with random witness data on the Bit Machine \
+                        and report witness data that the program accepts or that makes it panic. \
+                        The reported witness data is shrunk to a minimal example (requires feature \
+                        'arbitrary' to be enabled).\
+                        ",
+                    )
+                    .arg(
+                        Arg::new("prog_file")
+                            .required(true)
+                            .value_name("PROGRAM_FILE")
+                            .action(ArgAction::Set)
+                            .help("SimplicityHL program file to check"),
+                    )
+                    .arg(
+                        Arg::new("args_file")
+                            .long("args")
+                            .value_name("ARGUMENTS_FILE")
+                            .action(ArgAction::Set)
+                            .help("File containing the arguments of the program (requires feature 'serde')"),
+                    )
+                    .arg(
+                        Arg::new("tests")
+                            .long("tests")
+                            .value_name("N")
+                            .value_parser(clap::value_parser!(usize))
+                            .help("Number of random witnesses to try [default: 100]"),
+                    )
+                    .arg(
+                        Arg::new("seed")
+                            .long("seed")
+                            .value_name("SEED")
+                            .value_parser(clap::value_parser!(u64))
+                            .help("Seed of the random witness generator [default: random]"),
+                    )
+                    .arg(
+                        Arg::new("allow_success")
+                            .long("allow-success")
+                            .action(ArgAction::SetTrue)
+                            .help("Don't report witnesses that the program accepts"),
+                    )
+                    .arg(
+                        Arg::new("allow_panics")
+                            .long("allow-panics")
+                            .action(ArgAction::SetTrue)
+                            .help("Don't report witnesses that make the program panic"),
+                    )
+                    .args(env_args()),
+            )
     };
 
     let matches = command.get_matches();
+    if let Some(("fmt", fmt_matches)) = matches.subcommand() {
+        return run_fmt(fmt_matches);
+    }
+    if let Some(("test", test_matches)) = matches.subcommand() {
+        return run_test(test_matches);
+    }
+    if let Some(("run", run_matches)) = matches.subcommand() {
+        return run_run(run_matches);
+    }
+    if let Some(("repl", repl_matches)) = matches.subcommand() {
+        return run_repl(repl_matches);
+    }
+    if let Some(("strip", strip_matches)) = matches.subcommand() {
+        return run_strip(strip_matches);
+    }
+    if let Some(("quickcheck", quickcheck_matches)) = matches.subcommand() {
+        return run_quickcheck(quickcheck_matches);
+    }
 
     let prog_file = matches.get_one::<String>("prog_file").unwrap();
     let prog_path = std::path::Path::new(prog_file);
@@ -103,9 +311,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    if let Some(source_map_file) = matches.get_one::<String>("source_map") {
+        #[cfg(not(feature = "serde"))]
+        {
+            let _ = source_map_file;
+            return Err(
+                "Program was compiled without the 'serde' feature and cannot output source maps."
+                    .into(),
+            );
+        }
+        #[cfg(feature = "serde")]
+        {
+            let source_map = compiled.source_map().clone().with_file(prog_file.as_str());
+            let json = serde_json::to_string(&source_map)?;
+            std::fs::write(source_map_file, json).map_err(|e| format!("{source_map_file}: {e}"))?;
+        }
+    }
+
     let output = Output {
         program: Base64Display::new(&program_bytes, &STANDARD).to_string(),
         witness: witness_bytes.map(|bytes| Base64Display::new(&bytes, &STANDARD).to_string()),
+        #[cfg(feature = "serde")]
+        debug_symbols: include_debug_symbols.then(|| compiled.debug_symbols().clone()),
     };
 
     if output_json {
@@ -121,3 +348,433 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Arguments that configure the dummy transaction environment.
+fn env_args() -> [Arg; 3] {
+    [
+        Arg::new("lock_time")
+            .long("lock-time")
+            .value_name("LOCK_TIME")
+            .value_parser(clap::value_parser!(u32))
+            .help("Lock time of the dummy transaction (consensus encoding)"),
+        Arg::new("sequence")
+            .long("sequence")
+            .value_name("SEQUENCE")
+            .value_parser(clap::value_parser!(u32))
+            .help("Sequence of the input of the dummy transaction (consensus encoding)"),
+        Arg::new("fee_output")
+            .long("fee-output")
+            .action(ArgAction::SetTrue)
+            .help("Include a fee output in the dummy transaction"),
+    ]
+}
+
+/// Argument that enables the coverage report.
+fn coverage_arg() -> Arg {
+    Arg::new("coverage")
+        .long("coverage")
+        .value_name("LCOV_FILE")
+        .action(ArgAction::Set)
+        .help("Write the coverage of the source code as lcov tracefile and print annotated sources")
+}
+
+/// Create the dummy transaction environment that is configured by [`env_args`].
+fn dummy_env(
+    matches: &ArgMatches,
+) -> ElementsEnv<std::sync::Arc<simplicityhl::elements::Transaction>> {
+    use simplicityhl::elements::{LockTime, Sequence};
+
+    let lock_time = matches
+        .get_one::<u32>("lock_time")
+        .map_or(LockTime::ZERO, |n| LockTime::from_consensus(*n));
+    let sequence = matches
+        .get_one::<u32>("sequence")
+        .map_or(Sequence::MAX, |n| Sequence::from_consensus(*n));
+    simplicityhl::dummy_env::dummy_with(lock_time, sequence, matches.get_flag("fee_output"))
+}
+
+/// Run the `fmt` subcommand.
+fn run_fmt(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let check = matches.get_flag("check");
+    let max_width = matches
+        .get_one::<usize>("max_width")
+        .copied()
+        .unwrap_or(simplicityhl::format::DEFAULT_MAX_WIDTH);
+    let mut unformatted_files = Vec::new();
+
+    for prog_file in matches.get_many::<String>("files").unwrap() {
+        let prog_path = std::path::Path::new(prog_file);
+        let prog_text = std::fs::read_to_string(prog_path).map_err(|e| e.to_string())?;
+        let formatted = simplicityhl::format::format_with_width(&prog_text, max_width)
+            .map_err(|e| format!("{prog_file}:\n{e}"))?;
+
+        if formatted == prog_text {
+            continue;
+        }
+        if check {
+            println!("{prog_file} is not formatted");
+            unformatted_files.push(prog_file);
+        } else {
+            std::fs::write(prog_path, formatted).map_err(|e| e.to_string())?;
+        }
+    }
+
+    if unformatted_files.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} file(s) are not formatted", unformatted_files.len()).into())
+    }
+}
+
+/// Run the `test` subcommand.
+fn run_test(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    use simplicityhl::testing::TestSuite;
+
+    let prog_file = matches.get_one::<String>("prog_file").unwrap();
+    let mut files = Vec::new();
+    for file in std::iter::once(prog_file).chain(
+        matches
+            .get_many::<String>("test_files")
+            .into_iter()
+            .flatten(),
+    ) {
+        let text = std::fs::read_to_string(file).map_err(|e| e.to_string())?;
+        files.push((file.as_str(), text));
+    }
+    // Errors are reported in the file where they occur
+    let suite = TestSuite::from_files(&files)?;
+    let env = dummy_env(matches);
+    let lcov_file = matches.get_one::<String>("coverage");
+    let mut coverage = lcov_file
+        .map(|_| Coverage::new(&files))
+        .transpose()
+        .map_err(|e| format!("{prog_file}:\n{e}"))?;
+
+    let names = suite.names().collect::<Vec<_>>();
+    println!("running {} test(s)", names.len());
+    let mut failures = Vec::new();
+    for name in names {
+        let result = match coverage.as_mut() {
+            Some(coverage) => suite.run_with_coverage(name, &env, coverage),
+            None => suite.run(name, &env),
+        };
+        match result {
+            Ok(()) => println!("test {name} ... ok"),
+            Err(failure) => {
+                println!("test {name} ... FAILED");
+                failures.push((name, failure));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, failure) in &failures {
+            println!("\n---- {name} ----\n{failure}");
+        }
+    }
+    let n_failed = failures.len();
+    let n_passed = suite.names().count() - n_failed;
+    let result = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!("\ntest result: {result}. {n_passed} passed; {n_failed} failed");
+    if let (Some(coverage), Some(lcov_file)) = (coverage, lcov_file) {
+        write_coverage(&coverage, lcov_file)?;
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{n_failed} test(s) failed").into())
+    }
+}
+
+/// Run the `run` subcommand.
+fn run_run(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    use simplicityhl::testing::TestFailure;
+    use simplicityhl::{TemplateProgram, WitnessValues};
+
+    let prog_file = matches.get_one::<String>("prog_file").unwrap();
+    let prog_text = std::fs::read_to_string(prog_file).map_err(|e| e.to_string())?;
+    let env = dummy_env(matches);
+    let lcov_file = matches.get_one::<String>("coverage");
+    let mut coverage = lcov_file
+        .map(|_| Coverage::new(&[(prog_file, &prog_text)]))
+        .transpose()
+        .map_err(|e| format!("{prog_file}:\n{e}"))?;
+    let template = TemplateProgram::new(prog_text)?;
+    let compiled = match coverage {
+        Some(..) => template.instantiate_verified(Arguments::default(), true)?,
+        None => template.instantiate(Arguments::default(), true)?,
+    };
+
+    let mut runs = Vec::new();
+    if let Some(release_file) = matches.get_one::<String>("release") {
+        let release = read_release(release_file)?;
+        runs.push((release_file.as_str(), compiled.instrument(&release)));
+    } else {
+        let wit_files = matches
+            .get_many::<String>("wit_files")
+            .map_or_else(|| vec![None], |files| files.map(Some).collect::<Vec<_>>());
+        for wit_file in wit_files {
+            let witness = match wit_file {
+                Some(wit_file) => read_witness(wit_file)?,
+                None => WitnessValues::default(),
+            };
+            let name = wit_file.map_or("<no witness>", String::as_str);
+            runs.push((name, compiled.satisfy(witness)));
+        }
+    }
+
+    let mut n_failed = 0;
+    for (name, satisfied) in runs {
+        let result = satisfied
+            .map_err(TestFailure::Compile)
+            .and_then(|satisfied| match coverage.as_mut() {
+                Some(coverage) => coverage.run(&satisfied, &env),
+                None => simplicityhl::testing::execute(&satisfied, &env),
+            });
+        match result {
+            Ok(()) => println!("run {name} ... ok"),
+            Err(failure) => {
+                println!("run {name} ... FAILED\n{failure}");
+                n_failed += 1;
+            }
+        }
+    }
+    if let (Some(coverage), Some(lcov_file)) = (coverage, lcov_file) {
+        write_coverage(&coverage, lcov_file)?;
+    }
+
+    if n_failed == 0 {
+        Ok(())
+    } else {
+        Err(format!("{n_failed} run(s) failed").into())
+    }
+}
+
+/// Read the release program with its witness data from the given output of `simc --json`.
+fn read_release(
+    release_file: &str,
+) -> Result<Arc<RedeemNode<simplicityhl::simplicity::jet::Elements>>, Box<dyn std::error::Error>> {
+    #[cfg(not(feature = "serde"))]
+    {
+        let _ = release_file;
+        Err(
+            "Program was compiled without the 'serde' feature and cannot process JSON output."
+                .into(),
+        )
+    }
+    #[cfg(feature = "serde")]
+    {
+        use base64::Engine;
+        use simplicityhl::simplicity::BitIter;
+
+        #[derive(serde::Deserialize)]
+        struct ReleaseOutput {
+            program: String,
+            witness: Option<String>,
+        }
+
+        let release_text = std::fs::read_to_string(release_file).map_err(|e| e.to_string())?;
+        let release_output = serde_json::from_str::<ReleaseOutput>(&release_text)
+            .map_err(|e| format!("{release_file}: {e}"))?;
+        let witness = release_output
+            .witness
+            .ok_or_else(|| format!("{release_file}: missing witness data"))?;
+        let program_bytes = STANDARD
+            .decode(&release_output.program)
+            .map_err(|e| format!("{release_file}: {e}"))?;
+        let witness_bytes = STANDARD
+            .decode(&witness)
+            .map_err(|e| format!("{release_file}: {e}"))?;
+        let release = RedeemNode::decode(
+            BitIter::from(program_bytes.as_slice()),
+            BitIter::from(witness_bytes.as_slice()),
+        )
+        .map_err(|e| format!("{release_file}: {e}"))?;
+        Ok(release)
+    }
+}
+
+/// Read the witness data from the given file.
+fn read_witness(wit_file: &str) -> Result<simplicityhl::WitnessValues, Box<dyn std::error::Error>> {
+    #[cfg(not(feature = "serde"))]
+    {
+        let _ = wit_file;
+        Err(
+            "Program was compiled without the 'serde' feature and cannot process .wit files."
+                .into(),
+        )
+    }
+    #[cfg(feature = "serde")]
+    {
+        let wit_text = std::fs::read_to_string(wit_file).map_err(|e| e.to_string())?;
+        let witness = serde_json::from_str::<simplicityhl::WitnessValues>(&wit_text)
+            .map_err(|e| format!("{wit_file}: {e}"))?;
+        Ok(witness)
+    }
+}
+
+/// Read the arguments of the program from the given file.
+#[cfg_attr(not(feature = "arbitrary"), allow(dead_code))]
+fn read_arguments(args_file: &str) -> Result<Arguments, Box<dyn std::error::Error>> {
+    #[cfg(not(feature = "serde"))]
+    {
+        let _ = args_file;
+        Err(
+            "Program was compiled without the 'serde' feature and cannot process .args files."
+                .into(),
+        )
+    }
+    #[cfg(feature = "serde")]
+    {
+        let args_text = std::fs::read_to_string(args_file).map_err(|e| e.to_string())?;
+        let arguments = serde_json::from_str::<Arguments>(&args_text)
+            .map_err(|e| format!("{args_file}: {e}"))?;
+        Ok(arguments)
+    }
+}
+
+/// Write the coverage as lcov tracefile to `lcov_file`
+/// and print the annotated source files.
+fn write_coverage(coverage: &Coverage, lcov_file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::write(lcov_file, coverage.to_lcov()).map_err(|e| format!("{lcov_file}: {e}"))?;
+    for (name, report) in coverage.annotate() {
+        println!("\ncoverage of {name}:\n{report}");
+    }
+    println!("coverage written to {lcov_file}");
+    Ok(())
+}
+
+/// Run the `repl` subcommand.
+fn run_repl(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    use simplicityhl::repl::Repl;
+    use std::io::{BufRead, Write};
+
+    let mut repl = Repl::new(dummy_env(matches));
+    let mut input = String::new();
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        print!("{}", if input.is_empty() { ">> " } else { ".. " });
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            break;
+        };
+        if input.is_empty() && matches!(line.trim(), ":quit" | ":q") {
+            break;
+        }
+        input.push_str(&line);
+        input.push('\n');
+        if !Repl::is_complete(&input) {
+            continue;
+        }
+        match repl.eval_input(&input) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{output}"),
+            Err(error) => println!("{error}"),
+        }
+        input.clear();
+    }
+    Ok(())
+}
+
+/// Run the `strip` subcommand.
+fn run_strip(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(not(feature = "serde"))]
+    {
+        let _ = matches;
+        Err(
+            "Program was compiled without the 'serde' feature and cannot process debug symbols."
+                .into(),
+        )
+    }
+    #[cfg(feature = "serde")]
+    {
+        use base64::Engine;
+        use simplicityhl::simplicity::{BitIter, CommitNode};
+
+        #[derive(serde::Deserialize)]
+        struct DebugOutput {
+            program: String,
+            debug_symbols: simplicityhl::debug::DebugSymbols,
+        }
+
+        let debug_file = matches.get_one::<String>("debug_file").unwrap();
+        let debug_text = std::fs::read_to_string(debug_file).map_err(|e| e.to_string())?;
+        let debug_output = serde_json::from_str::<DebugOutput>(&debug_text)
+            .map_err(|e| format!("{debug_file}: {e}"))?;
+        let program_bytes = STANDARD
+            .decode(&debug_output.program)
+            .map_err(|e| format!("{debug_file}: {e}"))?;
+        let program = CommitNode::decode(BitIter::from(program_bytes.as_slice()))
+            .map_err(|e| format!("{debug_file}: {e}"))?;
+        let stripped = debug_output.debug_symbols.strip(&program)?;
+
+        if let Some(prog_file) = matches.get_one::<String>("check") {
+            let prog_text = std::fs::read_to_string(prog_file).map_err(|e| e.to_string())?;
+            let release = CompiledProgram::new(prog_text, Arguments::default(), false)?;
+            if release.commit().cmr() != stripped.cmr() {
+                return Err(format!(
+                    "CMR {} of stripped program does not match CMR {} of {prog_file}",
+                    stripped.cmr(),
+                    release.commit().cmr()
+                )
+                .into());
+            }
+        }
+
+        let program_bytes = stripped.to_vec_without_witness();
+        println!(
+            "Program:\n{}",
+            Base64Display::new(&program_bytes, &STANDARD)
+        );
+        println!("CMR:\n{}", stripped.cmr());
+        Ok(())
+    }
+}
+
+/// Run the `quickcheck` subcommand.
+fn run_quickcheck(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(not(feature = "arbitrary"))]
+    {
+        let _ = matches;
+        Err("Program was compiled without the 'arbitrary' feature and cannot generate witness data.".into())
+    }
+    #[cfg(feature = "arbitrary")]
+    {
+        use simplicityhl::quickcheck::{quickcheck, Config, Report};
+
+        let prog_file = matches.get_one::<String>("prog_file").unwrap();
+        let prog_text = std::fs::read_to_string(prog_file).map_err(|e| e.to_string())?;
+        let arguments = match matches.get_one::<String>("args_file") {
+            Some(args_file) => read_arguments(args_file)?,
+            None => Arguments::default(),
+        };
+        let compiled = CompiledProgram::new(prog_text, arguments, true)?;
+        let env = dummy_env(matches);
+        let seed = matches.get_one::<u64>("seed").copied().unwrap_or_else(|| {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default();
+            // Cast is safe because only the lower bits need to vary
+            now.as_nanos() as u64
+        });
+        let config = Config {
+            tests: matches.get_one::<usize>("tests").copied().unwrap_or(100),
+            seed,
+            allow_success: matches.get_flag("allow_success"),
+            allow_panics: matches.get_flag("allow_panics"),
+        };
+
+        match quickcheck(&compiled, &env, &config)? {
+            Report::Passed { tests } => {
+                println!("Passed {tests} test(s) (seed {seed})");
+                Ok(())
+            }
+            Report::Failed(counterexample) => {
+                println!("{counterexample}");
+                Err(format!("Found a counterexample (seed {seed})").into())
+            }
+        }
+    }
+}
@@ -1,9 +1,11 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use simplicity::dag::{InternalSharing, PostOrderIterItem};
+use simplicity::dag::{DagLike, InternalSharing, PostOrderIterItem};
 use simplicity::jet::Jet;
 use simplicity::node::{
-    self, Converter, CoreConstructible, Inner, NoDisconnect, NoWitness, Node, WitnessConstructible,
+    self, Converter, CoreConstructible, Inner, JetConstructible, NoDisconnect, NoWitness, Node,
+    WitnessConstructible,
 };
 use simplicity::Cmr;
 use simplicity::{types, FailEntropy};
@@ -250,6 +252,264 @@ pub fn populate_witnesses<J: Jet>(
     node.convert::<InternalSharing, _, _>(&mut populator)
 }
 
+/// Pair the nodes of the `target` code with the nodes of the `original` code at the same place.
+///
+/// The target code has the same structure as the original code,
+/// except for case nodes that became assertions, like in a pruned program,
+/// and except for the sharing of nodes, like in a program that was decoded from its encoding.
+/// A target node is paired with several original nodes if it stands for each of them.
+///
+/// The pairs are returned from the roots downwards, each pair once.
+pub fn pair_nodes<'a, 'b, M: node::Marker, N: node::Marker>(
+    original: &'a Node<M>,
+    target: &'b Node<N>,
+) -> Vec<(&'a Node<M>, &'b Node<N>)> {
+    let mut pairs = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![(original, target)];
+    while let Some((original, target)) = stack.pop() {
+        if !visited.insert((std::ptr::from_ref(original), std::ptr::from_ref(target))) {
+            continue;
+        }
+        pairs.push((original, target));
+        match (original.inner(), target.inner()) {
+            (Inner::Case(left, _), Inner::AssertL(target_left, _)) => {
+                stack.push((left, target_left));
+            }
+            (Inner::Case(_, right), Inner::AssertR(_, target_right)) => {
+                stack.push((right, target_right));
+            }
+            _ if original.n_children() == target.n_children() => {
+                stack.extend(original.right_child().zip(target.right_child()));
+                stack.extend(original.left_child().zip(target.left_child()));
+            }
+            _ => {}
+        }
+    }
+    pairs
+}
+
+/// Result of removing debug markers from target code.
+pub struct Stripped<N> {
+    /// Target code without debug markers.
+    pub node: N,
+    /// Map from the CMRs of the original nodes to the CMRs of the nodes they turned into.
+    ///
+    /// Debug markers and the nodes that attach them are missing from the map.
+    pub cmrs: HashMap<Cmr, Cmr>,
+    /// Position of the original node that each node of the stripped code stems from,
+    /// both in post order.
+    ///
+    /// A node that replaces the node which attaches a debug marker
+    /// stems from the innermost original node it was converted from.
+    pub origins: Vec<usize>,
+}
+
+/// How a node of target code with debug markers turns into target code without them.
+enum Strip<'a, M: node::Marker> {
+    /// The node is replaced by one of its descendants.
+    Replace(&'a Node<M>),
+    /// The node is the composition of the two given descendants.
+    Comp(&'a Node<M>, &'a Node<M>),
+    /// The node keeps its combinator and its children.
+    Keep,
+}
+
+/// Remove the debug markers from the given target code,
+/// where `is_marker` returns true for the CMRs of debug symbols.
+///
+/// Debug markers are no-ops that the compiler inserts next to release code:
+///
+/// - A call with a debug symbol is `comp (pair false args) (assertl (drop body) cmr)`
+///   instead of `comp args body`.
+/// - A step symbol is `comp (pair marker expr) (drop iden)` instead of `expr`.
+/// - The exit of a function body is `comp (pair body marker) (take iden)` instead of `body`.
+///
+/// The result is rebuilt inside the given inference context.
+/// Witness nodes are converted via `witness`.
+///
+/// ## Errors
+///
+/// The target code contains disconnect nodes.
+pub fn strip_markers<'brand, M, N, W, F, G>(
+    inference_context: &types::Context<'brand>,
+    root: &Node<M>,
+    is_marker: F,
+    mut witness: G,
+) -> Result<Stripped<Arc<Node<N>>>, String>
+where
+    M: node::Marker,
+    N: node::Marker<Jet = M::Jet>,
+    Arc<Node<N>>: CoreConstructible<'brand>
+        + JetConstructible<'brand, M::Jet>
+        + WitnessConstructible<'brand, W>,
+    F: Fn(Cmr) -> bool,
+    G: FnMut(&M::Witness) -> W,
+{
+    let items = root
+        .post_order_iter::<InternalSharing>()
+        .collect::<Vec<_>>();
+    let index = items
+        .iter()
+        .map(|item| (std::ptr::from_ref(item.node), item.index))
+        .collect::<HashMap<_, _>>();
+    let index_of = |node: &Node<M>| index[&std::ptr::from_ref(node)];
+
+    // Find the nodes that remain, from the root downwards
+    let mut needed = vec![false; items.len()];
+    if let Some(last) = needed.last_mut() {
+        *last = true;
+    }
+    for item in items.iter().rev() {
+        if !needed[item.index] {
+            continue;
+        }
+        match strip(item.node, &is_marker) {
+            Strip::Replace(node) => needed[index_of(node)] = true,
+            Strip::Comp(left, right) => {
+                needed[index_of(left)] = true;
+                needed[index_of(right)] = true;
+            }
+            Strip::Keep => {
+                for child in [item.left_index, item.right_index].into_iter().flatten() {
+                    needed[child] = true;
+                }
+            }
+        }
+    }
+
+    // Rebuild the remaining nodes, from the leaves upwards
+    let mut converted: Vec<Option<Arc<Node<N>>>> = vec![None; items.len()];
+    let mut cmrs = HashMap::new();
+    for item in &items {
+        if !needed[item.index] {
+            continue;
+        }
+        let get = |node: &Node<M>| {
+            converted[index_of(node)]
+                .clone()
+                .expect("children are converted before their parents")
+        };
+        let node = match strip(item.node, &is_marker) {
+            Strip::Replace(node) => get(node),
+            Strip::Comp(left, right) => {
+                Arc::<Node<N>>::comp(&get(left), &get(right)).map_err(|e| e.to_string())?
+            }
+            Strip::Keep => {
+                let ctx = inference_context;
+                match item.node.inner() {
+                    Inner::Iden => Arc::<Node<N>>::iden(ctx),
+                    Inner::Unit => Arc::<Node<N>>::unit(ctx),
+                    Inner::InjL(child) => Arc::<Node<N>>::injl(&get(child)),
+                    Inner::InjR(child) => Arc::<Node<N>>::injr(&get(child)),
+                    Inner::Take(child) => Arc::<Node<N>>::take(&get(child)),
+                    Inner::Drop(child) => Arc::<Node<N>>::drop_(&get(child)),
+                    Inner::Comp(left, right) => {
+                        Arc::<Node<N>>::comp(&get(left), &get(right)).map_err(|e| e.to_string())?
+                    }
+                    Inner::Case(left, right) => {
+                        Arc::<Node<N>>::case(&get(left), &get(right)).map_err(|e| e.to_string())?
+                    }
+                    Inner::AssertL(left, cmr) => {
+                        Arc::<Node<N>>::assertl(&get(left), *cmr).map_err(|e| e.to_string())?
+                    }
+                    Inner::AssertR(cmr, right) => {
+                        Arc::<Node<N>>::assertr(*cmr, &get(right)).map_err(|e| e.to_string())?
+                    }
+                    Inner::Pair(left, right) => {
+                        Arc::<Node<N>>::pair(&get(left), &get(right)).map_err(|e| e.to_string())?
+                    }
+                    Inner::Disconnect(..) => {
+                        return Err("Cannot strip debug markers from disconnect nodes".to_string())
+                    }
+                    Inner::Witness(value) => Arc::<Node<N>>::witness(ctx, witness(value)),
+                    Inner::Fail(entropy) => Arc::<Node<N>>::fail(ctx, *entropy),
+                    Inner::Jet(jet) => Arc::<Node<N>>::jet(ctx, *jet),
+                    Inner::Word(word) => Arc::<Node<N>>::const_word(ctx, word.shallow_clone()),
+                }
+            }
+        };
+        cmrs.insert(item.node.cmr(), node.cmr());
+        converted[item.index] = Some(node);
+    }
+
+    // Descendants come first in post order, so the innermost original node is found first
+    let mut origin_of = HashMap::new();
+    for (index, node) in converted.iter().enumerate() {
+        if let Some(node) = node {
+            origin_of.entry(Arc::as_ptr(node)).or_insert(index);
+        }
+    }
+    let node = converted
+        .pop()
+        .flatten()
+        .expect("the root is converted last");
+    let origins = node
+        .as_ref()
+        .post_order_iter::<InternalSharing>()
+        .map(|item| origin_of[&std::ptr::from_ref(item.node)])
+        .collect();
+    Ok(Stripped {
+        node,
+        cmrs,
+        origins,
+    })
+}
+
+fn strip<'a, M: node::Marker, F: Fn(Cmr) -> bool>(
+    node: &'a Node<M>,
+    is_marker: &F,
+) -> Strip<'a, M> {
+    let Inner::Comp(left, right) = node.inner() else {
+        return Strip::Keep;
+    };
+    match (left.inner(), right.inner()) {
+        (Inner::Pair(marker, expr), Inner::Drop(x))
+            if is_iden(x) && is_marker_node(marker, is_marker) =>
+        {
+            Strip::Replace(expr)
+        }
+        (Inner::Pair(expr, marker), Inner::Take(x))
+            if is_iden(x) && is_marker_node(marker, is_marker) =>
+        {
+            Strip::Replace(expr)
+        }
+        (Inner::Pair(bit, args), Inner::AssertL(nop, cmr)) if is_false(bit) && is_marker(*cmr) => {
+            match nop.inner() {
+                Inner::Drop(body) => Strip::Comp(args, body),
+                _ => Strip::Keep,
+            }
+        }
+        _ => Strip::Keep,
+    }
+}
+
+/// Check if the node is a debug marker `comp (pair false value) (assertl (drop unit) cmr)`.
+fn is_marker_node<M: node::Marker, F: Fn(Cmr) -> bool>(node: &Node<M>, is_marker: &F) -> bool {
+    let Inner::Comp(left, right) = node.inner() else {
+        return false;
+    };
+    match (left.inner(), right.inner()) {
+        (Inner::Pair(bit, _), Inner::AssertL(nop, cmr)) => {
+            is_false(bit) && matches!(nop.inner(), Inner::Drop(x) if is_unit(x)) && is_marker(*cmr)
+        }
+        _ => false,
+    }
+}
+
+fn is_iden<M: node::Marker>(node: &Node<M>) -> bool {
+    matches!(node.inner(), Inner::Iden)
+}
+
+fn is_unit<M: node::Marker>(node: &Node<M>) -> bool {
+    matches!(node.inner(), Inner::Unit)
+}
+
+/// Check if the node is `injl unit`, which outputs the bit `false`.
+fn is_false<M: node::Marker>(node: &Node<M>) -> bool {
+    matches!(node.inner(), Inner::InjL(x) if is_unit(x))
+}
+
 // This awkward construction is required by rust-simplicity to implement WitnessConstructible
 // for Node<WithNames<Construct>>. See
 //     https://docs.rs/simplicity-lang/latest/simplicity/node/trait.WitnessConstructible.html#foreign-impls
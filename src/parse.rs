@@ -24,7 +24,7 @@ use crate::types::{AliasedType, BuiltinAlias, TypeConstructible, UIntType};
 
 #[derive(Parser)]
 #[grammar = "minimal.pest"]
-struct IdentParser;
+pub(crate) struct IdentParser;
 
 /// A program is a sequence of items.
 #[derive(Clone, Debug)]
@@ -38,6 +38,45 @@ impl Program {
     pub fn items(&self) -> &[Item] {
         &self.items
     }
+
+    /// Iterate over the unit tests of the program.
+    pub fn tests(&self) -> impl Iterator<Item = &Function> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Function(function) if function.is_test() => Some(function),
+            _ => None,
+        })
+    }
+
+    /// Turn the unit test with the given name into a standalone program.
+    ///
+    /// The body of the test becomes the main function.
+    /// The original main function and all other unit tests are removed.
+    ///
+    /// Return `None` if there is no unit test with the given name.
+    pub fn test_program(&self, name: &FunctionName) -> Option<Self> {
+        let test = self.tests().find(|function| function.name() == name)?;
+        let items = self
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Function(function) if function == test => Some(Item::Function(Function {
+                    name: FunctionName::main(),
+                    is_test: false,
+                    ..function.clone()
+                })),
+                Item::Function(function)
+                    if function.is_test() || function.name().as_inner() == "main" =>
+                {
+                    None
+                }
+                _ => Some(item.clone()),
+            })
+            .collect();
+        Some(Self {
+            items,
+            span: self.span,
+        })
+    }
 }
 
 impl_eq_hash!(Program; items);
@@ -50,6 +89,8 @@ pub enum Item {
     TypeAlias(TypeAlias),
     /// A function.
     Function(Function),
+    /// A constant.
+    Constant(Constant),
     /// A module, which is ignored.
     Module,
 }
@@ -58,6 +99,7 @@ pub enum Item {
 #[derive(Clone, Debug)]
 pub struct Function {
     name: FunctionName,
+    is_test: bool,
     params: Arc<[FunctionParam]>,
     ret: Option<AliasedType>,
     body: Expression,
@@ -87,13 +129,21 @@ impl Function {
         &self.body
     }
 
+    /// Check if the function is a unit test, which is marked with `#[test]`.
+    ///
+    /// Unit tests are ignored when the program is compiled.
+    /// They are compiled and run by the test runner instead.
+    pub fn is_test(&self) -> bool {
+        self.is_test
+    }
+
     /// Access the span of the function.
     pub fn span(&self) -> &Span {
         &self.span
     }
 }
 
-impl_eq_hash!(Function; name, params, ret, body);
+impl_eq_hash!(Function; name, is_test, params, ret, body);
 
 /// Parameter of a function.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
@@ -247,6 +297,39 @@ impl TypeAlias {
 
 impl_eq_hash!(TypeAlias; name, ty);
 
+/// A constant that is evaluated at compile time.
+#[derive(Clone, Debug)]
+pub struct Constant {
+    name: Identifier,
+    ty: AliasedType,
+    expression: Expression,
+    span: Span,
+}
+
+impl Constant {
+    /// Access the name of the constant.
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
+
+    /// Access the declared type of the constant.
+    pub fn ty(&self) -> &AliasedType {
+        &self.ty
+    }
+
+    /// Access the expression that defines the constant.
+    pub fn expression(&self) -> &Expression {
+        &self.expression
+    }
+
+    /// Access the span of the constant.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl_eq_hash!(Constant; name, ty, expression);
+
 /// An expression is something that returns a value.
 #[derive(Clone, Debug)]
 pub struct Expression {
@@ -347,6 +430,10 @@ pub enum SingleExpressionInner {
     ///
     /// The exclusive upper bound on the list size is not known at this point
     List(Arc<[Expression]>),
+    /// Bounded loop over a range, an array or a list
+    For(Arc<ForLoop>),
+    /// Early exit from the enclosing loop
+    Break,
 }
 
 /// Match expression.
@@ -453,6 +540,178 @@ impl MatchPattern {
     }
 }
 
+/// Bounded `for` loop.
+///
+/// The loop binds each element of the iterable to the pattern and evaluates the body.
+/// The body returns the next value of the accumulator.
+/// The loop returns the final value of the accumulator,
+/// or nothing (unit) if there is no accumulator.
+#[derive(Clone, Debug)]
+pub struct ForLoop {
+    pattern: Pattern,
+    ty: Option<AliasedType>,
+    iterable: Iterable,
+    accumulator: Option<Accumulator>,
+    body: Arc<Expression>,
+    span: Span,
+}
+
+impl ForLoop {
+    /// Access the pattern that binds each element.
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    /// Access the declared type of each element, if any.
+    pub fn ty(&self) -> Option<&AliasedType> {
+        self.ty.as_ref()
+    }
+
+    /// Access the values that are iterated over.
+    pub fn iterable(&self) -> &Iterable {
+        &self.iterable
+    }
+
+    /// Access the accumulator of the loop, if any.
+    pub fn accumulator(&self) -> Option<&Accumulator> {
+        self.accumulator.as_ref()
+    }
+
+    /// Access the body of the loop.
+    pub fn body(&self) -> &Expression {
+        &self.body
+    }
+
+    /// Access the span of the loop.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Rewrite the body of a loop that may `break`.
+    ///
+    /// Each `break` in tail position becomes `Left(acc)` and
+    /// each other final value `x` becomes `Right(x)`,
+    /// where `acc` is the current value of the accumulator.
+    ///
+    /// Return `None` if the body contains no `break` in tail position.
+    pub(crate) fn breaking_body(&self) -> Option<Expression> {
+        fn has_break(expr: &Expression) -> bool {
+            match expr.inner() {
+                ExpressionInner::Block(_, Some(tail)) => has_break(tail),
+                ExpressionInner::Block(_, None) => false,
+                ExpressionInner::Single(single) => match single.inner() {
+                    SingleExpressionInner::Break => true,
+                    SingleExpressionInner::Expression(inner) => has_break(inner),
+                    SingleExpressionInner::Match(match_) => {
+                        has_break(match_.left().expression())
+                            || has_break(match_.right().expression())
+                    }
+                    _ => false,
+                },
+            }
+        }
+
+        fn wrap(inner: SingleExpressionInner, span: Span) -> Expression {
+            Expression {
+                inner: ExpressionInner::Single(SingleExpression { inner, span }),
+                span,
+            }
+        }
+
+        fn rewrite(expr: &Expression, acc: &SingleExpressionInner) -> Expression {
+            if !has_break(expr) {
+                let inner = SingleExpressionInner::Either(Either::Right(Arc::new(expr.clone())));
+                return wrap(inner, expr.span);
+            }
+            match expr.inner() {
+                ExpressionInner::Block(statements, Some(tail)) => Expression {
+                    inner: ExpressionInner::Block(
+                        statements.clone(),
+                        Some(Arc::new(rewrite(tail, acc))),
+                    ),
+                    span: expr.span,
+                },
+                ExpressionInner::Single(single) => match single.inner() {
+                    SingleExpressionInner::Break => {
+                        let acc = wrap(acc.clone(), expr.span);
+                        let inner = SingleExpressionInner::Either(Either::Left(Arc::new(acc)));
+                        wrap(inner, expr.span)
+                    }
+                    SingleExpressionInner::Expression(inner) => rewrite(inner, acc),
+                    SingleExpressionInner::Match(match_) => {
+                        let arm = |arm: &MatchArm| MatchArm {
+                            pattern: arm.pattern.clone(),
+                            expression: Arc::new(rewrite(arm.expression(), acc)),
+                        };
+                        let match_ = Match {
+                            scrutinee: match_.scrutinee.clone(),
+                            left: arm(match_.left()),
+                            right: arm(match_.right()),
+                            span: match_.span,
+                        };
+                        wrap(SingleExpressionInner::Match(match_), expr.span)
+                    }
+                    _ => unreachable!("Expression has break in tail position"),
+                },
+                ExpressionInner::Block(_, None) => {
+                    unreachable!("Expression has break in tail position")
+                }
+            }
+        }
+
+        let acc = match &self.accumulator {
+            Some(accumulator) => SingleExpressionInner::Variable(accumulator.name.clone()),
+            None => SingleExpressionInner::Tuple(Arc::from([])),
+        };
+        has_break(&self.body).then(|| rewrite(&self.body, &acc))
+    }
+}
+
+impl_eq_hash!(ForLoop; pattern, ty, iterable, accumulator, body);
+
+/// Values that a `for` loop iterates over.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Iterable {
+    /// Half-open range of unsigned integers.
+    Range(RangeBound, RangeBound),
+    /// Variable or constant of array or list type.
+    Variable(Identifier),
+}
+
+/// Bound of a range.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum RangeBound {
+    /// Integer literal.
+    Literal(usize),
+    /// Name of a constant.
+    Constant(Identifier),
+}
+
+/// Accumulator of a `for` loop.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Accumulator {
+    name: Identifier,
+    ty: AliasedType,
+    initial: Arc<Expression>,
+}
+
+impl Accumulator {
+    /// Access the name of the accumulator.
+    pub fn name(&self) -> &Identifier {
+        &self.name
+    }
+
+    /// Access the declared type of the accumulator.
+    pub fn ty(&self) -> &AliasedType {
+        &self.ty
+    }
+
+    /// Access the expression of the initial value.
+    pub fn initial(&self) -> &Expression {
+        &self.initial
+    }
+}
+
 /// Program root when parsing modules.
 #[derive(Clone, Debug)]
 pub struct ModuleProgram {
@@ -544,6 +803,7 @@ impl fmt::Display for Item {
         match self {
             Self::TypeAlias(alias) => write!(f, "{alias}"),
             Self::Function(function) => write!(f, "{function}"),
+            Self::Constant(constant) => write!(f, "{constant}"),
             // The parse tree contains no information about the contents of modules.
             // We print a random empty module `mod witness {}` here
             // so that `from_string(to_string(x)) = x` holds for all trees `x`.
@@ -558,8 +818,23 @@ impl fmt::Display for TypeAlias {
     }
 }
 
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "const {}: {} = {};",
+            self.name(),
+            self.ty(),
+            self.expression()
+        )
+    }
+}
+
 impl fmt::Display for Function {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_test() {
+            writeln!(f, "#[test]")?;
+        }
         write!(f, "fn {}(", self.name())?;
         for (i, param) in self.params().iter().enumerate() {
             if 0 < i {
@@ -590,6 +865,7 @@ pub enum ExprTree<'a> {
     Single(&'a SingleExpression),
     Call(&'a Call),
     Match(&'a Match),
+    For(&'a ForLoop),
 }
 
 impl TreeLike for ExprTree<'_> {
@@ -623,13 +899,15 @@ impl TreeLike for ExprTree<'_> {
                 | S::Variable(_)
                 | S::Witness(_)
                 | S::Parameter(_)
-                | S::Option(None) => Tree::Nullary,
+                | S::Option(None)
+                | S::Break => Tree::Nullary,
                 S::Option(Some(l))
                 | S::Either(Either::Left(l))
                 | S::Either(Either::Right(l))
                 | S::Expression(l) => Tree::Unary(Self::Expression(l)),
                 S::Call(call) => Tree::Unary(Self::Call(call)),
                 S::Match(match_) => Tree::Unary(Self::Match(match_)),
+                S::For(for_loop) => Tree::Unary(Self::For(for_loop)),
                 S::Tuple(elements) | S::Array(elements) | S::List(elements) => {
                     Tree::Nary(elements.iter().map(Self::Expression).collect())
                 }
@@ -640,6 +918,15 @@ impl TreeLike for ExprTree<'_> {
                 Self::Expression(match_.left().expression()),
                 Self::Expression(match_.right().expression()),
             ])),
+            Self::For(for_loop) => Tree::Nary(
+                for_loop
+                    .accumulator()
+                    .map(Accumulator::initial)
+                    .into_iter()
+                    .chain(std::iter::once(for_loop.body()))
+                    .map(Self::Expression)
+                    .collect(),
+            ),
         }
     }
 }
@@ -675,6 +962,7 @@ impl fmt::Display for ExprTree<'_> {
                     S::Witness(name) => write!(f, "witness::{name}")?,
                     S::Parameter(name) => write!(f, "param::{name}")?,
                     S::Option(None) => write!(f, "None")?,
+                    S::Break => write!(f, "break")?,
                     S::Option(Some(_)) => match data.n_children_yielded {
                         0 => write!(f, "Some(")?,
                         n => {
@@ -703,7 +991,7 @@ impl fmt::Display for ExprTree<'_> {
                             write!(f, ")")?;
                         }
                     },
-                    S::Call(..) | S::Match(..) => {}
+                    S::Call(..) | S::Match(..) | S::For(..) => {}
                     S::Tuple(tuple) => {
                         if data.n_children_yielded == 0 {
                             write!(f, "(")?;
@@ -754,6 +1042,21 @@ impl fmt::Display for ExprTree<'_> {
                         write!(f, ",\n}}")?;
                     }
                 },
+                Self::For(for_loop) => {
+                    if data.n_children_yielded == 0 {
+                        write!(f, "for {}", for_loop.pattern())?;
+                        if let Some(ty) = for_loop.ty() {
+                            write!(f, ": {ty}")?;
+                        }
+                        write!(f, " in {}", for_loop.iterable())?;
+                        match for_loop.accumulator() {
+                            Some(acc) => write!(f, ", mut {}: {} = ", acc.name(), acc.ty())?,
+                            None => write!(f, " ")?,
+                        }
+                    } else if !data.is_complete {
+                        write!(f, " ")?;
+                    }
+                }
             }
         }
 
@@ -791,6 +1094,24 @@ impl fmt::Display for Call {
     }
 }
 
+impl fmt::Display for Iterable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Iterable::Range(start, end) => write!(f, "{start}..{end}"),
+            Iterable::Variable(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl fmt::Display for RangeBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeBound::Literal(n) => write!(f, "{n}"),
+            RangeBound::Constant(name) => write!(f, "{name}"),
+        }
+    }
+}
+
 impl fmt::Display for CallName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -857,11 +1178,65 @@ macro_rules! impl_parse_wrapped_string {
 }
 
 impl_parse_wrapped_string!(FunctionName, function_name);
-impl_parse_wrapped_string!(Identifier, identifier);
 impl_parse_wrapped_string!(WitnessName, witness_name);
 impl_parse_wrapped_string!(AliasName, alias_name);
 impl_parse_wrapped_string!(ModuleName, module_name);
 
+impl PestParse for Identifier {
+    const RULE: Rule = Rule::identifier;
+
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, RichError> {
+        assert!(matches!(pair.as_rule(), Self::RULE));
+        let identifier = pair.as_str();
+        if Self::RESERVED.contains(&identifier) {
+            return Err(Error::Grammar(format!(
+                "`{identifier}` is a keyword and cannot be used as identifier"
+            )))
+            .with_span(&pair);
+        }
+        Ok(Self::from_str_unchecked(identifier))
+    }
+}
+
+/// Parse the concrete syntax tree of a program.
+///
+/// Unlike [`Program`], the concrete syntax tree contains every token of the source text
+/// together with its position. Whitespace and comments are skipped.
+pub fn parse_syntax_tree(s: &str) -> Result<pest::iterators::Pair<'_, Rule>, RichError> {
+    let mut pairs = IdentParser::parse(Rule::program, s)
+        .map_err(RichError::from)
+        .with_file(s)?;
+    Ok(pairs.next().unwrap())
+}
+
+/// Find the byte ranges of all comments in the source text.
+///
+/// Comments are not part of the parse tree, so this function scans the source text.
+/// SimplicityHL has no string literals,
+/// so every comment delimiter that is not inside another comment starts a new comment.
+pub fn comments(s: &str) -> Vec<std::ops::Range<usize>> {
+    let bytes = s.as_bytes();
+    let mut comments = Vec::new();
+    let mut index = 0;
+
+    while index + 1 < bytes.len() {
+        let end = match &bytes[index..index + 2] {
+            b"//" => s[index..].find('\n').map_or(s.len(), |n| index + n),
+            b"/*" => s[index + 2..]
+                .find("*/")
+                .map_or(s.len(), |n| index + 2 + n + 2),
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+        comments.push(index..end);
+        index = end;
+    }
+
+    comments
+}
+
 /// Copy of [`FromStr`] that internally uses the PEST parser.
 pub trait ParseFromStr: Sized {
     /// Parse a value from the string `s`.
@@ -904,6 +1279,15 @@ impl PestParse for Item {
         match pair.as_rule() {
             Rule::type_alias => TypeAlias::parse(pair).map(Item::TypeAlias),
             Rule::function => Function::parse(pair).map(Item::Function),
+            Rule::constant => Constant::parse(pair).map(Item::Constant),
+            Rule::test_function => {
+                let pair = pair.into_inner().nth(1).unwrap();
+                let function = Function::parse(pair)?;
+                Ok(Item::Function(Function {
+                    is_test: true,
+                    ..function
+                }))
+            }
             _ => Ok(Self::Module),
         }
     }
@@ -939,6 +1323,7 @@ impl PestParse for Function {
 
         Ok(Self {
             name,
+            is_test: false,
             params,
             ret,
             body,
@@ -1134,6 +1519,26 @@ impl PestParse for TypeAlias {
     }
 }
 
+impl PestParse for Constant {
+    const RULE: Rule = Rule::constant;
+
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, RichError> {
+        assert!(matches!(pair.as_rule(), Self::RULE));
+        let span = Span::from(&pair);
+        let mut it = pair.into_inner();
+        let _const_keyword = it.next().unwrap();
+        let name = Identifier::parse(it.next().unwrap())?;
+        let ty = AliasedType::parse(it.next().unwrap())?;
+        let expression = Expression::parse(it.next().unwrap())?;
+        Ok(Self {
+            name,
+            ty,
+            expression,
+            span,
+        })
+    }
+}
+
 impl PestParse for Expression {
     const RULE: Rule = Rule::expression;
 
@@ -1220,6 +1625,10 @@ impl PestParse for SingleExpression {
                 SingleExpressionInner::Expression(Expression::parse(inner_pair).map(Arc::new)?)
             }
             Rule::match_expr => Match::parse(inner_pair).map(SingleExpressionInner::Match)?,
+            Rule::for_expr => ForLoop::parse(inner_pair)
+                .map(Arc::new)
+                .map(SingleExpressionInner::For)?,
+            Rule::break_expr => SingleExpressionInner::Break,
             Rule::tuple_expr => inner_pair
                 .clone()
                 .into_inner()
@@ -1310,6 +1719,92 @@ impl PestParse for Match {
     }
 }
 
+impl PestParse for ForLoop {
+    const RULE: Rule = Rule::for_expr;
+
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, RichError> {
+        assert!(matches!(pair.as_rule(), Self::RULE));
+        let span = Span::from(&pair);
+        let mut it = pair.into_inner().peekable();
+        let _for_keyword = it.next().unwrap();
+        let pattern = Pattern::parse(it.next().unwrap())?;
+        let ty = it
+            .next_if(|pair| matches!(pair.as_rule(), Rule::ty))
+            .map(AliasedType::parse)
+            .transpose()?;
+        let _in_keyword = it.next().unwrap();
+        let iterable = Iterable::parse(it.next().unwrap())?;
+        let accumulator = it
+            .next_if(|pair| matches!(pair.as_rule(), Rule::for_accumulator))
+            .map(Accumulator::parse)
+            .transpose()?;
+        let body = Expression::parse(it.next().unwrap()).map(Arc::new)?;
+
+        Ok(Self {
+            pattern,
+            ty,
+            iterable,
+            accumulator,
+            body,
+            span,
+        })
+    }
+}
+
+impl PestParse for Iterable {
+    const RULE: Rule = Rule::for_iterable;
+
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, RichError> {
+        assert!(matches!(pair.as_rule(), Self::RULE));
+        let pair = pair.into_inner().next().unwrap();
+        match pair.as_rule() {
+            Rule::range => {
+                let mut it = pair.into_inner();
+                let start = RangeBound::parse(it.next().unwrap())?;
+                let end = RangeBound::parse(it.next().unwrap())?;
+                Ok(Self::Range(start, end))
+            }
+            Rule::variable_expr => {
+                Identifier::parse(pair.into_inner().next().unwrap()).map(Self::Variable)
+            }
+            _ => unreachable!("Corrupt grammar"),
+        }
+    }
+}
+
+impl PestParse for RangeBound {
+    const RULE: Rule = Rule::range_bound;
+
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, RichError> {
+        assert!(matches!(pair.as_rule(), Self::RULE));
+        let pair = pair.into_inner().next().unwrap();
+        match pair.as_rule() {
+            Rule::array_size => {
+                let n = pair.as_str().parse::<usize>().with_span(&pair)?;
+                Ok(Self::Literal(n))
+            }
+            Rule::const_name => {
+                Identifier::parse(pair.into_inner().next().unwrap()).map(Self::Constant)
+            }
+            _ => unreachable!("Corrupt grammar"),
+        }
+    }
+}
+
+impl PestParse for Accumulator {
+    const RULE: Rule = Rule::for_accumulator;
+
+    fn parse(pair: pest::iterators::Pair<Rule>) -> Result<Self, RichError> {
+        assert!(matches!(pair.as_rule(), Self::RULE));
+        let mut it = pair.into_inner();
+        let _mut_keyword = it.next().unwrap();
+        let name = Identifier::parse(it.next().unwrap())?;
+        let ty = AliasedType::parse(it.next().unwrap())?;
+        let initial = Expression::parse(it.next().unwrap()).map(Arc::new)?;
+        Ok(Self { name, ty, initial })
+    }
+}
+
 impl PestParse for MatchArm {
     const RULE: Rule = Rule::match_arm;
 
@@ -1361,6 +1856,7 @@ impl PestParse for AliasedType {
             Type(AliasedType),
             Size(usize),
             Bound(NonZeroPow2Usize),
+            Constant(Identifier),
         }
 
         impl Item {
@@ -1370,20 +1866,6 @@ impl PestParse for AliasedType {
                     _ => panic!("Not a type"),
                 }
             }
-
-            fn unwrap_size(self) -> usize {
-                match self {
-                    Item::Size(size) => size,
-                    _ => panic!("Not a size"),
-                }
-            }
-
-            fn unwrap_bound(self) -> NonZeroPow2Usize {
-                match self {
-                    Item::Bound(size) => size,
-                    _ => panic!("Not a bound"),
-                }
-            }
         }
 
         assert!(matches!(pair.as_rule(), Self::RULE));
@@ -1427,9 +1909,14 @@ impl PestParse for AliasedType {
                     output.push(Item::Type(AliasedType::tuple(elements)));
                 }
                 Rule::array_type => {
-                    let size = output.pop().unwrap().unwrap_size();
+                    let size = output.pop().unwrap();
                     let el = output.pop().unwrap().unwrap_type();
-                    output.push(Item::Type(AliasedType::array(el, size)));
+                    let ty = match size {
+                        Item::Size(size) => AliasedType::array(el, size),
+                        Item::Constant(name) => AliasedType::named_array(el, name),
+                        _ => panic!("Not a size"),
+                    };
+                    output.push(Item::Type(ty));
                 }
                 Rule::array_size => {
                     let size_str = data.node.0.as_str();
@@ -1437,14 +1924,23 @@ impl PestParse for AliasedType {
                     output.push(Item::Size(size));
                 }
                 Rule::list_type => {
-                    let bound = output.pop().unwrap().unwrap_bound();
+                    let bound = output.pop().unwrap();
                     let el = output.pop().unwrap().unwrap_type();
-                    output.push(Item::Type(AliasedType::list(el, bound)));
+                    let ty = match bound {
+                        Item::Bound(bound) => AliasedType::list(el, bound),
+                        Item::Constant(name) => AliasedType::named_list(el, name),
+                        _ => panic!("Not a bound"),
+                    };
+                    output.push(Item::Type(ty));
                 }
                 Rule::list_bound => {
                     let bound = NonZeroPow2Usize::parse(data.node.0)?;
                     output.push(Item::Bound(bound));
                 }
+                Rule::const_name => {
+                    let name = Identifier::parse(data.node.0.into_inner().next().unwrap())?;
+                    output.push(Item::Constant(name));
+                }
                 Rule::ty => {}
                 _ => unreachable!("Corrupt grammar"),
             }
@@ -1604,6 +2100,7 @@ impl TreeLike for TyPair<'_> {
             | Rule::unsigned_type
             | Rule::array_size
             | Rule::list_bound
+            | Rule::const_name
             | Rule::alias_name
             | Rule::builtin_alias => Tree::Nullary,
             Rule::ty | Rule::option_type => {
@@ -1633,12 +2130,27 @@ impl AsRef<Span> for Program {
     }
 }
 
+impl AsRef<Span> for Constant {
+    fn as_ref(&self) -> &Span {
+        &self.span
+    }
+}
+
 impl AsRef<Span> for Function {
     fn as_ref(&self) -> &Span {
         &self.span
     }
 }
 
+impl AsRef<Span> for Statement {
+    fn as_ref(&self) -> &Span {
+        match self {
+            Self::Assignment(assignment) => assignment.as_ref(),
+            Self::Expression(expression) => expression.as_ref(),
+        }
+    }
+}
+
 impl AsRef<Span> for Assignment {
     fn as_ref(&self) -> &Span {
         &self.span
@@ -1675,6 +2187,12 @@ impl AsRef<Span> for Match {
     }
 }
 
+impl AsRef<Span> for ForLoop {
+    fn as_ref(&self) -> &Span {
+        &self.span
+    }
+}
+
 impl AsRef<Span> for ModuleProgram {
     fn as_ref(&self) -> &Span {
         &self.span
@@ -1714,6 +2232,21 @@ impl<'a> arbitrary::Arbitrary<'a> for Function {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Constant {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let name = Identifier::arbitrary(u)?;
+        let ty = AliasedType::arbitrary(u)?;
+        let expression = <Expression as crate::ArbitraryRec>::arbitrary_rec(u, 3)?;
+        Ok(Self {
+            name,
+            ty,
+            expression,
+            span: Span::DUMMY,
+        })
+    }
+}
+
 #[cfg(feature = "arbitrary")]
 impl crate::ArbitraryRec for Function {
     fn arbitrary_rec(u: &mut arbitrary::Unstructured, budget: usize) -> arbitrary::Result<Self> {
@@ -1728,6 +2261,7 @@ impl crate::ArbitraryRec for Function {
         let body = Expression::arbitrary_rec(u, budget).map(Expression::into_block)?;
         Ok(Self {
             name,
+            is_test: false,
             params,
             ret,
             body,
@@ -0,0 +1,534 @@
+//! Property-based testing of SimplicityHL programs with random witness data
+//!
+//! A spending path that requires a signature should reject every witness
+//! that an attacker can come up with. The checker generates random witness values
+//! for the witness types of a program, runs the program on the Bit Machine,
+//! and reports witness values that are unexpectedly accepted or that make the program panic.
+//! The offending witness values are shrunk to a minimal example.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use simplicity::elements;
+use simplicity::jet::elements::ElementsEnv;
+
+use crate::debug::{FallibleCall, FallibleCallName};
+use crate::num::U256;
+use crate::str::WitnessName;
+use crate::testing::{execute, TestFailure};
+use crate::types::{TypeInner, UIntType};
+use crate::value::{UIntValue, ValueConstructible, ValueInner};
+use crate::{ArbitraryOfType, CompiledProgram, ResolvedType, Value, WitnessValues};
+
+/// Number of random bytes from which the witness values of a single test are generated.
+const BYTES_PER_TEST: usize = 4096;
+/// Maximum number of program runs while shrinking a counterexample.
+const MAX_SHRINK_RUNS: usize = 1000;
+
+/// Configuration of the property-based test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Number of random witnesses to try.
+    pub tests: usize,
+    /// Seed of the random witness generator.
+    pub seed: u64,
+    /// Whether the program may accept witnesses.
+    ///
+    /// By default, every witness that the program accepts is a counterexample.
+    pub allow_success: bool,
+    /// Whether the program may panic.
+    ///
+    /// By default, a witness that makes the program fail at `panic!` or at an `unwrap`
+    /// is a counterexample. Failures at `assert!` or at jets are always expected.
+    pub allow_panics: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tests: 100,
+            seed: 0,
+            allow_success: false,
+            allow_panics: false,
+        }
+    }
+}
+
+/// Outcome of running a program with given witness values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The program accepted the witness values.
+    Accepted,
+    /// The program rejected the witness values at an `assert!` or at a jet.
+    Rejected(FallibleCall),
+    /// The program panicked at `panic!` or at an `unwrap`,
+    /// or it failed at an unknown location.
+    Panicked(Option<FallibleCall>),
+}
+
+/// Witness values that violate the expectations of the [`Config`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Counterexample {
+    /// The shrunk witness values.
+    pub witness_values: WitnessValues,
+    /// The outcome of running the program with the shrunk witness values.
+    pub outcome: Outcome,
+    /// Number of tests that were run until the counterexample was found.
+    pub tests: usize,
+    /// Number of successful shrinking steps.
+    pub shrinks: usize,
+}
+
+/// Result of a property-based test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Report {
+    /// No counterexample was found in the given number of tests.
+    ///
+    /// A program without witnesses is run only once, because every run behaves the same.
+    Passed {
+        /// Number of tests that were run.
+        tests: usize,
+    },
+    /// A counterexample was found.
+    Failed(Counterexample),
+}
+
+/// Run the program with random witness values in the given environment
+/// and return the first counterexample, after shrinking it.
+///
+/// The program should be compiled with debug symbols,
+/// otherwise panics cannot be told apart from rejections.
+///
+/// ## Errors
+///
+/// The program cannot be run on the Bit Machine.
+pub fn quickcheck(
+    program: &CompiledProgram,
+    env: &ElementsEnv<Arc<elements::Transaction>>,
+    config: &Config,
+) -> Result<Report, String> {
+    let checker = Checker {
+        program,
+        env,
+        config,
+    };
+    let mut rng = SplitMix64(config.seed);
+    let mut bytes = vec![0; BYTES_PER_TEST];
+
+    let mut tests = 0;
+    for test in 1..=config.tests {
+        tests = test;
+        for chunk in bytes.chunks_mut(8) {
+            chunk.copy_from_slice(&rng.next_u64().to_le_bytes()[..chunk.len()]);
+        }
+        let mut u = arbitrary::Unstructured::new(&bytes);
+        let witness_values = WitnessValues::arbitrary_of_type(&mut u, &program.witness_types)
+            .expect("random bytes should produce witness values");
+        let outcome = checker.run(&witness_values)?;
+        if checker.is_counterexample(&outcome) {
+            let (witness_values, outcome, shrinks) = checker.shrink(witness_values, outcome)?;
+            return Ok(Report::Failed(Counterexample {
+                witness_values,
+                outcome,
+                tests: test,
+                shrinks,
+            }));
+        }
+        // Without witnesses, every run behaves the same
+        if program.witness_types.iter().next().is_none() {
+            break;
+        }
+    }
+    Ok(Report::Passed { tests })
+}
+
+struct Checker<'a> {
+    program: &'a CompiledProgram,
+    env: &'a ElementsEnv<Arc<elements::Transaction>>,
+    config: &'a Config,
+}
+
+impl Checker<'_> {
+    fn run(&self, witness_values: &WitnessValues) -> Result<Outcome, String> {
+        let satisfied = self.program.satisfy(witness_values.shallow_clone())?;
+        match execute(&satisfied, self.env) {
+            Ok(()) => Ok(Outcome::Accepted),
+            Err(TestFailure::Compile(error)) => Err(error),
+            Err(TestFailure::Run { call, .. }) => match call {
+                Some(call)
+                    if matches!(
+                        call.name(),
+                        FallibleCallName::Assert | FallibleCallName::Jet
+                    ) =>
+                {
+                    Ok(Outcome::Rejected(call))
+                }
+                call => Ok(Outcome::Panicked(call)),
+            },
+        }
+    }
+
+    fn is_counterexample(&self, outcome: &Outcome) -> bool {
+        match outcome {
+            Outcome::Accepted => !self.config.allow_success,
+            Outcome::Rejected(..) => false,
+            Outcome::Panicked(..) => !self.config.allow_panics,
+        }
+    }
+
+    /// Shrink the witness values one value at a time
+    /// while the program keeps producing an equivalent outcome.
+    ///
+    /// Panics are equivalent if they happen at the same call expression.
+    fn shrink(
+        &self,
+        witness_values: WitnessValues,
+        outcome: Outcome,
+    ) -> Result<(WitnessValues, Outcome, usize), String> {
+        let mut outcome = outcome;
+        let mut map = witness_values
+            .iter()
+            .map(|(name, value)| (name.shallow_clone(), value.clone()))
+            .collect::<HashMap<WitnessName, Value>>();
+        let mut names = map.keys().cloned().collect::<Vec<WitnessName>>();
+        names.sort_unstable();
+        let mut shrinks = 0;
+        let mut runs = 0;
+
+        'shrink: while runs < MAX_SHRINK_RUNS {
+            for name in &names {
+                for candidate in shrink_value(&map[name]) {
+                    if MAX_SHRINK_RUNS <= runs {
+                        break 'shrink;
+                    }
+                    runs += 1;
+                    let original = map.insert(name.shallow_clone(), candidate).unwrap();
+                    let candidate_values = WitnessValues::from(map.clone());
+                    let candidate_outcome = self.run(&candidate_values)?;
+                    if is_equivalent(&candidate_outcome, &outcome) {
+                        outcome = candidate_outcome;
+                        shrinks += 1;
+                        continue 'shrink;
+                    }
+                    map.insert(name.shallow_clone(), original);
+                }
+            }
+            break;
+        }
+
+        Ok((WitnessValues::from(map), outcome, shrinks))
+    }
+}
+
+/// Check if both outcomes are counterexamples of the same kind.
+///
+/// Panics must happen at the same call expression,
+/// so shrinking does not slip from one bug to another.
+fn is_equivalent(a: &Outcome, b: &Outcome) -> bool {
+    match (a, b) {
+        (Outcome::Accepted, Outcome::Accepted) => true,
+        (Outcome::Panicked(a), Outcome::Panicked(b)) => {
+            a.as_ref().map(FallibleCall::text) == b.as_ref().map(FallibleCall::text)
+        }
+        _ => false,
+    }
+}
+
+/// Return smaller values of the same type, from simplest to most complex.
+fn shrink_value(value: &Value) -> Vec<Value> {
+    let mut candidates = Vec::new();
+    let zero = zero(value.ty());
+    if &zero != value {
+        candidates.push(zero);
+    }
+
+    match value.inner() {
+        ValueInner::Boolean(..) => {}
+        ValueInner::UInt(integer) => {
+            candidates.extend(shrink_uint(*integer).into_iter().map(Value::from));
+        }
+        ValueInner::Either(either) => {
+            let (left_ty, right_ty) = match value.ty().as_inner() {
+                TypeInner::Either(left_ty, right_ty) => (left_ty.as_ref(), right_ty.as_ref()),
+                _ => unreachable!("Either value has Either type"),
+            };
+            match either {
+                either::Either::Left(left) => candidates.extend(
+                    shrink_value(left)
+                        .into_iter()
+                        .map(|left| Value::left(left, right_ty.clone())),
+                ),
+                either::Either::Right(right) => candidates.extend(
+                    shrink_value(right)
+                        .into_iter()
+                        .map(|right| Value::right(left_ty.clone(), right)),
+                ),
+            }
+        }
+        ValueInner::Option(option) => {
+            if let Some(inner) = option {
+                candidates.extend(shrink_value(inner).into_iter().map(Value::some));
+            }
+        }
+        ValueInner::Tuple(elements) => {
+            for (index, element) in elements.iter().enumerate() {
+                for shrunk in shrink_value(element) {
+                    let mut elements = elements.to_vec();
+                    elements[index] = shrunk;
+                    candidates.push(Value::tuple(elements));
+                }
+            }
+        }
+        ValueInner::Array(elements) => {
+            let element_ty = match value.ty().as_inner() {
+                TypeInner::Array(element_ty, _) => element_ty.as_ref(),
+                _ => unreachable!("Array value has array type"),
+            };
+            for (index, element) in elements.iter().enumerate() {
+                for shrunk in shrink_value(element) {
+                    let mut elements = elements.to_vec();
+                    elements[index] = shrunk;
+                    candidates.push(Value::array(elements, element_ty.clone()));
+                }
+            }
+        }
+        ValueInner::List(elements, bound) => {
+            let element_ty = match value.ty().as_inner() {
+                TypeInner::List(element_ty, _) => element_ty.as_ref(),
+                _ => unreachable!("List value has list type"),
+            };
+            for index in 0..elements.len() {
+                let mut elements = elements.to_vec();
+                elements.remove(index);
+                candidates.push(Value::list(elements, element_ty.clone(), *bound));
+            }
+            for (index, element) in elements.iter().enumerate() {
+                for shrunk in shrink_value(element) {
+                    let mut elements = elements.to_vec();
+                    elements[index] = shrunk;
+                    candidates.push(Value::list(elements, element_ty.clone(), *bound));
+                }
+            }
+        }
+    }
+
+    candidates
+}
+
+/// Return the simplest value of the given type.
+///
+/// The simplest value consists of zeroes, `false`, `Left`, `None` and empty lists.
+fn zero(ty: &ResolvedType) -> Value {
+    match ty.as_inner() {
+        TypeInner::Boolean => Value::from(false),
+        TypeInner::UInt(ty) => Value::from(match ty {
+            UIntType::U1 => UIntValue::U1(0),
+            UIntType::U2 => UIntValue::U2(0),
+            UIntType::U4 => UIntValue::U4(0),
+            UIntType::U8 => UIntValue::U8(0),
+            UIntType::U16 => UIntValue::U16(0),
+            UIntType::U32 => UIntValue::U32(0),
+            UIntType::U64 => UIntValue::U64(0),
+            UIntType::U128 => UIntValue::U128(0),
+            UIntType::U256 => UIntValue::U256(U256::MIN),
+        }),
+        TypeInner::Either(left, right) => Value::left(zero(left), right.as_ref().clone()),
+        TypeInner::Option(inner) => Value::none(inner.as_ref().clone()),
+        TypeInner::Tuple(elements) => Value::tuple(elements.iter().map(|ty| zero(ty))),
+        TypeInner::Array(element, size) => {
+            Value::array((0..*size).map(|_| zero(element)), element.as_ref().clone())
+        }
+        TypeInner::List(element, bound) => Value::list([], element.as_ref().clone(), *bound),
+    }
+}
+
+/// Return smaller nonzero integers of the same type: half of the integer and its predecessor.
+fn shrink_uint(integer: UIntValue) -> Vec<UIntValue> {
+    macro_rules! shrink {
+        ($n: expr, $variant: ident) => {{
+            let n = $n;
+            let mut candidates = vec![n / 2, n.saturating_sub(1)];
+            candidates.dedup();
+            candidates
+                .into_iter()
+                .filter(|m| 0 < *m && *m < n)
+                .map(UIntValue::$variant)
+                .collect()
+        }};
+    }
+
+    match integer {
+        UIntValue::U1(n) => shrink!(n, U1),
+        UIntValue::U2(n) => shrink!(n, U2),
+        UIntValue::U4(n) => shrink!(n, U4),
+        UIntValue::U8(n) => shrink!(n, U8),
+        UIntValue::U16(n) => shrink!(n, U16),
+        UIntValue::U32(n) => shrink!(n, U32),
+        UIntValue::U64(n) => shrink!(n, U64),
+        UIntValue::U128(n) => shrink!(n, U128),
+        UIntValue::U256(n) => {
+            // Divide by 256 until the integer becomes zero
+            let bytes = n.to_byte_array();
+            let mut shifted = [0; 32];
+            shifted[1..].copy_from_slice(&bytes[..31]);
+            match shifted == [0; 32] || shifted == bytes {
+                true => vec![],
+                false => vec![UIntValue::U256(U256::from_byte_array(shifted))],
+            }
+        }
+    }
+}
+
+/// Small, fast pseudo-random number generator.
+///
+/// Property-based tests do not need cryptographic randomness,
+/// but they need to be reproducible from a seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Accepted => write!(f, "The program accepted the witness values"),
+            Outcome::Rejected(call) => write!(
+                f,
+                "The program rejected the witness values at `{}`",
+                call.text()
+            ),
+            Outcome::Panicked(Some(call)) => write!(f, "The program panicked at `{}`", call.text()),
+            Outcome::Panicked(None) => write!(f, "The program panicked"),
+        }
+    }
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Found a counterexample after {} test(s) and {} shrink(s)",
+            self.tests, self.shrinks
+        )?;
+        writeln!(f, "{}:", self.outcome)?;
+        write!(f, "{}", self.witness_values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dummy_env, Arguments};
+
+    fn report(program_text: &str, config: &Config) -> Report {
+        let program = CompiledProgram::new(program_text, Arguments::default(), true)
+            .expect("program should compile");
+        quickcheck(&program, &dummy_env::dummy(), config).expect("program should run")
+    }
+
+    fn check(program_text: &str, config: &Config) -> Option<Counterexample> {
+        match report(program_text, config) {
+            Report::Passed { .. } => None,
+            Report::Failed(counterexample) => Some(counterexample),
+        }
+    }
+
+    fn witness(counterexample: &Counterexample, name: &str) -> String {
+        let name = WitnessName::from_str_unchecked(name);
+        counterexample
+            .witness_values
+            .get(&name)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn signature_rejects_random_witnesses() {
+        let program_text = r#"fn main() {
+    let pk: Pubkey = 0xf9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9;
+    let msg: u256 = jet::sha_256_ctx_8_finalize(jet::sha_256_ctx_8_init());
+    jet::bip_0340_verify((pk, msg), witness::SIG)
+}"#;
+        assert_eq!(
+            Report::Passed { tests: 100 },
+            report(program_text, &Config::default())
+        );
+    }
+
+    #[test]
+    fn run_once_without_witnesses() {
+        let config = Config {
+            allow_success: true,
+            ..Config::default()
+        };
+        assert_eq!(Report::Passed { tests: 1 }, report("fn main() {}", &config));
+    }
+
+    #[test]
+    fn shrink_accepted_witness() {
+        let program_text = r#"fn main() {
+    let (x, y): (u8, bool) = witness::X;
+    match jet::lt_8(x, 100) {
+        true => {},
+        false => assert!(jet::eq_8(x, 200)),
+    }
+}"#;
+        let counterexample = check(program_text, &Config::default()).unwrap();
+        assert_eq!(Outcome::Accepted, counterexample.outcome);
+        assert_eq!("(0, false)", witness(&counterexample, "X"));
+
+        let config = Config {
+            allow_success: true,
+            ..Config::default()
+        };
+        assert_eq!(None, check(program_text, &config));
+    }
+
+    #[test]
+    fn shrink_panicking_witness() {
+        let program_text = r#"fn main() {
+    let list: List<Option<u32>, 4> = witness::LIST;
+    let x: u32 = unwrap(witness::X);
+    assert!(jet::eq_32(x, 1337));
+}"#;
+        let counterexample = check(program_text, &Config::default()).unwrap();
+        match &counterexample.outcome {
+            Outcome::Panicked(Some(call)) => assert_eq!("unwrap(witness::X)", call.text()),
+            outcome => panic!("Unexpected outcome: {outcome:?}"),
+        }
+        assert_eq!("None", witness(&counterexample, "X"));
+        assert_eq!("list![]", witness(&counterexample, "LIST"));
+
+        let config = Config {
+            allow_panics: true,
+            ..Config::default()
+        };
+        assert_eq!(None, check(program_text, &config));
+    }
+
+    #[test]
+    fn shrink_integers() {
+        assert_eq!(
+            vec![UIntValue::U8(50), UIntValue::U8(99)],
+            shrink_uint(UIntValue::U8(100))
+        );
+        assert_eq!(Vec::<UIntValue>::new(), shrink_uint(UIntValue::U8(1)));
+        let mut bytes = [0; 32];
+        bytes[31] = 0xff;
+        bytes[30] = 0x01;
+        let mut shifted = [0; 32];
+        shifted[31] = 0x01;
+        assert_eq!(
+            vec![UIntValue::U256(U256::from_byte_array(shifted))],
+            shrink_uint(UIntValue::U256(U256::from_byte_array(bytes)))
+        );
+    }
+}
@@ -0,0 +1,406 @@
+//! Interactive evaluation of SimplicityHL expressions
+//!
+//! The REPL keeps a session of items (functions, type aliases and constants) and `let` statements.
+//! Each input is checked and evaluated as part of a program whose main function
+//! contains the statements of the session, followed by the input.
+//! The program is compiled to Simplicity and run on the Bit Machine.
+//!
+//! SimplicityHL checks expressions against an expected type.
+//! The REPL infers the type of an expression from the type error that results
+//! when the expression is used as a statement, which must be of unit type.
+//! Expressions whose type depends on the context, such as integer literals or casts,
+//! need to be bound with a `let` statement.
+
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+use simplicity::dag::{DagLike, InternalSharing};
+use simplicity::jet::elements::ElementsEnv;
+use simplicity::{elements, Cmr, RedeemNode};
+
+use crate::ast;
+use crate::debug::{TraceEvent, Tracer};
+use crate::error::{Error, Position, Span};
+use crate::parse::{self, ParseFromStr};
+use crate::pattern::BasePattern;
+use crate::testing::execute_with_tracer;
+use crate::types::TypeConstructible;
+use crate::{Arguments, CompiledProgram, ResolvedType, TemplateProgram, Value, WitnessValues};
+
+/// Help text of the REPL commands.
+pub const HELP: &str = "\
+Enter an expression to evaluate it, a `let` statement to bind variables,
+or a function definition, type alias or constant to add it to the session.
+
+Commands:
+  :type EXPR   Print the type of the expression
+  :cmr EXPR    Print the CMR of the expression in the release build
+  :cost EXPR   Print the cost of the expression in milli weight units
+  :help        Print this help text
+  :quit        Leave the REPL";
+
+/// Session of the REPL.
+pub struct Repl {
+    items: Vec<String>,
+    statements: Vec<String>,
+    env: ElementsEnv<Arc<elements::Transaction>>,
+}
+
+impl Repl {
+    /// Create an empty session that evaluates expressions in the given transaction environment.
+    pub fn new(env: ElementsEnv<Arc<elements::Transaction>>) -> Self {
+        Self {
+            items: Vec::new(),
+            statements: Vec::new(),
+            env,
+        }
+    }
+
+    /// Check if the `input` is complete,
+    /// meaning that all of its parentheses, brackets and braces are closed.
+    ///
+    /// Incomplete input continues on the next line.
+    pub fn is_complete(input: &str) -> bool {
+        let mut depth = 0i32;
+        for c in input.chars() {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        depth <= 0
+    }
+
+    /// Process one (complete) input of the REPL and return the text to print.
+    ///
+    /// ## Errors
+    ///
+    /// The input is invalid or it fails to run.
+    /// The session is left unchanged.
+    pub fn eval_input(&mut self, input: &str) -> Result<String, String> {
+        let input = input.trim();
+        if let Some(command) = input.strip_prefix(':') {
+            let (command, expression) = command
+                .split_once(char::is_whitespace)
+                .map_or((command, ""), |(command, expression)| {
+                    (command, expression.trim())
+                });
+            return match command {
+                "type" | "t" | "cmr" | "cost" if expression.is_empty() => {
+                    Err(format!("Missing expression, usage: `:{command} EXPR`"))
+                }
+                "type" | "t" => self.type_of(expression).map(|ty| ty.to_string()),
+                "cmr" => self.cmr(expression).map(|cmr| cmr.to_string()),
+                "cost" => self
+                    .cost(expression)
+                    .map(|cost| format!("{cost} milli weight units")),
+                "help" | "h" => Ok(HELP.to_string()),
+                _ => Err(format!("Unknown command `:{command}`, try `:help`")),
+            };
+        }
+
+        if input.is_empty() {
+            Ok(String::new())
+        } else if ["fn ", "type ", "const "]
+            .iter()
+            .any(|keyword| input.starts_with(keyword))
+        {
+            self.define(input).map(|()| String::new())
+        } else if input.starts_with("let ") {
+            self.bind(input)
+        } else {
+            let expression = input.strip_suffix(';').unwrap_or(input);
+            self.eval(expression)
+                .map(|(value, ty)| format!("{value}: {ty}"))
+        }
+    }
+
+    /// Add the function definition, type alias or constant to the session.
+    ///
+    /// ## Errors
+    ///
+    /// The item is invalid or it redefines an existing item.
+    pub fn define(&mut self, item: &str) -> Result<(), String> {
+        let program = parse::Program::parse_from_str(item).map_err(|e| e.to_string())?;
+        let is_main = |item: &parse::Item| matches!(item, parse::Item::Function(function) if function.name().as_inner() == "main");
+        if program.items().iter().any(is_main) {
+            return Err("The main function is defined by the REPL".to_string());
+        }
+
+        self.items.push(item.to_string());
+        let (text, _) = self.program("");
+        if let Err(error) = TemplateProgram::new(text) {
+            self.items.pop();
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Add the `let` statement to the session and return the values of the bound variables.
+    ///
+    /// ## Errors
+    ///
+    /// The statement is invalid or it fails to run.
+    pub fn bind(&mut self, statement: &str) -> Result<String, String> {
+        let statement = statement.strip_suffix(';').unwrap_or(statement);
+        let assignment = parse::Assignment::parse_from_str(statement).map_err(|e| e.to_string())?;
+
+        self.statements.push(format!("{statement};"));
+        let bound = self.run("").and_then(|_| {
+            BasePattern::from(assignment.pattern())
+                .identifiers()
+                .map(|identifier| {
+                    let (value, ty) = self.eval(identifier.as_inner())?;
+                    Ok(format!("{identifier}: {ty} = {value}"))
+                })
+                .collect::<Result<Vec<_>, String>>()
+        });
+        match bound {
+            Ok(lines) => Ok(lines.join("\n")),
+            Err(error) => {
+                self.statements.pop();
+                Err(error)
+            }
+        }
+    }
+
+    /// Infer the type of the `expression`.
+    ///
+    /// A cast `<T>::into(x)` may produce any type with the same structure as `T`,
+    /// so it has type `T`.
+    ///
+    /// ## Errors
+    ///
+    /// The expression is invalid or its type depends on the context.
+    pub fn type_of(&self, expression: &str) -> Result<ResolvedType, String> {
+        let (text, line) = self.program(&format!("{expression};"));
+        let analyzed = parse::Program::parse_from_str(&text)
+            .and_then(|program| ast::Program::analyze(&program));
+        let Err(error) = analyzed else {
+            return Ok(ResolvedType::unit());
+        };
+        if !is_expression_span(error.span(), &text, line, expression) {
+            return Err(error.with_file(Arc::from(text)).to_string());
+        }
+        match error.error() {
+            Error::ExpressionTypeMismatch(_, found) => Ok(found.clone()),
+            Error::InvalidCast(source, _) => Ok(source.clone()),
+            Error::ExpressionUnexpectedType(..) => Err(format!(
+                "Cannot infer the type of `{expression}`, bind it with a typed `let` statement"
+            )),
+            _ => Err(error.with_file(Arc::from(text)).to_string()),
+        }
+    }
+
+    /// Evaluate the `expression` on the Bit Machine and return its value and type.
+    ///
+    /// ## Errors
+    ///
+    /// The expression is invalid or it fails to run.
+    pub fn eval(&self, expression: &str) -> Result<(Value, ResolvedType), String> {
+        let ty = self.type_of(expression)?;
+        let value = self
+            .run(&format!("let _: {ty} = dbg!({expression});"))?
+            .ok_or_else(|| format!("The value of `{expression}` was not recorded"))?;
+        Ok((value, ty))
+    }
+
+    /// Compute the CMR of the `expression` in the release build.
+    ///
+    /// The CMR depends on the variables that are in scope.
+    ///
+    /// ## Errors
+    ///
+    /// The expression is invalid.
+    pub fn cmr(&self, expression: &str) -> Result<Cmr, String> {
+        self.with_node(expression, RedeemNode::cmr)
+    }
+
+    /// Compute an upper bound on the cost of the `expression` in milli weight units.
+    ///
+    /// ## Errors
+    ///
+    /// The expression is invalid.
+    pub fn cost(&self, expression: &str) -> Result<simplicity::Cost, String> {
+        self.with_node(expression, |node| node.bounds().cost)
+    }
+
+    /// Compile the `expression` without debug symbols
+    /// and apply `f` to the Simplicity node of the expression.
+    fn with_node<F, T>(&self, expression: &str, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&RedeemNode<simplicity::jet::Elements>) -> T,
+    {
+        let ty = self.type_of(expression)?;
+        let (text, line) = self.program(&format!("let _: {ty} =\n{expression};"));
+        let line = line.saturating_add(1);
+        let satisfied = CompiledProgram::new(text.as_str(), Arguments::default(), false)?
+            .satisfy(WitnessValues::default())?;
+        let source_map = satisfied.source_map();
+        satisfied
+            .redeem()
+            .as_ref()
+            .post_order_iter::<InternalSharing>()
+            // The last node within the expression computes it, even if it is attributed
+            // to a subexpression that it passes through, as with casts
+            .filter(|item| {
+                source_map
+                    .get(item.index)
+                    .is_some_and(|span| is_within_expression(span, line, expression))
+            })
+            .last()
+            .map(|item| f(item.node))
+            .ok_or_else(|| format!("`{expression}` was not compiled"))
+    }
+
+    /// Run the program with the `tail` appended to the session
+    /// and return the last value that was passed to `dbg!`.
+    fn run(&self, tail: &str) -> Result<Option<Value>, String> {
+        let (text, _) = self.program(tail);
+        let template = TemplateProgram::new(text.as_str())?;
+        let satisfied = template
+            .instantiate(Arguments::default(), true)?
+            .satisfy(WitnessValues::default())?;
+        let mut tracer = Tracer::new(satisfied.debug_symbols());
+        execute_with_tracer(&satisfied, &self.env, &mut tracer).map_err(|e| e.to_string())?;
+        let value = tracer
+            .into_events()
+            .into_iter()
+            .rev()
+            .find_map(|event| match event {
+                TraceEvent::Debug(debug) => Some(debug.value().clone()),
+                _ => None,
+            });
+        Ok(value)
+    }
+
+    /// Return the text of the session program with the `tail` at the end of the main function,
+    /// and the line where the `tail` starts.
+    fn program(&self, tail: &str) -> (String, NonZeroUsize) {
+        let mut text = String::new();
+        for item in &self.items {
+            text.push_str(item);
+            text.push('\n');
+        }
+        text.push_str("fn main() {\n");
+        for statement in &self.statements {
+            text.push_str(statement);
+            text.push('\n');
+        }
+        let line = NonZeroUsize::new(text.lines().count() + 1).expect("non-zero");
+        text.push_str(tail);
+        text.push_str("\n}\n");
+        (text, line)
+    }
+}
+
+/// Check if the `span` covers exactly the `expression`,
+/// which starts at the beginning of the given `line` of the program `text`.
+fn is_expression_span(span: &Span, text: &str, line: NonZeroUsize, expression: &str) -> bool {
+    span.start.line == line && span.start.col.get() == 1 && span.to_slice(text) == Some(expression)
+}
+
+/// Check if the `span` lies within the `expression`,
+/// which starts at the beginning of the given `line`.
+fn is_within_expression(span: &Span, line: NonZeroUsize, expression: &str) -> bool {
+    let end_line = line.get() + expression.matches('\n').count();
+    let end_col = expression
+        .rsplit('\n')
+        .next()
+        .map_or(0, |last| last.chars().count())
+        + 1;
+    let position = |position: &Position| (position.line.get(), position.col.get());
+    (line.get(), 1) <= position(&span.start) && position(&span.end) <= (end_line, end_col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy_env;
+
+    #[test]
+    fn session() {
+        let mut repl = Repl::new(dummy_env::dummy());
+        let mut eval = |input: &str| repl.eval_input(input);
+
+        assert_eq!(
+            Ok("0x0000000000000000000000000000000f: u128".to_string()),
+            eval("jet::full_multiply_64((2, 3), (4, 5))")
+        );
+        assert_eq!(
+            Ok("x: u16 = 258".to_string()),
+            eval("let x: u16 = <(u8, u8)>::into((1, 2));")
+        );
+        assert_eq!(
+            Ok(String::new()),
+            eval("fn double(x: u16) -> u16 {\n    let (_, y): (bool, u16) = jet::add_16(x, x);\n    y\n}")
+        );
+        assert_eq!(Ok("516: u16".to_string()), eval("double(x)"));
+        assert_eq!(Ok("u16".to_string()), eval(":type double(x)"));
+        assert_eq!(
+            Ok("(): ()".to_string()),
+            eval("assert!(jet::eq_16(x, 258))")
+        );
+        assert_eq!(Ok(String::new()), eval("const N: u16 = jet::max_16(3, 4);"));
+        assert_eq!(Ok("4: u16".to_string()), eval("N"));
+
+        // Failed inputs leave the session unchanged
+        assert!(eval("let y: u8 = jet::add_16(x, x);").is_err());
+        assert!(eval("let y: u16 = double(0); assert!(false);").is_err());
+        assert!(eval("y")
+            .unwrap_err()
+            .contains("Variable `y` is not defined"));
+        assert!(eval("fn double() {}").is_err());
+        assert!(eval("fn main() {}").is_err());
+        assert_eq!(Ok("516: u16".to_string()), eval("double(x)"));
+
+        assert_eq!(
+            Err("Cannot infer the type of `42`, bind it with a typed `let` statement".to_string()),
+            eval(":type 42")
+        );
+        assert_eq!(
+            Err("Assertion failed: `assert!(jet::eq_16(x, 0))`".to_string()),
+            eval("assert!(jet::eq_16(x, 0))")
+        );
+        assert_eq!(
+            Err("Missing expression, usage: `:type EXPR`".to_string()),
+            eval(":type")
+        );
+        assert_eq!(
+            Err("Missing expression, usage: `:cost EXPR`".to_string()),
+            eval(":cost   ")
+        );
+    }
+
+    #[test]
+    fn cast() {
+        let mut repl = Repl::new(dummy_env::dummy());
+        repl.bind("let p: (u8, u8) = (1, 2)").unwrap();
+        let cast = "<(u8, u8)>::into(p)";
+        assert_eq!(Ok("(1, 2): (u8, u8)".to_string()), repl.eval_input(cast));
+        assert_eq!(
+            Ok("(u8, u8)".to_string()),
+            repl.eval_input(&format!(":type {cast}"))
+        );
+        assert_eq!(
+            Ok(repl.cmr(cast).unwrap().to_string()),
+            repl.eval_input(&format!(":cmr {cast}"))
+        );
+    }
+
+    #[test]
+    fn cmr_and_cost() {
+        let mut repl = Repl::new(dummy_env::dummy());
+        let add = repl.cmr("jet::add_16(1, 2)").unwrap();
+        assert_ne!(add, repl.cmr("jet::add_16(1, 3)").unwrap());
+        repl.bind("let x: u16 = 1").unwrap();
+        // Constant expressions don't depend on the variables in scope
+        assert_eq!(add, repl.cmr("jet::add_16(1, 2)").unwrap());
+
+        let cost = |repl: &Repl, expression| repl.cost(expression).unwrap();
+        assert!(cost(&repl, "x") < cost(&repl, "jet::add_16(x, x)"));
+        assert!(Repl::is_complete("jet::add_16(x, x)"));
+        assert!(!Repl::is_complete("fn f() {"));
+    }
+}
@@ -1,10 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use serde::{de, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::debug::{DebugSymbols, TrackedCall, TrackedCallName, TrackedStep, TrackedStepName};
+use crate::error::{Position, Span};
 use crate::parse::ParseFromStr;
-use crate::str::WitnessName;
+use crate::source_map::SourceMap;
+use crate::str::{Identifier, WitnessName};
 use crate::types::ResolvedType;
 use crate::value::Value;
 use crate::witness::{Arguments, WitnessValues};
@@ -183,6 +188,369 @@ impl Serialize for Arguments {
     }
 }
 
+/// Serialized form of [`Position`].
+#[derive(Serialize, Deserialize)]
+struct PositionData {
+    line: std::num::NonZeroUsize,
+    col: std::num::NonZeroUsize,
+}
+
+/// Serialized form of [`Span`].
+#[derive(Serialize, Deserialize)]
+struct SpanData {
+    start: PositionData,
+    end: PositionData,
+}
+
+impl Serialize for Span {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let position = |p: Position| PositionData {
+            line: p.line,
+            col: p.col,
+        };
+        SpanData {
+            start: position(self.start),
+            end: position(self.end),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Span {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = SpanData::deserialize(deserializer)?;
+        let position = |p: PositionData| Position {
+            line: p.line,
+            col: p.col,
+        };
+        let (start, end) = (position(data.start), position(data.end));
+        if (end.line, end.col) < (start.line, start.col) {
+            return Err(de::Error::custom(format!(
+                "Span cannot start at {}:{} after its end at {}:{}",
+                start.line, start.col, end.line, end.col
+            )));
+        }
+        Ok(Span::new(start, end))
+    }
+}
+
+/// Serialized form of [`SourceMap`], where the nodes are listed in post order.
+#[derive(Serialize, Deserialize)]
+struct SourceMapData<N> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    nodes: Vec<N>,
+}
+
+/// Serialized form of a node of a [`SourceMap`],
+/// which is identified by the hex string of its CMR.
+#[derive(Serialize, Deserialize)]
+struct SourceMapNodeData<C, S> {
+    cmr: C,
+    span: S,
+}
+
+impl Serialize for SourceMap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let nodes = self
+            .iter()
+            .map(|(cmr, span)| SourceMapNodeData {
+                cmr: cmr.to_string(),
+                span,
+            })
+            .collect();
+        SourceMapData {
+            file: self.file().map(str::to_string),
+            nodes,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SourceMap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = SourceMapData::<SourceMapNodeData<String, Span>>::deserialize(deserializer)?;
+        let nodes = data
+            .nodes
+            .into_iter()
+            .map(|node| {
+                simplicity::Cmr::from_str(&node.cmr)
+                    .map(|cmr| (cmr, node.span))
+                    .map_err(|error| {
+                        de::Error::custom(format!("Invalid CMR `{}`: {error}", node.cmr))
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(SourceMap::from_parts(data.file, nodes))
+    }
+}
+
+/// Serialized form of [`TrackedCallName`].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum TrackedCallNameData {
+    Assert,
+    Panic,
+    Jet,
+    UnwrapLeft {
+        #[serde(rename = "type")]
+        ty: String,
+    },
+    UnwrapRight {
+        #[serde(rename = "type")]
+        ty: String,
+    },
+    Unwrap,
+    Debug {
+        #[serde(rename = "type")]
+        ty: String,
+    },
+}
+
+impl Serialize for TrackedCallName {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let data = match self {
+            TrackedCallName::Assert => TrackedCallNameData::Assert,
+            TrackedCallName::Panic => TrackedCallNameData::Panic,
+            TrackedCallName::Jet => TrackedCallNameData::Jet,
+            TrackedCallName::UnwrapLeft(ty) => {
+                TrackedCallNameData::UnwrapLeft { ty: ty.to_string() }
+            }
+            TrackedCallName::UnwrapRight(ty) => {
+                TrackedCallNameData::UnwrapRight { ty: ty.to_string() }
+            }
+            TrackedCallName::Unwrap => TrackedCallNameData::Unwrap,
+            TrackedCallName::Debug(ty) => TrackedCallNameData::Debug { ty: ty.to_string() },
+        };
+        data.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TrackedCallName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let parse_ty = |ty: &str| ResolvedType::parse_from_str(ty).map_err(de::Error::custom);
+        let name = match TrackedCallNameData::deserialize(deserializer)? {
+            TrackedCallNameData::Assert => TrackedCallName::Assert,
+            TrackedCallNameData::Panic => TrackedCallName::Panic,
+            TrackedCallNameData::Jet => TrackedCallName::Jet,
+            TrackedCallNameData::UnwrapLeft { ty } => TrackedCallName::UnwrapLeft(parse_ty(&ty)?),
+            TrackedCallNameData::UnwrapRight { ty } => TrackedCallName::UnwrapRight(parse_ty(&ty)?),
+            TrackedCallNameData::Unwrap => TrackedCallName::Unwrap,
+            TrackedCallNameData::Debug { ty } => TrackedCallName::Debug(parse_ty(&ty)?),
+        };
+        Ok(name)
+    }
+}
+
+/// Serialized form of [`TrackedCall`].
+#[derive(Serialize, Deserialize)]
+struct TrackedCallData<T, N> {
+    text: T,
+    #[serde(flatten)]
+    name: N,
+}
+
+impl Serialize for TrackedCall {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        TrackedCallData {
+            text: self.text(),
+            name: self.name(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TrackedCall {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = TrackedCallData::<String, TrackedCallName>::deserialize(deserializer)?;
+        Ok(TrackedCall {
+            text: Arc::from(data.text),
+            name: data.name,
+        })
+    }
+}
+
+/// Serialized form of a variable in scope of [`TrackedStepName::Statement`].
+#[derive(Serialize, Deserialize)]
+struct VariableData {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+/// Serialized form of [`TrackedStepName`].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum TrackedStepNameData {
+    Statement { variables: Vec<VariableData> },
+    EnterFunction,
+    ExitFunction,
+    Branch { matched: Span },
+}
+
+impl Serialize for TrackedStepName {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let data = match self {
+            TrackedStepName::Statement(variables) => TrackedStepNameData::Statement {
+                variables: variables
+                    .iter()
+                    .map(|(name, ty)| VariableData {
+                        name: name.to_string(),
+                        ty: ty.to_string(),
+                    })
+                    .collect(),
+            },
+            TrackedStepName::EnterFunction => TrackedStepNameData::EnterFunction,
+            TrackedStepName::ExitFunction => TrackedStepNameData::ExitFunction,
+            TrackedStepName::Branch(matched) => TrackedStepNameData::Branch { matched: *matched },
+        };
+        data.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TrackedStepName {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = match TrackedStepNameData::deserialize(deserializer)? {
+            TrackedStepNameData::Statement { variables } => {
+                let variables = variables
+                    .into_iter()
+                    .map(|variable| {
+                        let name = Identifier::parse_from_str(&variable.name)
+                            .map_err(de::Error::custom)?;
+                        let ty = ResolvedType::parse_from_str(&variable.ty)
+                            .map_err(de::Error::custom)?;
+                        Ok((name, ty))
+                    })
+                    .collect::<Result<_, D::Error>>()?;
+                TrackedStepName::Statement(variables)
+            }
+            TrackedStepNameData::EnterFunction => TrackedStepName::EnterFunction,
+            TrackedStepNameData::ExitFunction => TrackedStepName::ExitFunction,
+            TrackedStepNameData::Branch { matched } => TrackedStepName::Branch(matched),
+        };
+        Ok(name)
+    }
+}
+
+/// Serialized form of [`TrackedStep`].
+#[derive(Serialize, Deserialize)]
+struct TrackedStepData<N> {
+    span: Span,
+    #[serde(flatten)]
+    name: N,
+}
+
+impl Serialize for TrackedStep {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        TrackedStepData {
+            span: *self.span(),
+            name: self.name(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TrackedStep {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = TrackedStepData::<TrackedStepName>::deserialize(deserializer)?;
+        Ok(TrackedStep {
+            span: data.span,
+            name: data.name,
+        })
+    }
+}
+
+/// Serialized form of [`DebugSymbols`], where symbols are indexed by the hex string of their CMR.
+#[derive(Serialize, Deserialize)]
+struct DebugSymbolsData<C, S> {
+    calls: C,
+    #[serde(default)]
+    steps: S,
+}
+
+impl Serialize for DebugSymbols {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let calls = self
+            .calls
+            .iter()
+            .map(|(cmr, call)| (cmr.to_string(), call))
+            .collect::<BTreeMap<String, &TrackedCall>>();
+        let steps = self
+            .steps
+            .iter()
+            .map(|(cmr, step)| (cmr.to_string(), step))
+            .collect::<BTreeMap<String, &TrackedStep>>();
+        DebugSymbolsData { calls, steps }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DebugSymbols {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = DebugSymbolsData::<
+            HashMap<String, TrackedCall>,
+            HashMap<String, TrackedStep>,
+        >::deserialize(deserializer)?;
+        Ok(DebugSymbols {
+            calls: parse_cmr_keys(data.calls)?,
+            steps: parse_cmr_keys(data.steps)?,
+        })
+    }
+}
+
+fn parse_cmr_keys<V, E: de::Error>(
+    map: HashMap<String, V>,
+) -> Result<HashMap<simplicity::Cmr, V>, E> {
+    map.into_iter()
+        .map(|(cmr, value)| {
+            simplicity::Cmr::from_str(&cmr)
+                .map(|cmr| (cmr, value))
+                .map_err(|error| E::custom(format!("Invalid CMR `{cmr}`: {error}")))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -199,4 +567,73 @@ mod tests {
             Err(error) => assert!(error.to_string().contains("Name `A` is assigned twice")),
         }
     }
+
+    #[test]
+    fn source_map_serde_roundtrip() {
+        let program = "fn main() {\n    assert!(jet::eq_8(witness::A, 1));\n}\n";
+        let compiled = crate::CompiledProgram::new(program, Arguments::default(), false).unwrap();
+        let source_map = compiled.source_map().clone().with_file("main.simf");
+        let json = serde_json::to_string(&source_map).unwrap();
+        assert!(json.starts_with(r#"{"file":"main.simf","nodes":[{"cmr":"#));
+        let decoded = serde_json::from_str::<SourceMap>(&json).unwrap();
+        assert_eq!(source_map, decoded);
+    }
+
+    #[test]
+    fn span_serde_reversed() {
+        let s = r#"{"start":{"line":5,"col":1},"end":{"line":2,"col":3}}"#;
+        match serde_json::from_str::<Span>(s) {
+            Ok(_) => panic!("Reversed span was falsely accepted"),
+            Err(error) => assert!(error.to_string().contains("Span cannot start at 5:1")),
+        }
+        let s = r#"{"start":{"line":2,"col":4},"end":{"line":2,"col":3}}"#;
+        assert!(serde_json::from_str::<Span>(s).is_err());
+        let s = r#"{"start":{"line":2,"col":3},"end":{"line":2,"col":3}}"#;
+        assert!(serde_json::from_str::<Span>(s).is_ok());
+    }
+
+    #[test]
+    fn debug_symbols_serde_roundtrip() {
+        let program = r#"fn pick(x: Either<u8, u16>) -> u8 {
+    match x {
+        Left(a: u8) => a,
+        Right(b: u16) => {
+            let (_, lo): (u8, u8) = <u16>::into(b);
+            lo
+        },
+    }
+}
+
+fn main() {
+    let a: u8 = dbg!(pick(Left(1)));
+    let b: u16 = unwrap_right::<u8>(Right(2));
+    assert!(jet::eq_8(a, 1));
+}
+"#;
+        let compiled = crate::TemplateProgram::new(program)
+            .and_then(|template| template.instantiate_for_debugger(Arguments::default()))
+            .unwrap();
+        let debug_symbols = compiled.debug_symbols();
+        let json = serde_json::to_string(debug_symbols).unwrap();
+        let decoded = serde_json::from_str::<DebugSymbols>(&json).unwrap();
+        assert_eq!(debug_symbols, &decoded);
+
+        // Trace a program that was decoded from bytes, using the loaded debug symbols
+        let satisfied = compiled.satisfy(WitnessValues::default()).unwrap();
+        let (program_bytes, witness_bytes) = satisfied.redeem().to_vec_with_witness();
+        let loaded =
+            crate::SatisfiedProgram::decode(&program_bytes, &witness_bytes, decoded).unwrap();
+        let trace = |satisfied: &crate::SatisfiedProgram| {
+            let mut tracer = crate::debug::Tracer::new(satisfied.debug_symbols());
+            let mut mac = simplicity::BitMachine::for_program(satisfied.redeem()).unwrap();
+            mac.exec_with_tracker(satisfied.redeem(), &crate::dummy_env::dummy(), &mut tracer)
+                .unwrap();
+            tracer.into_events()
+        };
+        let events = trace(&loaded);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, crate::debug::TraceEvent::Debug(..))));
+        assert_eq!(trace(&satisfied), events);
+    }
 }
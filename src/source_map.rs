@@ -0,0 +1,353 @@
+//! Source maps from Simplicity target code to SimplicityHL source code
+//!
+//! A source map attributes each node of the Simplicity target code
+//! to the SimplicityHL expression that it was compiled from.
+//! Nodes are identified by their position in post order, where each node is visited once,
+//! as in [`InternalSharing`]. Different expressions may compile to nodes with the same CMR,
+//! such as two equal constants, but these are different nodes with their own spans.
+//!
+//! The CMR of a node serves as a secondary key that finds all nodes with this CMR.
+//! It locates nodes in programs with a different structure,
+//! such as programs that were decoded from their encoding, which shares equal nodes.
+//!
+//! Nodes that are compiled from an expression are attributed to the innermost expression.
+//! Auxiliary nodes, such as the nodes that access variables or that combine subexpressions,
+//! are attributed to the expression that surrounds them.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use simplicity::dag::{DagLike, InternalSharing};
+use simplicity::node::{self, Node};
+use simplicity::Cmr;
+
+use crate::error::Span;
+use crate::named;
+
+/// Map from the nodes of Simplicity target code to spans of SimplicityHL source code.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SourceMap {
+    file: Option<String>,
+    /// CMR and span of each node, in post order.
+    nodes: Vec<(Cmr, Span)>,
+    /// Positions of the nodes with a given CMR.
+    cmrs: HashMap<Cmr, Vec<usize>>,
+}
+
+impl SourceMap {
+    /// Access the name of the SimplicityHL source file, if it is known.
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// Set the name of the SimplicityHL source file.
+    pub fn with_file<S: Into<String>>(mut self, file: S) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Get the span of the SimplicityHL source code that the node at the given position
+    /// in post order was compiled from.
+    ///
+    /// Return `None` if the target code has no node at this position.
+    pub fn get(&self, index: usize) -> Option<&Span> {
+        self.nodes.get(index).map(|(_, span)| span)
+    }
+
+    /// Iterate over the spans of the nodes with the given CMR, in post order.
+    ///
+    /// The iterator is empty if no node of the target code has this CMR.
+    pub fn find<'a>(&'a self, cmr: &Cmr) -> impl Iterator<Item = &'a Span> + 'a {
+        self.cmrs
+            .get(cmr)
+            .into_iter()
+            .flatten()
+            .map(|index| &self.nodes[*index].1)
+    }
+
+    /// Iterate over the CMRs of the nodes with their spans, in post order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Cmr, &Span)> {
+        self.nodes.iter().map(|(cmr, span)| (cmr, span))
+    }
+
+    /// Return the number of nodes in the map.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check if the map contains no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Create a source map from the CMRs and spans of the nodes, in post order.
+    pub(crate) fn from_parts(file: Option<String>, nodes: Vec<(Cmr, Span)>) -> Self {
+        let mut cmrs = HashMap::<Cmr, Vec<usize>>::new();
+        for (index, (cmr, _)) in nodes.iter().enumerate() {
+            cmrs.entry(*cmr).or_default().push(index);
+        }
+        Self { file, nodes, cmrs }
+    }
+
+    /// Create the source map of the given target code,
+    /// where the nodes have the given spans, in post order.
+    ///
+    /// Each node without a span inherits the span of a node that uses it.
+    fn complete<M: node::Marker>(
+        file: Option<String>,
+        mut spans: Vec<Option<Span>>,
+        root: &Node<M>,
+    ) -> Self {
+        let items = root
+            .post_order_iter::<InternalSharing>()
+            .collect::<Vec<_>>();
+        debug_assert_eq!(items.len(), spans.len());
+
+        // Parents come after their children in post order
+        for item in items.iter().rev() {
+            let span = spans[item.index];
+            for child in [item.left_index, item.right_index].into_iter().flatten() {
+                if spans[child].is_none() {
+                    spans[child] = span;
+                }
+            }
+        }
+
+        let nodes = items
+            .iter()
+            .zip(spans)
+            .map(|(item, span)| {
+                let span = span.expect("the root has a span, which every node inherits");
+                (item.node.cmr(), span)
+            })
+            .collect();
+        Self::from_parts(file, nodes)
+    }
+
+    /// Carry the source map over to the given target code,
+    /// whose nodes stem from the nodes at the given positions, in post order.
+    ///
+    /// Nodes without origin inherit the span of a node that uses them.
+    pub(crate) fn map_nodes<M: node::Marker>(
+        &self,
+        origins: &[Option<usize>],
+        root: &Node<M>,
+    ) -> Self {
+        let spans = origins
+            .iter()
+            .map(|origin| origin.and_then(|index| self.get(index).copied()))
+            .collect();
+        Self::complete(self.file.clone(), spans, root)
+    }
+
+    /// Carry the source map of the `original` target code over to the `target` code,
+    /// which has the same structure except for case nodes that became assertions,
+    /// like a pruned program.
+    pub(crate) fn follow<M: node::Marker, N: node::Marker>(
+        &self,
+        original: &Node<M>,
+        target: &Node<N>,
+    ) -> Self {
+        self.map_nodes(&correspond(original, target), target)
+    }
+}
+
+/// Recorder of the spans of target code under construction.
+///
+/// Nodes are identified by their address, so the recorder keeps them alive.
+#[derive(Debug)]
+pub(crate) struct Recorder<N> {
+    spans: HashMap<*const N, Span>,
+    nodes: Vec<Arc<N>>,
+}
+
+impl<N> Default for Recorder<N> {
+    fn default() -> Self {
+        Self {
+            spans: HashMap::new(),
+            nodes: Vec::new(),
+        }
+    }
+}
+
+impl<N> Recorder<N> {
+    /// Attribute the node to the expression with the given span.
+    ///
+    /// Expressions are compiled from the inside out.
+    /// The span is ignored if the node is already attributed to an expression,
+    /// which happens when the node is passed through unchanged, as with parentheses.
+    pub fn insert(&mut self, node: &Arc<N>, span: Span) {
+        if let Entry::Vacant(entry) = self.spans.entry(Arc::as_ptr(node)) {
+            entry.insert(span);
+            self.nodes.push(Arc::clone(node));
+        }
+    }
+}
+
+impl<M: node::Marker> Recorder<Node<M>> {
+    /// Create the source map of the `target` code,
+    /// which was converted node by node from the `construct` code that was recorded.
+    pub fn finish<N: node::Marker>(&self, construct: &Node<M>, target: &Node<N>) -> SourceMap {
+        let spans = construct
+            .post_order_iter::<InternalSharing>()
+            .map(|item| self.spans.get(&std::ptr::from_ref(item.node)).copied())
+            .collect();
+        SourceMap::complete(None, spans, target)
+    }
+}
+
+/// Return the positions of the nodes of the given code, in post order.
+fn positions<M: node::Marker>(root: &Node<M>) -> HashMap<*const Node<M>, usize> {
+    root.post_order_iter::<InternalSharing>()
+        .map(|item| (std::ptr::from_ref(item.node), item.index))
+        .collect()
+}
+
+/// Find the node of the `original` code that each node of the `target` code stems from,
+/// using [`named::pair_nodes`].
+///
+/// Return the position of the original node for each target node, both in post order.
+fn correspond<M: node::Marker, N: node::Marker>(
+    original: &Node<M>,
+    target: &Node<N>,
+) -> Vec<Option<usize>> {
+    let original_positions = positions(original);
+    let target_positions = positions(target);
+    let mut origins = vec![None; target_positions.len()];
+    for (original, target) in named::pair_nodes(original, target) {
+        origins[target_positions[&std::ptr::from_ref(target)]]
+            .get_or_insert(original_positions[&std::ptr::from_ref(original)]);
+    }
+    origins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::str::WitnessName;
+    use crate::value::{Value, ValueConstructible};
+    use crate::{Arguments, CompiledProgram, WitnessValues};
+
+    const PROGRAM: &str = r#"fn double(x: u8) -> u8 {
+    let (_, y): (bool, u8) = jet::add_8(x, x);
+    y
+}
+
+fn main() {
+    let a: u8 = double(witness::A);
+    assert!(jet::eq_8(a, 6));
+}
+"#;
+
+    fn witness(a: u8) -> WitnessValues {
+        WitnessValues::from(HashMap::from([(
+            WitnessName::from_str_unchecked("A"),
+            Value::u8(a),
+        )]))
+    }
+
+    #[test]
+    fn every_node_is_mapped() {
+        let compiled = CompiledProgram::new(PROGRAM, Arguments::default(), false).unwrap();
+        let source_map = compiled.source_map();
+        let commit = compiled.commit();
+        for node in commit.as_ref().post_order_iter::<InternalSharing>() {
+            assert_eq!(
+                Some(&node.node.cmr()),
+                source_map.iter().nth(node.index).map(|x| x.0)
+            );
+            assert!(source_map.find(&node.node.cmr()).next().is_some());
+        }
+        assert_eq!(
+            commit.as_ref().post_order_iter::<InternalSharing>().count(),
+            source_map.len()
+        );
+
+        let satisfied = compiled.satisfy(witness(3)).unwrap();
+        let nodes = satisfied
+            .redeem()
+            .as_ref()
+            .post_order_iter::<InternalSharing>()
+            .count();
+        assert_eq!(nodes, satisfied.source_map().len());
+    }
+
+    #[test]
+    fn innermost_expression() {
+        let compiled = CompiledProgram::new(PROGRAM, Arguments::default(), false).unwrap();
+        let source_map = compiled.source_map();
+        let text = |index: usize| source_map.get(index).unwrap().to_slice(PROGRAM).unwrap();
+
+        let commit = compiled.commit();
+        let main_body = &PROGRAM[PROGRAM.find("{\n    let a").unwrap()..].trim_end();
+        assert_eq!(*main_body, text(source_map.len() - 1));
+        let jet = commit
+            .as_ref()
+            .post_order_iter::<InternalSharing>()
+            .find(|node| {
+                matches!(
+                    node.node.inner(),
+                    node::Inner::Jet(simplicity::jet::Elements::Add8)
+                )
+            })
+            .unwrap();
+        assert_eq!("jet::add_8(x, x)", text(jet.index));
+    }
+
+    #[test]
+    fn equal_nodes_have_own_spans() {
+        let program = "fn main() {\n    assert!(jet::eq_8(7, 7));\n}\n";
+        let compiled = CompiledProgram::new(program, Arguments::default(), false).unwrap();
+        let source_map = compiled.source_map();
+        let commit = compiled.commit();
+        let sevens: Vec<_> = commit
+            .as_ref()
+            .post_order_iter::<InternalSharing>()
+            .filter(|node| matches!(node.node.inner(), node::Inner::Word(..)))
+            .collect();
+        assert_eq!(2, sevens.len());
+        let spans: Vec<_> = sevens
+            .iter()
+            .map(|node| *source_map.get(node.index).unwrap())
+            .collect();
+        assert_ne!(spans[0], spans[1]);
+        assert!(spans.iter().all(|span| span.to_slice(program) == Some("7")));
+        let found: Vec<_> = source_map.find(&sevens[0].node.cmr()).copied().collect();
+        assert_eq!(spans, found);
+    }
+
+    #[test]
+    fn pruned_program() {
+        let program = r#"fn main() {
+    let a: Either<u8, u16> = witness::A;
+    match a {
+        Left(x: u8) => assert!(jet::is_zero_8(x)),
+        Right(y: u16) => assert!(jet::is_zero_16(y)),
+    };
+}
+"#;
+        let compiled = CompiledProgram::new(program, Arguments::default(), false).unwrap();
+        let witness = WitnessValues::from(HashMap::from([(
+            WitnessName::from_str_unchecked("A"),
+            Value::left(Value::u8(0), crate::types::UIntType::U16.into()),
+        )]));
+        let env = crate::dummy_env::dummy();
+        let pruned = compiled.satisfy_with_env(witness, Some(&env)).unwrap();
+        let source_map = pruned.source_map();
+        let nodes = pruned
+            .redeem()
+            .as_ref()
+            .post_order_iter::<InternalSharing>()
+            .collect::<Vec<_>>();
+        assert_eq!(nodes.len(), source_map.len());
+        let jet = nodes
+            .iter()
+            .find(|node| matches!(node.node.inner(), node::Inner::Jet(..)))
+            .unwrap();
+        assert_eq!(
+            Some("jet::is_zero_8(x)"),
+            source_map.get(jet.index).unwrap().to_slice(program)
+        );
+    }
+}
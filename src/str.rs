@@ -115,8 +115,29 @@ impl<'a> arbitrary::Arbitrary<'a> for FunctionName {
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct Identifier(Arc<str>);
 
+impl Identifier {
+    /// Keywords that cannot be used as identifiers.
+    pub const RESERVED: [&'static str; 4] = ["break", "for", "in", "mut"];
+}
+
 wrapped_string!(Identifier, "variable identifier");
-impl_arbitrary_lowercase_alpha!(Identifier);
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Identifier {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.int_in_range(1..=10)?;
+        let mut string = String::with_capacity(len);
+        for _ in 0..len {
+            let offset = u.int_in_range(0..=25)?;
+            string.push((b'a' + offset) as char)
+        }
+        if Self::RESERVED.contains(&string.as_str()) {
+            string.push('_');
+        }
+
+        Ok(Self::from_str_unchecked(string.as_str()))
+    }
+}
 
 /// The name of a witness.
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
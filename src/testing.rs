@@ -0,0 +1,382 @@
+//! Unit tests that are written in SimplicityHL
+//!
+//! A function that is marked with `#[test]` is a unit test.
+//! Unit tests take no inputs and return nothing, just like the main function.
+//! They are ignored when the program is compiled.
+//!
+//! The test runner compiles each unit test as a standalone program,
+//! where the body of the test replaces the main function.
+//! The test passes if the program runs successfully on the Bit Machine.
+
+use std::fmt;
+use std::sync::Arc;
+
+use simplicity::jet::elements::ElementsEnv;
+use simplicity::{elements, BitMachine};
+
+use crate::coverage::Coverage;
+use crate::debug::{FallibleCall, FallibleCallName, TraceEvent, Tracer};
+use crate::error::{Position, RichError, Span};
+use crate::parse::{self, ParseFromStr};
+use crate::str::FunctionName;
+use crate::{ast, Arguments, CompiledProgram, SatisfiedProgram, TemplateProgram, WitnessValues};
+
+/// The unit tests of a SimplicityHL program.
+#[derive(Clone, Debug)]
+pub struct TestSuite {
+    program: parse::Program,
+    file: Arc<str>,
+    /// Source files that make up the program, in the order of concatenation.
+    ///
+    /// Empty if the program was given as a single text.
+    sources: Vec<Source>,
+}
+
+/// Source file that is part of the program of a test suite.
+#[derive(Clone, Debug)]
+struct Source {
+    name: String,
+    text: Arc<str>,
+    /// Line of the program where the file starts.
+    first_line: usize,
+    /// Number of lines of the file.
+    n_lines: usize,
+}
+
+impl Source {
+    /// Convert the position in the program into the position in the source file.
+    ///
+    /// Return `None` if the position lies outside the file.
+    fn local_position(&self, position: Position) -> Option<Position> {
+        let line = position.line.get();
+        (self.first_line..self.first_line + self.n_lines)
+            .contains(&line)
+            .then(|| Position::new(line - self.first_line + 1, position.col.get()))
+    }
+}
+
+/// Reason why a unit test failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestFailure {
+    /// The unit test could not be compiled or satisfied.
+    Compile(String),
+    /// The unit test failed on the Bit Machine.
+    Run {
+        /// The fallible call expression where the execution failed, if it is known.
+        call: Option<FallibleCall>,
+        /// The error of the Bit Machine.
+        error: String,
+    },
+}
+
+impl TestSuite {
+    /// Parse the unit tests of a SimplicityHL program.
+    ///
+    /// The unit tests are only compiled when they are run.
+    ///
+    /// ## Errors
+    ///
+    /// The string is not a valid SimplicityHL program.
+    pub fn new<Str: Into<Arc<str>>>(s: Str) -> Result<Self, String> {
+        let file = s.into();
+        let program = parse::Program::parse_from_str(&file)?;
+        Ok(Self {
+            program,
+            file,
+            sources: vec![],
+        })
+    }
+
+    /// Parse the unit tests of a SimplicityHL program that is split into several source files,
+    /// such as a program and its test files.
+    ///
+    /// The files are joined by newlines.
+    /// Errors are reported in the file where they occur, preceded by the name of the file.
+    ///
+    /// ## Errors
+    ///
+    /// The joined files are not a valid SimplicityHL program.
+    pub fn from_files<N: AsRef<str>, T: AsRef<str>>(files: &[(N, T)]) -> Result<Self, String> {
+        let mut sources = Vec::with_capacity(files.len());
+        let mut first_line = 1;
+        for (name, text) in files {
+            let n_lines = text.as_ref().split('\n').count();
+            sources.push(Source {
+                name: name.as_ref().to_string(),
+                text: Arc::from(text.as_ref()),
+                first_line,
+                n_lines,
+            });
+            first_line += n_lines;
+        }
+        let file: Arc<str> = files
+            .iter()
+            .map(|(_, text)| text.as_ref())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into();
+        let program = parse::Program::parse_from_str(&file)
+            .map_err(|error| render_error(&sources, &file, error))?;
+        Ok(Self {
+            program,
+            file,
+            sources,
+        })
+    }
+
+    /// Iterate over the names of the unit tests, in the order of their definition.
+    pub fn names(&self) -> impl Iterator<Item = &FunctionName> {
+        self.program.tests().map(parse::Function::name)
+    }
+
+    /// Compile the unit test with the given name, including debug symbols.
+    ///
+    /// ## Errors
+    ///
+    /// - There is no unit test with the given name.
+    /// - The unit test is not a valid program.
+    pub fn compile(&self, name: &FunctionName) -> Result<CompiledProgram, String> {
+        self.template(name)
+            .and_then(|template| template.instantiate(Arguments::default(), true))
+    }
+
+    /// Compile the unit test with the given name, including debug symbols and step symbols.
+    ///
+    /// ## Errors
+    ///
+    /// - There is no unit test with the given name.
+    /// - The unit test is not a valid program.
+    pub fn compile_for_debugger(&self, name: &FunctionName) -> Result<CompiledProgram, String> {
+        self.template(name)
+            .and_then(|template| template.instantiate_for_debugger(Arguments::default()))
+    }
+
+    fn template(&self, name: &FunctionName) -> Result<TemplateProgram, String> {
+        let program = self
+            .program
+            .test_program(name)
+            .ok_or_else(|| format!("Test `{name}` does not exist"))?;
+        let ast_program = ast::Program::analyze(&program)
+            .map_err(|error| render_error(&self.sources, &self.file, error))?;
+        Ok(TemplateProgram {
+            simfony: ast_program,
+            file: Arc::clone(&self.file),
+        })
+    }
+
+    /// Run the unit test with the given name on the Bit Machine in the given environment.
+    ///
+    /// ## Errors
+    ///
+    /// The unit test does not compile or it fails at runtime.
+    pub fn run(
+        &self,
+        name: &FunctionName,
+        env: &ElementsEnv<Arc<elements::Transaction>>,
+    ) -> Result<(), TestFailure> {
+        let satisfied = self
+            .compile(name)
+            .and_then(|compiled| compiled.satisfy(WitnessValues::default()))
+            .map_err(TestFailure::Compile)?;
+        execute(&satisfied, env)
+    }
+
+    /// Run the unit test with the given name on the Bit Machine in the given environment
+    /// and record which parts of the source code were executed in the given `coverage`.
+    ///
+    /// ## Errors
+    ///
+    /// The unit test does not compile or it fails at runtime.
+    pub fn run_with_coverage(
+        &self,
+        name: &FunctionName,
+        env: &ElementsEnv<Arc<elements::Transaction>>,
+        coverage: &mut Coverage,
+    ) -> Result<(), TestFailure> {
+        let satisfied = self
+            .compile_for_debugger(name)
+            .and_then(|compiled| compiled.satisfy(WitnessValues::default()))
+            .map_err(TestFailure::Compile)?;
+        coverage.run(&satisfied, env)
+    }
+}
+
+/// Render the error in the source file where it occurs.
+///
+/// If there are no source files, then the error is rendered in the whole program.
+fn render_error(sources: &[Source], file: &Arc<str>, error: RichError) -> String {
+    let span = *error.span();
+    let Some((source, start)) = sources
+        .iter()
+        .find_map(|source| Some((source, source.local_position(span.start)?)))
+    else {
+        return error.with_file(Arc::clone(file)).to_string();
+    };
+    // Spans that run into the next file are cut at their start
+    let end = source.local_position(span.end).unwrap_or(start);
+    let error = RichError::new(error.error().clone(), Span::new(start, end))
+        .with_file(Arc::clone(&source.text));
+    format!("{}:\n{error}", source.name)
+}
+
+/// Run the satisfied program on the Bit Machine in the given environment.
+///
+/// Use the debug symbols of the program to find the call expression where the execution failed.
+///
+/// ## Errors
+///
+/// The program fails at runtime.
+pub fn execute(
+    satisfied: &SatisfiedProgram,
+    env: &ElementsEnv<Arc<elements::Transaction>>,
+) -> Result<(), TestFailure> {
+    let mut tracer = Tracer::new(satisfied.debug_symbols());
+    execute_with_tracer(satisfied, env, &mut tracer)
+}
+
+/// Run the satisfied program on the Bit Machine in the given environment
+/// and record the execution in the given `tracer`.
+pub(crate) fn execute_with_tracer(
+    satisfied: &SatisfiedProgram,
+    env: &ElementsEnv<Arc<elements::Transaction>>,
+    tracer: &mut Tracer,
+) -> Result<(), TestFailure> {
+    let mut mac = BitMachine::for_program(satisfied.redeem())
+        .map_err(|error| TestFailure::Compile(error.to_string()))?;
+    match mac.exec_with_tracker(satisfied.redeem(), env, tracer) {
+        Ok(..) => Ok(()),
+        Err(error) => {
+            // Fallible calls are tracked before they are evaluated,
+            // so the last tracked call is the one that failed.
+            let call = tracer.events().iter().rev().find_map(|event| match event {
+                TraceEvent::Call(call) => Some(call.clone()),
+                _ => None,
+            });
+            Err(TestFailure::Run {
+                call,
+                error: error.to_string(),
+            })
+        }
+    }
+}
+
+impl fmt::Display for TestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestFailure::Compile(error) => write!(f, "Failed to compile:\n{error}"),
+            TestFailure::Run { call: None, error } => write!(f, "Failed to run: {error}"),
+            TestFailure::Run {
+                call: Some(call), ..
+            } => match call.name() {
+                FallibleCallName::Assert => write!(f, "Assertion failed: `{}`", call.text()),
+                FallibleCallName::Panic => write!(f, "Explicit panic: `{}`", call.text()),
+                FallibleCallName::Jet => write!(f, "Jet failed: `{}`", call.text()),
+                FallibleCallName::UnwrapLeft(value) | FallibleCallName::UnwrapRight(value) => {
+                    write!(f, "Failed to unwrap `{value}`: `{}`", call.text())
+                }
+                FallibleCallName::Unwrap => {
+                    write!(f, "Called `unwrap` on a `None` value: `{}`", call.text())
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy_env;
+
+    const PROGRAM: &str = r#"fn add(a: u8, b: u8) -> u8 {
+    let (carry, sum): (bool, u8) = jet::add_8(a, b);
+    assert!(jet::eq_1(<bool>::into(carry), 0));
+    sum
+}
+
+fn main() {
+    let sum: u8 = add(witness::A, witness::B);
+    assert!(jet::eq_8(sum, 3));
+}
+
+#[test]
+fn add_small() {
+    assert!(jet::eq_8(add(1, 2), 3));
+}
+
+#[test]
+fn add_wrong() {
+    let sum: u8 = add(1, 2);
+    assert!(jet::eq_8(sum, 4));
+}
+
+#[test]
+fn add_overflow() {
+    let _: u8 = add(255, 1);
+}
+
+#[test]
+fn uses_witness() {
+    assert!(witness::C);
+}
+"#;
+
+    fn suite() -> TestSuite {
+        TestSuite::new(PROGRAM).expect("program should parse")
+    }
+
+    fn run(name: &str) -> Result<(), TestFailure> {
+        let name = FunctionName::from_str_unchecked(name);
+        suite().run(&name, &dummy_env::dummy())
+    }
+
+    #[test]
+    fn tests_are_ignored_by_compiler() {
+        // The witness in `uses_witness` would be rejected outside of the main function
+        TemplateProgram::new(PROGRAM).expect("program should compile");
+    }
+
+    #[test]
+    fn names() {
+        let names = suite().names().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            vec!["add_small", "add_wrong", "add_overflow", "uses_witness"],
+            names
+        );
+    }
+
+    #[test]
+    fn run_tests() {
+        assert_eq!(Ok(()), run("add_small"));
+        assert_eq!(
+            "Assertion failed: `assert!(jet::eq_8(sum, 4))`",
+            run("add_wrong").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Assertion failed: `assert!(jet::eq_1(<bool>::into(carry), 0))`",
+            run("add_overflow").unwrap_err().to_string()
+        );
+        assert!(matches!(
+            run("uses_witness").unwrap_err(),
+            TestFailure::Compile(..)
+        ));
+        assert!(matches!(run("main").unwrap_err(), TestFailure::Compile(..)));
+    }
+
+    #[test]
+    fn errors_in_test_files() {
+        let test_file = "#[test]\nfn wrong_type() {\n    let _: u16 = add(1, 2);\n}\n";
+        let suite = TestSuite::from_files(&[("program.simf", PROGRAM), ("tests.simf", test_file)])
+            .expect("program should parse");
+        let name = FunctionName::from_str_unchecked("wrong_type");
+        let failure = suite.run(&name, &dummy_env::dummy()).unwrap_err();
+        let expected = "tests.simf:
+  |
+3 |     let _: u16 = add(1, 2);
+  |                  ^^^^^^^^^ Expected expression of type `u16`, found type `u8`";
+        assert_eq!(TestFailure::Compile(expected.to_string()), failure);
+
+        let error = TestSuite::from_files(&[("program.simf", PROGRAM), ("tests.simf", "fn (")])
+            .unwrap_err();
+        assert!(error.starts_with("tests.simf:\n  |\n1 | fn ("), "{error}");
+    }
+}
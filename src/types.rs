@@ -6,8 +6,9 @@ use miniscript::iter::{Tree, TreeLike};
 use simplicity::types::{CompleteBound, Final};
 
 use crate::array::{BTreeSlice, Partition};
+use crate::error::Error;
 use crate::num::{NonZeroPow2Usize, Pow2Usize};
-use crate::str::AliasName;
+use crate::str::{AliasName, Identifier};
 
 /// Primitives of the SimplicityHL type system, excluding type aliases.
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
@@ -497,6 +498,12 @@ enum AliasedInner {
     Alias(AliasName),
     /// Builtin type alias.
     Builtin(BuiltinAlias),
+    /// Array whose size is given by a constant.
+    ///
+    /// Element type and name share one allocation to keep the type small.
+    NamedArray(Arc<(AliasedType, Identifier)>),
+    /// List whose bound is given by a constant.
+    NamedList(Arc<(AliasedType, Identifier)>),
     /// Type primitive.
     Inner(TypeInner<Arc<AliasedType>>),
 }
@@ -558,18 +565,67 @@ impl AliasedType {
         Self(AliasedInner::Builtin(builtin))
     }
 
+    /// Create an array type whose size is given by the constant `name`.
+    pub fn named_array(element: Self, name: Identifier) -> Self {
+        Self(AliasedInner::NamedArray(Arc::new((element, name))))
+    }
+
+    /// Create a list type whose bound is given by the constant `name`.
+    pub fn named_list(element: Self, name: Identifier) -> Self {
+        Self(AliasedInner::NamedList(Arc::new((element, name))))
+    }
+
     /// Resolve all aliases in the type based on the given map of `aliases` to types.
-    pub fn resolve<F>(&self, mut get_alias: F) -> Result<ResolvedType, AliasName>
+    ///
+    /// ## Errors
+    ///
+    /// There are any undefined aliases or the type uses a constant as size.
+    pub fn resolve<F>(&self, get_alias: F) -> Result<ResolvedType, Error>
     where
         F: FnMut(&AliasName) -> Option<ResolvedType>,
+    {
+        self.resolve_with(get_alias, |name| {
+            Err(Error::UndefinedConstant(name.clone()))
+        })
+    }
+
+    /// Resolve all aliases and constant sizes in the type.
+    ///
+    /// Aliases are looked up via `get_alias`.
+    /// Constants that are used as array sizes or list bounds are looked up via `get_size`.
+    ///
+    /// ## Errors
+    ///
+    /// There are any undefined aliases, `get_size` fails,
+    /// or a constant list bound is not a power of two greater than one.
+    pub fn resolve_with<F, G>(
+        &self,
+        mut get_alias: F,
+        mut get_size: G,
+    ) -> Result<ResolvedType, Error>
+    where
+        F: FnMut(&AliasName) -> Option<ResolvedType>,
+        G: FnMut(&Identifier) -> Result<usize, Error>,
     {
         let mut output = vec![];
         for data in self.post_order_iter() {
             match &data.node.0 {
                 AliasedInner::Alias(name) => {
-                    let resolved = get_alias(name).ok_or(name.clone())?;
+                    let resolved =
+                        get_alias(name).ok_or_else(|| Error::UndefinedAlias(name.clone()))?;
                     output.push(resolved);
                 }
+                AliasedInner::NamedArray(named) => {
+                    let size = get_size(&named.1)?;
+                    let element = output.pop().unwrap();
+                    output.push(ResolvedType::array(element, size));
+                }
+                AliasedInner::NamedList(named) => {
+                    let size = get_size(&named.1)?;
+                    let bound = NonZeroPow2Usize::new(size).ok_or(Error::ListBoundPow2(size))?;
+                    let element = output.pop().unwrap();
+                    output.push(ResolvedType::list(element, bound));
+                }
                 AliasedInner::Builtin(builtin) => {
                     let resolved = builtin.resolve();
                     output.push(resolved);
@@ -608,7 +664,7 @@ impl AliasedType {
     }
 
     /// Resolve all aliases in the type based on the builtin type aliases only.
-    pub fn resolve_builtin(&self) -> Result<ResolvedType, AliasName> {
+    pub fn resolve_builtin(&self) -> Result<ResolvedType, Error> {
         self.resolve(|_| None)
     }
 }
@@ -702,6 +758,9 @@ impl TreeLike for &AliasedType {
     fn as_node(&self) -> Tree<Self> {
         match &self.0 {
             AliasedInner::Alias(_) | AliasedInner::Builtin(_) => Tree::Nullary,
+            AliasedInner::NamedArray(named) | AliasedInner::NamedList(named) => {
+                Tree::Unary(&named.0)
+            }
             AliasedInner::Inner(inner) => match inner {
                 TypeInner::Boolean | TypeInner::UInt(..) => Tree::Nullary,
                 TypeInner::Option(l) | TypeInner::Array(l, _) | TypeInner::List(l, _) => {
@@ -728,6 +787,14 @@ impl fmt::Display for AliasedType {
             match &data.node.0 {
                 AliasedInner::Alias(alias) => write!(f, "{alias}")?,
                 AliasedInner::Builtin(builtin) => write!(f, "{builtin}")?,
+                AliasedInner::NamedArray(named) => match data.n_children_yielded {
+                    0 => f.write_str("[")?,
+                    _ => write!(f, "; {}]", named.1)?,
+                },
+                AliasedInner::NamedList(named) => match data.n_children_yielded {
+                    0 => f.write_str("List<")?,
+                    _ => write!(f, ", {}>", named.1)?,
+                },
                 AliasedInner::Inner(inner) => inner.display(f, data.n_children_yielded)?,
             }
         }
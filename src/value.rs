@@ -621,6 +621,31 @@ impl Value {
         self.ty() == ty
     }
 
+    /// Convert the value into a `usize`.
+    ///
+    /// Return `None` if the value is not an unsigned integer or if it is too large.
+    pub fn as_usize(&self) -> Option<usize> {
+        let ValueInner::UInt(value) = self.inner() else {
+            return None;
+        };
+        match *value {
+            UIntValue::U1(n) | UIntValue::U2(n) | UIntValue::U4(n) | UIntValue::U8(n) => {
+                Some(usize::from(n))
+            }
+            UIntValue::U16(n) => Some(usize::from(n)),
+            UIntValue::U32(n) => usize::try_from(n).ok(),
+            UIntValue::U64(n) => usize::try_from(n).ok(),
+            UIntValue::U128(n) => usize::try_from(n).ok(),
+            UIntValue::U256(n) => {
+                let (high, low) = n.as_ref().split_at(24);
+                if high.iter().any(|byte| *byte != 0) {
+                    return None;
+                }
+                usize::try_from(u64::from_be_bytes(low.try_into().unwrap())).ok()
+            }
+        }
+    }
+
     /// Create a value from the given `hexadecimal` string and type.
     pub fn parse_hexadecimal(hexadecimal: &Hexadecimal, ty: &ResolvedType) -> Result<Self, Error> {
         use miniscript::bitcoin::hex::FromHex;
@@ -657,7 +682,8 @@ impl Value {
     /// - Variable expressions
     /// - Witness expressions
     /// - Match expressions
-    /// - Call expressions
+    /// - Calls of functions other than pure jets
+    /// - Calls of pure jets that fail
     pub fn from_const_expr(expr: &ast::Expression) -> Option<Self> {
         use ast::ExprTree;
         use ast::SingleExpressionInner as S;
@@ -665,22 +691,29 @@ impl Value {
         let mut output = vec![];
         for data in ExprTree::Expression(expr).post_order_iter() {
             let single = match &data.node {
-                ExprTree::Expression(..) => continue, // skip
+                ExprTree::Expression(..) | ExprTree::Call(..) => continue, // skip
                 ExprTree::Single(single) => single,
                 ExprTree::Block(..)
                 | ExprTree::Statement(..)
                 | ExprTree::Assignment(..)
-                | ExprTree::Call(..)
                 | ExprTree::Match(..) => return None, // not const
             };
             let size = data.node.n_children();
             match single.inner() {
                 S::Constant(value) => output.push(value.clone()),
-                S::Witness(..)
-                | S::Parameter(..)
-                | S::Variable(..)
-                | S::Call(..)
-                | S::Match(..) => return None, // not const
+                S::Witness(..) | S::Parameter(..) | S::Variable(..) | S::Match(..) => return None, // not const
+                S::Call(call) => {
+                    let ast::CallName::Jet(jet) = call.name() else {
+                        return None; // not const
+                    };
+                    if !crate::jet::is_pure(*jet) {
+                        return None; // not const
+                    }
+                    let args = output.split_off(output.len() - call.args().len());
+                    let env = crate::dummy_env::dummy();
+                    let value = crate::interpret::jet::execute(*jet, &args, single.ty(), &env);
+                    output.push(value.ok()?);
+                }
                 S::Expression(..) => continue, // skip
                 S::Tuple(..) => {
                     let elements = output.split_off(output.len() - size);
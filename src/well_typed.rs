@@ -0,0 +1,657 @@
+//! Generator of well-typed SimplicityHL programs for fuzzing.
+//!
+//! [`parse::Program`] implements [`arbitrary::Arbitrary`], but its output is almost never
+//! well-typed, so most generated programs are rejected long before compilation.
+//! The generator in this module works the other way around:
+//! it first picks the type of each expression and then builds an expression of that type.
+//! The resulting programs always pass the type checker and exercise the compiler.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use arbitrary::Arbitrary;
+use simplicity::jet::Elements;
+
+use crate::jet::{source_type, target_type};
+use crate::num::NonZeroPow2Usize;
+use crate::parse::{self, ParseFromStr};
+use crate::types::{ResolvedType, TypeConstructible, TypeDeconstructible, TypeInner, UIntType};
+use crate::value::Value;
+use crate::ArbitraryOfType;
+
+/// Maximum depth of generated types.
+const MAX_TYPE_DEPTH: usize = 2;
+/// Maximum depth of generated expressions.
+const MAX_EXPRESSION_DEPTH: usize = 4;
+/// Maximum number of composite expressions in a generated program, including function bodies.
+const MAX_SIZE: usize = 64;
+/// Maximum weight of the types of jets that the generator calls.
+const MAX_JET_WEIGHT: usize = 16;
+
+/// A SimplicityHL program that is well-typed by construction.
+///
+/// The program uses custom functions, `match`, `array_fold`, `fold`, `for_while`,
+/// casts, jets, lists, witnesses and parameters.
+/// The generated program may fail at runtime, but it always compiles.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WellTypedProgram(parse::Program);
+
+impl WellTypedProgram {
+    /// Access the parse tree of the program.
+    pub fn as_parse_program(&self) -> &parse::Program {
+        &self.0
+    }
+}
+
+impl fmt::Display for WellTypedProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl<'a> Arbitrary<'a> for WellTypedProgram {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let text = Generator::default().program(u)?;
+        let program =
+            parse::Program::parse_from_str(&text).expect("generated program should parse");
+        Ok(Self(program))
+    }
+}
+
+/// Signature of a callable custom function or jet.
+#[derive(Clone, Debug)]
+struct Signature {
+    name: String,
+    params: Vec<ResolvedType>,
+    output: ResolvedType,
+}
+
+/// Return the signatures of the jets that the generator can call.
+///
+/// Jets with large types are excluded because their arguments would dominate the program.
+fn jets() -> &'static [Signature] {
+    static JETS: OnceLock<Vec<Signature>> = OnceLock::new();
+    JETS.get_or_init(|| {
+        Elements::ALL
+            .into_iter()
+            .filter(|jet| !matches!(jet, Elements::CheckSigVerify | Elements::Verify))
+            .filter_map(|jet| {
+                let params = source_type(jet)
+                    .iter()
+                    .map(|ty| ty.resolve_builtin().ok())
+                    .collect::<Option<Vec<ResolvedType>>>()?;
+                let output = target_type(jet).resolve_builtin().ok()?;
+                let weight = params.iter().map(weight).sum::<usize>() + weight(&output);
+                (weight <= MAX_JET_WEIGHT).then(|| Signature {
+                    name: format!("jet::{jet}"),
+                    params,
+                    output,
+                })
+            })
+            .collect()
+    })
+}
+
+/// Return the number of leaves of a value of the given type,
+/// assuming that lists are full.
+fn weight(ty: &ResolvedType) -> usize {
+    match ty.as_inner() {
+        TypeInner::Boolean | TypeInner::UInt(..) => 1,
+        TypeInner::Either(l, r) => 1 + weight(l).max(weight(r)),
+        TypeInner::Option(r) => 1 + weight(r),
+        TypeInner::Tuple(elements) => elements.iter().map(|ty| weight(ty)).sum(),
+        TypeInner::Array(element, size) => size * weight(element),
+        TypeInner::List(element, bound) => bound.get() * weight(element),
+    }
+}
+
+/// Return the type that is structurally equal to the given type,
+/// so it can be cast into the given type.
+fn cast_source(
+    u: &mut arbitrary::Unstructured,
+    target: &ResolvedType,
+) -> arbitrary::Result<ResolvedType> {
+    let half = |ty: UIntType| {
+        ty.bit_width()
+            .checked_div2()
+            .and_then(UIntType::from_bit_width)
+    };
+    let double = |ty: UIntType| UIntType::from_bit_width(ty.bit_width().mul2());
+
+    let source = match target.as_inner() {
+        // uN = (uN/2, uN/2) = [uN/2; 2]
+        TypeInner::UInt(ty) => match half(*ty) {
+            Some(half) => match u.int_in_range(0..=1)? {
+                0 => ResolvedType::tuple([half.into(), half.into()]),
+                _ => ResolvedType::array(half.into(), 2),
+            },
+            None => target.clone(),
+        },
+        TypeInner::Tuple(elements) => match elements.as_ref() {
+            [l, r] if l == r => match l.as_integer().and_then(double) {
+                Some(double) => double.into(),
+                None => target.clone(),
+            },
+            _ => target.clone(),
+        },
+        TypeInner::Array(element, 2) => match element.as_integer().and_then(double) {
+            Some(double) => double.into(),
+            None => target.clone(),
+        },
+        _ => target.clone(),
+    };
+    Ok(source)
+}
+
+/// State of the program generator.
+#[derive(Default)]
+struct Generator {
+    /// Definitions of custom functions in the order in which they must appear.
+    items: Vec<String>,
+    /// Signatures of the defined custom functions.
+    functions: Vec<Signature>,
+    /// Variables that are in scope.
+    variables: Vec<(String, ResolvedType)>,
+    /// Whether the generator is inside the main function,
+    /// which is the only place where witnesses and parameters may appear.
+    in_main: bool,
+    /// Counter for fresh names.
+    next_name: usize,
+    /// Number of composite expressions that have been generated so far.
+    size: usize,
+}
+
+impl Generator {
+    fn fresh_name(&mut self, prefix: &str) -> String {
+        let name = format!("{prefix}{}", self.next_name);
+        self.next_name += 1;
+        name
+    }
+
+    fn program(mut self, u: &mut arbitrary::Unstructured) -> arbitrary::Result<String> {
+        self.in_main = true;
+        let body = self.block(u, &ResolvedType::unit(), MAX_EXPRESSION_DEPTH)?;
+        self.items.push(format!("fn main() {body}"));
+        Ok(self.items.join("\n\n"))
+    }
+
+    fn ty(u: &mut arbitrary::Unstructured, depth: usize) -> arbitrary::Result<ResolvedType> {
+        let max_choice = if depth == 0 { 1 } else { 6 };
+        match u.int_in_range(0..=max_choice)? {
+            0 => Ok(ResolvedType::boolean()),
+            1 => UIntType::arbitrary(u).map(ResolvedType::from),
+            2 => Self::ty(u, depth - 1).map(ResolvedType::option),
+            3 => {
+                let left = Self::ty(u, depth - 1)?;
+                let right = Self::ty(u, depth - 1)?;
+                Ok(ResolvedType::either(left, right))
+            }
+            4 => {
+                let len = u.int_in_range(0..=3)?;
+                (0..len)
+                    .map(|_| Self::ty(u, depth - 1))
+                    .collect::<arbitrary::Result<Vec<ResolvedType>>>()
+                    .map(ResolvedType::tuple)
+            }
+            5 => {
+                let element = Self::ty(u, depth - 1)?;
+                let size = u.int_in_range(1..=3)?;
+                Ok(ResolvedType::array(element, size))
+            }
+            6 => {
+                let element = Self::ty(u, depth - 1)?;
+                let bound = match u.int_in_range(0..=1)? {
+                    0 => NonZeroPow2Usize::TWO,
+                    _ => NonZeroPow2Usize::TWO.mul2(),
+                };
+                Ok(ResolvedType::list(element, bound))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Generate a block expression of the given type.
+    fn block(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        let n_variables = self.variables.len();
+        let n_statements = u.int_in_range(0..=3)?;
+        let mut output = String::from("{\n");
+        for _ in 0..n_statements {
+            let statement = self.statement(u, depth)?;
+            output.push_str(&statement);
+            output.push_str(";\n");
+        }
+        if !ty.is_unit() || bool::arbitrary(u)? {
+            let expression = self.expression(u, ty, depth)?;
+            output.push_str(&expression);
+            output.push('\n');
+        }
+        output.push('}');
+        self.variables.truncate(n_variables);
+        Ok(output)
+    }
+
+    /// Generate a statement and bring its variables into scope.
+    fn statement(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        match u.int_in_range(0..=3)? {
+            0 => {
+                let condition = self.expression(u, &ResolvedType::boolean(), depth)?;
+                Ok(format!("assert!({condition})"))
+            }
+            _ => {
+                let ty = Self::ty(u, MAX_TYPE_DEPTH)?;
+                let expression = self.expression(u, &ty, depth)?;
+                let pattern = self.pattern(u, &ty)?;
+                Ok(format!("let {pattern}: {ty} = {expression}"))
+            }
+        }
+    }
+
+    /// Generate a pattern of the given type and bring its variables into scope.
+    fn pattern(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+    ) -> arbitrary::Result<String> {
+        match (ty.as_inner(), u.int_in_range(0..=3)?) {
+            (_, 0) => Ok("_".to_string()),
+            (TypeInner::Tuple(elements), 1) => {
+                let patterns = elements
+                    .iter()
+                    .map(|ty| self.pattern(u, ty))
+                    .collect::<arbitrary::Result<Vec<String>>>()?;
+                match patterns.len() {
+                    1 => Ok(format!("({},)", patterns[0])),
+                    _ => Ok(format!("({})", patterns.join(", "))),
+                }
+            }
+            (TypeInner::Array(element, size), 1) => {
+                let patterns = (0..*size)
+                    .map(|_| self.pattern(u, element))
+                    .collect::<arbitrary::Result<Vec<String>>>()?;
+                Ok(format!("[{}]", patterns.join(", ")))
+            }
+            _ => {
+                let name = self.fresh_name("x");
+                self.variables.push((name.clone(), ty.clone()));
+                Ok(name)
+            }
+        }
+    }
+
+    /// Generate an expression without subexpressions of the given type.
+    fn leaf(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+    ) -> arbitrary::Result<String> {
+        match u.int_in_range(0..=4)? {
+            1 | 2 => {
+                let candidates = self
+                    .variables
+                    .iter()
+                    .filter(|(_, var_ty)| var_ty == ty)
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<&str>>();
+                if !candidates.is_empty() {
+                    return u.choose(&candidates).map(|name| name.to_string());
+                }
+            }
+            3 if self.in_main => return Ok(format!("witness::{}", self.fresh_name("W"))),
+            4 if self.in_main => return Ok(format!("param::{}", self.fresh_name("P"))),
+            _ => {}
+        }
+        Value::arbitrary_of_type(u, ty).map(|value| value.to_string())
+    }
+
+    /// Generate an expression of the given type.
+    fn expression(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        if depth == 0 || MAX_SIZE <= self.size {
+            return self.leaf(u, ty);
+        }
+        self.size += 1;
+        let depth = depth - 1;
+
+        match u.int_in_range(0..=12)? {
+            0 | 1 => self.leaf(u, ty),
+            2 => self.block(u, ty, depth),
+            3 => self.constructor(u, ty, depth),
+            4 => self.match_expression(u, ty, depth),
+            5 => self.custom_call(u, ty, depth),
+            6 => self.jet_call(u, ty, depth),
+            7 => self.unwrap(u, ty, depth),
+            8 => {
+                let inner = self.expression(u, ty, depth)?;
+                Ok(format!("dbg!({inner})"))
+            }
+            9 => {
+                let source = cast_source(u, ty)?;
+                let inner = self.expression(u, &source, depth)?;
+                Ok(format!("<{source}>::into({inner})"))
+            }
+            10 => self.array_fold(u, ty, depth),
+            11 => self.list_fold(u, ty, depth),
+            12 => self.for_while(u, ty, depth),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Generate a comma-separated list of expressions of the given types.
+    fn arguments(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        tys: &[ResolvedType],
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        tys.iter()
+            .map(|ty| self.expression(u, ty, depth))
+            .collect::<arbitrary::Result<Vec<String>>>()
+            .map(|args| args.join(", "))
+    }
+
+    /// Generate an expression that constructs a value of the given type from its components.
+    fn constructor(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        match ty.as_inner() {
+            TypeInner::Boolean => {
+                let some_ty = Self::ty(u, 1)?;
+                let option_ty = ResolvedType::option(some_ty.clone());
+                let inner = self.expression(u, &option_ty, depth)?;
+                Ok(format!("is_none::<{some_ty}>({inner})"))
+            }
+            TypeInner::UInt(..) => self.leaf(u, ty),
+            TypeInner::Either(left, right) => match bool::arbitrary(u)? {
+                false => Ok(format!("Left({})", self.expression(u, left, depth)?)),
+                true => Ok(format!("Right({})", self.expression(u, right, depth)?)),
+            },
+            TypeInner::Option(inner) => match bool::arbitrary(u)? {
+                false => Ok("None".to_string()),
+                true => Ok(format!("Some({})", self.expression(u, inner, depth)?)),
+            },
+            TypeInner::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|ty| ty.as_ref().clone())
+                    .collect::<Vec<ResolvedType>>();
+                let args = self.arguments(u, &elements, depth)?;
+                match elements.len() {
+                    1 => Ok(format!("({args},)")),
+                    _ => Ok(format!("({args})")),
+                }
+            }
+            TypeInner::Array(element, size) => {
+                let elements = vec![element.as_ref().clone(); *size];
+                Ok(format!("[{}]", self.arguments(u, &elements, depth)?))
+            }
+            TypeInner::List(element, bound) => {
+                let len = u.int_in_range(0..=bound.get() - 1)?;
+                let elements = vec![element.as_ref().clone(); len];
+                Ok(format!("list![{}]", self.arguments(u, &elements, depth)?))
+            }
+        }
+    }
+
+    /// Generate a match expression of the given type.
+    ///
+    /// The scrutinee is an `Either`, an `Option` or a Boolean.
+    fn match_expression(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        let (scrutinee_ty, left_arm, right_arm) = match u.int_in_range(0..=2)? {
+            0 => {
+                let left_ty = Self::ty(u, 1)?;
+                let right_ty = Self::ty(u, 1)?;
+                let (x, y) = (self.fresh_name("x"), self.fresh_name("x"));
+                let left_arm = self.match_arm(
+                    u,
+                    ty,
+                    depth,
+                    format!("Left({x}: {left_ty})"),
+                    Some((x, left_ty.clone())),
+                )?;
+                let right_arm = self.match_arm(
+                    u,
+                    ty,
+                    depth,
+                    format!("Right({y}: {right_ty})"),
+                    Some((y, right_ty.clone())),
+                )?;
+                (ResolvedType::either(left_ty, right_ty), left_arm, right_arm)
+            }
+            1 => {
+                let some_ty = Self::ty(u, 1)?;
+                let x = self.fresh_name("x");
+                let none_arm = self.match_arm(u, ty, depth, "None".to_string(), None)?;
+                let some_arm = self.match_arm(
+                    u,
+                    ty,
+                    depth,
+                    format!("Some({x}: {some_ty})"),
+                    Some((x, some_ty.clone())),
+                )?;
+                (ResolvedType::option(some_ty), none_arm, some_arm)
+            }
+            _ => {
+                let false_arm = self.match_arm(u, ty, depth, "false".to_string(), None)?;
+                let true_arm = self.match_arm(u, ty, depth, "true".to_string(), None)?;
+                (ResolvedType::boolean(), false_arm, true_arm)
+            }
+        };
+        let scrutinee = self.expression(u, &scrutinee_ty, depth)?;
+        Ok(format!("match {scrutinee} {{\n{left_arm}\n{right_arm}\n}}"))
+    }
+
+    /// Generate a match arm whose body has the given type.
+    ///
+    /// The variable of the pattern, if any, is in scope inside the body.
+    fn match_arm(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+        pattern: String,
+        variable: Option<(String, ResolvedType)>,
+    ) -> arbitrary::Result<String> {
+        let n_variables = self.variables.len();
+        self.variables.extend(variable);
+        let body = self.expression(u, ty, depth);
+        self.variables.truncate(n_variables);
+        Ok(format!("{pattern} => {},", body?))
+    }
+
+    /// Define a custom function with the given signature and return its name.
+    ///
+    /// The definition is emitted before the function that is currently being generated,
+    /// so the new function can be called from there.
+    fn define_function(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        params: Vec<ResolvedType>,
+        output: ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        let name = self.fresh_name("f");
+        let variables = params
+            .iter()
+            .map(|ty| (self.fresh_name("x"), ty.clone()))
+            .collect::<Vec<(String, ResolvedType)>>();
+        let params_text = variables
+            .iter()
+            .map(|(name, ty)| format!("{name}: {ty}"))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        let outer_variables = std::mem::replace(&mut self.variables, variables);
+        let outer_in_main = std::mem::replace(&mut self.in_main, false);
+        let body = self.block(u, &output, depth);
+        self.variables = outer_variables;
+        self.in_main = outer_in_main;
+
+        self.items
+            .push(format!("fn {name}({params_text}) -> {output} {}", body?));
+        self.functions.push(Signature {
+            name: name.clone(),
+            params,
+            output,
+        });
+        Ok(name)
+    }
+
+    /// Generate a call of a custom function with the given output type.
+    ///
+    /// Either call an existing function or define a new one.
+    fn custom_call(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        let candidates = self
+            .functions
+            .iter()
+            .filter(|function| &function.output == ty)
+            .cloned()
+            .collect::<Vec<Signature>>();
+        let (name, params) = match candidates.is_empty() || bool::arbitrary(u)? {
+            true => {
+                let n_params = u.int_in_range(0..=2)?;
+                let params = (0..n_params)
+                    .map(|_| Self::ty(u, MAX_TYPE_DEPTH))
+                    .collect::<arbitrary::Result<Vec<ResolvedType>>>()?;
+                let name = self.define_function(u, params.clone(), ty.clone(), depth)?;
+                (name, params)
+            }
+            false => {
+                let function = u.choose(&candidates)?;
+                (function.name.clone(), function.params.clone())
+            }
+        };
+        let args = self.arguments(u, &params, depth)?;
+        Ok(format!("{name}({args})"))
+    }
+
+    /// Generate a call of a jet with the given output type.
+    fn jet_call(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        let candidates = jets()
+            .iter()
+            .filter(|jet| &jet.output == ty)
+            .collect::<Vec<&Signature>>();
+        if candidates.is_empty() {
+            return self.constructor(u, ty, depth);
+        }
+        let jet = u.choose(&candidates)?;
+        let args = self.arguments(u, &jet.params, depth)?;
+        Ok(format!("{}({args})", jet.name))
+    }
+
+    /// Generate an expression that unwraps an `Either` or an `Option` into the given type.
+    fn unwrap(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        match u.int_in_range(0..=2)? {
+            0 => {
+                let right_ty = Self::ty(u, 1)?;
+                let either_ty = ResolvedType::either(ty.clone(), right_ty.clone());
+                let inner = self.expression(u, &either_ty, depth)?;
+                Ok(format!("unwrap_left::<{right_ty}>({inner})"))
+            }
+            1 => {
+                let left_ty = Self::ty(u, 1)?;
+                let either_ty = ResolvedType::either(left_ty.clone(), ty.clone());
+                let inner = self.expression(u, &either_ty, depth)?;
+                Ok(format!("unwrap_right::<{left_ty}>({inner})"))
+            }
+            _ => {
+                let option_ty = ResolvedType::option(ty.clone());
+                let inner = self.expression(u, &option_ty, depth)?;
+                Ok(format!("unwrap({inner})"))
+            }
+        }
+    }
+
+    /// Generate an array fold whose accumulator has the given type.
+    fn array_fold(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        let element_ty = Self::ty(u, 1)?;
+        let size = u.int_in_range(1..=3)?;
+        let function =
+            self.define_function(u, vec![element_ty.clone(), ty.clone()], ty.clone(), depth)?;
+        let array_ty = ResolvedType::array(element_ty, size);
+        let args = self.arguments(u, &[array_ty, ty.clone()], depth)?;
+        Ok(format!("array_fold::<{function}, {size}>({args})"))
+    }
+
+    /// Generate a list fold whose accumulator has the given type.
+    fn list_fold(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        let element_ty = Self::ty(u, 1)?;
+        let bound = match bool::arbitrary(u)? {
+            false => NonZeroPow2Usize::TWO,
+            true => NonZeroPow2Usize::TWO.mul2(),
+        };
+        let function =
+            self.define_function(u, vec![element_ty.clone(), ty.clone()], ty.clone(), depth)?;
+        let list_ty = ResolvedType::list(element_ty, bound);
+        let args = self.arguments(u, &[list_ty, ty.clone()], depth)?;
+        Ok(format!("fold::<{function}, {bound}>({args})"))
+    }
+
+    /// Generate a for-while loop of the given type.
+    ///
+    /// Fall back to a constructor if the type is not an `Either`.
+    fn for_while(
+        &mut self,
+        u: &mut arbitrary::Unstructured,
+        ty: &ResolvedType,
+        depth: usize,
+    ) -> arbitrary::Result<String> {
+        let Some((_, accumulator_ty)) = ty.as_either() else {
+            return self.constructor(u, ty, depth);
+        };
+        let accumulator_ty = accumulator_ty.clone();
+        let context_ty = Self::ty(u, 1)?;
+        // Keep the number of iterations small: at most 2^4 = 16
+        let counter_ty =
+            ResolvedType::from(*u.choose(&[UIntType::U1, UIntType::U2, UIntType::U4])?);
+        let params = vec![accumulator_ty.clone(), context_ty.clone(), counter_ty];
+        let function = self.define_function(u, params, ty.clone(), depth)?;
+        let args = self.arguments(u, &[accumulator_ty, context_ty], depth)?;
+        Ok(format!("for_while::<{function}>({args})"))
+    }
+}
@@ -134,11 +134,7 @@ impl WitnessValues {
 impl ParseFromStr for ResolvedType {
     fn parse_from_str(s: &str) -> Result<Self, RichError> {
         let aliased = AliasedType::parse_from_str(s)?;
-        aliased
-            .resolve_builtin()
-            .map_err(Error::UndefinedAlias)
-            .with_span(s)
-            .with_file(s)
+        aliased.resolve_builtin().with_span(s).with_file(s)
     }
 }
 
@@ -205,6 +201,25 @@ impl crate::ArbitraryOfType for Arguments {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+impl crate::ArbitraryOfType for WitnessValues {
+    type Type = WitnessTypes;
+
+    fn arbitrary_of_type(
+        u: &mut arbitrary::Unstructured,
+        ty: &Self::Type,
+    ) -> arbitrary::Result<Self> {
+        let mut map = HashMap::new();
+        for (name, witness_ty) in ty.iter() {
+            map.insert(
+                name.shallow_clone(),
+                Value::arbitrary_of_type(u, witness_ty)?,
+            );
+        }
+        Ok(Self::from(map))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
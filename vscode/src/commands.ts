@@ -1,4 +1,4 @@
-import { ExtensionContext, commands } from "vscode";
+import { ExtensionContext, commands, env, window } from "vscode";
 import { LspClient } from "./client";
 
 export function registerRestartCommand(
@@ -14,3 +14,23 @@ export function registerRestartCommand(
 
   context.subscriptions.push(command);
 }
+
+/**
+ * Register the commands of the code lenses that copy a value to the clipboard.
+ *
+ * The language server cannot access the clipboard,
+ * so it passes the value as the argument of the command.
+ */
+export function registerCopyCommands(context: ExtensionContext) {
+  for (const [name, label] of [
+    ["simplicityhl.copyCmr", "CMR"],
+    ["simplicityhl.copyProgram", "Program"],
+  ] as const) {
+    const command = commands.registerCommand(name, async (value: string) => {
+      await env.clipboard.writeText(value);
+      void window.showInformationMessage(`${label} copied to clipboard`);
+    });
+
+    context.subscriptions.push(command);
+  }
+}
@@ -1,5 +1,5 @@
 import { LspClient } from "./client";
-import { registerRestartCommand } from "./commands"
+import { registerCopyCommands, registerRestartCommand } from "./commands"
 import { ExtensionContext } from "vscode"
 
 let client: LspClient;
@@ -8,7 +8,8 @@ export function activate(context: ExtensionContext) {
   client = new LspClient();
   void client.start();
 
-  registerRestartCommand(context, client);
+  registerRestartCommand(context, client);
+  registerCopyCommands(context);
 }
 export function deactivate(): Thenable<void> | undefined {
   if (!client) {